@@ -47,8 +47,12 @@ class CretonneLexer(RegexLexer):
             (r'\b(b\d+|i\d+|f32|f64)(x\d+)?\b', Keyword.Type),
             # v<nn> = value
             # ss<nn> = stack slot
+            # gv<nn> = global variable
             # jt<nn> = jump table
-            (r'(v|ss|jt)\d+', Name.Variable),
+            # heap<nn> = heap
+            (r'(v|ss|gv|jt|heap)\d+', Name.Variable),
+            # @<hex> = source location
+            (r'@[0-9a-fA-F]+', Comment.Special),
             # ebb<nn> = extended basic block
             (r'(ebb)\d+', Name.Label),
             # Match instruction names in context.
@@ -24,6 +24,21 @@ sig_ref = EntityRefKind('sig_ref', 'A function signature.')
 #: This is used to provide the callee and signature in a call instruction.
 func_ref = EntityRefKind('func_ref', 'An external function.')
 
+#: A reference to a global variable declared in the function preamble.
+#: This is used to provide the global variable whose address is computed by a
+#: :cton:inst:`global_addr` instruction.
+global_var = EntityRefKind('global_var', 'A global variable.')
+
 #: A reference to a jump table declared in the function preamble.
 jump_table = EntityRefKind(
         'jump_table', 'A jump table.', default_member='table')
+
+#: A reference to a heap declared in the function preamble.
+#: This is used to provide the heap that a :cton:inst:`heap_addr` instruction
+#: accesses.
+heap = EntityRefKind('heap', 'A heap.')
+
+#: A reference to a constant in the function's constant pool.
+#: This is used to provide the constant whose address is computed by a
+#: :cton:inst:`const_addr` instruction.
+constant = EntityRefKind('constant', 'A constant in the constant pool.')
@@ -8,9 +8,11 @@ in this module.
 from __future__ import absolute_import
 from cdsl.formats import InstructionFormat
 from cdsl.operands import VALUE, VARIABLE_ARGS
-from .immediates import imm64, uimm8, ieee32, ieee64, offset32, uoffset32
-from .immediates import intcc, floatcc, memflags, regunit
-from .entities import ebb, sig_ref, func_ref, jump_table, stack_slot
+from .immediates import imm64, uimm8, uimm32, ieee32, ieee64, offset32
+from .immediates import intcc, floatcc, memflags, regunit, trapcode
+from .immediates import atomicrmwop, ordering
+from .entities import ebb, sig_ref, func_ref, stack_slot, heap
+from .entities import jump_table, global_var, constant
 
 Nullary = InstructionFormat()
 
@@ -42,6 +44,7 @@ Jump = InstructionFormat(ebb, VARIABLE_ARGS)
 Branch = InstructionFormat(VALUE, ebb, VARIABLE_ARGS)
 BranchIcmp = InstructionFormat(intcc, VALUE, VALUE, ebb, VARIABLE_ARGS)
 BranchTable = InstructionFormat(VALUE, jump_table)
+BranchTableBase = InstructionFormat(jump_table)
 
 Call = InstructionFormat(func_ref, VARIABLE_ARGS)
 IndirectCall = InstructionFormat(sig_ref, VALUE, VARIABLE_ARGS)
@@ -52,12 +55,22 @@ Store = InstructionFormat(memflags, VALUE, VALUE, offset32)
 StackLoad = InstructionFormat(stack_slot, offset32)
 StackStore = InstructionFormat(VALUE, stack_slot, offset32)
 
+UnaryGlobalVar = InstructionFormat(global_var)
+UnaryConst = InstructionFormat(constant)
+
 # Accessing a WebAssembly heap.
-# TODO: Add a reference to a `heap` declared in the preamble.
-HeapLoad = InstructionFormat(VALUE, uoffset32)
-HeapStore = InstructionFormat(VALUE, VALUE, uoffset32)
+HeapAddr = InstructionFormat(heap, VALUE, uimm32)
+
+# Atomic read-modify-write is controlled by the type of the second VALUE
+# operand. The first VALUE operand is the address.
+AtomicRmw = InstructionFormat(atomicrmwop, VALUE, VALUE, typevar_operand=1)
+Fence = InstructionFormat(ordering)
 
 RegMove = InstructionFormat(VALUE, ('src', regunit), ('dst', regunit))
 
+Trap = InstructionFormat(trapcode)
+CondTrap = InstructionFormat(VALUE, trapcode)
+BinaryTrap = InstructionFormat(VALUE, VALUE, trapcode)
+
 # Finally extract the names of global variables in this module.
 InstructionFormat.extract_names(globals())
@@ -17,6 +17,12 @@ imm64 = ImmediateKind('imm64', 'A 64-bit immediate integer.')
 #: immediate bit counts on shift instructions.
 uimm8 = ImmediateKind('uimm8', 'An 8-bit immediate unsigned integer.')
 
+#: An unsigned 32-bit immediate integer operand.
+#:
+#: This is used to represent the size of a heap access in the
+#: :cton:inst:`heap_addr` instruction.
+uimm32 = ImmediateKind('uimm32', 'A 32-bit immediate unsigned integer.')
+
 #: A 32-bit immediate signed offset.
 #:
 #: This is used to represent an immediate address offset in load/store
@@ -102,3 +108,49 @@ regunit = ImmediateKind(
         'regunit',
         'A register unit in the target ISA',
         rust_type='RegUnit')
+
+#: A trap code indicating the reason for trapping.
+#:
+#: The Rust enum type also has a `User(u16)` variant for user-provided trap
+#: codes.
+trapcode = ImmediateKind(
+        'trapcode',
+        'A trap reason code.',
+        default_member='code', rust_type='TrapCode',
+        values={
+            'stk_ovf': 'StackOverflow',
+            'heap_oob': 'HeapOutOfBounds',
+            'int_ovf': 'IntegerOverflow',
+            'int_divz': 'IntegerDivisionByZero',
+            'unreachable': 'Unreachable',
+        })
+
+#: A read-modify-write operation for the :cton:inst:`atomic_rmw` instruction.
+#:
+#: This corresponds to the `ir::AtomicRmwOp` Rust type.
+atomicrmwop = ImmediateKind(
+        'atomicrmwop',
+        'An atomic read-modify-write operation.',
+        default_member='op', rust_type='AtomicRmwOp',
+        values={
+            'add': 'Add',
+            'sub': 'Sub',
+            'and': 'And',
+            'or': 'Or',
+            'xor': 'Xor',
+            'xchg': 'Xchg',
+        })
+
+#: A memory ordering constraint for the :cton:inst:`fence` instruction.
+#:
+#: This corresponds to the `ir::AtomicOrdering` Rust type.
+ordering = ImmediateKind(
+        'ordering',
+        'A memory ordering constraint.',
+        default_member='ordering', rust_type='AtomicOrdering',
+        values={
+            'acquire': 'Acquire',
+            'release': 'Release',
+            'acq_rel': 'AcqRel',
+            'seq_cst': 'SeqCst',
+        })
@@ -9,8 +9,9 @@ from cdsl.operands import Operand, VARIABLE_ARGS
 from cdsl.typevar import TypeVar
 from cdsl.instructions import Instruction, InstructionGroup
 from base.types import i8, f32, f64, b1
-from base.immediates import imm64, uimm8, ieee32, ieee64, offset32, uoffset32
-from base.immediates import intcc, floatcc, memflags, regunit
+from base.immediates import imm64, uimm8, uimm32, ieee32, ieee64, offset32
+from base.immediates import intcc, floatcc, memflags, regunit, trapcode
+from base.immediates import atomicrmwop, ordering
 from base import entities
 import base.formats  # noqa
 
@@ -121,11 +122,40 @@ br_table = Instruction(
         """,
         ins=(x, JT), is_branch=True)
 
+addr = Operand('addr', iAddr)
+
+jump_table_base = Instruction(
+        'jump_table_base', r"""
+        Get the address of a jump table.
+
+        The jump table ``JT`` is emitted as a sequence of 32-bit entries. Each
+        entry is the offset of the destination EBB relative to the start of
+        the table. Entries are loaded from ``addr + 4 * x``, and the sum of
+        ``addr`` and the sign-extended entry is the destination address.
+
+        This instruction is used when legalizing :inst:`br_table`.
+        """,
+        ins=JT, outs=addr)
+
+indirect_jump_table_br = Instruction(
+        'indirect_jump_table_br', r"""
+        Branch indirectly via a jump table entry.
+
+        Unconditionally jump to the address ``addr`` which must be the address
+        of one of the EBBs in the jump table ``JT``. The jump table is only
+        used to represent the possible destinations in the control flow graph.
+
+        This instruction is used when legalizing :inst:`br_table`.
+        """,
+        ins=(addr, JT), is_branch=True, is_terminator=True)
+
+code = Operand('code', trapcode)
+
 trap = Instruction(
         'trap', r"""
         Terminate execution unconditionally.
         """,
-        is_terminator=True, can_trap=True)
+        ins=code, is_terminator=True, can_trap=True)
 
 trapz = Instruction(
         'trapz', r"""
@@ -133,7 +163,7 @@ trapz = Instruction(
 
         if ``c`` is non-zero, execution continues at the following instruction.
         """,
-        ins=c, can_trap=True)
+        ins=(c, code), can_trap=True)
 
 trapnz = Instruction(
         'trapnz', r"""
@@ -141,7 +171,7 @@ trapnz = Instruction(
 
         if ``c`` is zero, execution continues at the following instruction.
         """,
-        ins=c, can_trap=True)
+        ins=(c, code), can_trap=True)
 
 rvals = Operand('rvals', VARIABLE_ARGS, doc='return values')
 
@@ -342,42 +372,133 @@ stack_addr = Instruction(
         ins=(SS, Offset), outs=addr)
 
 #
-# WebAssembly bounds-checked heap accesses.
+# Global variables.
 #
-# TODO: Add a `heap` operand that selects between multiple heaps.
-# TODO: Should the immediate offset be a `u32`?
-# TODO: Distinguish between `iAddr` for a heap and for a target address? i.e.,
-#       32-bit WebAssembly on a 64-bit target has two different types.
 
-Offset = Operand('Offset', uoffset32, 'Unsigned offset to effective address')
+GV = Operand('GV', entities.global_var)
 
-heap_load = Instruction(
-        'heap_load', r"""
-        Load a value at the address :math:`p + Offset` in the heap H.
+global_addr = Instruction(
+        'global_addr', r"""
+        Compute the address of global variable GV.
+        """,
+        ins=GV, outs=addr)
 
-        Trap if the heap access would be out of bounds.
+# A specialized form of global_addr instructions that only handles
+# symbolic names.
+globalsym_addr = Instruction(
+        'globalsym_addr', r"""
+        Compute the address of global variable GV, which is a symbolic name.
         """,
-        ins=(p, Offset), outs=a, can_load=True)
+        ins=GV, outs=addr)
+
+#
+# WebAssembly bounds-checked heap accesses.
+#
 
-heap_store = Instruction(
-        'heap_store', r"""
-        Store a value at the address :math:`p + Offset` in the heap H.
+HeapOffset = TypeVar('HeapOffset', 'An unsigned heap offset', ints=(32, 64))
 
-        Trap if the heap access would be out of bounds.
-        """,
-        ins=(x, p, Offset), can_store=True)
+H = Operand('H', entities.heap)
+p = Operand('p', HeapOffset)
+Size = Operand('Size', uimm32, 'Size in bytes')
 
 heap_addr = Instruction(
         'heap_addr', r"""
         Bounds check and compute absolute address of heap memory.
 
-        Verify that the address range ``p .. p + Size - 1`` is valid in the
-        heap H, and trap if not.
+        Verify that the offset range ``p .. p + Size - 1`` is in bounds for the
+        heap H, and generate an absolute address that is safe to dereference.
 
-        Convert the heap-relative address in ``p`` to a real absolute address
-        and return it.
+        1. If ``p + Size`` is not greater than the heap bound, return an
+           absolute address corresponding to a byte offset of ``p`` from the
+           heap's base address.
+        2. If ``p + Size`` is greater than the heap bound, generate a trap.
+
+        The offset ``p`` has the index type of the heap, which may be smaller
+        than the address type.
         """,
-        ins=(p, Offset), outs=addr)
+        ins=(H, p, Size), outs=addr, can_trap=True)
+
+#
+# Atomic memory operations.
+#
+
+AtomicMem = TypeVar(
+        'AtomicMem', 'Any integer type that can be accessed atomically',
+        ints=True)
+
+p = Operand('p', iAddr)
+x = Operand('x', AtomicMem, doc='Value to be stored')
+e = Operand('e', AtomicMem, doc='Expected value in memory')
+a = Operand('a', AtomicMem, doc='Value loaded')
+Op = Operand('Op', atomicrmwop)
+Order = Operand('Order', ordering)
+fail = Operand('fail', b1, doc='True if the store did not happen')
+
+atomic_load = Instruction(
+        'atomic_load', r"""
+        Atomically load from memory at ``p``.
+
+        The address ``p`` must be naturally aligned for the loaded type. All
+        atomic memory instructions are sequentially consistent.
+        """,
+        ins=p, outs=a, can_load=True)
+
+atomic_store = Instruction(
+        'atomic_store', r"""
+        Atomically store ``x`` to memory at ``p``.
+
+        The address ``p`` must be naturally aligned for the stored type.
+        """,
+        ins=(x, p), can_store=True)
+
+atomic_rmw = Instruction(
+        'atomic_rmw', r"""
+        Atomically read-modify-write memory at ``p``.
+
+        Load the old value from ``p``, compute ``old Op x``, and store the
+        result back to ``p`` as a single atomic operation. Return the old
+        value. The ``xchg`` operation stores ``x`` unmodified.
+        """,
+        ins=(Op, p, x), outs=a, can_load=True, can_store=True)
+
+atomic_cas = Instruction(
+        'atomic_cas', r"""
+        Atomic compare-and-swap.
+
+        Load the old value from ``p``. If it is equal to ``e``, store ``x`` to
+        ``p``. Return the old value in either case, so the exchange happened
+        if and only if ``a == e``.
+        """,
+        ins=(p, e, x), outs=a, can_load=True, can_store=True)
+
+fence = Instruction(
+        'fence', r"""
+        Memory fence.
+
+        Prevent memory accesses from being reordered across the fence, as
+        constrained by the ``Order`` immediate.
+        """,
+        ins=Order, can_load=True, can_store=True)
+
+load_reserved = Instruction(
+        'load_reserved', r"""
+        Load from memory at ``p`` and acquire a reservation on the address.
+
+        This is a legalization helper for targets with load-reserved /
+        store-conditional instructions. A subsequent
+        :inst:`store_conditional` to the same address only succeeds if no
+        other store to the reserved address happened in between.
+        """,
+        ins=p, outs=a, can_load=True)
+
+store_conditional = Instruction(
+        'store_conditional', r"""
+        Store ``x`` to memory at ``p`` if the reservation from the preceding
+        :inst:`load_reserved` is still valid.
+
+        Return true if the store failed, and false if it succeeded.
+        """,
+        ins=(x, p), outs=fail, can_store=True)
 
 #
 # Materializing constants.
@@ -416,6 +537,17 @@ f64const = Instruction(
         """,
         ins=N, outs=a)
 
+C = Operand('C', entities.constant)
+const_addr = Instruction(
+        'const_addr', r"""
+        Get the address of a constant in the function's constant pool.
+
+        Constants that can't be encoded as instruction immediates are moved to
+        the constant pool by the legalizer, and loaded from this address. The
+        constant pool is emitted after the function's code.
+        """,
+        ins=C, outs=addr)
+
 #
 # Generics.
 #
@@ -654,6 +786,24 @@ imul = Instruction(
         """,
         ins=(x, y), outs=a)
 
+umulhi = Instruction(
+        'umulhi', """
+        Unsigned integer multiplication, producing the high half of a
+        double-length result.
+
+        Polymorphic over all integer types (vector and scalar).
+        """,
+        ins=(x, y), outs=a)
+
+smulhi = Instruction(
+        'smulhi', """
+        Signed integer multiplication, producing the high half of a
+        double-length result.
+
+        Polymorphic over all integer types (vector and scalar).
+        """,
+        ins=(x, y), outs=a)
+
 udiv = Instruction(
         'udiv', r"""
         Unsigned integer division: :math:`a := \lfloor {x \over y} \rfloor`.
@@ -867,6 +1017,76 @@ isub_borrow = Instruction(
         """,
         ins=(x, y, b_in), outs=(a, b_out))
 
+#
+# Integer arithmetic with overflow detection.
+#
+of = Operand('of', b1, doc="Signed overflow flag")
+
+iadd_ov = Instruction(
+        'iadd_ov', r"""
+        Add integers with signed overflow detection.
+
+        Same as :inst:`iadd` with an additional output that is set when the
+        signed sum is not representable in the result type. Use
+        :inst:`iadd_cout` to detect unsigned overflow.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        """,
+        ins=(x, y), outs=(a, of))
+
+isub_ov = Instruction(
+        'isub_ov', r"""
+        Subtract integers with signed overflow detection.
+
+        Same as :inst:`isub` with an additional output that is set when the
+        signed difference is not representable in the result type. Use
+        :inst:`isub_bout` to detect unsigned overflow.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        """,
+        ins=(x, y), outs=(a, of))
+
+imul_ov = Instruction(
+        'imul_ov', r"""
+        Multiply integers with signed overflow detection.
+
+        Same as :inst:`imul` with an additional output that is set when the
+        signed product is not representable in the result type.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        """,
+        ins=(x, y), outs=(a, of))
+
+iadd_ov_trap = Instruction(
+        'iadd_ov_trap', r"""
+        Add integers and trap on signed overflow.
+
+        Computes the same result as :inst:`iadd`, but traps with ``code`` if
+        :inst:`iadd_ov` would report an overflow.
+        """,
+        ins=(x, y, code), outs=a, can_trap=True)
+
+isub_ov_trap = Instruction(
+        'isub_ov_trap', r"""
+        Subtract integers and trap on signed overflow.
+
+        Computes the same result as :inst:`isub`, but traps with ``code`` if
+        :inst:`isub_ov` would report an overflow.
+        """,
+        ins=(x, y, code), outs=a, can_trap=True)
+
+imul_ov_trap = Instruction(
+        'imul_ov_trap', r"""
+        Multiply integers and trap on signed overflow.
+
+        Computes the same result as :inst:`imul`, but traps with ``code`` if
+        :inst:`imul_ov` would report an overflow.
+        """,
+        ins=(x, y, code), outs=a, can_trap=True)
+
 #
 # Bitwise operations.
 #
@@ -1524,7 +1744,7 @@ fcvt_from_sint = Instruction(
 
 WideInt = TypeVar(
         'WideInt', 'An integer type with lanes from `i16` upwards',
-        ints=(16, 64), simd=True)
+        ints=(16, 128), simd=True)
 x = Operand('x', WideInt)
 lo = Operand(
         'lo', WideInt.half_width(), 'The low bits of `x`')
@@ -1545,8 +1765,8 @@ isplit = Instruction(
 
 
 NarrowInt = TypeVar(
-        'NarrowInt', 'An integer type with lanes type to `i32`',
-        ints=(8, 32), simd=True)
+        'NarrowInt', 'An integer type with lanes type to `i64`',
+        ints=(8, 64), simd=True)
 lo = Operand('lo', NarrowInt)
 hi = Operand('hi', NarrowInt)
 a = Operand(
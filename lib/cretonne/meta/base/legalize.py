@@ -11,7 +11,11 @@ from .immediates import intcc
 from .instructions import iadd, iadd_cout, iadd_cin, iadd_carry, iadd_imm
 from .instructions import isub, isub_bin, isub_bout, isub_borrow
 from .instructions import band, bor, bxor, isplit, iconcat
-from .instructions import icmp, iconst, bint
+from .instructions import imul, fadd, fsub, fmul, fdiv, vsplit, vconcat
+from .instructions import icmp, icmp_imm, iconst, bint, trapnz, smulhi
+from .instructions import umulhi
+from .instructions import iadd_ov, isub_ov, imul_ov
+from .instructions import iadd_ov_trap, isub_ov_trap, imul_ov_trap
 from cdsl.ast import Var
 from cdsl.xform import Rtl, XFormGroup
 
@@ -20,9 +24,19 @@ narrow = XFormGroup('narrow', """
         Legalize instructions by narrowing.
 
         The transformations in the 'narrow' group work by expressing
-        instructions in terms of smaller types. Operations on vector types are
-        expressed in terms of vector types with fewer lanes, and integer
-        operations are expressed in terms of smaller integer types.
+        instructions in terms of smaller types. Integer operations are
+        expressed in terms of smaller integer types.
+
+        Shifts and comparisons of wide integers can't be expressed as
+        patterns, so the legalizer narrows them in Rust code.
+        """)
+
+split_vector = XFormGroup('split_vector', """
+        Legalize vector instructions by splitting.
+
+        The transformations in the 'split_vector' group express operations on
+        vector types in terms of vector types with half as many lanes. This is
+        used for vectors that are wider than the target's SIMD registers.
         """)
 
 expand = XFormGroup('expand', """
@@ -47,6 +61,11 @@ c1 = Var('c1')
 c2 = Var('c2')
 c_in = Var('c_in')
 c_int = Var('c_int')
+code = Var('code')
+h = Var('h')
+m1 = Var('m1')
+m2 = Var('m2')
+of = Var('of')
 xl = Var('xl')
 xh = Var('xh')
 yl = Var('yl')
@@ -74,6 +93,22 @@ narrow.legalize(
             a << iconcat(al, ah)
         ))
 
+# The high part of the product gets the cross products, but not their
+# overflow.
+narrow.legalize(
+        a << imul(x, y),
+        Rtl(
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            al << imul(xl, yl),
+            h << umulhi(xl, yl),
+            m1 << imul(xl, yh),
+            m2 << imul(xh, yl),
+            a1 << iadd(h, m1),
+            ah << iadd(a1, m2),
+            a << iconcat(al, ah)
+        ))
+
 for bitop in [band, bor, bxor]:
     narrow.legalize(
             a << bitop(x, y),
@@ -85,6 +120,18 @@ for bitop in [band, bor, bxor]:
                 a << iconcat(al, ah)
             ))
 
+# Lane-wise vector operations are split into two halves.
+for binop in [iadd, isub, imul, band, bor, bxor, fadd, fsub, fmul, fdiv]:
+    split_vector.legalize(
+            a << binop(x, y),
+            Rtl(
+                (xl, xh) << vsplit(x),
+                (yl, yh) << vsplit(y),
+                al << binop(xl, yl),
+                ah << binop(xh, yh),
+                a << vconcat(al, ah)
+            ))
+
 # Expand integer operations with carry for RISC architectures that don't have
 # the flags.
 expand.legalize(
@@ -135,6 +182,51 @@ expand.legalize(
             b << bor(b1, b2)
         ))
 
+# Expand signed overflow detection into comparisons for targets that don't
+# have an overflow flag. An addition overflows when the result is less than
+# `x` exactly when `y` is non-negative.
+expand.legalize(
+        (a, of) << iadd_ov(x, y),
+        Rtl(
+            a << iadd(x, y),
+            c1 << icmp(intcc.slt, a, x),
+            c2 << icmp_imm(intcc.slt, y, 0),
+            of << bxor(c1, c2)
+        ))
+
+expand.legalize(
+        (a, of) << isub_ov(x, y),
+        Rtl(
+            a << isub(x, y),
+            c1 << icmp(intcc.slt, a, x),
+            c2 << icmp_imm(intcc.sgt, y, 0),
+            of << bxor(c1, c2)
+        ))
+
+# A product doesn't overflow when the high half is the sign extension of the
+# low half, i.e. 0 for a non-negative `a` and -1 for a negative `a`.
+expand.legalize(
+        (a, of) << imul_ov(x, y),
+        Rtl(
+            a << imul(x, y),
+            h << smulhi(x, y),
+            c1 << icmp_imm(intcc.slt, a, 0),
+            c_int << bint(c1),
+            a1 << iadd(h, c_int),
+            of << icmp_imm(intcc.ne, a1, 0)
+        ))
+
+for inst_trap, inst_ov in [
+        (iadd_ov_trap, iadd_ov),
+        (isub_ov_trap, isub_ov),
+        (imul_ov_trap, imul_ov)]:
+    expand.legalize(
+            a << inst_trap(x, y, code),
+            Rtl(
+                (a, of) << inst_ov(x, y),
+                trapnz(of, code)
+            ))
+
 # Expansions for immediates that are too large.
 expand.legalize(
         a << iadd_imm(x, y),
@@ -4,7 +4,7 @@ Cretonne shared settings.
 This module defines settings are are relevant for all code generators.
 """
 from __future__ import absolute_import
-from cdsl.settings import SettingGroup, BoolSetting, EnumSetting
+from cdsl.settings import SettingGroup, BoolSetting, EnumSetting, NumSetting
 
 group = SettingGroup('shared')
 
@@ -42,4 +42,23 @@ enable_atomics = BoolSetting(
         """Enable the use of atomic instructions""",
         default=True)
 
+loop_unroll_factor = NumSetting(
+        """
+        Number of copies of the body to make when unrolling small counted
+        loops.
+
+        A factor of 0 or 1 disables loop unrolling.
+        """,
+        default=1)
+
+if_conversion_cost = NumSetting(
+        """
+        Maximum number of instructions that if-conversion can add to the path
+        through a conditional branch when replacing it with `select`
+        instructions.
+
+        A cost of 0 disables if-conversion.
+        """,
+        default=4)
+
 group.close(globals())
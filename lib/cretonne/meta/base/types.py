@@ -20,6 +20,7 @@ i8 = IntType(8)     #: 8-bit int.
 i16 = IntType(16)   #: 16-bit int.
 i32 = IntType(32)   #: 32-bit int.
 i64 = IntType(64)   #: 64-bit int.
+i128 = IntType(128)  #: 128-bit int.
 
 #: IEEE single precision.
 f32 = FloatType(
@@ -307,7 +307,10 @@ class Encoding(object):
                 "Format {} must match recipe: {}".format(
                     self.inst.format, recipe.format))
 
-        if self.inst.is_branch:
+        # Indirect branches have no destination EBB and no range limit.
+        is_direct = any(
+                f.kind.name == 'ebb' for f in self.inst.format.imm_fields)
+        if self.inst.is_branch and is_direct:
             assert recipe.branch_range, (
                     'Recipe {} for {} must have a branch_range'
                     .format(recipe, self.inst.name))
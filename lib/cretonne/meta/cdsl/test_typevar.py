@@ -54,7 +54,7 @@ class TestTypeVar(TestCase):
         with self.assertRaises(AssertionError):
             x.half_width()
 
-        x2 = TypeVar('x2', 'i16 and up', ints=(16, 64))
+        x2 = TypeVar('x2', 'i16 and up', ints=(16, 128))
         with self.assertRaises(AssertionError):
             x2.double_width()
         self.assertEqual(str(x2.half_width()), '`half_width(x2)`')
@@ -19,7 +19,8 @@ except ImportError:
     pass
 
 MAX_LANES = 256
-MAX_BITS = 64
+MAX_INT_BITS = 128
+MAX_BOOL_BITS = 64
 
 
 def int_log2(x):
@@ -100,7 +101,7 @@ class TypeSet(object):
     Passing `True` instead of a range selects all available scalar types:
 
     >>> TypeSet(ints=True)
-    TypeSet(lanes=(1, 1), ints=(8, 64))
+    TypeSet(lanes=(1, 1), ints=(8, 128))
     >>> TypeSet(floats=True)
     TypeSet(lanes=(1, 1), floats=(32, 64))
     >>> TypeSet(bools=True)
@@ -110,7 +111,7 @@ class TypeSet(object):
     vector types:
 
     >>> TypeSet(lanes=True, ints=True)
-    TypeSet(lanes=(1, 256), ints=(8, 64))
+    TypeSet(lanes=(1, 256), ints=(8, 128))
 
     :param lanes: `(min, max)` inclusive range of permitted vector lane counts.
     :param ints: `(min, max)` inclusive range of permitted scalar integer
@@ -125,9 +126,10 @@ class TypeSet(object):
         # type: (BoolInterval, BoolInterval, BoolInterval, BoolInterval) -> None # noqa
         self.min_lanes, self.max_lanes = decode_interval(
                 lanes, (1, MAX_LANES), 1)
-        self.min_int, self.max_int = decode_interval(ints, (8, MAX_BITS))
+        self.min_int, self.max_int = decode_interval(ints, (8, MAX_INT_BITS))
         self.min_float, self.max_float = decode_interval(floats, (32, 64))
-        self.min_bool, self.max_bool = decode_interval(bools, (1, MAX_BITS))
+        self.min_bool, self.max_bool = decode_interval(
+                bools, (1, MAX_BOOL_BITS))
 
     def typeset_key(self):
         # type: () -> Tuple[int, int, int, int, int, int, int, int]
@@ -400,11 +402,13 @@ class TypeVar(object):
         if not self.is_derived:
             ts = self.type_set
             if ts.max_int:
-                assert ts.max_int < MAX_BITS, "Can't double all integer types."
+                assert ts.max_int < MAX_INT_BITS, \
+                    "Can't double all integer types."
             if ts.max_float:
-                assert ts.max_float < MAX_BITS, "Can't double all float types."
+                assert ts.max_float < 64, "Can't double all float types."
             if ts.max_bool:
-                assert ts.max_bool < MAX_BITS, "Can't double all bool types."
+                assert ts.max_bool < MAX_BOOL_BITS, \
+                    "Can't double all bool types."
 
         return TypeVar.derived(self, self.DOUBLEWIDTH)
 
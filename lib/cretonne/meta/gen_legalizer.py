@@ -242,4 +242,5 @@ def generate(isas, out_dir):
     fmt = Formatter()
     gen_xform_group(legalize.narrow, fmt)
     gen_xform_group(legalize.expand, fmt)
+    gen_xform_group(legalize.split_vector, fmt)
     fmt.update_file('legalizer.rs', out_dir)
@@ -3,31 +3,95 @@ Intel Encodings.
 """
 from __future__ import absolute_import
 from base import instructions as base
-from .defs import I32
+from base.immediates import atomicrmwop, intcc
+from base.types import i8, i16, i32, i64, f32, f64
+from base.formats import InsertLane
+from base.settings import enable_simd, enable_float
+from cdsl.ast import Var
+from cdsl.predicates import IsEqual
+from .defs import I32, I64
 from . import recipes as r
+from .settings import use_sse41
 
-I32.enc(base.iadd.i32, *r.rr(0x01))
-I32.enc(base.isub.i32, *r.rr(0x29))
+try:
+    from typing import Any  # noqa
+    from cdsl.instructions import MaybeBoundInst  # noqa
+    from cdsl.isa import EncRecipe  # noqa
+except ImportError:
+    pass
 
-I32.enc(base.band.i32, *r.rr(0x21))
-I32.enc(base.bor.i32,  *r.rr(0x09))
-I32.enc(base.bxor.i32, *r.rr(0x31))
+# Dummies for instruction predicates.
+p = Var('p')
+x = Var('x')
+y = Var('y')
+
+
+def enc_i32_i64(inst, recipe, bits, **kwargs):
+    # type: (MaybeBoundInst, EncRecipe, int, **Any) -> None
+    """
+    Add encodings for a 32-bit operation to both CPU modes.
+
+    Without a REX prefix, the 32-bit operations encode the same way in 64-bit
+    mode. They can only use the low 8 registers.
+    """
+    I32.enc(inst, recipe, bits, **kwargs)
+    I64.enc(inst, recipe, bits, **kwargs)
+
+
+for inst,           opc in [
+        (base.iadd, 0x01),
+        (base.isub, 0x29),
+        (base.band, 0x21),
+        (base.bor,  0x09),
+        (base.bxor, 0x31)]:
+    enc_i32_i64(inst.i32, *r.rr(opc))
+    I64.enc(inst.i64, *r.rr(opc, w=1))
 
 # Immediate instructions with sign-extended 8-bit and 32-bit immediate.
-for inst,                   rrr in [
-        (base.iadd_imm.i32, 0),
-        (base.band_imm.i32, 4),
-        (base.bor_imm.i32,  1),
-        (base.bxor_imm.i32, 6)]:
-    I32.enc(inst, *r.rib(0x83, rrr=rrr))
-    I32.enc(inst, *r.rid(0x81, rrr=rrr))
+for inst,               rrr in [
+        (base.iadd_imm, 0),
+        (base.band_imm, 4),
+        (base.bor_imm,  1),
+        (base.bxor_imm, 6)]:
+    enc_i32_i64(inst.i32, *r.rib(0x83, rrr=rrr))
+    enc_i32_i64(inst.i32, *r.rid(0x81, rrr=rrr))
+    I64.enc(inst.i64, *r.rib(0x83, rrr=rrr, w=1))
+    I64.enc(inst.i64, *r.rid(0x81, rrr=rrr, w=1))
+
+enc_i32_i64(base.iconst.i32, *r.puid(0xb8))
+# Writing a 32-bit register zero-extends into the 64-bit register, so that is
+# the shortest encoding for unsigned 32-bit immediates. Otherwise use a
+# sign-extended 32-bit immediate.
+I64.enc(base.iconst.i64, *r.puiq(0xb8))
+I64.enc(base.iconst.i64, *r.uid(0xc7, w=1))
 
 # 32-bit shifts and rotates.
 # Note that the dynamic shift amount is only masked by 5 or 6 bits; the 8-bit
 # and 16-bit shifts would need explicit masking.
-I32.enc(base.ishl.i32.i32, *r.rc(0xd3, rrr=4))
-I32.enc(base.ushr.i32.i32, *r.rc(0xd3, rrr=5))
-I32.enc(base.sshr.i32.i32, *r.rc(0xd3, rrr=7))
+enc_i32_i64(base.ishl.i32.i32, *r.rc(0xd3, rrr=4))
+enc_i32_i64(base.ushr.i32.i32, *r.rc(0xd3, rrr=5))
+enc_i32_i64(base.sshr.i32.i32, *r.rc(0xd3, rrr=7))
+for inst,               rrr in [
+        (base.ishl_imm, 4),
+        (base.ushr_imm, 5),
+        (base.sshr_imm, 7)]:
+    enc_i32_i64(inst.i32, *r.rib(0xc1, rrr=rrr))
+    I64.enc(inst.i64, *r.rib(0xc1, rrr=rrr, w=1))
+
+# Register copies and moves inserted by the register allocator.
+enc_i32_i64(base.copy.i32, *r.umr(0x89))
+enc_i32_i64(base.regmove.i32, *r.rmov(0x89))
+I64.enc(base.copy.i64, *r.umr(0x89, w=1))
+I64.enc(base.regmove.i64, *r.rmov(0x89, w=1))
+
+# Integer extensions from 32 to 64 bits. Writing a 32-bit register clears the
+# high bits, so uextend is a plain 32-bit move. sextend is movsxd.
+I64.enc(base.uextend.i64.i32, *r.umr(0x89))
+I64.enc(base.sextend.i64.i32, *r.urm(0x63, w=1))
+
+# Direct calls with a 32-bit PC-relative displacement, and returns.
+enc_i32_i64(base.call, *r.call_id(0xe8))
+enc_i32_i64(base.x_return, *r.ret(0xc3))
 
 # Loads and stores.
 I32.enc(base.store.i32.i32, *r.st(0x89))
@@ -61,3 +125,150 @@ I32.enc(base.uload8.i32.i32, *r.ldDisp32(0x0f, 0xb6))
 I32.enc(base.sload8.i32.i32, *r.ld(0x0f, 0xbe))
 I32.enc(base.sload8.i32.i32, *r.ldDisp8(0x0f, 0xbe))
 I32.enc(base.sload8.i32.i32, *r.ldDisp32(0x0f, 0xbe))
+
+# In 64-bit mode, the same encodings use a 64-bit address without a REX
+# prefix.
+I64.enc(base.store.i32.i64, *r.st(0x89))
+I64.enc(base.store.i32.i64, *r.stDisp8(0x89))
+I64.enc(base.store.i32.i64, *r.stDisp32(0x89))
+
+I64.enc(base.load.i32.i64, *r.ld(0x8b))
+I64.enc(base.load.i32.i64, *r.ldDisp8(0x8b))
+I64.enc(base.load.i32.i64, *r.ldDisp32(0x8b))
+
+I64.enc(base.load.i64.i64, *r.ld(0x8b, w=1))
+I64.enc(base.load.i64.i64, *r.ldDisp8(0x8b, w=1))
+I64.enc(base.load.i64.i64, *r.ldDisp32(0x8b, w=1))
+
+I64.enc(base.sload32.i64.i64, *r.ld(0x63, w=1))
+I64.enc(base.sload32.i64.i64, *r.ldDisp8(0x63, w=1))
+I64.enc(base.sload32.i64.i64, *r.ldDisp32(0x63, w=1))
+
+# Scalar float loads are movss and movsd.
+I32.enc(base.load.f32.i32, *r.fld(0xf3, 0x0f, 0x10), isap=enable_float)
+I32.enc(base.load.f64.i32, *r.fld(0xf2, 0x0f, 0x10), isap=enable_float)
+
+# Materialize the address of a symbolic global variable.
+I32.enc(base.globalsym_addr.i32, *r.gvaddr(0xb8))
+# The 32-bit immediate is zero-extended in 64-bit mode, so the symbol must be
+# in the low 4 GB of the address space.
+I64.enc(base.globalsym_addr.i64, *r.gvaddr(0xb8))
+
+# Materialize the address of a constant pool entry. It is absolute in 32-bit
+# mode and RIP-relative with lea in 64-bit mode.
+I32.enc(base.const_addr.i32, *r.constaddr(0xb8))
+I64.enc(base.const_addr.i64, *r.constrel(0x8d, w=1))
+
+# Compare and branch. The displacement is always 32 bits.
+enc_i32_i64(base.br_icmp.i32, *r.icbr(0x39))
+I64.enc(base.br_icmp.i64, *r.icbr(0x39, w=1))
+
+# Jump tables are addressed absolutely in 32-bit mode and RIP-relative with lea
+# in 64-bit mode. The indirect branch is jmp *r which uses a 64-bit register in
+# 64-bit mode without a REX prefix.
+I32.enc(base.jump_table_base.i32, *r.jtbase(0xb8))
+I64.enc(base.jump_table_base.i64, *r.jtrel(0x8d, w=1))
+I32.enc(base.indirect_jump_table_br.i32, *r.indjmp(0xff, rrr=4))
+I64.enc(base.indirect_jump_table_br.i64, *r.indjmp(0xff, rrr=4))
+
+# Trap as ud2.
+enc_i32_i64(base.trap, *r.trap(0x0f, 0x0b))
+
+# Overflow-checked arithmetic.
+I32.enc(base.iadd_ov.i32, *r.rrov(0x01))
+I32.enc(base.isub_ov.i32, *r.rrov(0x29))
+I32.enc(base.imul_ov.i32, *r.mulov(0x0f, 0xaf))
+I32.enc(base.iadd_ov_trap.i32, *r.rrovt(0x01))
+I32.enc(base.isub_ov_trap.i32, *r.rrovt(0x29))
+I32.enc(base.imul_ov_trap.i32, *r.mulovt(0x0f, 0xaf))
+
+# Atomic memory operations.
+I32.enc(base.atomic_load.i32.i32, *r.ald(0x8b))
+I32.enc(base.atomic_store.i32.i32, *r.ast(0x89))
+# xadd and xchg return the old memory value in the register operand. An xchg
+# with a memory operand is always locked, but the prefix doesn't hurt.
+I32.enc(base.atomic_rmw.i32.i32(atomicrmwop.add, p, x), *r.lrmw(0x0f, 0xc1))
+I32.enc(base.atomic_rmw.i32.i32(atomicrmwop.xchg, p, x), *r.lrmw(0x87))
+I32.enc(base.atomic_cas.i32.i32, *r.lcas(0x0f, 0xb1))
+# All fences are encoded as mfence. Acquire and release fences could be
+# elided on Intel, but mfence is always correct.
+I32.enc(base.fence, *r.fence(0x0f, 0xae, rrr=6))
+
+# 128-bit SIMD instructions.
+i8x16 = i8.by(16)
+i16x8 = i16.by(8)
+i32x4 = i32.by(4)
+i64x2 = i64.by(2)
+f32x4 = f32.by(4)
+f64x2 = f64.by(2)
+
+for ty,    padd, psub, pcmpeq, pcmpgt in [
+        (i8x16, 0xfc, 0xf8, 0x74, 0x64),
+        (i16x8, 0xfd, 0xf9, 0x75, 0x65),
+        (i32x4, 0xfe, 0xfa, 0x76, 0x66),
+        (i64x2, 0xd4, 0xfb, None, None)]:
+    I32.enc(base.iadd.bind(ty), *r.fa(0x66, 0x0f, padd), isap=enable_simd)
+    I32.enc(base.isub.bind(ty), *r.fa(0x66, 0x0f, psub), isap=enable_simd)
+    I32.enc(base.band.bind(ty), *r.fa(0x66, 0x0f, 0xdb), isap=enable_simd)
+    I32.enc(base.bor.bind(ty), *r.fa(0x66, 0x0f, 0xeb), isap=enable_simd)
+    I32.enc(base.bxor.bind(ty), *r.fa(0x66, 0x0f, 0xef), isap=enable_simd)
+    if pcmpeq:
+        I32.enc(
+                base.icmp.bind(ty)(intcc.eq, x, y),
+                *r.ficmp(0x66, 0x0f, pcmpeq), isap=enable_simd)
+        I32.enc(
+                base.icmp.bind(ty)(intcc.sgt, x, y),
+                *r.ficmp(0x66, 0x0f, pcmpgt), isap=enable_simd)
+
+# pcmpeqq and pmulld are SSE 4.1. There is no 64-bit lane multiplication.
+I32.enc(
+        base.icmp.i64x2(intcc.eq, x, y),
+        *r.ficmp(0x66, 0x0f, 0x38, 0x29), isap=use_sse41)
+I32.enc(base.imul.i16x8, *r.fa(0x66, 0x0f, 0xd5), isap=enable_simd)
+I32.enc(base.imul.i32x4, *r.fa(0x66, 0x0f, 0x38, 0x40), isap=use_sse41)
+
+# Float arithmetic: The ps instructions have no prefix, the pd instructions
+# have a 66 prefix.
+for inst,      op in [
+        (base.fadd, 0x58),
+        (base.fsub, 0x5c),
+        (base.fmul, 0x59),
+        (base.fdiv, 0x5e)]:
+    I32.enc(inst.f32x4, *r.fa(0x0f, op), isap=enable_simd)
+    I32.enc(inst.f64x2, *r.fa(0x66, 0x0f, op), isap=enable_simd)
+
+# Lane insertion and extraction. Only pextrw and pinsrw are SSE2.
+I32.enc(
+        base.extractlane.i8x16, *r.fextr(0x66, 0x0f, 0x3a, 0x14),
+        isap=use_sse41)
+I32.enc(base.extractlane.i16x8, *r.fextrw(0x66, 0x0f, 0xc5), isap=enable_simd)
+I32.enc(
+        base.extractlane.i32x4, *r.fextr(0x66, 0x0f, 0x3a, 0x16),
+        isap=use_sse41)
+I32.enc(base.extractlane.f32x4, *r.fextrf(0x66, 0x0f, 0x70), isap=enable_simd)
+I32.enc(base.extractlane.f64x2, *r.fextrf(0x66, 0x0f, 0x70), isap=enable_simd)
+
+I32.enc(
+        base.insertlane.i8x16, *r.finsr(0x66, 0x0f, 0x3a, 0x20),
+        isap=use_sse41)
+I32.enc(base.insertlane.i16x8, *r.finsr(0x66, 0x0f, 0xc4), isap=enable_simd)
+I32.enc(
+        base.insertlane.i32x4, *r.finsr(0x66, 0x0f, 0x3a, 0x22),
+        isap=use_sse41)
+I32.enc(
+        base.insertlane.f32x4, *r.finsrf(0x66, 0x0f, 0x3a, 0x21),
+        isap=use_sse41)
+# movsd replaces lane 0, unpcklpd replaces lane 1.
+I32.enc(
+        base.insertlane.f64x2, *r.finsrd(0xf2, 0x0f, 0x10),
+        instp=IsEqual(InsertLane.lane, 0), isap=enable_simd)
+I32.enc(
+        base.insertlane.f64x2, *r.finsrd(0x66, 0x0f, 0x14),
+        instp=IsEqual(InsertLane.lane, 1), isap=enable_simd)
+
+# Splats are a movd from the GPR followed by shuffles.
+I32.enc(base.splat.i8x16, *r.splat8(0x66, 0x0f, 0x6e), isap=enable_simd)
+I32.enc(base.splat.i16x8, *r.splat16(0x66, 0x0f, 0x6e), isap=enable_simd)
+I32.enc(base.splat.i32x4, *r.splat32(0x66, 0x0f, 0x6e), isap=enable_simd)
+I32.enc(base.splat.f32x4, *r.fsplat(0x66, 0x0f, 0x70), isap=enable_simd)
+I32.enc(base.splat.f64x2, *r.fsplat(0x66, 0x0f, 0x70), isap=enable_simd)
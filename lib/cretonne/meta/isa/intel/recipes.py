@@ -3,9 +3,14 @@ Intel Encoding recipes.
 """
 from __future__ import absolute_import
 from cdsl.isa import EncRecipe
-from cdsl.predicates import IsSignedInt, IsEqual
-from base.formats import Binary, BinaryImm, Store, Load
-from .registers import GPR, ABCD
+from cdsl.predicates import IsSignedInt, IsUnsignedInt, IsEqual
+from base.formats import Unary, UnaryImm, Binary, BinaryImm, Ternary
+from base.formats import MultiAry, Call, RegMove
+from base.formats import Store, Load, UnaryGlobalVar, UnaryConst, Trap
+from base.formats import AtomicRmw, Fence
+from base.formats import BranchIcmp, BranchTable, BranchTableBase
+from base.formats import IntCompare, InsertLane, ExtractLane, BinaryTrap
+from .registers import GPR, ABCD, FPR
 
 try:
     from typing import Tuple, Dict  # noqa
@@ -62,6 +67,10 @@ OPCODE_PREFIX = {
 #
 # There is some redundancy between bits 8-11 and the recipe names, but we have
 # enough bits, and the pp+mm format is ready for supporting VEX prefixes.
+#
+# Encodings with the REX.W bit set get a `Rex` recipe name prefix, and they are
+# one byte longer. Registers %r8-%r15 are not allocatable yet, so the emitted
+# REX prefix is always 0x48 with no R, X, or B bits.
 
 
 def decode_ops(ops, rrr=0, w=0):
@@ -73,6 +82,8 @@ def decode_ops(ops, rrr=0, w=0):
     assert rrr <= 0b111
     assert w <= 1
     name, mmpp = OPCODE_PREFIX[ops[:-1]]
+    if w:
+        name = 'Rex' + name
     op = ops[-1]
     assert op <= 256
     return (name, op | (mmpp << 8) | (rrr << 12) | (w << 15))
@@ -130,7 +141,7 @@ class TailRecipe:
             self.recipes[name] = EncRecipe(
                 name + self.name,
                 self.format,
-                len(ops) + self.size,
+                len(ops) + w + self.size,
                 ins=self.ins,
                 outs=self.outs,
                 branch_range=self.branch_range,
@@ -139,6 +150,9 @@ class TailRecipe:
         return (self.recipes[name], bits)
 
 
+# Trap instruction with no operands.
+trap = TailRecipe('trap', Trap, size=0, ins=(), outs=())
+
 # XX /r
 rr = TailRecipe('rr', Binary, size=1, ins=(GPR, GPR), outs=0)
 
@@ -155,6 +169,39 @@ rid = TailRecipe(
         'rid', BinaryImm, size=5, ins=GPR, outs=0,
         instp=IsSignedInt(BinaryImm.imm, 32))
 
+# XX+rd id unary with 32-bit immediate.
+puid = TailRecipe('puid', UnaryImm, size=4, ins=(), outs=GPR)
+
+# XX+rd id unary with 32-bit immediate zero-extended to 64 bits.
+puiq = TailRecipe(
+        'puiq', UnaryImm, size=4, ins=(), outs=GPR,
+        instp=IsUnsignedInt(UnaryImm.imm, 32))
+
+# XX /n id unary with 32-bit immediate sign-extended.
+uid = TailRecipe(
+        'uid', UnaryImm, size=5, ins=(), outs=GPR,
+        instp=IsSignedInt(UnaryImm.imm, 32))
+
+# XX /r with operands swapped, moving the input to a new output register.
+umr = TailRecipe('umr', Unary, size=1, ins=GPR, outs=GPR)
+
+# XX /r reading the input from r/m and writing the output to reg.
+urm = TailRecipe('urm', Unary, size=1, ins=GPR, outs=GPR)
+
+# XX /r register-register move for a `regmove`.
+rmov = TailRecipe('rmov', RegMove, size=1, ins=GPR, outs=())
+
+#
+# Call and return recipes.
+#
+# The arguments and return values are in the fixed ABI locations already.
+
+# XX id direct call with a Call function relocation.
+call_id = TailRecipe('call_id', Call, size=4, ins=(), outs=())
+
+# XX return.
+ret = TailRecipe('ret', MultiAry, size=0, ins=(), outs=())
+
 #
 # Store recipes.
 #
@@ -201,3 +248,133 @@ ldDisp8 = TailRecipe(
 ldDisp32 = TailRecipe(
         'ldDisp32', Load, size=5, ins=(GPR), outs=(GPR),
         instp=IsSignedInt(Load.offset, 32))
+
+# XX /r load into an SSE register with no offset.
+fld = TailRecipe(
+        'fld', Load, size=1, ins=(GPR), outs=(FPR),
+        instp=IsEqual(Load.offset, 0))
+
+#
+# Global variable recipes
+#
+
+# XX+rd id with Abs4 globalsym relocation.
+gvaddr = TailRecipe('gvaddr', UnaryGlobalVar, size=4, ins=(), outs=GPR)
+
+# XX+rd id with ConstAbs4 constant pool relocation.
+constaddr = TailRecipe('constaddr', UnaryConst, size=4, ins=(), outs=GPR)
+
+# XX /r RIP-relative address of a constant pool entry, for lea. The constant
+# pool follows the function body, so the displacement is filled in when
+# emitted. A ConstPcrel4 relocation is also recorded.
+constrel = TailRecipe('constrel', UnaryConst, size=5, ins=(), outs=GPR)
+
+#
+# Branch recipes.
+#
+
+# XX /r compare followed by a jcc with a 32-bit displacement. The condition
+# code is taken from the instruction.
+icbr = TailRecipe(
+        'icbr', BranchIcmp, size=7, ins=(GPR, GPR), outs=(),
+        branch_range=(8, 32))
+
+# XX+rd id with Abs4 jump table relocation.
+jtbase = TailRecipe('jtbase', BranchTableBase, size=4, ins=(), outs=GPR)
+
+# XX /r RIP-relative address of a jump table, for lea. The jump table follows
+# the function body, so the displacement is known without a relocation.
+jtrel = TailRecipe('jtrel', BranchTableBase, size=5, ins=(), outs=GPR)
+
+# XX /n indirect jump through a register.
+indjmp = TailRecipe('indjmp', BranchTable, size=1, ins=GPR, outs=())
+
+#
+# Atomic memory recipes.
+#
+# The atomic instructions are sequentially consistent. On Intel, plain loads
+# have acquire semantics, and locked instructions are full barriers.
+
+# XX /r register-indirect atomic load.
+ald = TailRecipe('ald', Unary, size=1, ins=GPR, outs=GPR)
+
+# XX /r register-indirect store followed by mfence (0F AE F0).
+ast = TailRecipe('ast', Binary, size=4, ins=(GPR, GPR), outs=())
+
+# F0 XX /r locked read-modify-write returning the old value in the register
+# operand.
+lrmw = TailRecipe('lrmw', AtomicRmw, size=2, ins=(GPR, GPR), outs=1)
+
+# F0 XX /r locked compare-and-exchange with the expected and old value in
+# %rax.
+lcas = TailRecipe(
+        'lcas', Ternary, size=2, ins=(GPR, GPR.rax, GPR), outs=GPR.rax)
+
+# XX /n memory fence with no operands.
+fence = TailRecipe('fence', Fence, size=1, ins=(), outs=())
+
+#
+# Overflow-checked arithmetic recipes.
+#
+# The overflow flag is materialized with seto and movzbl, so it needs a
+# register with an 8-bit sub-register. The trapping versions jump over a ud2
+# with jno.
+
+# XX /r followed by seto and movzbl.
+rrov = TailRecipe('rrov', Binary, size=7, ins=(GPR, GPR), outs=(0, ABCD))
+
+# XX /r two-operand multiplication with the destination in the ModR/M reg
+# field, followed by seto and movzbl.
+mulov = TailRecipe('mulov', Binary, size=7, ins=(GPR, GPR), outs=(0, ABCD))
+
+# XX /r followed by jno and ud2.
+rrovt = TailRecipe('rrovt', BinaryTrap, size=5, ins=(GPR, GPR), outs=0)
+
+# XX /r two-operand multiplication followed by jno and ud2.
+mulovt = TailRecipe('mulovt', BinaryTrap, size=5, ins=(GPR, GPR), outs=0)
+
+#
+# SIMD recipes.
+#
+# The 128-bit SSE instructions operate on the FPR registers. Unlike the
+# integer instructions above, they put the destination operand in the ModR/M
+# reg field.
+
+# XX /r two-operand vector instruction with the result tied to the first
+# operand.
+fa = TailRecipe('fa', Binary, size=1, ins=(FPR, FPR), outs=0)
+
+# XX /r vector integer comparison. The condition code is matched by
+# instruction predicates on the encodings.
+ficmp = TailRecipe('ficmp', IntCompare, size=1, ins=(FPR, FPR), outs=0)
+
+# XX /r ib extract a lane into a GPR in the ModR/M reg field (pextrw).
+fextrw = TailRecipe('fextrw', ExtractLane, size=2, ins=FPR, outs=GPR)
+
+# XX /r ib extract a lane into a GPR in the ModR/M r/m field (pextrb,
+# pextrd).
+fextr = TailRecipe('fextr', ExtractLane, size=2, ins=FPR, outs=GPR)
+
+# XX /r ib move a float lane to lane 0 of the result with pshufd.
+fextrf = TailRecipe('fextrf', ExtractLane, size=2, ins=FPR, outs=FPR)
+
+# XX /r ib insert a GPR into a lane (pinsrb, pinsrw, pinsrd).
+finsr = TailRecipe('finsr', InsertLane, size=2, ins=(FPR, GPR), outs=0)
+
+# XX /r ib insert lane 0 of an FPR into an f32 lane (insertps).
+finsrf = TailRecipe('finsrf', InsertLane, size=2, ins=(FPR, FPR), outs=0)
+
+# XX /r insert lane 0 of an FPR into an f64 lane. The lane is matched by
+# instruction predicates on the encodings (movsd, unpcklpd).
+finsrd = TailRecipe('finsrd', InsertLane, size=1, ins=(FPR, FPR), outs=0)
+
+# XX /r movd from a GPR followed by shuffles that broadcast lane 0.
+# 32-bit lanes: pshufd.
+splat32 = TailRecipe('splat32', Unary, size=6, ins=GPR, outs=FPR)
+# 16-bit lanes: pshuflw, pshufd.
+splat16 = TailRecipe('splat16', Unary, size=11, ins=GPR, outs=FPR)
+# 8-bit lanes: punpcklbw, pshuflw, pshufd.
+splat8 = TailRecipe('splat8', Unary, size=15, ins=GPR, outs=FPR)
+
+# XX /r ib broadcast a float in lane 0 of an FPR with pshufd.
+fsplat = TailRecipe('fsplat', Unary, size=2, ins=FPR, outs=FPR)
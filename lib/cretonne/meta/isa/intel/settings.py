@@ -2,10 +2,17 @@
 Intel settings.
 """
 from __future__ import absolute_import
-from cdsl.settings import SettingGroup
+from cdsl.settings import SettingGroup, BoolSetting
+from cdsl.predicates import And
 import base.settings as shared
 from .defs import ISA
 
 ISA.settings = SettingGroup('intel', parent=shared.group)
 
+# SSE2 is assumed to be available, so only the later SSE extensions have
+# settings.
+has_sse41 = BoolSetting("CPU supports SSE 4.1 instructions")
+
+use_sse41 = And(has_sse41, shared.enable_simd)
+
 ISA.settings.close(globals())
@@ -3,17 +3,20 @@ RISC-V Encodings.
 """
 from __future__ import absolute_import
 from base import instructions as base
-from base.immediates import intcc
+from base.immediates import intcc, atomicrmwop, ordering
 from .defs import RV32, RV64
-from .recipes import OPIMM, OPIMM32, OP, OP32, LUI, BRANCH, JALR, JAL
-from .recipes import R, Rshamt, Ricmp, I, Iicmp, Iret
-from .recipes import U, UJ, UJcall, SB, SBzero
-from .settings import use_m
+from .recipes import OPIMM, OPIMM32, OP, OP32, LUI, AIUPC, BRANCH, JALR, JAL
+from .recipes import LOAD, LOAD_FP, AMO, FENCE
+from .recipes import R, Rshamt, Ricmp, I, Iz, Isext, Iicmp, Iret, Iload, Ijt
+from .recipes import Irmov, Ifload, U, Ujt, Uconst, UJ, UJcall, SB, SBzero
+from .recipes import Ramo, Ramold, Ramost, Rsc, Ifence
+from .settings import use_m, use_a, use_f, use_d
 from cdsl.ast import Var
 
 # Dummies for instruction predicates.
 x = Var('x')
 y = Var('y')
+p = Var('p')
 dest = Var('dest')
 args = Var('args')
 
@@ -74,11 +77,89 @@ RV32.enc(base.iconst.i32, U, LUI())
 RV64.enc(base.iconst.i32, U, LUI())
 RV64.enc(base.iconst.i64, U, LUI())
 
+# Small integer constants are materialized by addi from %x0.
+RV32.enc(base.iconst.i32, Iz, OPIMM(0b000))
+RV64.enc(base.iconst.i32, Iz, OPIMM(0b000))
+RV64.enc(base.iconst.i64, Iz, OPIMM(0b000))
+
+# Sign extension from 32 bits is addiw with a zero immediate.
+RV64.enc(base.sextend.i64.i32, Isext, OPIMM32(0b000))
+
+# Register moves inserted by the register allocator are `addi rd, rs, 0`.
+RV32.enc(base.regmove.i32, Irmov, OPIMM(0b000))
+RV64.enc(base.regmove.i64, Irmov, OPIMM(0b000))
+RV64.enc(base.regmove.i32, Irmov, OPIMM(0b000))
+
+# Loads. RV64 has a zero-extending lwu, and lw sign-extends.
+RV32.enc(base.load.i32.i32, Iload, LOAD(0b010))
+RV64.enc(base.load.i32.i64, Iload, LOAD(0b010))
+RV64.enc(base.load.i64.i64, Iload, LOAD(0b011))
+RV64.enc(base.sload32.i64.i64, Iload, LOAD(0b010))
+RV64.enc(base.uload32.i64.i64, Iload, LOAD(0b110))
+
+# Float loads from the "F" and "D" standard extensions.
+RV32.enc(base.load.f32.i32, Ifload, LOAD_FP(0b010), isap=use_f)
+RV64.enc(base.load.f32.i64, Ifload, LOAD_FP(0b010), isap=use_f)
+RV32.enc(base.load.f64.i32, Ifload, LOAD_FP(0b011), isap=use_d)
+RV64.enc(base.load.f64.i64, Ifload, LOAD_FP(0b011), isap=use_d)
+
 # "M" Standard Extension for Integer Multiplication and Division.
 # Gated by the `use_m` flag.
 RV32.enc(base.imul.i32, R, OP(0b000, 0b0000001), isap=use_m)
 RV64.enc(base.imul.i64, R, OP(0b000, 0b0000001), isap=use_m)
 RV64.enc(base.imul.i32, R, OP32(0b000, 0b0000001), isap=use_m)
+# There are no 'w' variants of the high multiplications.
+for inst,         f3 in [
+        (base.smulhi, 0b001),
+        (base.umulhi, 0b011)]:
+    RV32.enc(inst.i32, R, OP(f3, 0b0000001), isap=use_m)
+    RV64.enc(inst.i64, R, OP(f3, 0b0000001), isap=use_m)
+
+# "A" Standard Extension for Atomic Instructions.
+# Gated by the `use_a` flag. There is no amosub, and compare-and-swap is
+# expanded to an LR/SC loop.
+# The operations are named by strings since `and` and `or` are Python
+# keywords.
+for op,      f5 in [
+        ('add',  0b00000),
+        ('xchg', 0b00001),
+        ('xor',  0b00100),
+        ('and',  0b01100),
+        ('or',   0b01000)
+        ]:
+    rmw = getattr(atomicrmwop, op)
+    RV32.enc(
+            base.atomic_rmw.i32.i32(rmw, p, x), Ramo, AMO(0b010, f5),
+            isap=use_a)
+    RV64.enc(
+            base.atomic_rmw.i64.i64(rmw, p, x), Ramo, AMO(0b011, f5),
+            isap=use_a)
+    RV64.enc(
+            base.atomic_rmw.i32.i64(rmw, p, x), Ramo, AMO(0b010, f5),
+            isap=use_a)
+
+# Atomic loads and stores are `amoor` with a zero operand and `amoswap` with
+# the old value discarded.
+for inst,               recipe, f5 in [
+        (base.atomic_load,       Ramold, 0b01000),
+        (base.atomic_store,      Ramost, 0b00001),
+        (base.load_reserved,     Ramold, 0b00010),
+        (base.store_conditional, Rsc,    0b00011)
+        ]:
+    RV32.enc(inst.i32.i32, recipe, AMO(0b010, f5), isap=use_a)
+    RV64.enc(inst.i64.i64, recipe, AMO(0b011, f5), isap=use_a)
+    RV64.enc(inst.i32.i64, recipe, AMO(0b010, f5), isap=use_a)
+
+# Fences use the predecessor and successor sets from the memory ordering.
+# The sets are the IORW bits, and only the RW bits are needed here.
+for order,              pred,   succ in [
+        (ordering.acquire, 0b0010, 0b0011),
+        (ordering.release, 0b0011, 0b0001),
+        (ordering.acq_rel, 0b0011, 0b0011),
+        (ordering.seq_cst, 0b0011, 0b0011)
+        ]:
+    RV32.enc(base.fence(order), Ifence, FENCE(pred, succ))
+    RV64.enc(base.fence(order), Ifence, FENCE(pred, succ))
 
 # Control flow.
 
@@ -109,6 +190,17 @@ for inst,           f3 in [
     RV32.enc(inst.b1, SBzero, BRANCH(f3))
     RV64.enc(inst.b1, SBzero, BRANCH(f3))
 
+# Jump tables are addressed PC-relative, and the indirect branch is a JALR
+# with no link register.
+RV32.enc(base.jump_table_base.i32, Ujt, AIUPC())
+RV64.enc(base.jump_table_base.i64, Ujt, AIUPC())
+RV32.enc(base.indirect_jump_table_br.i32, Ijt, JALR())
+RV64.enc(base.indirect_jump_table_br.i64, Ijt, JALR())
+
+# Constant pool entries are also addressed PC-relative.
+RV32.enc(base.const_addr.i32, Uconst, AIUPC())
+RV64.enc(base.const_addr.i64, Uconst, AIUPC())
+
 # Returns are a special case of JALR using %x1 to hold the return address.
 # The return address is provided by a special-purpose `link` return value that
 # is added by legalize_signature().
@@ -13,7 +13,9 @@ from cdsl.isa import EncRecipe
 from cdsl.predicates import IsSignedInt
 from base.formats import Binary, BinaryImm, MultiAry, IntCompare, IntCompareImm
 from base.formats import UnaryImm, BranchIcmp, Branch, Jump, Call
-from .registers import GPR
+from base.formats import Unary, AtomicRmw, Fence, Load
+from base.formats import BranchTable, BranchTableBase, UnaryConst, RegMove
+from .registers import GPR, FPR
 
 # The low 7 bits of a RISC-V instruction is the base opcode. All 32-bit
 # instructions have 11 as the two low bits, with bits 6:2 determining the base
@@ -29,6 +31,12 @@ def LOAD(funct3):
     return 0b00000 | (funct3 << 5)
 
 
+def LOAD_FP(funct3):
+    # type: (int) -> int
+    assert funct3 <= 0b111
+    return 0b00001 | (funct3 << 5)
+
+
 def STORE(funct3):
     # type: (int) -> int
     assert funct3 <= 0b111
@@ -78,6 +86,34 @@ def OP32(funct3, funct7):
     return 0b01110 | (funct3 << 5) | (funct7 << 8)
 
 
+def MISCMEM(funct3=0):
+    # type: (int) -> int
+    assert funct3 <= 0b111
+    return 0b00011 | (funct3 << 5)
+
+
+def FENCE(pred, succ):
+    # type: (int, int) -> int
+    """
+    Fence with `pred` and `succ` sets of IORW bits. The 8 bits go in the
+    funct7 position of the encbits.
+    """
+    assert pred <= 0b1111
+    assert succ <= 0b1111
+    return MISCMEM(0b000) | (pred << 12) | (succ << 8)
+
+
+def AMO(funct3, funct5):
+    # type: (int, int) -> int
+    """
+    Atomic memory operation. All atomic instructions are sequentially
+    consistent, so both the aq and rl bits are set.
+    """
+    assert funct3 <= 0b111
+    assert funct5 <= 0b11111
+    return 0b01011 | (funct3 << 5) | (((funct5 << 2) | 0b11) << 8)
+
+
 def AIUPC():
     # type: () -> int
     return 0b00101
@@ -107,6 +143,31 @@ Iicmp = EncRecipe(
         'Iicmp', IntCompareImm, size=4, ins=GPR, outs=GPR,
         instp=IsSignedInt(IntCompareImm.imm, 12))
 
+# I-type instruction with a hardcoded %x0 rs1.
+Iz = EncRecipe(
+        'Iz', UnaryImm, size=4, ins=(), outs=GPR,
+        instp=IsSignedInt(UnaryImm.imm, 12))
+
+# I-type instruction with a zero immediate, used for sign extension.
+Isext = EncRecipe('Isext', Unary, size=4, ins=GPR, outs=GPR)
+
+# I-type instruction with a zero immediate, used for register moves.
+Irmov = EncRecipe('Irmov', RegMove, size=4, ins=GPR, outs=())
+
+# I-type load with a 12-bit signed offset.
+Iload = EncRecipe(
+        'Iload', Load, size=4, ins=GPR, outs=GPR,
+        instp=IsSignedInt(Load.offset, 12))
+
+# I-type load into a float register with a 12-bit signed offset.
+Ifload = EncRecipe(
+        'Ifload', Load, size=4, ins=GPR, outs=FPR,
+        instp=IsSignedInt(Load.offset, 12))
+
+# I-type encoding for `jalr` as an indirect branch through a jump table,
+# with rd = %x0 and no offset.
+Ijt = EncRecipe('Ijt', BranchTable, size=4, ins=GPR, outs=())
+
 # I-type encoding for `jalr` as a return instruction. We won't use the
 # immediate offset.
 # The variable return values are not encoded.
@@ -117,6 +178,14 @@ U = EncRecipe(
         'U', UnaryImm, size=4, ins=(), outs=GPR,
         instp=IsSignedInt(UnaryImm.imm, 32, 12))
 
+# U-type auipc followed by an addi computing the PC-relative address of a jump
+# table. The encbits are for the auipc.
+Ujt = EncRecipe('Ujt', BranchTableBase, size=8, ins=(), outs=GPR)
+
+# The same auipc + addi sequence computing the address of a constant in the
+# constant pool. A ConstPcrel relocation is also recorded.
+Uconst = EncRecipe('Uconst', UnaryConst, size=8, ins=(), outs=GPR)
+
 # UJ-type unconditional branch instructions.
 UJ = EncRecipe('UJ', Jump, size=4, ins=(), outs=(), branch_range=(0, 21))
 UJcall = EncRecipe('UJcall', Call, size=4, ins=(), outs=())
@@ -134,3 +203,18 @@ SBzero = EncRecipe(
         'SBzero', Branch, size=4,
         ins=(GPR), outs=(),
         branch_range=(0, 13))
+
+# R-type atomic memory operation. The encbits are the same as for `R`.
+Ramo = EncRecipe('Ramo', AtomicRmw, size=4, ins=(GPR, GPR), outs=GPR)
+
+# R-type atomic load with rs2 fixed to zero, used for `lr` and `amoor`.
+Ramold = EncRecipe('Ramold', Unary, size=4, ins=GPR, outs=GPR)
+
+# R-type atomic store with rd fixed to zero, used for `amoswap`.
+Ramost = EncRecipe('Ramost', Binary, size=4, ins=(GPR, GPR), outs=())
+
+# R-type store-conditional. The stored value is the first operand.
+Rsc = EncRecipe('Rsc', Binary, size=4, ins=(GPR, GPR), outs=GPR)
+
+# I-type fence with the predecessor and successor sets in the immediate.
+Ifence = EncRecipe('Ifence', Fence, size=4, ins=(), outs=())
@@ -196,6 +196,8 @@ mod tests {
                    ValueConversion::VectorSplit);
         assert_eq!(legalize_abi_value(types::I64, &arg),
                    ValueConversion::IntSplit);
+        assert_eq!(legalize_abi_value(types::I128, &arg),
+                   ValueConversion::IntSplit);
 
         // Vector of integers is broken down, then sign-extended.
         arg.extension = ArgumentExtension::Sext;
@@ -0,0 +1,253 @@
+//! Disassembling binary machine code.
+//!
+//! The disassemblers only recognize the instructions that Cretonne's own code generators emit, so
+//! they are meant for inspecting and testing generated code, not for general binaries. Each ISA
+//! that has a disassembler returns it from `TargetIsa::disassembler()`.
+
+use binemit::CodeOffset;
+use isa::RegUnit;
+use std::fmt::{self, Write};
+
+/// A machine instruction decoded by a `Disassembler`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedInst {
+    /// The size of the instruction in bytes.
+    pub size: CodeOffset,
+
+    /// The assembler mnemonic, including any prefixes and suffixes.
+    pub mnemonic: String,
+
+    /// The operands in assembler syntax, separated by commas.
+    pub operands: String,
+
+    /// The register units of the register operands in the order they appear in `operands`.
+    ///
+    /// The registers used in memory operands are included.
+    pub regs: Vec<RegUnit>,
+}
+
+impl fmt::Display for DecodedInst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.operands.is_empty() {
+            write!(f, "{}", self.mnemonic)
+        } else {
+            write!(f, "{} {}", self.mnemonic, self.operands)
+        }
+    }
+}
+
+/// Decoder for the machine code emitted for an ISA.
+pub trait Disassembler {
+    /// Decode the instruction at the start of `code`.
+    ///
+    /// The `offset` of the instruction is used to compute the targets of relative branches.
+    /// Returns `None` if the bytes are not an instruction that Cretonne emits.
+    fn decode(&self, code: &[u8], offset: CodeOffset) -> Option<DecodedInst>;
+}
+
+/// Disassemble `code` which starts at `offset`, one instruction per line.
+///
+/// Each line has the offset of the instruction, its bytes in hexadecimal, and the decoded
+/// instruction. Bytes that can't be decoded are shown one at a time as `.byte` directives.
+pub fn disassemble(dis: &Disassembler, code: &[u8], offset: CodeOffset) -> String {
+    let mut text = String::new();
+    let mut pos = 0;
+    while pos < code.len() {
+        let at = offset + pos as CodeOffset;
+        let (size, inst) = match dis.decode(&code[pos..], at) {
+            Some(inst) => (inst.size as usize, inst.to_string()),
+            None => (1, format!(".byte 0x{:02x}", code[pos])),
+        };
+        let mut bytes = String::new();
+        for byte in &code[pos..pos + size] {
+            write!(bytes, "{:02x} ", byte).unwrap();
+        }
+        writeln!(text, "{:6x}:  {:<24}{}", at, bytes, inst).unwrap();
+        pos += size;
+    }
+    text
+}
+
+/// Decode every instruction in `code` which starts at `offset`.
+///
+/// Returns `None` unless the whole `code` decodes to a sequence of instructions.
+pub fn decode_all(dis: &Disassembler, code: &[u8], offset: CodeOffset) -> Option<Vec<DecodedInst>> {
+    let mut insts = Vec::new();
+    let mut pos = 0;
+    while pos < code.len() {
+        let inst = dis.decode(&code[pos..], offset + pos as CodeOffset)?;
+        pos += inst.size as usize;
+        insts.push(inst);
+    }
+    if pos == code.len() { Some(insts) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binemit::{CodeSink, Reloc};
+    use ir::{Constant, Ebb, FuncRef, Function, GlobalVar, InstructionData, JumpTable, MemFlags,
+             Opcode, SourceLoc, TrapCode, Type, Value, ValueLoc, types};
+    use ir::immediates::{Imm64, Offset32};
+    use isa::{self, TargetIsa};
+    use settings::{self, Configurable};
+
+    /// Code sink that collects the emitted bytes and ignores everything else.
+    struct VecSink(Vec<u8>);
+
+    impl CodeSink for VecSink {
+        fn offset(&self) -> CodeOffset {
+            self.0.len() as CodeOffset
+        }
+        fn put1(&mut self, x: u8) {
+            self.0.push(x);
+        }
+        fn put2(&mut self, x: u16) {
+            self.put1(x as u8);
+            self.put1((x >> 8) as u8);
+        }
+        fn put4(&mut self, x: u32) {
+            self.put2(x as u16);
+            self.put2((x >> 16) as u16);
+        }
+        fn put8(&mut self, x: u64) {
+            self.put4(x as u32);
+            self.put4((x >> 32) as u32);
+        }
+        fn reloc_ebb(&mut self, _: Reloc, _: Ebb) {}
+        fn reloc_func(&mut self, _: Reloc, _: FuncRef) {}
+        fn reloc_globalsym(&mut self, _: Reloc, _: GlobalVar) {}
+        fn reloc_jt(&mut self, _: Reloc, _: JumpTable) {}
+        fn reloc_constant(&mut self, _: Reloc, _: Constant) {}
+        fn trap(&mut self, _: TrapCode, _: SourceLoc) {}
+        fn srcloc(&mut self, _: SourceLoc) {}
+    }
+
+    fn isa(name: &str) -> Box<TargetIsa> {
+        let mut builder = settings::builder();
+        builder.set_bool("is_64bit", false).unwrap();
+        isa::lookup(name)
+            .unwrap()
+            .finish(settings::Flags::new(&builder))
+    }
+
+    /// Encode and emit a single instruction, and then decode it again.
+    ///
+    /// The arguments are EBB arguments in the registers `args`, and the result, if any, is
+    /// assigned to the register `result`. Returns the decoded mnemonic and registers.
+    fn round_trip<F>(isa: &TargetIsa,
+                     ty: Type,
+                     args: &[RegUnit],
+                     result: RegUnit,
+                     make: F)
+                     -> (String, Vec<RegUnit>)
+        where F: FnOnce(&[Value]) -> InstructionData
+    {
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        func.layout.append_ebb(ebb);
+        let mut values = Vec::new();
+        for &reg in args {
+            let v = func.dfg.append_ebb_arg(ebb, ty);
+            *func.locations.ensure(v) = ValueLoc::Reg(reg);
+            values.push(v);
+        }
+        let inst = func.dfg.make_inst(make(&values));
+        func.dfg.make_inst_results(inst, ty);
+        func.layout.append_inst(inst, ebb);
+        for &v in func.dfg.inst_results(inst) {
+            *func.locations.ensure(v) = ValueLoc::Reg(result);
+        }
+        let enc = isa.encode(&func.dfg, &func.dfg[inst], ty)
+            .expect("no encoding");
+        *func.encodings.ensure(inst) = enc;
+
+        let mut sink = VecSink(Vec::new());
+        isa.emit_inst(&func, inst, &mut sink);
+        let dis = isa.disassembler().unwrap();
+        let decoded = decode_all(&*dis, &sink.0, 0).expect("can't decode emitted code");
+        assert_eq!(decoded.len(), 1, "{:?}", decoded);
+        (decoded[0].mnemonic.clone(), decoded[0].regs.clone())
+    }
+
+    fn binary(opcode: Opcode) -> Box<Fn(&[Value]) -> InstructionData> {
+        Box::new(move |args| InstructionData::Binary { opcode, args: [args[0], args[1]] })
+    }
+
+    fn binary_imm(opcode: Opcode, imm: i64) -> Box<Fn(&[Value]) -> InstructionData> {
+        Box::new(move |args| {
+                     InstructionData::BinaryImm {
+                         opcode,
+                         arg: args[0],
+                         imm: Imm64::new(imm),
+                     }
+                 })
+    }
+
+    fn load(offset: i32) -> Box<Fn(&[Value]) -> InstructionData> {
+        Box::new(move |args| {
+                     InstructionData::Load {
+                         opcode: Opcode::Load,
+                         flags: MemFlags::new(),
+                         arg: args[0],
+                         offset: Offset32::new(offset),
+                     }
+                 })
+    }
+
+    fn store(offset: i32) -> Box<Fn(&[Value]) -> InstructionData> {
+        Box::new(move |args| {
+                     InstructionData::Store {
+                         opcode: Opcode::Store,
+                         flags: MemFlags::new(),
+                         args: [args[0], args[1]],
+                         offset: Offset32::new(offset),
+                     }
+                 })
+    }
+
+    #[test]
+    fn intel_round_trip() {
+        let isa = isa("intel");
+        let isa = &*isa;
+        let i32 = types::I32;
+        // %rcx = 1, %rsi = 6, %rdi = 7.
+        assert_eq!(round_trip(isa, i32, &[1, 6], 1, binary(Opcode::Iadd)),
+                   ("addl".to_string(), vec![6, 1]));
+        assert_eq!(round_trip(isa, i32, &[6, 1], 6, binary(Opcode::Isub)),
+                   ("subl".to_string(), vec![1, 6]));
+        assert_eq!(round_trip(isa, i32, &[6, 1], 6, binary(Opcode::Ishl)),
+                   ("shll".to_string(), vec![1, 6]));
+        assert_eq!(round_trip(isa, i32, &[7], 7, binary_imm(Opcode::BxorImm, 10)),
+                   ("xorl".to_string(), vec![7]));
+        assert_eq!(round_trip(isa, i32, &[7], 7, binary_imm(Opcode::IaddImm, 100000)),
+                   ("addl".to_string(), vec![7]));
+        assert_eq!(round_trip(isa, i32, &[1], 7, load(0)),
+                   ("movl".to_string(), vec![1, 7]));
+        assert_eq!(round_trip(isa, i32, &[1], 7, load(-50)),
+                   ("movl".to_string(), vec![1, 7]));
+        assert_eq!(round_trip(isa, i32, &[7, 6], 0, store(10000)),
+                   ("movl".to_string(), vec![7, 6]));
+    }
+
+    #[test]
+    fn riscv_round_trip() {
+        let isa = isa("riscv");
+        let isa = &*isa;
+        let i32 = types::I32;
+        assert_eq!(round_trip(isa, i32, &[10, 21], 7, binary(Opcode::Iadd)),
+                   ("add".to_string(), vec![7, 10, 21]));
+        assert_eq!(round_trip(isa, i32, &[21, 10], 16, binary(Opcode::Isub)),
+                   ("sub".to_string(), vec![16, 21, 10]));
+        assert_eq!(round_trip(isa, i32, &[10, 21], 7, binary(Opcode::Sshr)),
+                   ("sra".to_string(), vec![7, 10, 21]));
+        assert_eq!(round_trip(isa, i32, &[21], 16, binary_imm(Opcode::BandImm, -905)),
+                   ("andi".to_string(), vec![16, 21]));
+        assert_eq!(round_trip(isa, i32, &[10], 7, binary_imm(Opcode::IshlImm, 31)),
+                   ("slli".to_string(), vec![7, 10]));
+        assert_eq!(round_trip(isa, i32, &[10], 7, load(-4)),
+                   ("lw".to_string(), vec![7, 10]));
+        assert_eq!(round_trip(isa, i32, &[21, 10], 21, binary(Opcode::Bor)),
+                   ("or".to_string(), vec![21, 21, 10]));
+    }
+}
@@ -0,0 +1,300 @@
+//! Code sink that writes binary machine code into contiguous memory.
+//!
+//! The `CodeSink` trait is the most general way of extracting binary machine code from Cretonne,
+//! and it is implemented by things like the `test binemit` file test driver to generate
+//! hexadecimal machine code. The `CodeSink` has some undesirable performance properties because of
+//! the dual abstraction: `TargetIsa` is a trait object implemented by each supported ISA, so it
+//! can't have any generic functions that could be specialized for each `CodeSink` implementation.
+//! This results in many virtual function callbacks (one per `put*` call) when
+//! `TargetIsa::emit_inst()` is used.
+//!
+//! The `MemoryCodeSink` type fixes the performance problem because it is a type known to
+//! `TargetIsa` so it can specialize its machine code generation for the type. The trade-off is
+//! that a `MemoryCodeSink` will always write binary machine code to raw memory. It forwards any
+//! relocations to a `RelocSink` trait object and any trap and source location information to a
+//! `TrapSink` trait object. Relocations are also not very common, so the virtual function calls
+//! don't matter much.
+
+use super::{CodeSink, CodeOffset, Reloc};
+use entity_map::EntityMap;
+use ir::{Ebb, FuncRef, GlobalVar, JumpTable, Constant, SourceLoc, TrapCode};
+use std::ptr::write_unaligned;
+
+/// A `CodeSink` that writes binary machine code directly into memory.
+///
+/// A `MemoryCodeSink` object should be used when emitting a Cretonne IL function into executable
+/// memory. It writes machine code directly to a caller-provided byte buffer, and it sends
+/// relocation and trap information to the `RelocSink` and `TrapSink` trait objects.
+///
+/// The buffer must be large enough to hold the whole function. The size is returned by
+/// `binemit::relax_branches()`. All supported targets are little-endian.
+pub struct MemoryCodeSink<'a> {
+    data: &'a mut [u8],
+    offset: usize,
+    ebb_offsets: &'a EntityMap<Ebb, CodeOffset>,
+    relocs: &'a mut RelocSink,
+    traps: &'a mut TrapSink,
+}
+
+impl<'a> MemoryCodeSink<'a> {
+    /// Create a new memory code sink that writes a function to the `data` buffer.
+    ///
+    /// The EBB offsets computed by `binemit::relax_branches()` are used to report the final
+    /// offset of EBB relocations.
+    pub fn new(data: &'a mut [u8],
+               ebb_offsets: &'a EntityMap<Ebb, CodeOffset>,
+               relocs: &'a mut RelocSink,
+               traps: &'a mut TrapSink)
+               -> MemoryCodeSink<'a> {
+        MemoryCodeSink {
+            data,
+            offset: 0,
+            ebb_offsets,
+            relocs,
+            traps,
+        }
+    }
+
+    /// Write `x` to the buffer and advance the offset by its size.
+    fn write<T>(&mut self, x: T) {
+        let size = ::std::mem::size_of::<T>();
+        assert!(self.offset + size <= self.data.len(),
+                "Code buffer overflow at offset {}",
+                self.offset);
+        // The bounds check above makes this write safe. The buffer has no alignment guarantees.
+        unsafe {
+            let p = self.data.as_mut_ptr().offset(self.offset as isize);
+            write_unaligned(p as *mut T, x);
+        }
+        self.offset += size;
+    }
+}
+
+/// A trait for receiving relocations for code that is emitted directly into memory.
+///
+/// Each relocation is reported with the offset of the code to be patched, relative to the
+/// beginning of the function.
+pub trait RelocSink {
+    /// Add a relocation referencing an EBB whose final offset is `ebb_offset`.
+    fn reloc_ebb(&mut self, offset: CodeOffset, reloc: Reloc, ebb_offset: CodeOffset);
+
+    /// Add a relocation referencing an external function.
+    fn reloc_func(&mut self, offset: CodeOffset, reloc: Reloc, func: FuncRef);
+
+    /// Add a relocation referencing a global variable symbol.
+    fn reloc_globalsym(&mut self, offset: CodeOffset, reloc: Reloc, gv: GlobalVar);
+
+    /// Add a relocation referencing a jump table.
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable);
+
+    /// Add a relocation referencing a constant in the function's constant pool.
+    ///
+    /// The constant is emitted at `func.const_offsets[constant]` relative to the beginning of the
+    /// function.
+    fn reloc_constant(&mut self, offset: CodeOffset, reloc: Reloc, constant: Constant);
+}
+
+/// A trait for receiving trap codes and offsets.
+///
+/// If you don't need information about possible traps, you can use the `NullTrapSink`
+/// implementation.
+pub trait TrapSink {
+    /// Add trap information for a specific offset.
+    fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode);
+
+    /// Record the source location of the instruction emitted at `offset`.
+    ///
+    /// This is called for every instruction, including the ones that can't trap. The default
+    /// implementation ignores the source locations.
+    fn srcloc(&mut self, _offset: CodeOffset, _srcloc: SourceLoc) {}
+}
+
+/// A `TrapSink` implementation that does nothing.
+pub struct NullTrapSink {}
+
+impl TrapSink for NullTrapSink {
+    fn trap(&mut self, _offset: CodeOffset, _srcloc: SourceLoc, _code: TrapCode) {}
+}
+
+impl<'a> CodeSink for MemoryCodeSink<'a> {
+    fn offset(&self) -> CodeOffset {
+        self.offset as CodeOffset
+    }
+
+    fn put1(&mut self, x: u8) {
+        self.write(x);
+    }
+
+    fn put2(&mut self, x: u16) {
+        self.write(x.to_le());
+    }
+
+    fn put4(&mut self, x: u32) {
+        self.write(x.to_le());
+    }
+
+    fn put8(&mut self, x: u64) {
+        self.write(x.to_le());
+    }
+
+    fn reloc_ebb(&mut self, rel: Reloc, ebb: Ebb) {
+        let ofs = self.offset();
+        let ebb_offset = self.ebb_offsets[ebb];
+        self.relocs.reloc_ebb(ofs, rel, ebb_offset);
+    }
+
+    fn reloc_func(&mut self, rel: Reloc, func: FuncRef) {
+        let ofs = self.offset();
+        self.relocs.reloc_func(ofs, rel, func);
+    }
+
+    fn reloc_globalsym(&mut self, rel: Reloc, gv: GlobalVar) {
+        let ofs = self.offset();
+        self.relocs.reloc_globalsym(ofs, rel, gv);
+    }
+
+    fn reloc_jt(&mut self, rel: Reloc, jt: JumpTable) {
+        let ofs = self.offset();
+        self.relocs.reloc_jt(ofs, rel, jt);
+    }
+
+    fn reloc_constant(&mut self, rel: Reloc, constant: Constant) {
+        let ofs = self.offset();
+        self.relocs.reloc_constant(ofs, rel, constant);
+    }
+
+    fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+        let ofs = self.offset();
+        self.traps.trap(ofs, srcloc, code);
+    }
+
+    fn srcloc(&mut self, srcloc: SourceLoc) {
+        let ofs = self.offset();
+        self.traps.srcloc(ofs, srcloc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use entity_map::EntityRef;
+
+    #[derive(Default)]
+    struct Relocs {
+        log: Vec<String>,
+    }
+
+    impl RelocSink for Relocs {
+        fn reloc_ebb(&mut self, offset: CodeOffset, reloc: Reloc, ebb_offset: CodeOffset) {
+            self.log
+                .push(format!("{}: ebb {} -> {}", offset, reloc.0, ebb_offset));
+        }
+
+        fn reloc_func(&mut self, offset: CodeOffset, reloc: Reloc, func: FuncRef) {
+            self.log.push(format!("{}: func {} {}", offset, reloc.0, func));
+        }
+
+        fn reloc_globalsym(&mut self, offset: CodeOffset, reloc: Reloc, gv: GlobalVar) {
+            self.log.push(format!("{}: gv {} {}", offset, reloc.0, gv));
+        }
+
+        fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+            self.log.push(format!("{}: jt {} {}", offset, reloc.0, jt));
+        }
+
+        fn reloc_constant(&mut self, offset: CodeOffset, reloc: Reloc, constant: Constant) {
+            self.log
+                .push(format!("{}: const {} {}", offset, reloc.0, constant));
+        }
+    }
+
+    #[derive(Default)]
+    struct Traps {
+        log: Vec<String>,
+    }
+
+    impl TrapSink for Traps {
+        fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode) {
+            self.log.push(format!("{}: trap {} {}", offset, code, srcloc));
+        }
+
+        fn srcloc(&mut self, offset: CodeOffset, srcloc: SourceLoc) {
+            self.log.push(format!("{}: srcloc {}", offset, srcloc));
+        }
+    }
+
+    #[test]
+    fn little_endian() {
+        let mut buf = [0u8; 15];
+        let offsets = EntityMap::new();
+        let mut relocs = Relocs::default();
+        let mut traps = NullTrapSink {};
+        {
+            let mut sink = MemoryCodeSink::new(&mut buf, &offsets, &mut relocs, &mut traps);
+            sink.put1(0x01);
+            sink.put2(0x0302);
+            sink.put4(0x07060504);
+            sink.put8(0x0f0e0d0c0b0a0908);
+            assert_eq!(sink.offset(), 15);
+        }
+        assert_eq!(buf,
+                   [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Code buffer overflow")]
+    fn overflow() {
+        let mut buf = [0u8; 3];
+        let offsets = EntityMap::new();
+        let mut relocs = Relocs::default();
+        let mut traps = NullTrapSink {};
+        let mut sink = MemoryCodeSink::new(&mut buf, &offsets, &mut relocs, &mut traps);
+        sink.put2(0);
+        sink.put2(0);
+    }
+
+    #[test]
+    fn relocations() {
+        let mut buf = [0u8; 16];
+        let mut offsets = EntityMap::new();
+        let ebb = Ebb::new(3);
+        *offsets.ensure(ebb) = 12;
+        let mut relocs = Relocs::default();
+        let mut traps = NullTrapSink {};
+        {
+            let mut sink = MemoryCodeSink::new(&mut buf, &offsets, &mut relocs, &mut traps);
+            sink.put4(0);
+            sink.reloc_ebb(Reloc(1), ebb);
+            sink.put4(0);
+            sink.reloc_func(Reloc(0), FuncRef::new(2));
+            sink.reloc_jt(Reloc(2), JumpTable::new(0));
+            sink.put4(0);
+            sink.reloc_globalsym(Reloc(0), GlobalVar::new(1));
+            sink.reloc_constant(Reloc(3), Constant::new(4));
+        }
+        assert_eq!(relocs.log,
+                   ["4: ebb 1 -> 12",
+                    "8: func 0 fn2",
+                    "8: jt 2 jt0",
+                    "12: gv 0 gv1",
+                    "12: const 3 const4"]);
+    }
+
+    #[test]
+    fn srclocs() {
+        let mut buf = [0u8; 8];
+        let offsets = EntityMap::new();
+        let mut relocs = Relocs::default();
+        let mut traps = Traps::default();
+        {
+            let mut sink = MemoryCodeSink::new(&mut buf, &offsets, &mut relocs, &mut traps);
+            sink.srcloc(SourceLoc::new(7));
+            sink.put4(0);
+            sink.srcloc(SourceLoc::new(9));
+            sink.trap(TrapCode::HeapOutOfBounds, SourceLoc::new(9));
+            sink.put4(0);
+        }
+        assert_eq!(traps.log,
+                   ["0: srcloc @0007", "4: srcloc @0009", "4: trap heap_oob @0009"]);
+    }
+}
@@ -3,11 +3,17 @@
 //! The `binemit` module contains code for translating Cretonne's intermediate representation into
 //! binary machine code.
 
+mod disasm;
+mod memorysink;
 mod relaxation;
 
+pub use self::disasm::{DecodedInst, Disassembler, disassemble, decode_all};
+pub use self::memorysink::{MemoryCodeSink, RelocSink, TrapSink, NullTrapSink};
 pub use self::relaxation::relax_branches;
 
-use ir::{Ebb, FuncRef, JumpTable, Function, Inst};
+use ir::{Ebb, FuncRef, GlobalVar, JumpTable, Constant, Function, Inst, Opcode, SourceLoc,
+         TrapCode};
+use isa::TargetIsa;
 
 /// Offset in bytes from the beginning of the function.
 ///
@@ -44,8 +50,41 @@ pub trait CodeSink {
     /// Add a relocation referencing an external function at the current offset.
     fn reloc_func(&mut self, Reloc, FuncRef);
 
+    /// Add a relocation referencing a global variable symbol at the current offset.
+    fn reloc_globalsym(&mut self, Reloc, GlobalVar);
+
     /// Add a relocation referencing a jump table.
     fn reloc_jt(&mut self, Reloc, JumpTable);
+
+    /// Add a relocation referencing a constant in the function's constant pool.
+    fn reloc_constant(&mut self, Reloc, Constant);
+
+    /// Add trap information for the current offset.
+    ///
+    /// The instruction emitted at the current offset may trap for the given reason. The source
+    /// location of the trapping instruction is provided for building trap tables.
+    fn trap(&mut self, TrapCode, SourceLoc);
+
+    /// Set the source location of the instruction emitted at the current offset.
+    ///
+    /// This is called before each instruction is emitted, whether it can trap or not. It makes it
+    /// possible to map any code offset, like a call return address, back to the source.
+    fn srcloc(&mut self, SourceLoc);
+}
+
+/// Report a trap at the current offset if `inst` can trap.
+///
+/// Recipes call this before emitting the bytes of an instruction that can trap. The trap code is
+/// derived from the instruction by `InstructionData::trap_code()`.
+pub fn trap_site<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    let srcloc = func.srclocs.get_or_default(inst);
+    if let Some(code) = func.dfg[inst].trap_code() {
+        sink.trap(code, srcloc);
+    }
+    // A signed division can trap for two reasons at the same offset.
+    if func.dfg[inst].opcode() == Opcode::Sdiv {
+        sink.trap(TrapCode::IntegerOverflow, srcloc);
+    }
 }
 
 /// Report a bad encoding error.
@@ -55,3 +94,141 @@ pub fn bad_encoding(func: &Function, inst: Inst) -> ! {
            func.encodings[inst],
            func.dfg.display_inst(inst));
 }
+
+/// Emit a function to `sink`, given an instruction emitter function.
+///
+/// This function is called from the `TargetIsa::emit_function()` implementations with the
+/// appropriate instruction emitter.
+pub fn emit_function<CS, EI>(func: &Function, emit_inst: EI, sink: &mut CS)
+    where CS: CodeSink,
+          EI: Fn(&Function, Inst, &mut CS)
+{
+    for ebb in func.layout.ebbs() {
+        assert_eq!(func.offsets[ebb],
+                   sink.offset(),
+                   "Wrong offset for {}, run relax_branches() first",
+                   ebb);
+        for inst in func.layout.ebb_insts(ebb) {
+            sink.srcloc(func.srclocs.get_or_default(inst));
+            emit_inst(func, inst, sink);
+        }
+    }
+
+    emit_jump_tables(func, sink);
+    emit_constants(func, sink);
+}
+
+/// Emit the jump tables of `func` after the function body.
+fn emit_jump_tables<CS: CodeSink + ?Sized>(func: &Function, sink: &mut CS) {
+    for jt in func.jump_tables.keys() {
+        // Pad the code up to the aligned jump table.
+        while sink.offset() < func.jt_offsets[jt] {
+            sink.put1(0);
+        }
+        emit_jump_table(func, jt, sink);
+    }
+}
+
+/// Emit the jump table `jt` at the current offset.
+///
+/// Each entry is the offset of the destination EBB relative to the start of the table. Holes in
+/// the table are emitted as 0.
+pub fn emit_jump_table<CS: CodeSink + ?Sized>(func: &Function, jt: JumpTable, sink: &mut CS) {
+    let jt_offset = func.jt_offsets[jt];
+    assert_eq!(sink.offset(),
+               jt_offset,
+               "Wrong offset for {}, run relax_branches() first",
+               jt);
+    let jt_data = &func.jump_tables[jt];
+    for idx in 0..jt_data.len() {
+        let rel = jt_data
+            .get_entry(idx)
+            .map_or(0, |ebb| func.offsets[ebb].wrapping_sub(jt_offset));
+        sink.put4(rel);
+    }
+}
+
+/// Emit the constant pool of `func` after the jump tables.
+fn emit_constants<CS: CodeSink + ?Sized>(func: &Function, sink: &mut CS) {
+    for constant in func.constants.keys() {
+        // Pad the code up to the aligned constant.
+        while sink.offset() < func.const_offsets[constant] {
+            sink.put1(0);
+        }
+        emit_constant(func, constant, sink);
+    }
+}
+
+/// Emit the constant pool entry `constant` at the current offset.
+pub fn emit_constant<CS: CodeSink + ?Sized>(func: &Function, constant: Constant, sink: &mut CS) {
+    assert_eq!(sink.offset(),
+               func.const_offsets[constant],
+               "Wrong offset for {}, run relax_branches() first",
+               constant);
+    let data = &func.constants[constant];
+    match data.size() {
+        1 => sink.put1(data.bits() as u8),
+        2 => sink.put2(data.bits() as u16),
+        4 => sink.put4(data.bits() as u32),
+        8 => sink.put8(data.bits()),
+        size => panic!("Unsupported constant size {} for {}", size, constant),
+    }
+}
+
+/// Emit `func` as binary machine code into the `mem` buffer.
+///
+/// The function must have been prepared by `relax_branches()` which also returns the required size
+/// of `mem`. Relocations between the EBBs of the function are resolved here by patching `mem`, so
+/// only the function, global variable, jump table, and constant relocations are passed on to
+/// `relocs`. The jump tables and the constant pool are emitted after the function body at the
+/// offsets given by `func.jt_offsets` and `func.const_offsets`.
+///
+/// Returns the number of bytes emitted.
+pub fn emit_to_memory(func: &Function,
+                      isa: &TargetIsa,
+                      mem: &mut [u8],
+                      relocs: &mut RelocSink,
+                      traps: &mut TrapSink)
+                      -> CodeOffset {
+    let mut local = LocalRelocs {
+        relocs,
+        ebbs: Vec::new(),
+    };
+    let size = {
+        let mut sink = MemoryCodeSink::new(mem, &func.offsets, &mut local, traps);
+        isa.emit_function(func, &mut sink);
+        sink.offset()
+    };
+    for (offset, reloc, target) in local.ebbs {
+        isa.apply_reloc(reloc, mem, offset, target);
+    }
+    size
+}
+
+/// A `RelocSink` that collects the EBB relocations and forwards everything else.
+struct LocalRelocs<'a> {
+    relocs: &'a mut RelocSink,
+    ebbs: Vec<(CodeOffset, Reloc, CodeOffset)>,
+}
+
+impl<'a> RelocSink for LocalRelocs<'a> {
+    fn reloc_ebb(&mut self, offset: CodeOffset, reloc: Reloc, ebb_offset: CodeOffset) {
+        self.ebbs.push((offset, reloc, ebb_offset));
+    }
+
+    fn reloc_func(&mut self, offset: CodeOffset, reloc: Reloc, func: FuncRef) {
+        self.relocs.reloc_func(offset, reloc, func);
+    }
+
+    fn reloc_globalsym(&mut self, offset: CodeOffset, reloc: Reloc, gv: GlobalVar) {
+        self.relocs.reloc_globalsym(offset, reloc, gv);
+    }
+
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+        self.relocs.reloc_jt(offset, reloc, jt);
+    }
+
+    fn reloc_constant(&mut self, offset: CodeOffset, reloc: Reloc, constant: Constant) {
+        self.relocs.reloc_constant(offset, reloc, constant);
+    }
+}
@@ -26,6 +26,17 @@
 //!     jump ebb17
 //! ebb23:
 //! ```
+//!
+//! # Jump tables
+//!
+//! Jump tables are emitted after the function body, aligned to 4 bytes. Each entry is a 32-bit
+//! offset of the destination EBB relative to the start of the table. The table offsets are stored
+//! in `func.jt_offsets`.
+//!
+//! # Constant pool
+//!
+//! The constant pool is emitted after the jump tables. Each constant is aligned to its size, and
+//! the constant offsets are stored in `func.const_offsets`.
 
 use binemit::CodeOffset;
 use entity_map::EntityMap;
@@ -35,8 +46,10 @@ use iterators::IteratorExtras;
 
 /// Relax branches and compute the final layout of EBB headers in `func`.
 ///
-/// Fill in the `func.offsets` table so the function is ready for binary emission.
-pub fn relax_branches(func: &mut Function, isa: &TargetIsa) {
+/// Fill in the `func.offsets`, `func.jt_offsets`, and `func.const_offsets` tables so the function
+/// is ready for binary emission. Return the total size of the function's code, jump tables, and
+/// constant pool in bytes.
+pub fn relax_branches(func: &mut Function, isa: &TargetIsa) -> CodeOffset {
     let encinfo = isa.encoding_info();
 
     // Clear all offsets so we can recognize EBBs that haven't been visited yet.
@@ -48,11 +61,12 @@ pub fn relax_branches(func: &mut Function, isa: &TargetIsa) {
 
     // The relaxation algorithm iterates to convergence.
     let mut go_again = true;
+    let mut offset = 0;
     while go_again {
         go_again = false;
 
         // Visit all instructions in layout order
-        let mut offset = 0;
+        offset = 0;
         let mut pos = Cursor::new(&mut func.layout);
         while let Some(ebb) = pos.next_ebb() {
             // Record the offset for `ebb` and make sure we iterate until offsets are stable.
@@ -91,6 +105,35 @@ pub fn relax_branches(func: &mut Function, isa: &TargetIsa) {
             }
         }
     }
+
+    // Place the jump tables after the code.
+    func.jt_offsets.clear();
+    func.jt_offsets.resize(func.jump_tables.len());
+    for jt in func.jump_tables.keys() {
+        offset = align_jump_table(offset);
+        func.jt_offsets[jt] = offset;
+        offset += JUMP_TABLE_ENTRY_SIZE * func.jump_tables[jt].len() as CodeOffset;
+    }
+
+    // Place the constant pool after the jump tables.
+    func.const_offsets.clear();
+    func.const_offsets.resize(func.constants.len());
+    for constant in func.constants.keys() {
+        let size = func.constants[constant].size();
+        offset = (offset + size - 1) & !(size - 1);
+        func.const_offsets[constant] = offset;
+        offset += size;
+    }
+
+    offset
+}
+
+/// Size of a jump table entry in bytes.
+const JUMP_TABLE_ENTRY_SIZE: CodeOffset = 4;
+
+/// Get the offset of a jump table that would follow code ending at `offset`.
+fn align_jump_table(offset: CodeOffset) -> CodeOffset {
+    (offset + JUMP_TABLE_ENTRY_SIZE - 1) & !(JUMP_TABLE_ENTRY_SIZE - 1)
 }
 
 /// Convert `jump` instructions to `fallthrough` instructions where possible and verify that any
@@ -9,17 +9,26 @@
 //! contexts concurrently. Typically, you would have one context per compilation thread and only a
 //! single ISA instance.
 
+use binemit::{self, CodeOffset, RelocSink, TrapSink};
 use dominator_tree::DominatorTree;
 use flowgraph::ControlFlowGraph;
-use ir::Function;
+use ir::{Function, Opcode};
 use loop_analysis::LoopAnalysis;
 use isa::TargetIsa;
 use legalize_function;
 use regalloc;
-use result::CtonResult;
+use result::{CtonError, CtonResult};
 use verifier;
 use simple_gvn::do_simple_gvn;
 use licm::do_licm;
+use redundant_ebb_args::do_remove_redundant_ebb_args;
+use sccp::do_sccp;
+use simplify_cfg::do_simplify_cfg;
+use strength_reduction::do_strength_reduction;
+use unroll::do_unroll_loops;
+use if_conversion::do_if_conversion;
+use settings::Flags;
+use stats::{CodeStats, FunctionStats, OpcodeCounts};
 
 /// Persistent data structures and compilation pipeline.
 pub struct Context {
@@ -54,6 +63,75 @@ impl Context {
         }
     }
 
+    /// Compile the function for `isa`.
+    ///
+    /// Run the function through all the passes necessary to generate binary machine code: The
+    /// legalizer, the register allocator, and branch relaxation. After this, the function can be
+    /// emitted with `emit_to_memory()`.
+    ///
+    /// Returns the size of the function's machine code in bytes.
+    pub fn compile(&mut self, isa: &TargetIsa) -> Result<CodeOffset, CtonError> {
+        self.compile_pipeline(isa, None)
+    }
+
+    /// Compile the function for `isa` like `compile()`, and collect statistics about the
+    /// generated code.
+    ///
+    /// The instructions are counted before and after legalization, and the `regmove`, `spill`, and
+    /// `fill` instructions added by the register allocator are counted separately.
+    pub fn compile_with_stats(&mut self, isa: &TargetIsa) -> Result<FunctionStats, CtonError> {
+        let mut stats = CodeStats::default();
+        self.compile_pipeline(isa, Some(&mut stats))?;
+        Ok(FunctionStats {
+               name: self.func.name.clone(),
+               isa: isa.name(),
+               stats,
+           })
+    }
+
+    /// Run the compilation passes shared by `compile()` and `compile_with_stats()`.
+    ///
+    /// If `stats` is given, it is filled in with the statistics for the function.
+    fn compile_pipeline(&mut self,
+                        isa: &TargetIsa,
+                        mut stats: Option<&mut CodeStats>)
+                        -> Result<CodeOffset, CtonError> {
+        if let Some(ref mut stats) = stats {
+            stats.before_legalization = OpcodeCounts::of(&self.func);
+        }
+        self.flowgraph();
+        self.verify_if(isa)?;
+        self.legalize(isa)?;
+        if let Some(ref mut stats) = stats {
+            stats.after_legalization = OpcodeCounts::of(&self.func);
+        }
+        self.regalloc(isa)?;
+        if let Some(ref mut stats) = stats {
+            let allocated = OpcodeCounts::of(&self.func);
+            let (regmoves, spills, fills) = {
+                let inserted = |opcode| {
+                    allocated
+                        .get(opcode)
+                        .saturating_sub(stats.after_legalization.get(opcode))
+                };
+                (inserted(Opcode::Regmove), inserted(Opcode::Spill), inserted(Opcode::Fill))
+            };
+            stats.regmoves = regmoves;
+            stats.spills = spills;
+            stats.fills = fills;
+            stats.frame_size = self.func
+                .stack_slots
+                .keys()
+                .map(|ss| self.func.stack_slots[ss].size)
+                .sum();
+        }
+        let code_size = self.relax_branches(isa);
+        if let Some(stats) = stats {
+            stats.code_size = code_size;
+        }
+        Ok(code_size)
+    }
+
     /// Run the verifier on the function.
     ///
     /// Also check that the dominator tree and control flow graph are consistent with the function.
@@ -75,7 +153,7 @@ impl Context {
 
     /// Run the legalizer for `isa` on the function.
     pub fn legalize(&mut self, isa: &TargetIsa) -> CtonResult {
-        legalize_function(&mut self.func, &mut self.cfg, &self.domtree, isa);
+        legalize_function(&mut self.func, &mut self.cfg, &mut self.domtree, isa)?;
         self.verify_if(isa)
     }
 
@@ -102,9 +180,87 @@ impl Context {
         self.verify(None).map_err(Into::into)
     }
 
+    /// Simplify the control flow graph of the function.
+    ///
+    /// This requires the control flow graph to be up to date, and it keeps both the control flow
+    /// graph and the dominator tree up to date.
+    pub fn simplify_cfg(&mut self) -> CtonResult {
+        do_simplify_cfg(&mut self.func, &mut self.cfg, &mut self.domtree);
+        self.verify(None).map_err(Into::into)
+    }
+
+    /// Perform sparse conditional constant propagation on the function.
+    ///
+    /// This requires the control flow graph to be up to date, and it recomputes both the control
+    /// flow graph and the dominator tree.
+    pub fn sccp(&mut self) -> CtonResult {
+        do_sccp(&mut self.func, &mut self.cfg, &mut self.domtree);
+        self.verify(None).map_err(Into::into)
+    }
+
+    /// Remove EBB arguments that always receive the same value.
+    ///
+    /// This requires the control flow graph to be up to date.
+    pub fn remove_redundant_ebb_args(&mut self) -> CtonResult {
+        do_remove_redundant_ebb_args(&mut self.func, &self.cfg);
+        self.verify(None).map_err(Into::into)
+    }
+
+    /// Perform loop strength reduction on the function.
+    pub fn strength_reduction(&mut self) -> CtonResult {
+        do_strength_reduction(&mut self.func,
+                              &self.cfg,
+                              &self.domtree,
+                              &mut self.loop_analysis);
+        self.verify(None).map_err(Into::into)
+    }
+
+    /// Unroll small counted loops in the function by the `loop_unroll_factor` setting.
+    pub fn unroll_loops(&mut self, flags: &Flags) -> CtonResult {
+        do_unroll_loops(&mut self.func,
+                        &mut self.cfg,
+                        &mut self.domtree,
+                        &mut self.loop_analysis,
+                        flags.loop_unroll_factor());
+        self.verify(None).map_err(Into::into)
+    }
+
+    /// Convert cheap conditional branches to `select` instructions, limited by the
+    /// `if_conversion_cost` setting.
+    ///
+    /// This requires the control flow graph to be up to date, and it keeps both the control flow
+    /// graph and the dominator tree up to date.
+    pub fn if_conversion(&mut self, flags: &Flags) -> CtonResult {
+        do_if_conversion(&mut self.func,
+                         &mut self.cfg,
+                         &mut self.domtree,
+                         flags.if_conversion_cost());
+        self.verify(None).map_err(Into::into)
+    }
+
     /// Run the register allocator.
     pub fn regalloc(&mut self, isa: &TargetIsa) -> CtonResult {
         self.regalloc
             .run(isa, &mut self.func, &self.cfg, &self.domtree)
     }
+
+    /// Run the branch relaxation pass and return the final code size.
+    pub fn relax_branches(&mut self, isa: &TargetIsa) -> CodeOffset {
+        binemit::relax_branches(&mut self.func, isa)
+    }
+
+    /// Emit machine code directly into the `mem` buffer.
+    ///
+    /// Run `relax_branches()` first to compute the required size of `mem`. Relocations between
+    /// EBBs are resolved here, and the remaining relocations are passed to `relocs`.
+    ///
+    /// Returns the number of bytes emitted.
+    pub fn emit_to_memory(&self,
+                          mem: &mut [u8],
+                          relocs: &mut RelocSink,
+                          traps: &mut TrapSink,
+                          isa: &TargetIsa)
+                          -> CodeOffset {
+        binemit::emit_to_memory(&self.func, isa, mem, relocs, traps)
+    }
 }
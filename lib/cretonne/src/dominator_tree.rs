@@ -326,7 +326,7 @@ impl DominatorTree {
 #[cfg(test)]
 mod test {
     use flowgraph::ControlFlowGraph;
-    use ir::{Function, InstBuilder, Cursor, types};
+    use ir::{Function, InstBuilder, Cursor, TrapCode, types};
     use super::*;
 
     #[test]
@@ -409,7 +409,7 @@ mod test {
             jmp02 = dfg.ins(cur).jump(ebb2, &[]);
 
             cur.insert_ebb(ebb1);
-            trap = dfg.ins(cur).trap();
+            trap = dfg.ins(cur).trap(TrapCode::User(0));
 
             cur.insert_ebb(ebb2);
             jmp21 = dfg.ins(cur).jump(ebb1, &[]);
@@ -0,0 +1,236 @@
+//! An if-conversion pass.
+//!
+//! A conditional branch that selects between two cheap computations is replaced by straight-line
+//! code that performs both computations and picks the results with `select` instructions. This
+//! avoids branch mispredictions at the cost of executing both arms.
+//!
+//! The pass recognizes an EBB `head` that ends in a `brz` or `brnz` followed by side-effect free
+//! instructions and a `jump`. The two sides of the branch must meet at a common `join` EBB. Each
+//! side either goes to `join` directly, or through an arm EBB which has no other predecessors and
+//! contains nothing but side-effect free instructions and a `jump` to `join`:
+//!
+//! ```cton
+//!     brnz v0, ebb1(v1)
+//!     v2 = iadd_imm v1, 1
+//!     jump ebb2(v2)
+//!
+//! ebb1(v3: i32):
+//!     v4 = ishl_imm v3, 1
+//!     jump ebb2(v4)
+//! ```
+//!
+//! The arm EBBs are merged into `head`, and the arguments passed to `join` are chosen with
+//! `select v0, v4, v2`. The cost of a conversion is the number of instructions moved out of the
+//! arms plus the number of `select` instructions needed, and it is limited by the
+//! `if_conversion_cost` setting.
+
+use dominator_tree::DominatorTree;
+use flowgraph::ControlFlowGraph;
+use ir::{Function, Cursor, Ebb, Inst, Value, Opcode, InstBuilder};
+
+/// Convert the cheap conditional branches in `func` to `select` instructions.
+///
+/// At most `max_cost` instructions are added to the path through each converted branch. A
+/// `max_cost` of 0 leaves the function unchanged.
+pub fn do_if_conversion(func: &mut Function,
+                        cfg: &mut ControlFlowGraph,
+                        domtree: &mut DominatorTree,
+                        max_cost: u8) {
+    if max_cost == 0 {
+        return;
+    }
+    let mut changed = false;
+    // Visit the EBBs in reverse layout order so nested diamonds are converted from the inside out.
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    for &ebb in ebbs.iter().rev() {
+        // Converting a branch can expose another conditional branch earlier in the same EBB.
+        while func.layout.is_ebb_inserted(ebb) && convert_ebb(func, cfg, ebb, max_cost as usize) {
+            changed = true;
+        }
+    }
+    if changed {
+        domtree.compute(func, cfg);
+    }
+}
+
+/// Can `inst` be executed speculatively?
+fn is_hoistable(func: &Function, inst: Inst) -> bool {
+    let opcode = func.dfg[inst].opcode();
+    !(opcode.is_branch() || opcode.is_terminator() || opcode.is_call() || opcode.can_trap() ||
+      opcode.can_load() || opcode.can_store())
+}
+
+/// One side of a conditional branch.
+struct Side {
+    /// The arm EBB passed through on the way to the join EBB, if any.
+    arm: Option<Ebb>,
+    /// The EBB where the two sides meet.
+    join: Ebb,
+    /// Instructions in `arm` to be moved into the head EBB.
+    insts: Vec<Inst>,
+}
+
+/// Find the possible ways that `branch` in `head` can reach a join EBB.
+///
+/// Returns the direct path first, and then the path through an arm EBB if there is one.
+fn sides(func: &Function, cfg: &ControlFlowGraph, head: Ebb, branch: Inst) -> Vec<Side> {
+    let dest = func.dfg[branch]
+        .branch_destination()
+        .expect("Expected a single destination branch");
+    let mut sides = vec![Side {
+                             arm: None,
+                             join: dest,
+                             insts: Vec::new(),
+                         }];
+
+    if dest == head || Some(dest) == func.layout.entry_block() {
+        return sides;
+    }
+    let preds = cfg.get_predecessors(dest);
+    if preds.len() != 1 || preds[0].1 != branch {
+        return sides;
+    }
+    let mut insts: Vec<Inst> = func.layout.ebb_insts(dest).collect();
+    let jump = match insts.pop() {
+        Some(jump) => jump,
+        None => return sides,
+    };
+    if func.dfg[jump].opcode() != Opcode::Jump || !insts.iter().all(|&i| is_hoistable(func, i)) {
+        return sides;
+    }
+    let join = func.dfg[jump].branch_destination().unwrap();
+    if join != dest {
+        sides.push(Side {
+                       arm: Some(dest),
+                       join,
+                       insts,
+                   });
+    }
+    sides
+}
+
+/// Try to if-convert the conditional branch at the end of `ebb`.
+///
+/// Returns `true` if the function was changed.
+fn convert_ebb(func: &mut Function,
+               cfg: &mut ControlFlowGraph,
+               head: Ebb,
+               max_cost: usize)
+               -> bool {
+    let insts: Vec<Inst> = func.layout.ebb_insts(head).collect();
+    let last = match insts.last() {
+        Some(&inst) if func.dfg[inst].opcode() == Opcode::Jump => inst,
+        _ => return false,
+    };
+
+    // Find the conditional branch preceding the straight-line code at the end of the EBB.
+    let mut num_fall = 0;
+    let branch = loop {
+        let inst = match insts.len().checked_sub(num_fall + 2) {
+            Some(idx) => insts[idx],
+            None => return false,
+        };
+        if is_hoistable(func, inst) {
+            num_fall += 1;
+            continue;
+        }
+        match func.dfg[inst].opcode() {
+            Opcode::Brz | Opcode::Brnz => break inst,
+            _ => return false,
+        }
+    };
+
+    // Pick a pair of sides that meet in the same join EBB.
+    let taken_sides = sides(func, cfg, head, branch);
+    let fall_sides = sides(func, cfg, head, last);
+    let (taken, fall) = match taken_sides
+              .iter()
+              .flat_map(|t| fall_sides.iter().map(move |f| (t, f)))
+              .find(|&(t, f)| t.join == f.join) {
+        Some(pair) => pair,
+        None => return false,
+    };
+    let join = taken.join;
+
+    // Collect the arguments passed to `join` on each side, looking through the arm EBBs.
+    let taken_args = join_args(func, branch, taken);
+    let fall_args = join_args(func, last, fall);
+    let num_selects = taken_args
+        .iter()
+        .zip(&fall_args)
+        .filter(|&(t, f)| t != f)
+        .count();
+    let cost = num_fall + taken.insts.len() + fall.insts.len() + num_selects;
+    if cost > max_cost {
+        return false;
+    }
+
+    // Merge the arm EBBs into `head`, in front of the final jump.
+    for &(side, from) in &[(taken, branch), (fall, last)] {
+        if let Some(arm) = side.arm {
+            let params = func.dfg.ebb_args(arm).to_vec();
+            let args = func.dfg.inst_variable_args(from).to_vec();
+            func.dfg.detach_ebb_args(arm);
+            for (&param, &arg) in params.iter().zip(&args) {
+                func.dfg.change_to_alias(param, arg);
+            }
+            while let Some(inst) = func.layout.first_inst(arm) {
+                func.layout.remove_inst(inst);
+                if side.insts.contains(&inst) {
+                    func.layout.insert_inst(inst, last);
+                }
+            }
+            // With its instructions gone, this removes all the outgoing edges of `arm`.
+            cfg.recompute_ebb(func, arm);
+            func.layout.remove_ebb(arm);
+        }
+    }
+
+    // Choose the join arguments with `select` instructions.
+    let cond = func.dfg.inst_fixed_args(branch)[0];
+    let brnz = func.dfg[branch].opcode() == Opcode::Brnz;
+    let mut args = Vec::with_capacity(taken_args.len());
+    {
+        let mut pos = Cursor::new(&mut func.layout);
+        pos.goto_inst(last);
+        for (&t, &f) in taken_args.iter().zip(&fall_args) {
+            args.push(if t == f {
+                          t
+                      } else if brnz {
+                          func.dfg.ins(&mut pos).select(cond, t, f)
+                      } else {
+                          func.dfg.ins(&mut pos).select(cond, f, t)
+                      });
+        }
+    }
+    func.layout.remove_inst(branch);
+    func.dfg.replace(last).jump(join, &args);
+    cfg.recompute_ebb(func, head);
+    true
+}
+
+/// Get the arguments passed to the join EBB by `branch` going to `side`.
+///
+/// The arguments of an arm EBB are replaced by the values passed by `branch`, so the returned
+/// values are valid in the head EBB.
+fn join_args(func: &Function, branch: Inst, side: &Side) -> Vec<Value> {
+    let args = func.dfg.inst_variable_args(branch);
+    match side.arm {
+        None => args.iter().map(|&arg| func.dfg.resolve_aliases(arg)).collect(),
+        Some(arm) => {
+            let params = func.dfg.ebb_args(arm);
+            let jump = func.layout.last_inst(arm).unwrap();
+            func.dfg
+                .inst_variable_args(jump)
+                .iter()
+                .map(|&arg| {
+                         let arg = func.dfg.resolve_aliases(arg);
+                         match params.iter().position(|&p| p == arg) {
+                             Some(num) => func.dfg.resolve_aliases(args[num]),
+                             None => arg,
+                         }
+                     })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! Induction variable detection.
+//!
+//! Loops in Cretonne don't have phi nodes. Instead, the values that change between iterations are
+//! passed as arguments to the loop header EBB. A *basic induction variable* is an argument of the
+//! loop header that is incremented by the same constant on every back edge.
+
+use flowgraph::ControlFlowGraph;
+use ir::{DataFlowGraph, Function, InstructionData, Opcode, Value, ValueDef};
+use ir::instructions::BranchInfo;
+use loop_analysis::{Loop, LoopAnalysis};
+
+/// A basic induction variable of a loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InductionVar {
+    /// The loop header argument holding the induction variable.
+    pub value: Value,
+
+    /// The amount added to `value` on every iteration of the loop.
+    pub step: i64,
+}
+
+/// Find the basic induction variables of the loop `lp`.
+///
+/// Returns the induction variables in the order of the header arguments.
+pub fn basic_induction_vars(func: &Function,
+                            cfg: &ControlFlowGraph,
+                            loop_analysis: &LoopAnalysis,
+                            lp: Loop)
+                            -> Vec<InductionVar> {
+    let header = loop_analysis.loop_header(lp);
+    let mut back_edges = Vec::new();
+    for &(pred, branch) in cfg.get_predecessors(header) {
+        if !loop_analysis.is_in_loop(pred, lp) {
+            continue;
+        }
+        match func.dfg[branch].analyze_branch(&func.dfg.value_lists) {
+            BranchInfo::SingleDest(_, args) => back_edges.push(args),
+            // The header has no arguments if it's the target of a jump table.
+            _ => return Vec::new(),
+        }
+    }
+
+    let mut ivs = Vec::new();
+    'args: for (num, &value) in func.dfg.ebb_args(header).iter().enumerate() {
+        let mut step = None;
+        for args in &back_edges {
+            let incr = match constant_increment(&func.dfg, args[num], value) {
+                Some(incr) => incr,
+                None => continue 'args,
+            };
+            if step.is_some() && step != Some(incr) {
+                continue 'args;
+            }
+            step = Some(incr);
+        }
+        if let Some(step) = step {
+            ivs.push(InductionVar { value, step });
+        }
+    }
+    ivs
+}
+
+/// If `value` is computed as `base` plus a constant, return the constant.
+fn constant_increment(dfg: &DataFlowGraph, value: Value, base: Value) -> Option<i64> {
+    let inst = match dfg.value_def(dfg.resolve_aliases(value)) {
+        ValueDef::Res(inst, 0) => inst,
+        _ => return None,
+    };
+    match dfg[inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } if dfg.resolve_aliases(arg) == base => Some(imm.into()),
+        InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args,
+        } => {
+            if dfg.resolve_aliases(args[0]) == base {
+                iconst_value(dfg, args[1])
+            } else if dfg.resolve_aliases(args[1]) == base {
+                iconst_value(dfg, args[0])
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// If `value` is defined by an `iconst` instruction, return its immediate.
+pub fn iconst_value(dfg: &DataFlowGraph, value: Value) -> Option<i64> {
+    let inst = match dfg.value_def(dfg.resolve_aliases(value)) {
+        ValueDef::Res(inst, 0) => inst,
+        _ => return None,
+    };
+    match dfg[inst] {
+        InstructionData::UnaryImm {
+            opcode: Opcode::Iconst,
+            imm,
+        } => Some(imm.into()),
+        _ => None,
+    }
+}
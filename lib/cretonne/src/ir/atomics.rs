@@ -0,0 +1,120 @@
+//! Operations and memory orderings for atomic instructions.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// The read-modify-write operation performed by an `atomic_rmw` instruction.
+///
+/// The instruction returns the old value in memory, and stores the result of the operation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum AtomicRmwOp {
+    /// Wrapping addition.
+    Add,
+    /// Wrapping subtraction.
+    Sub,
+    /// Bitwise and.
+    And,
+    /// Bitwise or.
+    Or,
+    /// Bitwise xor.
+    Xor,
+    /// Exchange: Store the operand unmodified.
+    Xchg,
+}
+
+impl Display for AtomicRmwOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::AtomicRmwOp::*;
+        f.write_str(match *self {
+                        Add => "add",
+                        Sub => "sub",
+                        And => "and",
+                        Or => "or",
+                        Xor => "xor",
+                        Xchg => "xchg",
+                    })
+    }
+}
+
+impl FromStr for AtomicRmwOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::AtomicRmwOp::*;
+        match s {
+            "add" => Ok(Add),
+            "sub" => Ok(Sub),
+            "and" => Ok(And),
+            "or" => Ok(Or),
+            "xor" => Ok(Xor),
+            "xchg" => Ok(Xchg),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Memory ordering constraint imposed by a `fence` instruction.
+///
+/// These correspond to the C++11 memory orderings, except that the atomic memory instructions
+/// themselves are always sequentially consistent. Weaker orderings are only expressed with
+/// explicit fences.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum AtomicOrdering {
+    /// Memory accesses after the fence can't be moved before loads preceding the fence.
+    Acquire,
+    /// Memory accesses before the fence can't be moved after stores following the fence.
+    Release,
+    /// Both `Acquire` and `Release`.
+    AcqRel,
+    /// Sequentially consistent: No memory accesses can be moved across the fence.
+    SeqCst,
+}
+
+impl Display for AtomicOrdering {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::AtomicOrdering::*;
+        f.write_str(match *self {
+                        Acquire => "acquire",
+                        Release => "release",
+                        AcqRel => "acq_rel",
+                        SeqCst => "seq_cst",
+                    })
+    }
+}
+
+impl FromStr for AtomicOrdering {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::AtomicOrdering::*;
+        match s {
+            "acquire" => Ok(Acquire),
+            "release" => Ok(Release),
+            "acq_rel" => Ok(AcqRel),
+            "seq_cst" => Ok(SeqCst),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        use super::AtomicRmwOp::*;
+        use super::AtomicOrdering::*;
+
+        for op in &[Add, Sub, And, Or, Xor, Xchg] {
+            assert_eq!(op.to_string().parse(), Ok(*op));
+        }
+        assert_eq!("nand".parse::<AtomicRmwOp>(), Err(()));
+
+        for ord in &[Acquire, Release, AcqRel, SeqCst] {
+            assert_eq!(ord.to_string().parse(), Ok(*ord));
+        }
+        assert_eq!(AcqRel.to_string(), "acq_rel");
+        assert_eq!("relaxed".parse::<AtomicOrdering>(), Err(()));
+    }
+}
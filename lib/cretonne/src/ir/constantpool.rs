@@ -0,0 +1,195 @@
+//! Constant pool representation.
+//!
+//! Constants that can't be materialized with an instruction immediate are placed in a per-function
+//! constant pool and loaded from memory. Pool entries are assigned an `ir::entities::Constant`
+//! reference and declared in the function preamble. The pool is emitted after the function's code
+//! and jump tables.
+
+use entity_map::{EntityMap, Keys, PrimaryEntityData};
+use ir::entities::Constant;
+use ir::immediates::{Imm64, Ieee32, Ieee64};
+use ir::types::{self, Type};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Index;
+
+/// Contents of a constant pool entry.
+///
+/// A constant is a scalar value of type `ty`. The bits are stored in a `u64`, using the low
+/// `ty.bits()` bits. Integer constants are sign-extended.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct ConstantData {
+    ty: Type,
+    bits: u64,
+}
+
+impl ConstantData {
+    /// Create a new integer constant of type `ty`.
+    ///
+    /// The type must be a scalar integer type no wider than 64 bits.
+    pub fn int(ty: Type, imm: Imm64) -> ConstantData {
+        assert!(ty.is_int() && ty.is_scalar() && ty.bits() <= 64,
+                "Unsupported constant type: {}",
+                ty);
+        let x: i64 = imm.into();
+        let shift = 64 - ty.bits();
+        ConstantData {
+            ty,
+            bits: ((x << shift) >> shift) as u64,
+        }
+    }
+
+    /// Create a new `f32` constant.
+    pub fn f32(imm: Ieee32) -> ConstantData {
+        ConstantData {
+            ty: types::F32,
+            bits: imm.bits() as u64,
+        }
+    }
+
+    /// Create a new `f64` constant.
+    pub fn f64(imm: Ieee64) -> ConstantData {
+        ConstantData {
+            ty: types::F64,
+            bits: imm.bits(),
+        }
+    }
+
+    /// Get the type of this constant.
+    pub fn ty(&self) -> Type {
+        self.ty
+    }
+
+    /// Get the size of this constant in bytes.
+    ///
+    /// Constants are aligned to their size in the emitted constant pool.
+    pub fn size(&self) -> u32 {
+        self.ty.bits() as u32 / 8
+    }
+
+    /// Get the bits of this constant.
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+}
+
+impl fmt::Display for ConstantData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "constant.{} ", self.ty)?;
+        match self.ty {
+            types::F32 => write!(f, "{}", Ieee32::with_bits(self.bits as u32)),
+            types::F64 => write!(f, "{}", Ieee64::with_bits(self.bits)),
+            _ => write!(f, "{}", Imm64::new(self.bits as i64)),
+        }
+    }
+}
+
+impl PrimaryEntityData for ConstantData {}
+
+/// The constant pool of a function.
+#[derive(Clone)]
+pub struct ConstantPool {
+    // The constants, in declaration order.
+    constants: EntityMap<Constant, ConstantData>,
+
+    // Map from contents to the first constant with those contents, used by `insert()`.
+    lookup: HashMap<ConstantData, Constant>,
+}
+
+impl ConstantPool {
+    /// Create a new empty constant pool.
+    pub fn new() -> ConstantPool {
+        ConstantPool {
+            constants: EntityMap::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    /// Get the number of constants in the pool.
+    pub fn len(&self) -> usize {
+        self.constants.len()
+    }
+
+    /// Check if the pool is empty.
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+
+    /// Check if `c` is a valid constant reference.
+    pub fn is_valid(&self, c: Constant) -> bool {
+        self.constants.is_valid(c)
+    }
+
+    /// Get the constant reference that will be assigned by the next `push()`.
+    pub fn next_key(&self) -> Constant {
+        self.constants.next_key()
+    }
+
+    /// Iterate over all the constant references in the pool.
+    pub fn keys(&self) -> Keys<Constant> {
+        self.constants.keys()
+    }
+
+    /// Append a new constant to the pool, even if an identical constant exists.
+    pub fn push(&mut self, data: ConstantData) -> Constant {
+        let c = self.constants.push(data);
+        self.lookup.entry(data).or_insert(c);
+        c
+    }
+
+    /// Get a reference to a constant with the contents `data`, reusing an existing constant if
+    /// possible.
+    pub fn insert(&mut self, data: ConstantData) -> Constant {
+        match self.lookup.get(&data) {
+            Some(&c) => c,
+            None => self.push(data),
+        }
+    }
+}
+
+impl Index<Constant> for ConstantPool {
+    type Output = ConstantData;
+
+    fn index(&self, c: Constant) -> &ConstantData {
+        &self.constants[c]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ir::types::{I32, I64};
+
+    #[test]
+    fn display() {
+        assert_eq!(ConstantData::int(I64, Imm64::new(0x1234_5678_9abc)).to_string(),
+                   "constant.i64 0x1234_5678_9abc");
+        assert_eq!(ConstantData::int(I32, Imm64::new(-2)).to_string(),
+                   "constant.i32 -2");
+        assert_eq!(ConstantData::f32(Ieee32::new(1.0)).to_string(),
+                   "constant.f32 0x1.000000p0");
+        assert_eq!(ConstantData::f64(Ieee64::new(-0.5)).to_string(),
+                   "constant.f64 -0x1.0000000000000p-1");
+    }
+
+    #[test]
+    fn insert() {
+        let mut pool = ConstantPool::new();
+        assert!(pool.is_empty());
+
+        let a = ConstantData::f32(Ieee32::new(1.0));
+        let b = ConstantData::int(I32, Imm64::new(0x3f80_0000));
+        let c0 = pool.insert(a);
+        let c1 = pool.insert(b);
+        assert!(c0 != c1);
+        assert_eq!(pool.insert(a), c0);
+        assert_eq!(pool.len(), 2);
+
+        // `push` always allocates a new entry.
+        let c2 = pool.push(a);
+        assert!(c2 != c0);
+        assert_eq!(pool.insert(a), c0);
+        assert_eq!(pool.keys().collect::<Vec<_>>(), [c0, c1, c2]);
+        assert_eq!(pool[c1].size(), 4);
+    }
+}
@@ -722,6 +722,34 @@ impl DataFlowGraph {
         num as usize
     }
 
+    /// Removes `val` from `ebb`'s arguments by shifting the following arguments down.
+    /// Returns the position of `val` before removal.
+    ///
+    /// Unlike `swap_remove_ebb_arg()`, this preserves the order of the remaining arguments, so
+    /// branches to `ebb` only need to remove the corresponding argument. This is an O(n)
+    /// operation.
+    ///
+    /// Panics if `val` is not an `Ebb` argument.
+    pub fn remove_ebb_arg(&mut self, val: Value) -> usize {
+        let (ebb, num) = if let ValueData::Arg { num, ebb, .. } = self.values[val] {
+            (ebb, num)
+        } else {
+            panic!("{} must be an EBB argument", val);
+        };
+        self.ebbs[ebb]
+            .args
+            .remove(num as usize, &mut self.value_lists);
+        for index in num..(self.num_ebb_args(ebb) as u16) {
+            let arg_val = self.ebbs[ebb].args.as_slice(&self.value_lists)[index as usize];
+            if let ValueData::Arg { num: ref mut old_num, .. } = self.values[arg_val] {
+                *old_num = index;
+            } else {
+                panic!("{} should be an Ebb argument but is not", arg_val);
+            }
+        }
+        num as usize
+    }
+
     /// Append an existing argument value to `ebb`.
     ///
     /// The appended value can't already be attached to something else.
@@ -823,7 +851,7 @@ impl<'a> fmt::Display for DisplayInst<'a> {
 mod tests {
     use super::*;
     use ir::types;
-    use ir::{Function, Cursor, Opcode, InstructionData};
+    use ir::{Function, Cursor, Opcode, InstructionData, TrapCode};
 
     #[test]
     fn make_inst() {
@@ -863,9 +891,12 @@ mod tests {
     fn no_results() {
         let mut dfg = DataFlowGraph::new();
 
-        let idata = InstructionData::Nullary { opcode: Opcode::Trap };
+        let idata = InstructionData::Trap {
+            opcode: Opcode::Trap,
+            code: TrapCode::User(0),
+        };
         let inst = dfg.make_inst(idata);
-        assert_eq!(dfg.display_inst(inst).to_string(), "trap");
+        assert_eq!(dfg.display_inst(inst).to_string(), "trap user0");
 
         // Result slice should be empty.
         assert_eq!(dfg.inst_results(inst), &[]);
@@ -963,6 +994,29 @@ mod tests {
         assert_eq!(dfg.ebb_args(ebb), &[]);
     }
 
+    #[test]
+    fn remove_ebb_arguments() {
+        let mut dfg = DataFlowGraph::new();
+
+        let ebb = dfg.make_ebb();
+        let arg1 = dfg.append_ebb_arg(ebb, types::F32);
+        let arg2 = dfg.append_ebb_arg(ebb, types::I32);
+        let arg3 = dfg.append_ebb_arg(ebb, types::I64);
+        assert_eq!(dfg.ebb_args(ebb), &[arg1, arg2, arg3]);
+
+        assert_eq!(dfg.remove_ebb_arg(arg1), 0);
+        assert_eq!(dfg.value_is_attached(arg1), false);
+        assert_eq!(dfg.value_is_attached(arg2), true);
+        assert_eq!(dfg.value_is_attached(arg3), true);
+        assert_eq!(dfg.ebb_args(ebb), &[arg2, arg3]);
+        assert_eq!(dfg.value_def(arg3), ValueDef::Arg(ebb, 1));
+        assert_eq!(dfg.remove_ebb_arg(arg3), 1);
+        assert_eq!(dfg.value_is_attached(arg3), false);
+        assert_eq!(dfg.ebb_args(ebb), &[arg2]);
+        assert_eq!(dfg.remove_ebb_arg(arg2), 0);
+        assert_eq!(dfg.ebb_args(ebb), &[]);
+    }
+
     #[test]
     fn aliases() {
         use ir::InstBuilder;
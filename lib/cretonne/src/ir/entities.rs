@@ -102,6 +102,11 @@ entity_impl!(Inst, "inst");
 pub struct StackSlot(u32);
 entity_impl!(StackSlot, "ss");
 
+/// An opaque reference to a global variable.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct GlobalVar(u32);
+entity_impl!(GlobalVar, "gv");
+
 /// An opaque reference to a jump table.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct JumpTable(u32);
@@ -117,6 +122,16 @@ entity_impl!(FuncRef, "fn");
 pub struct SigRef(u32);
 entity_impl!(SigRef, "sig");
 
+/// A reference to a heap.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Heap(u32);
+entity_impl!(Heap, "heap");
+
+/// A reference to a constant in the function's constant pool.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Constant(u32);
+entity_impl!(Constant, "const");
+
 /// A reference to any of the entities defined in this module.
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum AnyEntity {
@@ -130,12 +145,18 @@ pub enum AnyEntity {
     Value(Value),
     /// A stack slot.
     StackSlot(StackSlot),
+    /// A global variable.
+    GlobalVar(GlobalVar),
     /// A jump table.
     JumpTable(JumpTable),
     /// An external function.
     FuncRef(FuncRef),
     /// A function call signature.
     SigRef(SigRef),
+    /// A heap.
+    Heap(Heap),
+    /// A constant in the constant pool.
+    Constant(Constant),
 }
 
 impl Display for AnyEntity {
@@ -146,9 +167,12 @@ impl Display for AnyEntity {
             AnyEntity::Inst(r) => r.fmt(fmt),
             AnyEntity::Value(r) => r.fmt(fmt),
             AnyEntity::StackSlot(r) => r.fmt(fmt),
+            AnyEntity::GlobalVar(r) => r.fmt(fmt),
             AnyEntity::JumpTable(r) => r.fmt(fmt),
             AnyEntity::FuncRef(r) => r.fmt(fmt),
             AnyEntity::SigRef(r) => r.fmt(fmt),
+            AnyEntity::Heap(r) => r.fmt(fmt),
+            AnyEntity::Constant(r) => r.fmt(fmt),
         }
     }
 }
@@ -177,6 +201,12 @@ impl From<StackSlot> for AnyEntity {
     }
 }
 
+impl From<GlobalVar> for AnyEntity {
+    fn from(r: GlobalVar) -> AnyEntity {
+        AnyEntity::GlobalVar(r)
+    }
+}
+
 impl From<JumpTable> for AnyEntity {
     fn from(r: JumpTable) -> AnyEntity {
         AnyEntity::JumpTable(r)
@@ -195,6 +225,18 @@ impl From<SigRef> for AnyEntity {
     }
 }
 
+impl From<Heap> for AnyEntity {
+    fn from(r: Heap) -> AnyEntity {
+        AnyEntity::Heap(r)
+    }
+}
+
+impl From<Constant> for AnyEntity {
+    fn from(r: Constant) -> AnyEntity {
+        AnyEntity::Constant(r)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -31,6 +31,9 @@ pub struct Signature {
     /// This can be computed from the legalized `argument_types` array as the maximum (offset plus
     /// byte size) of the `ArgumentLoc::Stack(offset)` argument.
     pub argument_bytes: Option<u32>,
+
+    /// Calling convention.
+    pub call_conv: CallConv,
 }
 
 impl Signature {
@@ -40,6 +43,7 @@ impl Signature {
             argument_types: Vec::new(),
             return_types: Vec::new(),
             argument_bytes: None,
+            call_conv: CallConv::Native,
         }
     }
 
@@ -94,6 +98,9 @@ impl<'a> fmt::Display for DisplaySignature<'a> {
             write!(f, " -> ")?;
             write_list(f, &self.0.return_types, self.1)?;
         }
+        if self.0.call_conv != CallConv::Native {
+            write!(f, " {}", self.0.call_conv)?;
+        }
         Ok(())
     }
 }
@@ -236,10 +243,16 @@ pub enum ArgumentPurpose {
     /// Some calling conventions have registers that must be saved by the callee. These registers
     /// are represented as `CalleeSaved` arguments and return values.
     CalleeSaved,
+
+    /// A VM context pointer.
+    ///
+    /// This is a pointer to a context struct containing details about the current sandbox. It is
+    /// used as a base pointer for finding heaps.
+    VMContext,
 }
 
 /// Text format names of the `ArgumentPurpose` variants.
-static PURPOSE_NAMES: [&str; 5] = ["normal", "sret", "link", "fp", "csr"];
+static PURPOSE_NAMES: [&str; 6] = ["normal", "sret", "link", "fp", "csr", "vmctx"];
 
 impl fmt::Display for ArgumentPurpose {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -256,6 +269,68 @@ impl FromStr for ArgumentPurpose {
             "link" => Ok(ArgumentPurpose::Link),
             "fp" => Ok(ArgumentPurpose::FramePointer),
             "csr" => Ok(ArgumentPurpose::CalleeSaved),
+            "vmctx" => Ok(ArgumentPurpose::VMContext),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Calling convention identifiers.
+///
+/// The calling convention determines how arguments and return values are passed between
+/// functions. Each ISA implements its own set of conventions in its `legalize_signature()`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CallConv {
+    /// The native calling convention of the target platform.
+    Native,
+
+    /// A fast convention for calls between Cretonne functions.
+    ///
+    /// This is currently the same as `Native`, but it may pass more arguments in registers in the
+    /// future.
+    Fast,
+
+    /// A convention for rarely called functions.
+    ///
+    /// This is currently the same as `Native`, but it may preserve more registers in the callee
+    /// in the future.
+    Cold,
+
+    /// The System V AMD64 ABI used by most Unix-like systems.
+    SystemV,
+
+    /// The Windows x64 `fastcall` convention.
+    WindowsFastcall,
+
+    /// A convention for calls to and from a WebAssembly VM embedding, like SpiderMonkey's
+    /// Baldrdash. The VM context pointer is passed as a `vmctx` argument.
+    Baldrdash,
+}
+
+/// Text format names of the `CallConv` variants.
+static CALL_CONV_NAMES: [&str; 6] = ["native",
+                                     "fast",
+                                     "cold",
+                                     "system_v",
+                                     "windows_fastcall",
+                                     "baldrdash"];
+
+impl fmt::Display for CallConv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(CALL_CONV_NAMES[*self as usize])
+    }
+}
+
+impl FromStr for CallConv {
+    type Err = ();
+    fn from_str(s: &str) -> Result<CallConv, ()> {
+        match s {
+            "native" => Ok(CallConv::Native),
+            "fast" => Ok(CallConv::Fast),
+            "cold" => Ok(CallConv::Cold),
+            "system_v" => Ok(CallConv::SystemV),
+            "windows_fastcall" => Ok(CallConv::WindowsFastcall),
+            "baldrdash" => Ok(CallConv::Baldrdash),
             _ => Err(()),
         }
     }
@@ -299,13 +374,29 @@ mod tests {
                            ArgumentPurpose::StructReturn,
                            ArgumentPurpose::Link,
                            ArgumentPurpose::FramePointer,
-                           ArgumentPurpose::CalleeSaved];
+                           ArgumentPurpose::CalleeSaved,
+                           ArgumentPurpose::VMContext];
         for (&e, &n) in all_purpose.iter().zip(PURPOSE_NAMES.iter()) {
             assert_eq!(e.to_string(), n);
             assert_eq!(Ok(e), n.parse());
         }
     }
 
+    #[test]
+    fn call_conv() {
+        let all_conv = [CallConv::Native,
+                        CallConv::Fast,
+                        CallConv::Cold,
+                        CallConv::SystemV,
+                        CallConv::WindowsFastcall,
+                        CallConv::Baldrdash];
+        for (&e, &n) in all_conv.iter().zip(CALL_CONV_NAMES.iter()) {
+            assert_eq!(e.to_string(), n);
+            assert_eq!(Ok(e), n.parse());
+        }
+        assert_eq!("stdcall".parse::<CallConv>(), Err(()));
+    }
+
     #[test]
     fn signatures() {
         let mut sig = Signature::new();
@@ -333,5 +424,9 @@ mod tests {
 
         // Writing ABI-annotated signatures.
         assert_eq!(sig.to_string(), "(i32 [24], i32x4 [8]) -> f32, b8");
+
+        // The native calling convention is implied.
+        sig.call_conv = CallConv::SystemV;
+        assert_eq!(sig.to_string(), "(i32 [24], i32x4 [8]) -> f32, b8 system_v");
     }
 }
@@ -10,7 +10,7 @@ use std::ascii::AsciiExt;
 ///
 /// Function names are mostly a testing and debugging tool.
 /// In particular, `.cton` files use function names to identify functions.
-#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub struct FunctionName(NameRepr);
 
 impl FunctionName {
@@ -48,6 +48,12 @@ impl FunctionName {
     }
 }
 
+impl AsRef<[u8]> for FunctionName {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+}
+
 /// Tries to interpret bytes as ASCII alphanumerical characters and `_`.
 fn try_as_name(bytes: &[u8]) -> Option<String> {
     let mut name = String::with_capacity(bytes.len());
@@ -63,7 +69,7 @@ fn try_as_name(bytes: &[u8]) -> Option<String> {
 
 const NAME_LENGTH_THRESHOLD: usize = 22;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum NameRepr {
     Short {
         length: u8,
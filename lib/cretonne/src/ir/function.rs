@@ -6,7 +6,8 @@
 use binemit::CodeOffset;
 use entity_map::{EntityMap, PrimaryEntityData};
 use ir::{FunctionName, Signature, Value, Inst, Ebb, StackSlot, StackSlotData, JumpTable,
-         JumpTableData, ValueLoc, DataFlowGraph, Layout};
+         JumpTableData, Constant, ConstantPool, GlobalVar, GlobalVarData, Heap, HeapData, ValueLoc,
+         DataFlowGraph, Layout, SourceLocs};
 use isa::{TargetIsa, Encoding};
 use std::fmt::{self, Display, Debug, Formatter};
 use write::write_function;
@@ -29,6 +30,15 @@ pub struct Function {
     /// Jump tables used in this function.
     pub jump_tables: EntityMap<JumpTable, JumpTableData>,
 
+    /// Constant pool for values that can't be encoded as instruction immediates.
+    pub constants: ConstantPool,
+
+    /// Global variables referenced.
+    pub global_vars: EntityMap<GlobalVar, GlobalVarData>,
+
+    /// Heaps accessed by this function.
+    pub heaps: EntityMap<Heap, HeapData>,
+
     /// Data flow graph containing the primary definition of all instructions, EBBs and values.
     pub dfg: DataFlowGraph,
 
@@ -42,16 +52,36 @@ pub struct Function {
     /// Location assigned to every value.
     pub locations: EntityMap<Value, ValueLoc>,
 
+    /// Source locations.
+    ///
+    /// Track the original source location for each instruction. The source locations are not
+    /// interpreted by Cretonne, only preserved.
+    pub srclocs: SourceLocs,
+
     /// Code offsets of the EBB headers.
     ///
     /// This information is only transiently available after the `binemit::relax_branches` function
     /// computes it, and it can easily be recomputed by calling that function. It is not included
     /// in the textual IL format.
     pub offsets: EntityMap<Ebb, CodeOffset>,
+
+    /// Code offsets of the jump tables.
+    ///
+    /// Jump tables are emitted after the function body. Like `offsets`, this information is
+    /// computed by `binemit::relax_branches`.
+    pub jt_offsets: EntityMap<JumpTable, CodeOffset>,
+
+    /// Code offsets of the constant pool entries.
+    ///
+    /// The constant pool is emitted after the jump tables. Like `offsets`, this information is
+    /// computed by `binemit::relax_branches`.
+    pub const_offsets: EntityMap<Constant, CodeOffset>,
 }
 
 impl PrimaryEntityData for StackSlotData {}
 impl PrimaryEntityData for JumpTableData {}
+impl PrimaryEntityData for GlobalVarData {}
+impl PrimaryEntityData for HeapData {}
 
 impl Function {
     /// Create a function with the given name and signature.
@@ -61,11 +91,17 @@ impl Function {
             signature: sig,
             stack_slots: EntityMap::new(),
             jump_tables: EntityMap::new(),
+            constants: ConstantPool::new(),
+            global_vars: EntityMap::new(),
+            heaps: EntityMap::new(),
             dfg: DataFlowGraph::new(),
             layout: Layout::new(),
             encodings: EntityMap::new(),
             locations: EntityMap::new(),
+            srclocs: EntityMap::new(),
             offsets: EntityMap::new(),
+            jt_offsets: EntityMap::new(),
+            const_offsets: EntityMap::new(),
         }
     }
 
@@ -0,0 +1,76 @@
+//! Global variables.
+//!
+//! Global variables are declared in the preamble and assigned an `ir::entities::GlobalVar`
+//! reference. The kind of global variable is represented by a `GlobalVarData` value defined in
+//! this module.
+
+use ir::{GlobalVar, FunctionName};
+use ir::immediates::Offset32;
+use std::fmt::{self, Display, Formatter};
+
+/// Information about a global variable declaration.
+#[derive(Clone)]
+pub enum GlobalVarData {
+    /// Variable is part of the VM context struct, its address is a constant offset from the VM
+    /// context pointer.
+    ///
+    /// The VM context pointer is the function argument with the `vmctx` purpose.
+    VmCtx {
+        /// Offset from the `vmctx` pointer to this global.
+        offset: Offset32,
+    },
+
+    /// Variable is part of a struct pointed to by another global variable.
+    ///
+    /// The `base` global variable is assumed to contain a pointer to a struct. This global
+    /// variable lives at an offset into the struct. The memory must be accessible, and naturally
+    /// aligned to hold a pointer value.
+    Deref {
+        /// The base pointer global variable.
+        base: GlobalVar,
+
+        /// Byte offset to be added to the pointer loaded from `base`.
+        offset: Offset32,
+    },
+
+    /// Variable is at an address identified by a symbolic name. Cretonne itself
+    /// does not interpret this name; it's used by embedders to link with other
+    /// data structures.
+    Sym {
+        /// The symbolic name.
+        name: FunctionName,
+    },
+}
+
+impl Display for GlobalVarData {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            GlobalVarData::VmCtx { offset } => write!(f, "vmctx{}", offset),
+            GlobalVarData::Deref { base, offset } => write!(f, "deref({}){}", base, offset),
+            GlobalVarData::Sym { ref name } => write!(f, "globalsym {}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GlobalVarData;
+    use ir::{GlobalVar, FunctionName};
+    use ir::immediates::Offset32;
+    use entity_map::EntityRef;
+
+    #[test]
+    fn display() {
+        let vmctx = GlobalVarData::VmCtx { offset: Offset32::new(0) };
+        assert_eq!(vmctx.to_string(), "vmctx");
+
+        let deref = GlobalVarData::Deref {
+            base: GlobalVar::new(0),
+            offset: Offset32::new(-8),
+        };
+        assert_eq!(deref.to_string(), "deref(gv0)-8");
+
+        let sym = GlobalVarData::Sym { name: FunctionName::new("foo") };
+        assert_eq!(sym.to_string(), "globalsym %foo");
+    }
+}
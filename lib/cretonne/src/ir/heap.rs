@@ -0,0 +1,110 @@
+//! Heaps.
+//!
+//! Heaps are declared in the preamble and assigned an `ir::entities::Heap` reference. The
+//! properties of a heap are stored in a `HeapData` struct defined in this module.
+
+use ir::immediates::{Imm64, Offset32};
+use ir::Type;
+use std::fmt::{self, Display, Formatter};
+
+/// Information about a heap declaration.
+#[derive(Clone)]
+pub struct HeapData {
+    /// Where the heap base address is found.
+    pub base: HeapBase,
+
+    /// Size in bytes of the guard pages following the heap.
+    ///
+    /// Accesses to the guard pages are guaranteed to trap, so a bounds check can be omitted when
+    /// the access can't go past the guard pages.
+    pub guard_size: Imm64,
+
+    /// Heap style, with additional style-specific info.
+    pub style: HeapStyle,
+
+    /// The type of the offsets used to address the heap.
+    pub index_type: Type,
+}
+
+/// Location of the heap base address.
+#[derive(Clone)]
+pub enum HeapBase {
+    /// The heap base address is stored in the VM context at the given offset.
+    ///
+    /// The VM context pointer is the function argument with the `vmctx` purpose.
+    VmCtx(Offset32),
+}
+
+/// Style of heap including style-specific information.
+#[derive(Clone)]
+pub enum HeapStyle {
+    /// A dynamic heap can be relocated to a different base address when it is grown.
+    Dynamic {
+        /// Offset in the VM context of the current heap bound in bytes.
+        ///
+        /// The bound is a pointer-sized integer.
+        bound: Offset32,
+    },
+
+    /// A static heap has a fixed base address and a number of not-yet-allocated pages before the
+    /// guard pages.
+    Static {
+        /// Heap bound in bytes. The guard pages are allocated after the bound.
+        bound: Imm64,
+    },
+}
+
+impl Display for HeapBase {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            HeapBase::VmCtx(offset) => write!(f, "vmctx{}", offset),
+        }
+    }
+}
+
+impl Display for HeapData {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self.style {
+                        HeapStyle::Dynamic { .. } => "dynamic",
+                        HeapStyle::Static { .. } => "static",
+                    })?;
+
+        write!(f, " {}", self.base)?;
+        match self.style {
+            HeapStyle::Dynamic { bound } => write!(f, ", bound vmctx{}", bound)?,
+            HeapStyle::Static { bound } => write!(f, ", bound {}", bound)?,
+        }
+        write!(f,
+               ", guard {}, index_type {}",
+               self.guard_size,
+               self.index_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeapData, HeapBase, HeapStyle};
+    use ir::immediates::{Imm64, Offset32};
+    use ir::types;
+
+    #[test]
+    fn display() {
+        let static_heap = HeapData {
+            base: HeapBase::VmCtx(Offset32::new(0)),
+            guard_size: Imm64::new(0x8000_0000),
+            style: HeapStyle::Static { bound: Imm64::new(0x1_0000_0000) },
+            index_type: types::I32,
+        };
+        assert_eq!(static_heap.to_string(),
+                   "static vmctx, bound 0x0001_0000_0000, guard 0x8000_0000, index_type i32");
+
+        let dynamic_heap = HeapData {
+            base: HeapBase::VmCtx(Offset32::new(16)),
+            guard_size: Imm64::new(0x1000),
+            style: HeapStyle::Dynamic { bound: Offset32::new(24) },
+            index_type: types::I64,
+        };
+        assert_eq!(dynamic_heap.to_string(),
+                   "dynamic vmctx+16, bound vmctx+24, guard 4096, index_type i64");
+    }
+}
@@ -149,6 +149,11 @@ impl FromStr for Imm64 {
 /// This is used to indicate lane indexes typically.
 pub type Uimm8 = u8;
 
+/// 32-bit unsigned integer immediate operand.
+///
+/// This is used to specify the size of a heap access.
+pub type Uimm32 = u32;
+
 /// 32-bit signed immediate offset.
 ///
 /// This is used to encode an immediate offset for load/store instructions. All supported ISAs have
@@ -219,7 +224,7 @@ impl FromStr for Offset32 {
 
 /// 32-bit unsigned immediate offset.
 ///
-/// This is used to encode an immediate offset for WebAssembly heap_load/heap_store instructions.
+/// This is used to encode an immediate offset for WebAssembly memory accesses.
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub struct Uoffset32(u32);
 
@@ -535,6 +540,16 @@ impl Ieee32 {
     pub fn new(x: f32) -> Ieee32 {
         Ieee32(unsafe { mem::transmute(x) })
     }
+
+    /// Create a new `Ieee32` with the bit pattern `bits`.
+    pub fn with_bits(bits: u32) -> Ieee32 {
+        Ieee32(bits)
+    }
+
+    /// Get the bit pattern of this number.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
 }
 
 impl Display for Ieee32 {
@@ -560,6 +575,16 @@ impl Ieee64 {
     pub fn new(x: f64) -> Ieee64 {
         Ieee64(unsafe { mem::transmute(x) })
     }
+
+    /// Create a new `Ieee64` with the bit pattern `bits`.
+    pub fn with_bits(bits: u64) -> Ieee64 {
+        Ieee64(bits)
+    }
+
+    /// Get the bit pattern of this number.
+    pub fn bits(self) -> u64 {
+        self.0
+    }
 }
 
 impl Display for Ieee64 {
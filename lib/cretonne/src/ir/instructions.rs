@@ -10,8 +10,9 @@ use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 use std::ops::{Deref, DerefMut};
 
-use ir::{Value, Type, Ebb, JumpTable, SigRef, FuncRef, StackSlot, MemFlags};
-use ir::immediates::{Imm64, Uimm8, Ieee32, Ieee64, Offset32, Uoffset32};
+use ir::{Value, Type, Ebb, JumpTable, SigRef, FuncRef, StackSlot, GlobalVar, Heap, Constant,
+         MemFlags, TrapCode, AtomicRmwOp, AtomicOrdering};
+use ir::immediates::{Imm64, Uimm8, Uimm32, Ieee32, Ieee64, Offset32};
 use ir::condcodes::*;
 use ir::types;
 use isa::RegUnit;
@@ -161,6 +162,7 @@ pub enum InstructionData {
         arg: Value,
         table: JumpTable,
     },
+    BranchTableBase { opcode: Opcode, table: JumpTable },
     Call {
         opcode: Opcode,
         func_ref: FuncRef,
@@ -182,15 +184,16 @@ pub enum InstructionData {
         stack_slot: StackSlot,
         offset: Offset32,
     },
-    HeapLoad {
+    UnaryGlobalVar {
         opcode: Opcode,
-        arg: Value,
-        offset: Uoffset32,
+        global_var: GlobalVar,
     },
-    HeapStore {
+    UnaryConst { opcode: Opcode, constant: Constant },
+    HeapAddr {
         opcode: Opcode,
-        args: [Value; 2],
-        offset: Uoffset32,
+        heap: Heap,
+        arg: Value,
+        imm: Uimm32,
     },
     Load {
         opcode: Opcode,
@@ -210,6 +213,26 @@ pub enum InstructionData {
         src: RegUnit,
         dst: RegUnit,
     },
+    AtomicRmw {
+        opcode: Opcode,
+        op: AtomicRmwOp,
+        args: [Value; 2],
+    },
+    Fence {
+        opcode: Opcode,
+        ordering: AtomicOrdering,
+    },
+    Trap { opcode: Opcode, code: TrapCode },
+    CondTrap {
+        opcode: Opcode,
+        arg: Value,
+        code: TrapCode,
+    },
+    BinaryTrap {
+        opcode: Opcode,
+        args: [Value; 2],
+        code: TrapCode,
+    },
 }
 
 /// A variable list of `Value` operands used for function call arguments and passing arguments to
@@ -347,6 +370,52 @@ impl InstructionData {
             _ => CallInfo::NotACall,
         }
     }
+
+    /// Get the trap code to report if this instruction traps.
+    ///
+    /// The trap instructions carry an explicit code. The other codes are implied by the
+    /// instruction:
+    ///
+    /// - Loads and stores with the `heap` flag report `heap_oob`. Other memory accesses don't say
+    ///   why they would fault, so they have no trap code.
+    /// - Atomic memory instructions have no flags. They report `heap_oob`.
+    /// - `udiv`, `urem`, and `srem` only trap on a zero divisor, and report `int_divz`. The
+    ///   remainder of `-2^(B-1) / -1` is 0, so `srem` can't overflow.
+    /// - `sdiv` also traps when the quotient overflows. Both reasons share the instruction, so
+    ///   this returns `int_divz` and `binemit::trap_site()` reports `int_ovf` as well.
+    /// - Float to integer conversions report `int_ovf`.
+    ///
+    /// Returns `None` for instructions that can't trap, or that have no trap code.
+    pub fn trap_code(&self) -> Option<TrapCode> {
+        match *self {
+            InstructionData::Trap { code, .. } |
+            InstructionData::CondTrap { code, .. } |
+            InstructionData::BinaryTrap { code, .. } => Some(code),
+            InstructionData::Load { flags, .. } |
+            InstructionData::Store { flags, .. } => {
+                if flags.heap() && !flags.notrap() {
+                    Some(TrapCode::HeapOutOfBounds)
+                } else {
+                    None
+                }
+            }
+            _ => {
+                match self.opcode() {
+                    Opcode::Udiv | Opcode::Sdiv | Opcode::Urem | Opcode::Srem => {
+                        Some(TrapCode::IntegerDivisionByZero)
+                    }
+                    Opcode::FcvtToUint | Opcode::FcvtToSint => Some(TrapCode::IntegerOverflow),
+                    // Atomic instructions don't have memory flags, so they can always trap.
+                    Opcode::HeapAddr |
+                    Opcode::AtomicLoad |
+                    Opcode::AtomicStore |
+                    Opcode::AtomicRmw |
+                    Opcode::AtomicCas => Some(TrapCode::HeapOutOfBounds),
+                    _ => None,
+                }
+            }
+        }
+    }
 }
 
 /// Information about branch and jump instructions.
@@ -623,6 +692,7 @@ pub enum ResolvedConstraint {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use entity_map::EntityRef;
 
     #[test]
     fn opcodes() {
@@ -653,6 +723,51 @@ mod tests {
         assert_eq!(mem::size_of::<Opcode>(), mem::size_of::<Option<Opcode>>());
     }
 
+    #[test]
+    fn trap_code() {
+        let mut flags = MemFlags::new();
+        let load = InstructionData::Load {
+            opcode: Opcode::Load,
+            flags,
+            arg: Value::new(0),
+            offset: Offset32::new(0),
+        };
+        assert_eq!(load.trap_code(), None);
+        flags.set_heap();
+        let load = InstructionData::Load {
+            opcode: Opcode::Load,
+            flags,
+            arg: Value::new(0),
+            offset: Offset32::new(0),
+        };
+        assert_eq!(load.trap_code(), Some(TrapCode::HeapOutOfBounds));
+        flags.set_notrap();
+        let load = InstructionData::Load {
+            opcode: Opcode::Load,
+            flags,
+            arg: Value::new(0),
+            offset: Offset32::new(0),
+        };
+        assert_eq!(load.trap_code(), None);
+
+        let div = InstructionData::Binary {
+            opcode: Opcode::Udiv,
+            args: [Value::new(0), Value::new(1)],
+        };
+        assert_eq!(div.trap_code(), Some(TrapCode::IntegerDivisionByZero));
+        let add = InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args: [Value::new(0), Value::new(1)],
+        };
+        assert_eq!(add.trap_code(), None);
+
+        let trap = InstructionData::Trap {
+            opcode: Opcode::Trap,
+            code: TrapCode::User(3),
+        };
+        assert_eq!(trap.trap_code(), Some(TrapCode::User(3)));
+    }
+
     #[test]
     fn instruction_data() {
         use std::mem;
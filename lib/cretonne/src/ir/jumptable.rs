@@ -31,6 +31,16 @@ impl JumpTableData {
         }
     }
 
+    /// Get the number of table entries, including holes.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Check if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
     /// Set a table entry.
     ///
     /// The table will grow as needed to fit `idx`.
@@ -128,6 +138,7 @@ mod tests {
     fn empty() {
         let jt = JumpTableData::new();
 
+        assert!(jt.is_empty());
         assert_eq!(jt.get_entry(0), None);
         assert_eq!(jt.get_entry(10), None);
 
@@ -148,6 +159,7 @@ mod tests {
         jt.set_entry(0, e2);
         jt.set_entry(10, e1);
 
+        assert_eq!(jt.len(), 11);
         assert_eq!(jt.to_string(),
                    "jump_table ebb2, 0, 0, 0, 0, 0, 0, 0, 0, 0, ebb1");
 
@@ -339,6 +339,34 @@ impl Layout {
         self.assign_ebb_seq(ebb);
     }
 
+    /// Remove `ebb` from the layout.
+    ///
+    /// The EBB must be empty. Remove all of its instructions first.
+    pub fn remove_ebb(&mut self, ebb: Ebb) {
+        assert!(self.is_ebb_inserted(ebb), "EBB not in the layout");
+        assert!(self.first_inst(ebb).is_none(), "Cannot remove EBB with instructions");
+
+        // Clear the `ebb` node and extract links.
+        let prev;
+        let next;
+        {
+            let n = &mut self.ebbs[ebb];
+            prev = n.prev;
+            next = n.next;
+            n.prev = None.into();
+            n.next = None.into();
+        }
+        // Fix up links to `ebb`.
+        match prev.expand() {
+            None => self.first_ebb = next.expand(),
+            Some(p) => self.ebbs[p].next = next,
+        }
+        match next.expand() {
+            None => self.last_ebb = prev.expand(),
+            Some(n) => self.ebbs[n].prev = prev,
+        }
+    }
+
     /// Return an iterator over all EBBs in layout order.
     pub fn ebbs<'f>(&'f self) -> Ebbs<'f> {
         Ebbs {
@@ -1132,6 +1160,37 @@ mod tests {
         verify(&mut layout, &[(e2, &[]), (e0, &[]), (e1, &[])]);
     }
 
+    #[test]
+    fn remove_ebb() {
+        let mut layout = Layout::new();
+        let e0 = Ebb::new(0);
+        let e1 = Ebb::new(1);
+        let e2 = Ebb::new(2);
+
+        layout.append_ebb(e0);
+        layout.append_ebb(e1);
+        layout.append_ebb(e2);
+        verify(&mut layout, &[(e0, &[]), (e1, &[]), (e2, &[])]);
+
+        layout.remove_ebb(e1);
+        assert!(!layout.is_ebb_inserted(e1));
+        verify(&mut layout, &[(e0, &[]), (e2, &[])]);
+
+        layout.remove_ebb(e0);
+        assert!(!layout.is_ebb_inserted(e0));
+        assert_eq!(layout.entry_block(), Some(e2));
+        verify(&mut layout, &[(e2, &[])]);
+
+        layout.remove_ebb(e2);
+        assert_eq!(layout.entry_block(), None);
+        verify(&mut layout, &[]);
+
+        // Removed EBBs can be inserted again.
+        layout.append_ebb(e1);
+        layout.insert_ebb(e0, e1);
+        verify(&mut layout, &[(e0, &[]), (e1, &[])]);
+    }
+
     #[test]
     fn insert_ebb_after() {
         let mut layout = Layout::new();
@@ -5,9 +5,11 @@ use std::fmt;
 enum FlagBit {
     Notrap,
     Aligned,
+    Readonly,
+    Heap,
 }
 
-const NAMES: [&str; 2] = ["notrap", "aligned"];
+const NAMES: [&str; 4] = ["notrap", "aligned", "readonly", "heap"];
 
 /// Flags for memory operations like load/store.
 ///
@@ -78,6 +80,34 @@ impl MemFlags {
     pub fn set_aligned(&mut self) {
         self.set(FlagBit::Aligned)
     }
+
+    /// Test if the `readonly` flag is set.
+    ///
+    /// Loads with this flag read from memory that is not written by any store or call while the
+    /// function is running. This makes it possible to move the load across stores and calls, for
+    /// example when hoisting it out of a loop.
+    pub fn readonly(self) -> bool {
+        self.read(FlagBit::Readonly)
+    }
+
+    /// Set the `readonly` flag.
+    pub fn set_readonly(&mut self) {
+        self.set(FlagBit::Readonly)
+    }
+
+    /// Test if the `heap` flag is set.
+    ///
+    /// The `heap` flag marks an access through an address computed by `heap_addr`. If the access
+    /// traps, the heap access was out of bounds, for example because it hit a guard page. The
+    /// trap is reported to the code sink as `heap_oob`.
+    pub fn heap(self) -> bool {
+        self.read(FlagBit::Heap)
+    }
+
+    /// Set the `heap` flag.
+    pub fn set_heap(&mut self) {
+        self.set(FlagBit::Heap)
+    }
 }
 
 impl fmt::Display for MemFlags {
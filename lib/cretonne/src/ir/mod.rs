@@ -7,23 +7,34 @@ pub mod immediates;
 pub mod instructions;
 pub mod stackslot;
 pub mod jumptable;
+pub mod constantpool;
+pub mod globalvar;
+pub mod heap;
 pub mod dfg;
 pub mod layout;
 pub mod function;
+pub mod sourceloc;
+mod atomics;
 mod builder;
 mod extfunc;
 mod funcname;
 mod memflags;
 mod progpoint;
+mod trapcode;
 mod valueloc;
 
 pub use ir::funcname::FunctionName;
-pub use ir::extfunc::{Signature, ArgumentType, ArgumentExtension, ArgumentPurpose, ExtFuncData};
+pub use ir::extfunc::{Signature, CallConv, ArgumentType, ArgumentExtension, ArgumentPurpose,
+                      ExtFuncData};
 pub use ir::types::Type;
-pub use ir::entities::{Ebb, Inst, Value, StackSlot, JumpTable, FuncRef, SigRef};
+pub use ir::entities::{Ebb, Inst, Value, StackSlot, GlobalVar, JumpTable, FuncRef, SigRef, Heap,
+                       Constant};
 pub use ir::instructions::{Opcode, InstructionData, VariableArgs, ValueList, ValueListPool};
 pub use ir::stackslot::StackSlotData;
 pub use ir::jumptable::JumpTableData;
+pub use ir::constantpool::{ConstantData, ConstantPool};
+pub use ir::globalvar::GlobalVarData;
+pub use ir::heap::{HeapData, HeapBase, HeapStyle};
 pub use ir::valueloc::{ValueLoc, ArgumentLoc};
 pub use ir::dfg::{DataFlowGraph, ValueDef};
 pub use ir::layout::{Layout, Cursor};
@@ -31,3 +42,6 @@ pub use ir::function::Function;
 pub use ir::builder::InstBuilder;
 pub use ir::progpoint::{ProgramPoint, ProgramOrder, ExpandedProgramPoint};
 pub use ir::memflags::MemFlags;
+pub use ir::sourceloc::{SourceLoc, SourceLocs};
+pub use ir::trapcode::TrapCode;
+pub use ir::atomics::{AtomicRmwOp, AtomicOrdering};
@@ -1,15 +1,30 @@
 //! ARM 64 ABI implementation.
 
-use ir;
+use ir::{self, CallConv};
 use isa::RegClass;
 use regalloc::AllocatableSet;
 use settings as shared_settings;
 use super::registers::{GPR, FPR};
 
+/// Is `call_conv` supported on ARM?
+pub fn supports_call_conv(call_conv: CallConv) -> bool {
+    // All the supported conventions use the standard AAPCS64 register assignments.
+    match call_conv {
+        CallConv::Native | CallConv::Fast | CallConv::Cold | CallConv::SystemV |
+        CallConv::Baldrdash => true,
+        CallConv::WindowsFastcall => false,
+    }
+}
+
 /// Legalize `sig`.
-pub fn legalize_signature(_sig: &mut ir::Signature,
+///
+/// The calling convention of `sig` must be supported.
+pub fn legalize_signature(sig: &mut ir::Signature,
                           _flags: &shared_settings::Flags,
                           _current: bool) {
+    debug_assert!(supports_call_conv(sig.call_conv),
+                  "The {} calling convention is not supported on ARM",
+                  sig.call_conv);
     unimplemented!()
 }
 
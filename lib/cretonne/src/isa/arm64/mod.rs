@@ -6,7 +6,7 @@ mod binemit;
 mod enc_tables;
 mod registers;
 
-use binemit::CodeSink;
+use binemit::{CodeSink, MemoryCodeSink, emit_function};
 use super::super::settings as shared_settings;
 use isa::enc_tables::{lookup_enclist, general_encoding};
 use isa::Builder as IsaBuilder;
@@ -72,6 +72,10 @@ impl TargetIsa for Isa {
                 })
     }
 
+    fn supports_call_conv(&self, call_conv: ir::CallConv) -> bool {
+        abi::supports_call_conv(call_conv)
+    }
+
     fn legalize_signature(&self, sig: &mut ir::Signature, current: bool) {
         abi::legalize_signature(sig, &self.shared_flags, current)
     }
@@ -88,6 +92,10 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, sink)
     }
 
+    fn emit_function(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
+        emit_function(func, binemit::emit_inst, sink)
+    }
+
     fn reloc_names(&self) -> &'static [&'static str] {
         &binemit::RELOC_NAMES
     }
@@ -84,7 +84,14 @@ pub fn lookup_enclist<OffT1, OffT2>(ctrl_typevar: Type,
 {
     // TODO: The choice of legalization actions here is naive. This needs to be configurable.
     probe(level1_table, ctrl_typevar, ctrl_typevar.index())
-        .ok_or_else(|| if ctrl_typevar.lane_type().bits() > 32 {
+        .ok_or_else(|| if !ctrl_typevar.is_scalar() {
+                        // Vectors that don't fit in a 128-bit SIMD register are split in halves.
+                        if ctrl_typevar.bits() > 128 {
+                            Legalize::SplitVector
+                        } else {
+                            Legalize::Expand
+                        }
+                    } else if ctrl_typevar.lane_type().bits() > 32 {
                         Legalize::Narrow
                     } else {
                         Legalize::Expand
@@ -1,16 +1,167 @@
 //! Intel ABI implementation.
 
-use ir;
-use isa::RegClass;
+use abi::{ArgAction, ValueConversion, ArgAssigner, legalize_args};
+use ir::{self, Type, ArgumentType, ArgumentLoc, ArgumentExtension, CallConv};
+use isa::{RegClass, RegUnit};
 use regalloc::AllocatableSet;
 use settings as shared_settings;
 use super::registers::{GPR, FPR};
 
+/// Argument registers for the System V AMD64 ABI: `%rdi`, `%rsi`, `%rdx`, `%rcx`, `%r8`, `%r9`.
+static SYSV_ARGS: [RegUnit; 6] = [7, 6, 2, 1, 8, 9];
+
+/// Argument registers for the Windows x64 convention: `%rcx`, `%rdx`, `%r8`, `%r9`.
+static FASTCALL_ARGS: [RegUnit; 4] = [1, 2, 8, 9];
+
+/// Argument registers for the Cretonne conventions in 32-bit mode: `%eax`, `%edx`, `%ecx`.
+///
+/// This is like GCC's `regparm(3)` convention. The standard 32-bit conventions pass most arguments
+/// on the stack, but Intel doesn't have the spill and fill encodings needed to access stack
+/// arguments yet.
+static REGPARM_ARGS: [RegUnit; 3] = [0, 2, 1];
+
+/// Return value registers: `%rax`, `%rdx`.
+static RETURNS: [RegUnit; 2] = [0, 2];
+
+struct Args {
+    pointer_bytes: u32,
+    pointer_type: Type,
+    int_regs: &'static [RegUnit],
+    float_regs: usize,
+    /// Integer and float arguments share positions, so each argument uses up one of each.
+    shared_positions: bool,
+    ints: usize,
+    floats: usize,
+    offset: u32,
+}
+
+impl Args {
+    fn new(bits: u16,
+           int_regs: &'static [RegUnit],
+           float_regs: usize,
+           shared_positions: bool)
+           -> Args {
+        Args {
+            pointer_bytes: bits as u32 / 8,
+            pointer_type: Type::int(bits).unwrap(),
+            int_regs,
+            float_regs,
+            shared_positions,
+            ints: 0,
+            floats: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl ArgAssigner for Args {
+    fn assign(&mut self, arg: &ArgumentType) -> ArgAction {
+        let ty = arg.value_type;
+
+        // Vectors wider than an SSE register are broken down.
+        if !ty.is_scalar() && ty.bits() > 128 {
+            return ValueConversion::VectorSplit.into();
+        }
+
+        // Large integers and booleans are broken down to fit in a register.
+        if ty.is_scalar() && !ty.is_float() && ty.bits() as u32 > self.pointer_bytes * 8 {
+            return ValueConversion::IntSplit.into();
+        }
+
+        // Small integers are extended to the size of a pointer register.
+        if ty.is_int() && (ty.bits() as u32) < self.pointer_bytes * 8 {
+            match arg.extension {
+                ArgumentExtension::None => {}
+                ArgumentExtension::Uext => return ValueConversion::Uext(self.pointer_type).into(),
+                ArgumentExtension::Sext => return ValueConversion::Sext(self.pointer_type).into(),
+            }
+        }
+
+        // Floats and vectors go in SSE registers, everything else in general purpose registers.
+        let is_sse = ty.is_float() || !ty.is_scalar();
+        let reg = if is_sse {
+            if self.floats < self.float_regs {
+                Some(FPR.unit(self.floats))
+            } else {
+                None
+            }
+        } else {
+            self.int_regs.get(self.ints).cloned()
+        };
+
+        if let Some(reg) = reg {
+            if is_sse || self.shared_positions {
+                self.floats += 1;
+            }
+            if !is_sse || self.shared_positions {
+                self.ints += 1;
+            }
+            ArgumentLoc::Reg(reg).into()
+        } else {
+            // Assign a stack location. Stack slots are pointer-sized, except for vectors.
+            let size = if is_sse && !ty.is_scalar() {
+                16
+            } else {
+                self.pointer_bytes
+            };
+            self.offset = (self.offset + size - 1) & !(size - 1);
+            let loc = ArgumentLoc::Stack(self.offset);
+            self.offset += size;
+            loc.into()
+        }
+    }
+}
+
+/// Is `call_conv` supported on Intel?
+///
+/// All the calling conventions are supported in 64-bit mode. In 32-bit mode, only the `native`,
+/// `fast`, and `cold` conventions are supported, and they pass their first arguments in registers.
+/// The 32-bit System V, Windows `fastcall`, and Baldrdash conventions are defined elsewhere, and
+/// they are not implemented.
+pub fn supports_call_conv(call_conv: CallConv, flags: &shared_settings::Flags) -> bool {
+    match call_conv {
+        CallConv::Native | CallConv::Fast | CallConv::Cold => true,
+        CallConv::SystemV | CallConv::WindowsFastcall | CallConv::Baldrdash => flags.is_64bit(),
+    }
+}
+
 /// Legalize `sig`.
-pub fn legalize_signature(_sig: &mut ir::Signature,
-                          _flags: &shared_settings::Flags,
+pub fn legalize_signature(sig: &mut ir::Signature,
+                          flags: &shared_settings::Flags,
                           _current: bool) {
-    unimplemented!()
+    let bits = if flags.is_64bit() { 64 } else { 32 };
+
+    // In 32-bit mode, the supported conventions pass the first arguments in registers.
+    debug_assert!(supports_call_conv(sig.call_conv, flags),
+                  "Unsupported calling convention: {}",
+                  sig.call_conv);
+    let (int_regs, float_regs): (&'static [RegUnit], usize) = if !flags.is_64bit() {
+        (&REGPARM_ARGS, 8)
+    } else {
+        match sig.call_conv {
+            CallConv::Native | CallConv::Fast | CallConv::Cold | CallConv::SystemV |
+            CallConv::Baldrdash => (&SYSV_ARGS, 8),
+            CallConv::WindowsFastcall => (&FASTCALL_ARGS, 4),
+        }
+    };
+
+    let fastcall = flags.is_64bit() && sig.call_conv == CallConv::WindowsFastcall;
+    let mut args = Args::new(bits, int_regs, float_regs, fastcall);
+    if fastcall {
+        // The Windows convention reserves a 32-byte shadow area for the register arguments.
+        args.offset = 32;
+    }
+    legalize_args(&mut sig.argument_types, &mut args);
+
+    // Return values use `%rax`, `%rdx`, and `%xmm0`, `%xmm1`. The Windows convention only uses
+    // the first register of each kind.
+    let (int_rets, float_rets): (&'static [RegUnit], usize) = if fastcall {
+        (&RETURNS[0..1], 1)
+    } else {
+        (&RETURNS, 2)
+    };
+    let mut rets = Args::new(bits, int_rets, float_rets, fastcall);
+    legalize_args(&mut sig.return_types, &mut rets);
 }
 
 /// Get register class for a type appearing in a legalized signature.
@@ -19,6 +170,25 @@ pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
 }
 
 /// Get the set of allocatable registers for `func`.
-pub fn allocatable_registers(_func: &ir::Function) -> AllocatableSet {
-    unimplemented!()
+///
+/// Functions don't have a prologue that saves callee-saved registers yet, so only the caller-saved
+/// registers are allocatable. The encodings don't use REX prefixes, so `%r8`-`%r15` and
+/// `%xmm8`-`%xmm15` are unavailable too.
+pub fn allocatable_registers(_func: &ir::Function,
+                             flags: &shared_settings::Flags)
+                             -> AllocatableSet {
+    let mut regs = AllocatableSet::new();
+    regs.take(GPR, GPR.unit(3)); // %rbx is callee-saved.
+    regs.take(GPR, GPR.unit(4)); // Stack pointer.
+    regs.take(GPR, GPR.unit(5)); // Frame pointer.
+    if !flags.is_64bit() {
+        // %esi and %edi are callee-saved in 32-bit mode.
+        regs.take(GPR, GPR.unit(6));
+        regs.take(GPR, GPR.unit(7));
+    }
+    for u in 8..16 {
+        regs.take(GPR, GPR.unit(u));
+        regs.take(FPR, FPR.unit(u));
+    }
+    regs
 }
@@ -1,12 +1,48 @@
 //! Emitting binary Intel machine code.
 
-use binemit::{CodeSink, bad_encoding};
-use ir::{Function, Inst, InstructionData};
+use binemit::{CodeSink, CodeOffset, Reloc, bad_encoding, trap_site};
+use ir::{Function, Inst, InstructionData, SourceLoc, TrapCode, Type};
+use ir::condcodes::IntCC;
 use isa::RegUnit;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-intel.rs"));
 
-pub static RELOC_NAMES: [&'static str; 1] = ["Call"];
+/// Intel relocation kinds.
+pub enum RelocKind {
+    /// A 4-byte call displacement relative to the end of the displacement field.
+    Call,
+    /// A 4-byte absolute address.
+    Abs4,
+    /// A 4-byte absolute address of a constant in the function's constant pool.
+    ConstAbs4,
+    /// A 4-byte displacement to a constant in the function's constant pool, relative to the end of
+    /// the displacement field. The displacement is filled in when the constant pool is emitted
+    /// after the function body.
+    ConstPcrel4,
+}
+
+pub static RELOC_NAMES: [&'static str; 4] = ["Call", "Abs4", "ConstAbs4", "ConstPcrel4"];
+
+impl Into<Reloc> for RelocKind {
+    fn into(self) -> Reloc {
+        Reloc(self as u16)
+    }
+}
+
+/// Patch the relocation `reloc` at `offset` in `code` to refer to `target`.
+///
+/// Only PC-relative relocations can be resolved without knowing where the code will be loaded.
+pub fn apply_reloc(reloc: Reloc, code: &mut [u8], offset: CodeOffset, target: CodeOffset) {
+    assert_eq!(reloc.0,
+               RelocKind::Call as u16,
+               "Can't resolve {} relocation locally",
+               RELOC_NAMES[reloc.0 as usize]);
+    let ofs = offset as usize;
+    let disp = target.wrapping_sub(offset + 4);
+    for (n, byte) in code[ofs..ofs + 4].iter_mut().enumerate() {
+        *byte = (disp >> (8 * n)) as u8;
+    }
+}
 
 // Emit single-byte opcode.
 fn put_op1<CS: CodeSink + ?Sized>(bits: u16, sink: &mut CS) {
@@ -14,6 +50,13 @@ fn put_op1<CS: CodeSink + ?Sized>(bits: u16, sink: &mut CS) {
     sink.put1(bits as u8);
 }
 
+// Emit single-byte opcode with a REX.W prefix.
+fn put_rexop1<CS: CodeSink + ?Sized>(bits: u16, sink: &mut CS) {
+    debug_assert!(bits & 0x8f00 == 0x8000, "Invalid encoding bits for RexOp1*");
+    sink.put1(0x48);
+    sink.put1(bits as u8);
+}
+
 // Emit two-byte opcode: 0F XX
 fn put_op2<CS: CodeSink + ?Sized>(bits: u16, sink: &mut CS) {
     debug_assert!(bits & 0x0f00 == 0x0400, "Invalid encoding bits for Op2*");
@@ -32,6 +75,28 @@ fn put_mp1<CS: CodeSink + ?Sized>(bits: u16, sink: &mut CS) {
     sink.put1(bits as u8);
 }
 
+// Emit two-byte opcode (0F XX) with mandatory prefix.
+fn put_mp2<CS: CodeSink + ?Sized>(bits: u16, sink: &mut CS) {
+    debug_assert!(bits & 0x0c00 == 0x0400, "Invalid encoding bits for Mp2*");
+    let pp = (bits >> 8) & 3;
+    sink.put1(PREFIX[(pp - 1) as usize]);
+    sink.put1(0x0f);
+    sink.put1(bits as u8);
+}
+
+// Second byte of the three-byte opcode maps 0F 38 and 0F 3A.
+const OP3_BYTE2: [u8; 2] = [0x38, 0x3a];
+
+// Emit three-byte opcode (0F 38 XX or 0F 3A XX) with mandatory prefix.
+fn put_mp3<CS: CodeSink + ?Sized>(bits: u16, sink: &mut CS) {
+    debug_assert!(bits & 0x0800 == 0x0800, "Invalid encoding bits for Mp3*");
+    let pp = (bits >> 8) & 3;
+    sink.put1(PREFIX[(pp - 1) as usize]);
+    sink.put1(0x0f);
+    sink.put1(OP3_BYTE2[((bits >> 10) & 1) as usize]);
+    sink.put1(bits as u8);
+}
+
 /// Emit a ModR/M byte for reg-reg operands.
 fn modrm_rr<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS) {
     let reg = reg as u8 & 7;
@@ -88,6 +153,12 @@ fn modrm_disp32<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS)
     sink.put1(b);
 }
 
+/// Emit a single-byte opcode with the register number in the low 3 bits.
+fn put_op1_reg<CS: CodeSink + ?Sized>(bits: u16, reg: RegUnit, sink: &mut CS) {
+    debug_assert!(bits & 0x0f07 == 0, "Invalid encoding bits for Op1*_reg");
+    sink.put1(bits as u8 | (reg as u8 & 7));
+}
+
 fn recipe_op1rr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Binary { args, .. } = func.dfg[inst] {
         put_op1(func.encodings[inst].bits(), sink);
@@ -99,6 +170,71 @@ fn recipe_op1rr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut C
     }
 }
 
+fn recipe_rexop1rr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        put_rexop1(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[0]].unwrap_reg(),
+                 func.locations[args[1]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1umr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        let res = func.dfg.first_result(inst);
+        put_op1(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[res].unwrap_reg(),
+                 func.locations[arg].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_rexop1umr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        let res = func.dfg.first_result(inst);
+        put_rexop1(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[res].unwrap_reg(),
+                 func.locations[arg].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_rexop1urm<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        let res = func.dfg.first_result(inst);
+        put_rexop1(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[arg].unwrap_reg(),
+                 func.locations[res].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1rmov<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::RegMove { src, dst, .. } = func.dfg[inst] {
+        put_op1(func.encodings[inst].bits(), sink);
+        modrm_rr(dst, src, sink);
+    } else {
+        panic!("Expected RegMove format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_rexop1rmov<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::RegMove { src, dst, .. } = func.dfg[inst] {
+        put_rexop1(func.encodings[inst].bits(), sink);
+        modrm_rr(dst, src, sink);
+    } else {
+        panic!("Expected RegMove format: {:?}", func.dfg[inst]);
+    }
+}
+
 fn recipe_op1rc<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Binary { args, .. } = func.dfg[inst] {
         let bits = func.encodings[inst].bits();
@@ -121,6 +257,18 @@ fn recipe_op1rib<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut
     }
 }
 
+fn recipe_rexop1rib<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BinaryImm { arg, imm, .. } = func.dfg[inst] {
+        let bits = func.encodings[inst].bits();
+        put_rexop1(bits, sink);
+        modrm_r_bits(func.locations[arg].unwrap_reg(), bits, sink);
+        let imm: i64 = imm.into();
+        sink.put1(imm as u8);
+    } else {
+        panic!("Expected BinaryImm format: {:?}", func.dfg[inst]);
+    }
+}
+
 fn recipe_op1rid<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::BinaryImm { arg, imm, .. } = func.dfg[inst] {
         let bits = func.encodings[inst].bits();
@@ -133,10 +281,23 @@ fn recipe_op1rid<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut
     }
 }
 
+fn recipe_rexop1rid<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BinaryImm { arg, imm, .. } = func.dfg[inst] {
+        let bits = func.encodings[inst].bits();
+        put_rexop1(bits, sink);
+        modrm_r_bits(func.locations[arg].unwrap_reg(), bits, sink);
+        let imm: i64 = imm.into();
+        sink.put4(imm as u32);
+    } else {
+        panic!("Expected BinaryImm format: {:?}", func.dfg[inst]);
+    }
+}
+
 // Store recipes.
 
 fn recipe_op1st<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Store { args, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op1(func.encodings[inst].bits(), sink);
         modrm_rm(func.locations[args[1]].unwrap_reg(),
                  func.locations[args[0]].unwrap_reg(),
@@ -153,6 +314,7 @@ fn recipe_op1st_abcd<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &
 
 fn recipe_mp1st<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Store { args, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_mp1(func.encodings[inst].bits(), sink);
         modrm_rm(func.locations[args[1]].unwrap_reg(),
                  func.locations[args[0]].unwrap_reg(),
@@ -164,6 +326,7 @@ fn recipe_mp1st<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut C
 
 fn recipe_op1stdisp8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Store { args, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op1(func.encodings[inst].bits(), sink);
         modrm_disp8(func.locations[args[1]].unwrap_reg(),
                     func.locations[args[0]].unwrap_reg(),
@@ -181,6 +344,7 @@ fn recipe_op1stdisp8_abcd<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, si
 
 fn recipe_mp1stdisp8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Store { args, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_mp1(func.encodings[inst].bits(), sink);
         modrm_disp8(func.locations[args[1]].unwrap_reg(),
                     func.locations[args[0]].unwrap_reg(),
@@ -194,6 +358,7 @@ fn recipe_mp1stdisp8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &
 
 fn recipe_op1stdisp32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Store { args, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op1(func.encodings[inst].bits(), sink);
         modrm_disp32(func.locations[args[1]].unwrap_reg(),
                      func.locations[args[0]].unwrap_reg(),
@@ -211,6 +376,7 @@ fn recipe_op1stdisp32_abcd<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, s
 
 fn recipe_mp1stdisp32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Store { args, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_mp1(func.encodings[inst].bits(), sink);
         modrm_disp32(func.locations[args[1]].unwrap_reg(),
                      func.locations[args[0]].unwrap_reg(),
@@ -226,6 +392,7 @@ fn recipe_mp1stdisp32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink:
 
 fn recipe_op1ld<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Load { arg, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op1(func.encodings[inst].bits(), sink);
         modrm_rm(func.locations[arg].unwrap_reg(),
                  func.locations[func.dfg.first_result(inst)].unwrap_reg(),
@@ -235,8 +402,21 @@ fn recipe_op1ld<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut C
     }
 }
 
+fn recipe_rexop1ld<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Load { arg, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_rexop1(func.encodings[inst].bits(), sink);
+        modrm_rm(func.locations[arg].unwrap_reg(),
+                 func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Load format: {:?}", func.dfg[inst]);
+    }
+}
+
 fn recipe_op1lddisp8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Load { arg, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op1(func.encodings[inst].bits(), sink);
         modrm_disp8(func.locations[arg].unwrap_reg(),
                     func.locations[func.dfg.first_result(inst)].unwrap_reg(),
@@ -248,8 +428,23 @@ fn recipe_op1lddisp8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &
     }
 }
 
+fn recipe_rexop1lddisp8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Load { arg, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_rexop1(func.encodings[inst].bits(), sink);
+        modrm_disp8(func.locations[arg].unwrap_reg(),
+                    func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                    sink);
+        let offset: i32 = offset.into();
+        sink.put1(offset as u8);
+    } else {
+        panic!("Expected Load format: {:?}", func.dfg[inst]);
+    }
+}
+
 fn recipe_op1lddisp32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Load { arg, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op1(func.encodings[inst].bits(), sink);
         modrm_disp32(func.locations[arg].unwrap_reg(),
                      func.locations[func.dfg.first_result(inst)].unwrap_reg(),
@@ -261,8 +456,35 @@ fn recipe_op1lddisp32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink:
     }
 }
 
+fn recipe_rexop1lddisp32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Load { arg, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_rexop1(func.encodings[inst].bits(), sink);
+        modrm_disp32(func.locations[arg].unwrap_reg(),
+                     func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                     sink);
+        let offset: i32 = offset.into();
+        sink.put4(offset as u32);
+    } else {
+        panic!("Expected Load format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2fld<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Load { arg, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rm(func.locations[arg].unwrap_reg(),
+                 func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Load format: {:?}", func.dfg[inst]);
+    }
+}
+
 fn recipe_op2ld<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Load { arg, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op2(func.encodings[inst].bits(), sink);
         modrm_rm(func.locations[arg].unwrap_reg(),
                  func.locations[func.dfg.first_result(inst)].unwrap_reg(),
@@ -274,6 +496,7 @@ fn recipe_op2ld<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut C
 
 fn recipe_op2lddisp8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Load { arg, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op2(func.encodings[inst].bits(), sink);
         modrm_disp8(func.locations[arg].unwrap_reg(),
                     func.locations[func.dfg.first_result(inst)].unwrap_reg(),
@@ -287,6 +510,7 @@ fn recipe_op2lddisp8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &
 
 fn recipe_op2lddisp32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::Load { arg, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
         put_op2(func.encodings[inst].bits(), sink);
         modrm_disp32(func.locations[arg].unwrap_reg(),
                      func.locations[func.dfg.first_result(inst)].unwrap_reg(),
@@ -297,3 +521,570 @@ fn recipe_op2lddisp32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink:
         panic!("Expected Load format: {:?}", func.dfg[inst]);
     }
 }
+
+// Global variable recipes.
+
+fn recipe_op1gvaddr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::UnaryGlobalVar { global_var, .. } = func.dfg[inst] {
+        put_op1_reg(func.encodings[inst].bits(),
+                    func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                    sink);
+        sink.reloc_globalsym(RelocKind::Abs4.into(), global_var);
+        sink.put4(0);
+    } else {
+        panic!("Expected UnaryGlobalVar format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1puid<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::UnaryImm { imm, .. } = func.dfg[inst] {
+        put_op1_reg(func.encodings[inst].bits(),
+                    func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                    sink);
+        let imm: i64 = imm.into();
+        sink.put4(imm as u32);
+    } else {
+        panic!("Expected UnaryImm format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1puiq<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    recipe_op1puid(func, inst, sink)
+}
+
+fn recipe_rexop1uid<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::UnaryImm { imm, .. } = func.dfg[inst] {
+        let bits = func.encodings[inst].bits();
+        put_rexop1(bits, sink);
+        modrm_r_bits(func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                     bits,
+                     sink);
+        let imm: i64 = imm.into();
+        sink.put4(imm as u32);
+    } else {
+        panic!("Expected UnaryImm format: {:?}", func.dfg[inst]);
+    }
+}
+
+// Branch recipes.
+
+/// Get the jcc condition code bits for `cond`.
+fn icc2opc(cond: IntCC) -> u8 {
+    use ir::condcodes::IntCC::*;
+    match cond {
+        Equal => 0x4,
+        NotEqual => 0x5,
+        SignedLessThan => 0xc,
+        SignedGreaterThanOrEqual => 0xd,
+        SignedGreaterThan => 0xf,
+        SignedLessThanOrEqual => 0xe,
+        UnsignedLessThan => 0x2,
+        UnsignedGreaterThanOrEqual => 0x3,
+        UnsignedGreaterThan => 0x7,
+        UnsignedLessThanOrEqual => 0x6,
+    }
+}
+
+fn recipe_op1icbr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BranchIcmp {
+               cond,
+               destination,
+               ref args,
+               ..
+           } = func.dfg[inst] {
+        let args = &args.as_slice(&func.dfg.value_lists)[0..2];
+        // cmp x, y
+        put_op1(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[0]].unwrap_reg(),
+                 func.locations[args[1]].unwrap_reg(),
+                 sink);
+        // jcc rel32, relative to the end of the instruction.
+        sink.put1(0x0f);
+        sink.put1(0x80 | icc2opc(cond));
+        let disp = func.offsets[destination] as i64 - (sink.offset() + 4) as i64;
+        sink.put4(disp as u32);
+    } else {
+        panic!("Expected BranchIcmp format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_rexop1icbr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BranchIcmp {
+               cond,
+               destination,
+               ref args,
+               ..
+           } = func.dfg[inst] {
+        let args = &args.as_slice(&func.dfg.value_lists)[0..2];
+        // cmp x, y
+        put_rexop1(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[0]].unwrap_reg(),
+                 func.locations[args[1]].unwrap_reg(),
+                 sink);
+        // jcc rel32, relative to the end of the instruction.
+        sink.put1(0x0f);
+        sink.put1(0x80 | icc2opc(cond));
+        let disp = func.offsets[destination] as i64 - (sink.offset() + 4) as i64;
+        sink.put4(disp as u32);
+    } else {
+        panic!("Expected BranchIcmp format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1constaddr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::UnaryConst { constant, .. } = func.dfg[inst] {
+        put_op1_reg(func.encodings[inst].bits(),
+                    func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                    sink);
+        sink.reloc_constant(RelocKind::ConstAbs4.into(), constant);
+        sink.put4(0);
+    } else {
+        panic!("Expected UnaryConst format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_rexop1constrel<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::UnaryConst { constant, .. } = func.dfg[inst] {
+        put_rexop1(func.encodings[inst].bits(), sink);
+        // Mode 00 with r/m = 101 is RIP-relative in 64-bit mode.
+        modrm_rm(5,
+                 func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                 sink);
+        // The displacement is relative to the end of the instruction.
+        sink.reloc_constant(RelocKind::ConstPcrel4.into(), constant);
+        let disp = func.const_offsets[constant] as i64 - (sink.offset() + 4) as i64;
+        sink.put4(disp as u32);
+    } else {
+        panic!("Expected UnaryConst format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1jtbase<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BranchTableBase { table, .. } = func.dfg[inst] {
+        put_op1_reg(func.encodings[inst].bits(),
+                    func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                    sink);
+        sink.reloc_jt(RelocKind::Abs4.into(), table);
+        sink.put4(0);
+    } else {
+        panic!("Expected BranchTableBase format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_rexop1jtrel<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BranchTableBase { table, .. } = func.dfg[inst] {
+        put_rexop1(func.encodings[inst].bits(), sink);
+        // Mode 00 with r/m = 101 is RIP-relative in 64-bit mode.
+        modrm_rm(5,
+                 func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                 sink);
+        // The displacement is relative to the end of the instruction.
+        let disp = func.jt_offsets[table] as i64 - (sink.offset() + 4) as i64;
+        sink.put4(disp as u32);
+    } else {
+        panic!("Expected BranchTableBase format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1indjmp<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BranchTable { arg, .. } = func.dfg[inst] {
+        let bits = func.encodings[inst].bits();
+        put_op1(bits, sink);
+        modrm_r_bits(func.locations[arg].unwrap_reg(), bits, sink);
+    } else {
+        panic!("Expected BranchTable format: {:?}", func.dfg[inst]);
+    }
+}
+
+// Call and return recipes.
+
+fn recipe_op1call_id<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Call { func_ref, .. } = func.dfg[inst] {
+        put_op1(func.encodings[inst].bits(), sink);
+        sink.reloc_func(RelocKind::Call.into(), func_ref);
+        sink.put4(0);
+    } else {
+        panic!("Expected Call format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1ret<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    put_op1(func.encodings[inst].bits(), sink);
+}
+
+// Trap recipes.
+
+fn recipe_op2trap<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Trap { .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_op2(func.encodings[inst].bits(), sink);
+    } else {
+        panic!("Expected Trap format: {:?}", func.dfg[inst]);
+    }
+}
+
+// Overflow-checked arithmetic recipes.
+
+/// Materialize the overflow flag in `reg` with `seto` followed by `movzbl`.
+fn put_seto<CS: CodeSink + ?Sized>(reg: RegUnit, sink: &mut CS) {
+    sink.put1(0x0f);
+    sink.put1(0x90);
+    modrm_rr(reg, 0, sink);
+    sink.put1(0x0f);
+    sink.put1(0xb6);
+    modrm_rr(reg, reg, sink);
+}
+
+/// Emit a `jno` over a `ud2` instruction that traps with `code`.
+fn put_trap_ov<CS: CodeSink + ?Sized>(code: TrapCode, srcloc: SourceLoc, sink: &mut CS) {
+    sink.put1(0x71);
+    sink.put1(0x02);
+    sink.trap(code, srcloc);
+    sink.put1(0x0f);
+    sink.put1(0x0b);
+}
+
+fn recipe_op1rrov<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        put_op1(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[0]].unwrap_reg(),
+                 func.locations[args[1]].unwrap_reg(),
+                 sink);
+        put_seto(func.locations[func.dfg.inst_results(inst)[1]].unwrap_reg(), sink);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op2mulov<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        put_op2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+        put_seto(func.locations[func.dfg.inst_results(inst)[1]].unwrap_reg(), sink);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1rrovt<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BinaryTrap { args, code, .. } = func.dfg[inst] {
+        put_op1(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[0]].unwrap_reg(),
+                 func.locations[args[1]].unwrap_reg(),
+                 sink);
+        put_trap_ov(code, func.srclocs.get_or_default(inst), sink);
+    } else {
+        panic!("Expected BinaryTrap format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op2mulovt<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BinaryTrap { args, code, .. } = func.dfg[inst] {
+        put_op2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+        put_trap_ov(code, func.srclocs.get_or_default(inst), sink);
+    } else {
+        panic!("Expected BinaryTrap format: {:?}", func.dfg[inst]);
+    }
+}
+
+// Atomic memory recipes.
+
+fn recipe_op1ald<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_op1(func.encodings[inst].bits(), sink);
+        modrm_rm(func.locations[arg].unwrap_reg(),
+                 func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1ast<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_op1(func.encodings[inst].bits(), sink);
+        modrm_rm(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+        // mfence.
+        sink.put1(0x0f);
+        sink.put1(0xae);
+        sink.put1(0xf0);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op1lrmw<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::AtomicRmw { args, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        sink.put1(0xf0);
+        put_op1(func.encodings[inst].bits(), sink);
+        modrm_rm(func.locations[args[0]].unwrap_reg(),
+                 func.locations[args[1]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected AtomicRmw format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op2lrmw<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::AtomicRmw { args, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        sink.put1(0xf0);
+        put_op2(func.encodings[inst].bits(), sink);
+        modrm_rm(func.locations[args[0]].unwrap_reg(),
+                 func.locations[args[1]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected AtomicRmw format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op2lcas<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Ternary { args, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        sink.put1(0xf0);
+        put_op2(func.encodings[inst].bits(), sink);
+        modrm_rm(func.locations[args[0]].unwrap_reg(),
+                 func.locations[args[2]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Ternary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_op2fence<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Fence { .. } = func.dfg[inst] {
+        let bits = func.encodings[inst].bits();
+        put_op2(bits, sink);
+        modrm_r_bits(0, bits, sink);
+    } else {
+        panic!("Expected Fence format: {:?}", func.dfg[inst]);
+    }
+}
+
+// SIMD recipes.
+//
+// The SSE instructions have the destination operand in the ModR/M reg field, so the operand order
+// for `modrm_rr` is reversed compared to the integer recipes above.
+
+fn recipe_op2fa<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        put_op2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2fa<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp3fa<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        put_mp3(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2ficmp<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::IntCompare { args, .. } = func.dfg[inst] {
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected IntCompare format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp3ficmp<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::IntCompare { args, .. } = func.dfg[inst] {
+        put_mp3(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected IntCompare format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2fextrw<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::ExtractLane { arg, lane, .. } = func.dfg[inst] {
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[arg].unwrap_reg(),
+                 func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                 sink);
+        sink.put1(lane);
+    } else {
+        panic!("Expected ExtractLane format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp3fextr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::ExtractLane { arg, lane, .. } = func.dfg[inst] {
+        put_mp3(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                 func.locations[arg].unwrap_reg(),
+                 sink);
+        sink.put1(lane);
+    } else {
+        panic!("Expected ExtractLane format: {:?}", func.dfg[inst]);
+    }
+}
+
+/// Compute the `pshufd` immediate that broadcasts float lane `lane` of `ty` to all lanes.
+fn pshufd_lane(ty: Type, lane: u8) -> u8 {
+    if ty.lane_bits() == 64 {
+        // Move the two 32-bit halves of the lane.
+        let lo = 2 * lane;
+        lo | (lo + 1) << 2 | lo << 4 | (lo + 1) << 6
+    } else {
+        lane * 0b01010101
+    }
+}
+
+fn recipe_mp2fextrf<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::ExtractLane { arg, lane, .. } = func.dfg[inst] {
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[arg].unwrap_reg(),
+                 func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                 sink);
+        sink.put1(pshufd_lane(func.dfg.value_type(arg), lane));
+    } else {
+        panic!("Expected ExtractLane format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2finsr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::InsertLane { args, lane, .. } = func.dfg[inst] {
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+        sink.put1(lane);
+    } else {
+        panic!("Expected InsertLane format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp3finsr<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::InsertLane { args, lane, .. } = func.dfg[inst] {
+        put_mp3(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+        sink.put1(lane);
+    } else {
+        panic!("Expected InsertLane format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp3finsrf<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::InsertLane { args, lane, .. } = func.dfg[inst] {
+        put_mp3(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+        // The insertps immediate has the destination lane in bits 4-5.
+        sink.put1(lane << 4);
+    } else {
+        panic!("Expected InsertLane format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2finsrd<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::InsertLane { args, .. } = func.dfg[inst] {
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[args[1]].unwrap_reg(),
+                 func.locations[args[0]].unwrap_reg(),
+                 sink);
+    } else {
+        panic!("Expected InsertLane format: {:?}", func.dfg[inst]);
+    }
+}
+
+/// Emit a shuffle instruction `XX /r ib` operating on a single register.
+fn put_shuffle<CS: CodeSink + ?Sized>(opcode: &[u8], reg: RegUnit, imm: Option<u8>, sink: &mut CS) {
+    for &b in opcode {
+        sink.put1(b);
+    }
+    modrm_rr(reg, reg, sink);
+    if let Some(imm) = imm {
+        sink.put1(imm);
+    }
+}
+
+// Opcodes used by the splat recipes.
+const PSHUFD: [u8; 3] = [0x66, 0x0f, 0x70];
+const PSHUFLW: [u8; 3] = [0xf2, 0x0f, 0x70];
+const PUNPCKLBW: [u8; 3] = [0x66, 0x0f, 0x60];
+
+fn recipe_mp2splat32<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        let out = func.locations[func.dfg.first_result(inst)].unwrap_reg();
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[arg].unwrap_reg(), out, sink);
+        put_shuffle(&PSHUFD, out, Some(0), sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2splat16<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        let out = func.locations[func.dfg.first_result(inst)].unwrap_reg();
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[arg].unwrap_reg(), out, sink);
+        put_shuffle(&PSHUFLW, out, Some(0), sink);
+        put_shuffle(&PSHUFD, out, Some(0), sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2splat8<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        let out = func.locations[func.dfg.first_result(inst)].unwrap_reg();
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[arg].unwrap_reg(), out, sink);
+        put_shuffle(&PUNPCKLBW, out, None, sink);
+        put_shuffle(&PSHUFLW, out, Some(0), sink);
+        put_shuffle(&PSHUFD, out, Some(0), sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_mp2fsplat<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        let out = func.locations[func.dfg.first_result(inst)].unwrap_reg();
+        put_mp2(func.encodings[inst].bits(), sink);
+        modrm_rr(func.locations[arg].unwrap_reg(), out, sink);
+        sink.put1(pshufd_lane(func.dfg.value_type(func.dfg.first_result(inst)), 0));
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
@@ -0,0 +1,653 @@
+//! Disassembling Intel machine code.
+//!
+//! This decodes the subset of the 32-bit and 64-bit instruction sets that the Intel encoding
+//! recipes emit, and prints it in AT&T syntax. Since Cretonne only emits the REX.W prefix and no
+//! SIB bytes yet, the decoder doesn't understand the other REX bits or SIB bytes.
+
+use binemit::{CodeOffset, DecodedInst, Disassembler};
+use isa::RegUnit;
+
+/// Register unit number of `%xmm0`.
+const FPR_BASE: RegUnit = 16;
+
+/// Decoder for Intel machine code in 32-bit or 64-bit mode.
+pub struct Disasm {
+    /// Decode 64-bit mode code, where addresses are 64 bits.
+    pub is_64bit: bool,
+}
+
+impl Disassembler for Disasm {
+    fn decode(&self, code: &[u8], offset: CodeOffset) -> Option<DecodedInst> {
+        let mut inst = Inst {
+            code,
+            pos: 0,
+            offset,
+            is_64bit: self.is_64bit,
+            rex_w: false,
+            operands: Vec::new(),
+            regs: Vec::new(),
+        };
+        let mnemonic = inst.decode()?;
+        Some(DecodedInst {
+                 size: inst.pos as CodeOffset,
+                 mnemonic,
+                 operands: inst.operands.join(", "),
+                 regs: inst.regs,
+             })
+    }
+}
+
+/// The kind of register named by a register operand.
+#[derive(Clone, Copy)]
+enum Kind {
+    /// A general purpose register of the given size in bytes.
+    Gpr(u8),
+    /// An SSE register.
+    Xmm,
+}
+
+/// The r/m operand of a ModR/M byte.
+enum Rm {
+    /// A register operand, mode 11.
+    Reg(RegUnit),
+    /// A register-indirect memory operand with an optional displacement.
+    Mem(RegUnit, Option<i32>),
+    /// A RIP-relative memory operand, only in 64-bit mode.
+    RipRel(i32),
+}
+
+/// A decoded ModR/M byte.
+struct ModRm {
+    reg: RegUnit,
+    rm: Rm,
+}
+
+/// Names of the condition codes in `jcc` and `setcc` mnemonics, indexed by the low opcode bits.
+const CONDS: [&str; 16] = ["o", "no", "b", "ae", "e", "ne", "be", "a", "s", "ns", "p", "np", "l",
+                           "ge", "le", "g"];
+
+/// An instruction being decoded.
+struct Inst<'a> {
+    code: &'a [u8],
+    pos: usize,
+    offset: CodeOffset,
+    is_64bit: bool,
+    /// The instruction has a REX.W prefix, selecting 64-bit operands.
+    rex_w: bool,
+    operands: Vec<String>,
+    regs: Vec<RegUnit>,
+}
+
+impl<'a> Inst<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.code.get(self.pos).cloned()
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn imm32(&mut self) -> Option<i32> {
+        let mut x = 0;
+        for n in 0..4 {
+            x |= (self.byte()? as u32) << (8 * n);
+        }
+        Some(x as i32)
+    }
+
+    /// Decode a ModR/M byte and its displacement.
+    fn modrm(&mut self) -> Option<ModRm> {
+        let b = self.byte()?;
+        let reg = (b >> 3 & 7) as RegUnit;
+        let base = (b & 7) as RegUnit;
+        let rm = match b >> 6 {
+            // %rsp selects a SIB byte, and %rbp selects an absolute or RIP-relative address.
+            0b00 if base != 4 && base != 5 => Rm::Mem(base, None),
+            0b00 if base == 5 && self.is_64bit => Rm::RipRel(self.imm32()?),
+            0b01 if base != 4 => Rm::Mem(base, Some(self.byte()? as i8 as i32)),
+            0b10 if base != 4 => Rm::Mem(base, Some(self.imm32()?)),
+            0b11 => Rm::Reg(base),
+            _ => return None,
+        };
+        Some(ModRm { reg, rm })
+    }
+
+    /// Add a register operand.
+    fn reg(&mut self, reg: RegUnit, kind: Kind) -> Option<()> {
+        let name = match kind {
+            Kind::Gpr(1) => {
+                // Without a REX prefix, the encodings 4-7 are the high byte registers which
+                // aren't register units.
+                if reg >= 4 {
+                    return None;
+                }
+                format!("%{}l", ["a", "c", "d", "b"][reg as usize])
+            }
+            Kind::Gpr(size) => gpr_name(reg, size),
+            Kind::Xmm => format!("%xmm{}", reg),
+        };
+        self.operands.push(name);
+        self.regs
+            .push(match kind {
+                      Kind::Xmm => FPR_BASE + reg,
+                      Kind::Gpr(_) => reg,
+                  });
+        Some(())
+    }
+
+    /// Add the r/m operand, which is a register of `kind` or a memory operand.
+    fn rm(&mut self, rm: &Rm, kind: Kind) -> Option<()> {
+        match *rm {
+            Rm::Reg(reg) => self.reg(reg, kind),
+            Rm::Mem(base, disp) => {
+                let base_name = gpr_name(base, if self.is_64bit { 8 } else { 4 });
+                self.operands
+                    .push(match disp {
+                              Some(d) => format!("{}({})", d, base_name),
+                              None => format!("({})", base_name),
+                          });
+                self.regs.push(base);
+                Some(())
+            }
+            Rm::RipRel(disp) => {
+                self.operands.push(format!("{}(%rip)", disp));
+                Some(())
+            }
+        }
+    }
+
+    /// Add a register-only r/m operand.
+    fn rm_reg(&mut self, rm: &Rm, kind: Kind) -> Option<()> {
+        match *rm {
+            Rm::Reg(reg) => self.reg(reg, kind),
+            Rm::Mem(..) | Rm::RipRel(_) => None,
+        }
+    }
+
+    /// Add an immediate operand.
+    fn imm(&mut self, imm: i64) {
+        self.operands.push(format!("${}", imm));
+    }
+
+    /// Add an unsigned 8-bit immediate operand from the code.
+    fn ib(&mut self) -> Option<()> {
+        let b = self.byte()?;
+        self.imm(b as i64);
+        Some(())
+    }
+
+    /// Add a branch target operand with a displacement relative to the end of the instruction.
+    fn target(&mut self, disp: i32) {
+        let end = self.offset as i64 + self.pos as i64;
+        self.operands.push(format!("{:#x}", (end + disp as i64) as u32));
+    }
+
+    /// Add operands for an `XX /r` instruction that reads the ModR/M reg operand and modifies
+    /// the r/m operand.
+    fn mr(&mut self, kind: Kind) -> Option<()> {
+        let m = self.modrm()?;
+        self.reg(m.reg, kind)?;
+        self.rm(&m.rm, kind)
+    }
+
+    /// Add operands for an `XX /r` instruction that reads the r/m operand and modifies the
+    /// ModR/M reg operand.
+    fn rm_to_reg(&mut self, src: Kind, dst: Kind) -> Option<()> {
+        let m = self.modrm()?;
+        self.rm(&m.rm, src)?;
+        self.reg(m.reg, dst)
+    }
+
+    /// Decode the instruction, adding its operands. Returns the mnemonic.
+    fn decode(&mut self) -> Option<String> {
+        let lock = self.peek() == Some(0xf0);
+        if lock {
+            self.pos += 1;
+        }
+        let prefix = match self.peek()? {
+            p @ 0x66 | p @ 0xf2 | p @ 0xf3 => {
+                self.pos += 1;
+                Some(p)
+            }
+            _ => None,
+        };
+        if self.is_64bit && prefix.is_none() && self.peek() == Some(0x48) {
+            self.pos += 1;
+            self.rex_w = true;
+        }
+        let op = self.byte()?;
+        let mnemonic = if op == 0x0f {
+            self.decode_0f(prefix)?
+        } else {
+            self.decode_op1(prefix, op)?
+        };
+        if !lock {
+            return Some(mnemonic);
+        }
+        // Only the read-modify-write instructions with a memory operand can be locked.
+        match (mnemonic.as_str(), self.regs.len()) {
+            ("xaddl", 2) | ("xchgl", 2) | ("cmpxchgl", 2) if self.operands[1].ends_with(')') => {
+                Some(format!("lock {}", mnemonic))
+            }
+            _ => None,
+        }
+    }
+
+    /// Decode an instruction with a one-byte opcode.
+    fn decode_op1(&mut self, prefix: Option<u8>, op: u8) -> Option<String> {
+        // The 66 operand size prefix is only used for 16-bit stores.
+        let (size, suffix) = match prefix {
+            None if self.rex_w => (8, "q"),
+            None => (4, "l"),
+            Some(0x66) if op == 0x89 => (2, "w"),
+            _ => return None,
+        };
+        // These instructions don't take a REX.W prefix.
+        match op {
+            0x71 | 0x88 | 0xc3 | 0xe8 | 0xff if self.rex_w => return None,
+            _ if op & 0xf8 == 0xb8 && self.rex_w => return None,
+            _ => {}
+        }
+        let name = match op {
+            0x01 | 0x09 | 0x21 | 0x29 | 0x31 | 0x39 | 0x87 | 0x89 => {
+                self.mr(Kind::Gpr(size))?;
+                match op {
+                    0x01 => "add",
+                    0x09 => "or",
+                    0x21 => "and",
+                    0x29 => "sub",
+                    0x31 => "xor",
+                    0x39 => "cmp",
+                    0x87 => "xchg",
+                    _ => "mov",
+                }
+            }
+            0x88 => {
+                self.mr(Kind::Gpr(1))?;
+                return Some("movb".to_string());
+            }
+            0x8b => {
+                self.rm_to_reg(Kind::Gpr(size), Kind::Gpr(size))?;
+                "mov"
+            }
+            0x63 if self.rex_w => {
+                self.rm_to_reg(Kind::Gpr(4), Kind::Gpr(8))?;
+                return Some("movslq".to_string());
+            }
+            0x8d => {
+                let m = self.modrm()?;
+                if let Rm::Reg(_) = m.rm {
+                    return None;
+                }
+                self.rm(&m.rm, Kind::Gpr(size))?;
+                self.reg(m.reg, Kind::Gpr(size))?;
+                "lea"
+            }
+            0xc7 => {
+                let m = self.modrm()?;
+                if m.reg != 0 {
+                    return None;
+                }
+                let imm = self.imm32()?;
+                self.imm(imm as i64);
+                self.rm(&m.rm, Kind::Gpr(size))?;
+                "mov"
+            }
+            0x71 => {
+                let disp = self.byte()? as i8 as i32;
+                self.target(disp);
+                return Some("jno".to_string());
+            }
+            0x81 | 0x83 => {
+                let m = self.modrm()?;
+                let imm = if op == 0x83 {
+                    self.byte()? as i8 as i32
+                } else {
+                    self.imm32()?
+                };
+                self.imm(imm as i64);
+                self.rm(&m.rm, Kind::Gpr(size))?;
+                match m.reg {
+                    0 => "add",
+                    1 => "or",
+                    4 => "and",
+                    5 => "sub",
+                    6 => "xor",
+                    7 => "cmp",
+                    _ => return None,
+                }
+            }
+            _ if op & 0xf8 == 0xb8 => {
+                let imm = self.imm32()?;
+                self.imm(imm as i64);
+                self.reg((op & 7) as RegUnit, Kind::Gpr(4))?;
+                "mov"
+            }
+            0xc1 | 0xd3 => {
+                let m = self.modrm()?;
+                if op == 0xc1 {
+                    self.ib()?;
+                } else {
+                    self.reg(1, Kind::Gpr(1))?;
+                }
+                self.rm(&m.rm, Kind::Gpr(size))?;
+                match m.reg {
+                    4 => "shl",
+                    5 => "shr",
+                    7 => "sar",
+                    _ => return None,
+                }
+            }
+            0xc3 => return Some("ret".to_string()),
+            0xe8 => {
+                let disp = self.imm32()?;
+                self.target(disp);
+                return Some("call".to_string());
+            }
+            0xff => {
+                let m = self.modrm()?;
+                if m.reg != 4 {
+                    return None;
+                }
+                let size = if self.is_64bit { 8 } else { 4 };
+                self.rm(&m.rm, Kind::Gpr(size))?;
+                self.operands[0].insert(0, '*');
+                return Some(if self.is_64bit { "jmpq" } else { "jmpl" }.to_string());
+            }
+            _ => return None,
+        };
+        Some(format!("{}{}", name, suffix))
+    }
+
+    /// Decode an instruction with a `0f` escape byte.
+    fn decode_0f(&mut self, prefix: Option<u8>) -> Option<String> {
+        if self.rex_w {
+            return None;
+        }
+        let op = self.byte()?;
+        let name = match (prefix, op) {
+            (Some(0x66), 0x38) => return self.decode_0f38(),
+            (Some(0x66), 0x3a) => return self.decode_0f3a(),
+            (None, 0x0b) => "ud2",
+            (None, _) if op & 0xf0 == 0x80 => {
+                let disp = self.imm32()?;
+                self.target(disp);
+                return Some(format!("j{}", CONDS[(op & 0xf) as usize]));
+            }
+            (None, _) if op & 0xf0 == 0x90 => {
+                let m = self.modrm()?;
+                if m.reg != 0 {
+                    return None;
+                }
+                self.rm(&m.rm, Kind::Gpr(1))?;
+                return Some(format!("set{}", CONDS[(op & 0xf) as usize]));
+            }
+            (None, 0xae) => {
+                // mfence is the only fence emitted.
+                if self.byte()? != 0xf0 {
+                    return None;
+                }
+                "mfence"
+            }
+            (None, 0xaf) => {
+                self.rm_to_reg(Kind::Gpr(4), Kind::Gpr(4))?;
+                "imull"
+            }
+            (None, 0xb1) | (None, 0xc1) => {
+                self.mr(Kind::Gpr(4))?;
+                if op == 0xb1 { "cmpxchgl" } else { "xaddl" }
+            }
+            (None, 0xb6) | (None, 0xbe) => {
+                self.rm_to_reg(Kind::Gpr(1), Kind::Gpr(4))?;
+                if op == 0xb6 { "movzbl" } else { "movsbl" }
+            }
+            (None, 0xb7) | (None, 0xbf) => {
+                self.rm_to_reg(Kind::Gpr(2), Kind::Gpr(4))?;
+                if op == 0xb7 { "movzwl" } else { "movswl" }
+            }
+            (Some(0xf3), 0x10) => {
+                self.rm_to_reg(Kind::Xmm, Kind::Xmm)?;
+                "movss"
+            }
+            (Some(0xf2), 0x10) => {
+                self.rm_to_reg(Kind::Xmm, Kind::Xmm)?;
+                "movsd"
+            }
+            (Some(0x66), 0x6e) => {
+                self.rm_to_reg(Kind::Gpr(4), Kind::Xmm)?;
+                "movd"
+            }
+            (Some(0x66), 0x70) | (Some(0xf2), 0x70) => {
+                let m = self.modrm()?;
+                self.ib()?;
+                self.rm(&m.rm, Kind::Xmm)?;
+                self.reg(m.reg, Kind::Xmm)?;
+                if prefix == Some(0x66) {
+                    "pshufd"
+                } else {
+                    "pshuflw"
+                }
+            }
+            (Some(0x66), 0xc4) => {
+                let m = self.modrm()?;
+                self.ib()?;
+                self.rm_reg(&m.rm, Kind::Gpr(4))?;
+                self.reg(m.reg, Kind::Xmm)?;
+                "pinsrw"
+            }
+            (Some(0x66), 0xc5) => {
+                let m = self.modrm()?;
+                self.ib()?;
+                self.rm_reg(&m.rm, Kind::Xmm)?;
+                self.reg(m.reg, Kind::Gpr(4))?;
+                "pextrw"
+            }
+            (None, _) => {
+                let name = match op {
+                    0x58 => "addps",
+                    0x59 => "mulps",
+                    0x5c => "subps",
+                    0x5e => "divps",
+                    _ => return None,
+                };
+                self.rm_to_reg(Kind::Xmm, Kind::Xmm)?;
+                name
+            }
+            (Some(0x66), _) => {
+                let name = match op {
+                    0x14 => "unpcklpd",
+                    0x58 => "addpd",
+                    0x59 => "mulpd",
+                    0x5c => "subpd",
+                    0x5e => "divpd",
+                    0x60 => "punpcklbw",
+                    0x64 => "pcmpgtb",
+                    0x65 => "pcmpgtw",
+                    0x66 => "pcmpgtd",
+                    0x74 => "pcmpeqb",
+                    0x75 => "pcmpeqw",
+                    0x76 => "pcmpeqd",
+                    0xd4 => "paddq",
+                    0xd5 => "pmullw",
+                    0xdb => "pand",
+                    0xeb => "por",
+                    0xef => "pxor",
+                    0xf8 => "psubb",
+                    0xf9 => "psubw",
+                    0xfa => "psubd",
+                    0xfb => "psubq",
+                    0xfc => "paddb",
+                    0xfd => "paddw",
+                    0xfe => "paddd",
+                    _ => return None,
+                };
+                self.rm_to_reg(Kind::Xmm, Kind::Xmm)?;
+                name
+            }
+            _ => return None,
+        };
+        Some(name.to_string())
+    }
+
+    /// Decode an instruction with a `66 0f 38` opcode prefix.
+    fn decode_0f38(&mut self) -> Option<String> {
+        let name = match self.byte()? {
+            0x29 => "pcmpeqq",
+            0x40 => "pmulld",
+            _ => return None,
+        };
+        self.rm_to_reg(Kind::Xmm, Kind::Xmm)?;
+        Some(name.to_string())
+    }
+
+    /// Decode an instruction with a `66 0f 3a` opcode prefix. These all have an 8-bit
+    /// immediate.
+    fn decode_0f3a(&mut self) -> Option<String> {
+        let op = self.byte()?;
+        let m = self.modrm()?;
+        self.ib()?;
+        let name = match op {
+            0x14 | 0x16 => {
+                self.reg(m.reg, Kind::Xmm)?;
+                self.rm_reg(&m.rm, Kind::Gpr(4))?;
+                if op == 0x14 { "pextrb" } else { "pextrd" }
+            }
+            0x20 | 0x22 => {
+                self.rm_reg(&m.rm, Kind::Gpr(4))?;
+                self.reg(m.reg, Kind::Xmm)?;
+                if op == 0x20 { "pinsrb" } else { "pinsrd" }
+            }
+            0x21 => {
+                self.rm_reg(&m.rm, Kind::Xmm)?;
+                self.reg(m.reg, Kind::Xmm)?;
+                "insertps"
+            }
+            _ => return None,
+        };
+        Some(name.to_string())
+    }
+}
+
+/// Get the AT&T name of a general purpose register of `size` bytes.
+fn gpr_name(reg: RegUnit, size: u8) -> String {
+    let base = ["ax", "cx", "dx", "bx", "sp", "bp", "si", "di"][reg as usize & 7];
+    match size {
+        2 => format!("%{}", base),
+        8 => format!("%r{}", base),
+        _ => format!("%e{}", base),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dis(code: &[u8], is_64bit: bool) -> String {
+        match (Disasm { is_64bit }).decode(code, 0x10) {
+            Some(inst) => {
+                assert_eq!(inst.size as usize, code.len(), "{}", inst);
+                inst.to_string()
+            }
+            None => "-".to_string(),
+        }
+    }
+
+    fn dis32(code: &[u8]) -> String {
+        dis(code, false)
+    }
+
+    #[test]
+    fn integer() {
+        // Expected output from llvm-mc, see filetests/isa/intel/binary32.cton.
+        assert_eq!(dis32(&[0x01, 0xf1]), "addl %esi, %ecx");
+        assert_eq!(dis32(&[0x29, 0xce]), "subl %ecx, %esi");
+        assert_eq!(dis32(&[0x83, 0xc1, 0x80]), "addl $-128, %ecx");
+        assert_eq!(dis32(&[0x81, 0xe1, 0x00, 0x0c, 0xfe, 0xff]), "andl $-128000, %ecx");
+        assert_eq!(dis32(&[0xbe, 0x01, 0x00, 0x00, 0x00]), "movl $1, %esi");
+        assert_eq!(dis32(&[0xd3, 0xf9]), "sarl %cl, %ecx");
+        assert_eq!(dis32(&[0xc1, 0xee, 0x04]), "shrl $4, %esi");
+        assert_eq!(dis32(&[0x89, 0xcb]), "movl %ecx, %ebx");
+        assert_eq!(dis32(&[0x0f, 0xaf, 0xce]), "imull %esi, %ecx");
+        assert_eq!(dis32(&[0x0f, 0x90, 0xc3]), "seto %bl");
+        assert_eq!(dis32(&[0x0f, 0xb6, 0xdb]), "movzbl %bl, %ebx");
+    }
+
+    #[test]
+    fn memory() {
+        assert_eq!(dis32(&[0x89, 0x0e]), "movl %ecx, (%esi)");
+        assert_eq!(dis32(&[0x89, 0x71, 0x9c]), "movl %esi, -100(%ecx)");
+        assert_eq!(dis32(&[0x89, 0xb1, 0xf0, 0xd8, 0xff, 0xff]), "movl %esi, -10000(%ecx)");
+        assert_eq!(dis32(&[0x66, 0x89, 0x4e, 0x64]), "movw %cx, 100(%esi)");
+        assert_eq!(dis32(&[0x88, 0x0e]), "movb %cl, (%esi)");
+        assert_eq!(dis32(&[0x8b, 0x56, 0xce]), "movl -50(%esi), %edx");
+        assert_eq!(dis32(&[0x0f, 0xbf, 0x16]), "movswl (%esi), %edx");
+        assert_eq!(dis32(&[0xf0, 0x0f, 0xc1, 0x31]), "lock xaddl %esi, (%ecx)");
+        assert_eq!(dis32(&[0xf0, 0x87, 0x31]), "lock xchgl %esi, (%ecx)");
+        assert_eq!(dis32(&[0xf0, 0x0f, 0xb1, 0x31]), "lock cmpxchgl %esi, (%ecx)");
+        assert_eq!(dis32(&[0x0f, 0xae, 0xf0]), "mfence");
+        assert_eq!(dis(&[0x8b, 0x51, 0x32], true), "movl 50(%rcx), %edx");
+        assert_eq!(dis(&[0x48, 0x63, 0x56, 0x04], true), "movslq 4(%rsi), %rdx");
+        assert_eq!(dis(&[0x48, 0x8d, 0x05, 0x10, 0x00, 0x00, 0x00], true),
+                   "leaq 16(%rip), %rax");
+    }
+
+    #[test]
+    fn rex_w() {
+        assert_eq!(dis(&[0x48, 0x01, 0xf1], true), "addq %rsi, %rcx");
+        assert_eq!(dis(&[0x48, 0x83, 0xc1, 0xf6], true), "addq $-10, %rcx");
+        assert_eq!(dis(&[0x48, 0xc1, 0xe6, 0x02], true), "shlq $2, %rsi");
+        assert_eq!(dis(&[0x48, 0xc7, 0xc2, 0xff, 0xff, 0xff, 0xff], true), "movq $-1, %rdx");
+        assert_eq!(dis(&[0x48, 0x63, 0xca], true), "movslq %edx, %rcx");
+        // REX prefixes are only decoded in 64-bit mode, and not for all instructions.
+        assert_eq!(dis32(&[0x48, 0x01, 0xf1]), "-");
+        assert_eq!(dis(&[0x48, 0xc3], true), "-");
+        assert_eq!(dis(&[0x48, 0x0f, 0x0b], true), "-");
+    }
+
+    #[test]
+    fn control_flow() {
+        assert_eq!(dis32(&[0xc3]), "ret");
+        assert_eq!(dis32(&[0x0f, 0x0b]), "ud2");
+        assert_eq!(dis32(&[0x71, 0x02]), "jno 0x14");
+        assert_eq!(dis32(&[0x0f, 0x83, 0x07, 0x00, 0x00, 0x00]), "jae 0x1d");
+        assert_eq!(dis32(&[0xe8, 0xf0, 0xff, 0xff, 0xff]), "call 0x5");
+        assert_eq!(dis32(&[0xff, 0xe2]), "jmpl *%edx");
+    }
+
+    #[test]
+    fn simd() {
+        // Expected output from llvm-mc, see filetests/isa/intel/binary32-simd.cton.
+        assert_eq!(dis32(&[0x0f, 0x58, 0xd5]), "addps %xmm5, %xmm2");
+        assert_eq!(dis32(&[0x66, 0x0f, 0x5c, 0xe3]), "subpd %xmm3, %xmm4");
+        assert_eq!(dis32(&[0x66, 0x0f, 0xfe, 0xd5]), "paddd %xmm5, %xmm2");
+        assert_eq!(dis32(&[0x66, 0x0f, 0x38, 0x29, 0xff]), "pcmpeqq %xmm7, %xmm7");
+        assert_eq!(dis32(&[0x66, 0x0f, 0x3a, 0x14, 0xe3, 0x0f]), "pextrb $15, %xmm4, %ebx");
+        assert_eq!(dis32(&[0x66, 0x0f, 0xc5, 0xd3, 0x07]), "pextrw $7, %xmm3, %edx");
+        assert_eq!(dis32(&[0x66, 0x0f, 0x3a, 0x22, 0xd6, 0x02]), "pinsrd $2, %esi, %xmm2");
+        assert_eq!(dis32(&[0x66, 0x0f, 0xc4, 0xd9, 0x05]), "pinsrw $5, %ecx, %xmm3");
+        assert_eq!(dis32(&[0x66, 0x0f, 0x3a, 0x21, 0xd1, 0x20]), "insertps $32, %xmm1, %xmm2");
+        assert_eq!(dis32(&[0x66, 0x0f, 0x70, 0xfc, 0xee]), "pshufd $238, %xmm4, %xmm7");
+        assert_eq!(dis32(&[0xf2, 0x0f, 0x70, 0xdb, 0x00]), "pshuflw $0, %xmm3, %xmm3");
+        assert_eq!(dis32(&[0x66, 0x0f, 0x6e, 0xd1]), "movd %ecx, %xmm2");
+        assert_eq!(dis32(&[0xf2, 0x0f, 0x10, 0xde]), "movsd %xmm6, %xmm3");
+        assert_eq!(dis32(&[0xf3, 0x0f, 0x10, 0x29]), "movss (%ecx), %xmm5");
+    }
+
+    #[test]
+    fn invalid() {
+        // Truncated instructions.
+        assert_eq!(dis32(&[]), "-");
+        assert_eq!(dis32(&[0x81, 0xc1, 0x00]), "-");
+        assert_eq!(dis32(&[0x66, 0x0f, 0x3a, 0x14, 0xe3]), "-");
+        // SIB bytes and high byte registers.
+        assert_eq!(dis32(&[0x8b, 0x04, 0x24]), "-");
+        assert_eq!(dis32(&[0x0f, 0x90, 0xc4]), "-");
+        // Lock prefix on a register operand.
+        assert_eq!(dis32(&[0xf0, 0x01, 0xf1]), "-");
+        // Unknown opcodes.
+        assert_eq!(dis32(&[0x90]), "-");
+        assert_eq!(dis32(&[0x66, 0x01, 0xf1]), "-");
+    }
+}
@@ -1,7 +1,8 @@
 //! Encoding tables for Intel ISAs.
 
+use ir::condcodes::IntCC;
 use ir::types;
-use ir::{Opcode, InstructionData};
+use ir::{Opcode, InstructionData, AtomicRmwOp};
 use isa::EncInfo;
 use isa::constraints::*;
 use isa::enc_tables::{Level1Entry, Level2Entry};
@@ -3,10 +3,11 @@
 pub mod settings;
 mod abi;
 mod binemit;
+mod disasm;
 mod enc_tables;
 mod registers;
 
-use binemit::CodeSink;
+use binemit::{CodeSink, CodeOffset, MemoryCodeSink, Reloc, Disassembler, emit_function};
 use super::super::settings as shared_settings;
 use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, general_encoding};
 use isa::Builder as IsaBuilder;
@@ -79,6 +80,10 @@ impl TargetIsa for Isa {
                 })
     }
 
+    fn supports_call_conv(&self, call_conv: ir::CallConv) -> bool {
+        abi::supports_call_conv(call_conv, &self.shared_flags)
+    }
+
     fn legalize_signature(&self, sig: &mut ir::Signature, current: bool) {
         abi::legalize_signature(sig, &self.shared_flags, current)
     }
@@ -88,14 +93,90 @@ impl TargetIsa for Isa {
     }
 
     fn allocatable_registers(&self, func: &ir::Function) -> regalloc::AllocatableSet {
-        abi::allocatable_registers(func)
+        abi::allocatable_registers(func, &self.shared_flags)
     }
 
     fn emit_inst(&self, func: &ir::Function, inst: ir::Inst, sink: &mut CodeSink) {
         binemit::emit_inst(func, inst, sink)
     }
 
+    fn emit_function(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
+        emit_function(func, binemit::emit_inst, sink)
+    }
+
+    fn apply_reloc(&self, reloc: Reloc, code: &mut [u8], offset: CodeOffset, target: CodeOffset) {
+        binemit::apply_reloc(reloc, code, offset, target)
+    }
+
+    fn disassembler(&self) -> Option<Box<Disassembler>> {
+        Some(Box::new(disasm::Disasm { is_64bit: self.shared_flags.is_64bit() }))
+    }
+
     fn reloc_names(&self) -> &'static [&'static str] {
         &binemit::RELOC_NAMES
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use settings::{self, Configurable};
+    use binemit;
+    use isa;
+    use ir::CallConv;
+    use result::CtonError;
+    use Context;
+
+    #[test]
+    fn test_32bit_call_conv() {
+        let shared_flags = settings::Flags::new(&settings::builder());
+        let isa = isa::lookup("intel").unwrap().finish(shared_flags);
+        assert!(isa.supports_call_conv(CallConv::Native));
+        assert!(isa.supports_call_conv(CallConv::Fast));
+        assert!(!isa.supports_call_conv(CallConv::SystemV));
+        assert!(!isa.supports_call_conv(CallConv::WindowsFastcall));
+        assert!(!isa.supports_call_conv(CallConv::Baldrdash));
+
+        // The legalizer rejects the function instead of using the wrong registers.
+        let mut ctx = Context::new();
+        ctx.func.signature.call_conv = CallConv::WindowsFastcall;
+        ctx.flowgraph();
+        match ctx.legalize(&*isa) {
+            Err(CtonError::Unsupported(msg)) => {
+                assert_eq!(msg, "the windows_fastcall calling convention on intel")
+            }
+            _ => panic!("windows_fastcall should be rejected in 32-bit mode"),
+        }
+    }
+
+    #[test]
+    fn test_64bit_call_conv() {
+        let mut shared_builder = settings::builder();
+        shared_builder.set_bool("is_64bit", true).unwrap();
+        let shared_flags = settings::Flags::new(&shared_builder);
+        let isa = isa::lookup("intel").unwrap().finish(shared_flags);
+        assert!(isa.supports_call_conv(CallConv::SystemV));
+        assert!(isa.supports_call_conv(CallConv::WindowsFastcall));
+        assert!(isa.supports_call_conv(CallConv::Baldrdash));
+    }
+
+    #[test]
+    fn test_apply_reloc() {
+        let shared_flags = settings::Flags::new(&settings::builder());
+        let isa = isa::lookup("intel").unwrap().finish(shared_flags);
+
+        // A `call` and a `jmp` with 4-byte displacements.
+        let mut code = [0xe8, 0, 0, 0, 0, 0xe9, 0, 0, 0, 0];
+        isa.apply_reloc(binemit::Reloc(0), &mut code, 1, 10);
+        isa.apply_reloc(binemit::Reloc(0), &mut code, 6, 0);
+        assert_eq!(code, [0xe8, 5, 0, 0, 0, 0xe9, 0xf6, 0xff, 0xff, 0xff]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't resolve Abs4 relocation locally")]
+    fn test_apply_reloc_abs() {
+        let shared_flags = settings::Flags::new(&settings::builder());
+        let isa = isa::lookup("intel").unwrap().finish(shared_flags);
+        let mut code = [0; 4];
+        isa.apply_reloc(binemit::Reloc(1), &mut code, 0, 8);
+    }
+}
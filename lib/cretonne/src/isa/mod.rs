@@ -44,6 +44,7 @@ pub use isa::constraints::{RecipeConstraints, OperandConstraint, ConstraintKind,
 pub use isa::encoding::{Encoding, EncInfo};
 pub use isa::registers::{RegInfo, RegUnit, RegClass, RegClassIndex, regs_overlap};
 
+use binemit;
 use binemit::CodeSink;
 use settings;
 use ir;
@@ -123,6 +124,9 @@ pub enum Legalize {
 
     /// Expanding in terms of other instructions using the same types.
     Expand,
+
+    /// Split a vector type into halves with fewer lanes.
+    SplitVector,
 }
 
 /// Methods that are specialized to a target ISA.
@@ -186,6 +190,12 @@ pub trait TargetIsa {
     /// allocation.
     fn legalize_signature(&self, sig: &mut ir::Signature, current: bool);
 
+    /// Does this ISA support the calling convention `call_conv`?
+    ///
+    /// Signatures passed to `legalize_signature()` must use a supported calling convention. The
+    /// legalizer returns an error for functions using an unsupported convention.
+    fn supports_call_conv(&self, call_conv: ir::CallConv) -> bool;
+
     /// Get the register class that should be used to represent an ABI argument or return value of
     /// type `ty`. This should be the top-level register class that contains the argument
     /// registers.
@@ -206,6 +216,29 @@ pub trait TargetIsa {
     /// fastest way of emitting code.
     fn emit_inst(&self, func: &ir::Function, inst: ir::Inst, sink: &mut CodeSink);
 
+    /// Emit a whole function into memory.
+    ///
+    /// This is more performant than calling `emit_inst()` for each instruction.
+    fn emit_function(&self, func: &ir::Function, sink: &mut binemit::MemoryCodeSink);
+
+    /// Resolve a relocation of kind `reloc` at `offset` in `code`, referring to `target`.
+    ///
+    /// Both offsets are relative to the beginning of `code`. This is used by
+    /// `binemit::emit_to_memory()` to resolve references between EBBs in the same function.
+    fn apply_reloc(&self,
+                   reloc: binemit::Reloc,
+                   _code: &mut [u8],
+                   _offset: binemit::CodeOffset,
+                   _target: binemit::CodeOffset) {
+        panic!("Can't resolve {} relocation locally",
+               self.reloc_names()[reloc.0 as usize]);
+    }
+
+    /// Get a disassembler for the machine code emitted for this ISA, if there is one.
+    fn disassembler(&self) -> Option<Box<binemit::Disassembler>> {
+        None
+    }
+
     /// Get a static array of names associated with relocations in this ISA.
     ///
     /// This array can be indexed by the contents of `binemit::Reloc` objects passed to a
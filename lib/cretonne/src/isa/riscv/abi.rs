@@ -6,7 +6,7 @@
 //! This doesn't support the soft-float ABI at the moment.
 
 use abi::{ArgAction, ValueConversion, ArgAssigner, legalize_args};
-use ir::{self, Type, ArgumentType, ArgumentLoc, ArgumentExtension, ArgumentPurpose};
+use ir::{self, Type, ArgumentType, ArgumentLoc, ArgumentExtension, ArgumentPurpose, CallConv};
 use isa::RegClass;
 use regalloc::AllocatableSet;
 use settings as shared_settings;
@@ -84,11 +84,27 @@ impl ArgAssigner for Args {
     }
 }
 
+/// Is `call_conv` supported on RISC-V?
+pub fn supports_call_conv(call_conv: CallConv) -> bool {
+    // All the supported conventions use the standard RISC-V register assignments.
+    match call_conv {
+        CallConv::Native | CallConv::Fast | CallConv::Cold | CallConv::SystemV |
+        CallConv::Baldrdash => true,
+        CallConv::WindowsFastcall => false,
+    }
+}
+
 /// Legalize `sig` for RISC-V.
+///
+/// The calling convention of `sig` must be supported.
 pub fn legalize_signature(sig: &mut ir::Signature,
                           flags: &shared_settings::Flags,
                           isa_flags: &settings::Flags,
                           current: bool) {
+    debug_assert!(supports_call_conv(sig.call_conv),
+                  "The {} calling convention is not supported on RISC-V",
+                  sig.call_conv);
+
     let bits = if flags.is_64bit() { 64 } else { 32 };
 
     let mut args = Args::new(bits, isa_flags.enable_e());
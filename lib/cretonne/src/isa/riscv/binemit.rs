@@ -1,6 +1,6 @@
 //! Emitting binary RISC-V machine code.
 
-use binemit::{CodeSink, Reloc, bad_encoding};
+use binemit::{CodeSink, CodeOffset, Reloc, bad_encoding, trap_site};
 use ir::{Function, Inst, InstructionData};
 use isa::RegUnit;
 use predicates::is_signed_int;
@@ -11,9 +11,13 @@ include!(concat!(env!("OUT_DIR"), "/binemit-riscv.rs"));
 pub enum RelocKind {
     /// A jal call to a function.
     Call,
+    /// An auipc + addi pair computing the PC-relative address of a constant in the function's
+    /// constant pool. The displacement is filled in when the constant pool is emitted after the
+    /// function body.
+    ConstPcrel,
 }
 
-pub static RELOC_NAMES: [&'static str; 1] = ["Call"];
+pub static RELOC_NAMES: [&'static str; 2] = ["Call", "ConstPcrel"];
 
 impl Into<Reloc> for RelocKind {
     fn into(self) -> Reloc {
@@ -21,6 +25,27 @@ impl Into<Reloc> for RelocKind {
     }
 }
 
+/// Patch the relocation `reloc` at `offset` in `code` to refer to `target`.
+///
+/// Only the `jal` displacement of a `Call` relocation can be resolved without knowing where the
+/// code will be loaded.
+pub fn apply_reloc(reloc: Reloc, code: &mut [u8], offset: CodeOffset, target: CodeOffset) {
+    assert_eq!(reloc.0,
+               RelocKind::Call as u16,
+               "Can't resolve {} relocation locally",
+               RELOC_NAMES[reloc.0 as usize]);
+    let ofs = offset as usize;
+    let mut i = 0;
+    for (n, &byte) in code[ofs..ofs + 4].iter().enumerate() {
+        i |= (byte as u32) << (8 * n);
+    }
+    let imm = target as i64 - offset as i64;
+    i = (i & 0xfff) | uj_imm(imm);
+    for (n, byte) in code[ofs..ofs + 4].iter_mut().enumerate() {
+        *byte = (i >> (8 * n)) as u8;
+    }
+}
+
 /// R-type instructions.
 ///
 ///   31     24  19  14     11 6
@@ -123,6 +148,57 @@ fn recipe_rshamt<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut
     }
 }
 
+fn recipe_ramo<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::AtomicRmw { args, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_r(func.encodings[inst].bits(),
+              func.locations[args[0]].unwrap_reg(),
+              func.locations[args[1]].unwrap_reg(),
+              func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+              sink);
+    } else {
+        panic!("Expected AtomicRmw format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_ramold<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_r(func.encodings[inst].bits(),
+              func.locations[arg].unwrap_reg(),
+              0,
+              func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+              sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_ramost<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        put_r(func.encodings[inst].bits(),
+              func.locations[args[1]].unwrap_reg(),
+              func.locations[args[0]].unwrap_reg(),
+              0,
+              sink);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_rsc<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Binary { args, .. } = func.dfg[inst] {
+        put_r(func.encodings[inst].bits(),
+              func.locations[args[1]].unwrap_reg(),
+              func.locations[args[0]].unwrap_reg(),
+              func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+              sink);
+    } else {
+        panic!("Expected Binary format: {:?}", func.dfg[inst]);
+    }
+}
+
 /// I-type instructions.
 ///
 ///   31  19  14     11 6
@@ -160,6 +236,69 @@ fn recipe_i<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     }
 }
 
+fn recipe_iz<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::UnaryImm { imm, .. } = func.dfg[inst] {
+        put_i(func.encodings[inst].bits(),
+              0,
+              imm.into(),
+              func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+              sink);
+    } else {
+        panic!("Expected UnaryImm format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_isext<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Unary { arg, .. } = func.dfg[inst] {
+        put_i(func.encodings[inst].bits(),
+              func.locations[arg].unwrap_reg(),
+              0,
+              func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+              sink);
+    } else {
+        panic!("Expected Unary format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_irmov<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::RegMove { src, dst, .. } = func.dfg[inst] {
+        put_i(func.encodings[inst].bits(), src, 0, dst, sink);
+    } else {
+        panic!("Expected RegMove format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_iload<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Load { arg, offset, .. } = func.dfg[inst] {
+        trap_site(func, inst, sink);
+        let offset: i32 = offset.into();
+        put_i(func.encodings[inst].bits(),
+              func.locations[arg].unwrap_reg(),
+              offset as i64,
+              func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+              sink);
+    } else {
+        panic!("Expected Load format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_ifload<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    // `put_i` only uses the low 5 bits of the float register unit.
+    recipe_iload(func, inst, sink)
+}
+
+fn recipe_ijt<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BranchTable { arg, .. } = func.dfg[inst] {
+        put_i(func.encodings[inst].bits(),
+              func.locations[arg].unwrap_reg(),
+              0, // no offset.
+              0, // rd = %x0: no address written.
+              sink);
+    } else {
+        panic!("Expected BranchTable format: {:?}", func.dfg[inst]);
+    }
+}
+
 fn recipe_iicmp<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     if let InstructionData::IntCompareImm { arg, imm, .. } = func.dfg[inst] {
         put_i(func.encodings[inst].bits(),
@@ -182,6 +321,18 @@ fn recipe_iret<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS
           sink);
 }
 
+/// Fence instructions are I-type with the predecessor and successor sets in the immediate.
+///
+/// Encoding bits: `opcode[6:2] | (funct3 << 5) | (succ << 8) | (pred << 12)`
+fn recipe_ifence<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::Fence { .. } = func.dfg[inst] {
+        let bits = func.encodings[inst].bits();
+        put_i(bits, 0, (bits >> 8) as i64, 0, sink);
+    } else {
+        panic!("Expected Fence format: {:?}", func.dfg[inst]);
+    }
+}
+
 /// U-type instructions.
 ///
 ///   31  11 6
@@ -214,6 +365,46 @@ fn recipe_u<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
     }
 }
 
+/// Emit an `auipc` + `addi` pair computing the address at `target` relative to the start of the
+/// function.
+fn put_pcrel_addr<CS: CodeSink + ?Sized>(bits: u16,
+                                          target: CodeOffset,
+                                          rd: RegUnit,
+                                          sink: &mut CS) {
+    let disp = target as i64 - sink.offset() as i64;
+    // The addi immediate is sign-extended, so round the auipc immediate to compensate.
+    let lo = (disp << 52) >> 52;
+    put_u(bits, disp - lo, rd, sink);
+    // addi rd, rd, lo
+    put_i(0b00100, rd, lo, rd, sink);
+}
+
+/// Compute the address of a jump table with an auipc and addi pair.
+///
+/// The jump tables are emitted after the function body, so the displacement is known.
+fn recipe_ujt<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::BranchTableBase { table, .. } = func.dfg[inst] {
+        put_pcrel_addr(func.encodings[inst].bits(),
+                       func.jt_offsets[table],
+                       func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                       sink);
+    } else {
+        panic!("Expected BranchTableBase format: {:?}", func.dfg[inst]);
+    }
+}
+
+fn recipe_uconst<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
+    if let InstructionData::UnaryConst { constant, .. } = func.dfg[inst] {
+        sink.reloc_constant(RelocKind::ConstPcrel.into(), constant);
+        put_pcrel_addr(func.encodings[inst].bits(),
+                       func.const_offsets[constant],
+                       func.locations[func.dfg.first_result(inst)].unwrap_reg(),
+                       sink);
+    } else {
+        panic!("Expected UnaryConst format: {:?}", func.dfg[inst]);
+    }
+}
+
 /// SB-type branch instructions.
 ///
 ///   31  24  19  14     11  6
@@ -297,21 +488,26 @@ fn put_uj<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS
     let opcode5 = bits & 0x1f;
     let rd = rd as u32 & 0x1f;
 
-    assert!(is_signed_int(imm, 21, 1), "UJ out of range {:#x}", imm);
-    let imm = imm as u32;
-
     // 0-6: opcode
     let mut i = 0x3;
     i |= opcode5 << 2;
     i |= rd << 7;
+    i |= uj_imm(imm);
+
+    sink.put4(i);
+}
+
+/// Get the bits 12-31 of a UJ-type instruction with the displacement `imm`.
+fn uj_imm(imm: i64) -> u32 {
+    assert!(is_signed_int(imm, 21, 1), "UJ out of range {:#x}", imm);
+    let imm = imm as u32;
 
     // The displacement is completely hashed up.
-    i |= imm & 0xff000;
+    let mut i = imm & 0xff000;
     i |= ((imm >> 11) & 0x1) << 20;
     i |= ((imm >> 1) & 0x3ff) << 21;
     i |= ((imm >> 20) & 0x1) << 31;
-
-    sink.put4(i);
+    i
 }
 
 fn recipe_uj<CS: CodeSink + ?Sized>(func: &Function, inst: Inst, sink: &mut CS) {
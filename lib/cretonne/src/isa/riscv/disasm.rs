@@ -0,0 +1,476 @@
+//! Disassembling RISC-V machine code.
+//!
+//! This decodes the RV32I and RV64I base instruction sets along with the M and A extensions and
+//! the floating point loads and stores. Compressed instructions are not supported since Cretonne
+//! doesn't emit them.
+
+use binemit::{CodeOffset, DecodedInst, Disassembler};
+use isa::RegUnit;
+
+/// Register unit number of the first floating point register.
+const FPR_BASE: RegUnit = 32;
+
+/// Decoder for RV32 or RV64 machine code.
+pub struct Disasm {
+    /// Decode the RV64 instructions too.
+    pub is_64bit: bool,
+}
+
+impl Disassembler for Disasm {
+    fn decode(&self, code: &[u8], offset: CodeOffset) -> Option<DecodedInst> {
+        if code.len() < 4 {
+            return None;
+        }
+        let mut word = 0;
+        for (n, &byte) in code[0..4].iter().enumerate() {
+            word |= (byte as u32) << (8 * n);
+        }
+        let mut inst = Inst {
+            word,
+            offset,
+            operands: Vec::new(),
+            regs: Vec::new(),
+        };
+        let mnemonic = inst.decode(self.is_64bit)?;
+        Some(DecodedInst {
+                 size: 4,
+                 mnemonic,
+                 operands: inst.operands.join(", "),
+                 regs: inst.regs,
+             })
+    }
+}
+
+/// An instruction word being decoded.
+struct Inst {
+    word: u32,
+    offset: CodeOffset,
+    operands: Vec<String>,
+    regs: Vec<RegUnit>,
+}
+
+impl Inst {
+    /// Extract `bits` bits starting at bit `lsb`.
+    fn field(&self, lsb: u32, bits: u32) -> u32 {
+        (self.word >> lsb) & ((1 << bits) - 1)
+    }
+
+    fn rd(&self) -> RegUnit {
+        self.field(7, 5) as RegUnit
+    }
+
+    fn rs1(&self) -> RegUnit {
+        self.field(15, 5) as RegUnit
+    }
+
+    fn rs2(&self) -> RegUnit {
+        self.field(20, 5) as RegUnit
+    }
+
+    fn funct3(&self) -> u32 {
+        self.field(12, 3)
+    }
+
+    fn funct7(&self) -> u32 {
+        self.field(25, 7)
+    }
+
+    /// The sign-extended I-type immediate.
+    fn imm_i(&self) -> i32 {
+        self.word as i32 >> 20
+    }
+
+    /// The sign-extended S-type immediate.
+    fn imm_s(&self) -> i32 {
+        (self.word as i32 >> 25) << 5 | self.field(7, 5) as i32
+    }
+
+    /// The sign-extended SB-type branch displacement.
+    fn imm_sb(&self) -> i32 {
+        (self.word as i32 >> 31) << 12 | (self.field(7, 1) << 11) as i32 |
+        (self.field(25, 6) << 5) as i32 | (self.field(8, 4) << 1) as i32
+    }
+
+    /// The sign-extended UJ-type jump displacement.
+    fn imm_uj(&self) -> i32 {
+        (self.word as i32 >> 31) << 20 | (self.field(12, 8) << 12) as i32 |
+        (self.field(20, 1) << 11) as i32 | (self.field(21, 10) << 1) as i32
+    }
+
+    /// Add an integer register operand.
+    fn gpr(&mut self, reg: RegUnit) {
+        self.operands.push(format!("x{}", reg));
+        self.regs.push(reg);
+    }
+
+    /// Add a floating point register operand.
+    fn fpr(&mut self, reg: RegUnit) {
+        self.operands.push(format!("f{}", reg));
+        self.regs.push(FPR_BASE + reg);
+    }
+
+    /// Add an immediate operand.
+    fn imm(&mut self, imm: i32) {
+        self.operands.push(imm.to_string());
+    }
+
+    /// Add a memory operand `imm(rs1)`.
+    fn mem(&mut self, imm: i32) {
+        let base = self.rs1();
+        self.operands.push(format!("{}(x{})", imm, base));
+        self.regs.push(base);
+    }
+
+    /// Add a branch target operand from a displacement relative to the instruction.
+    fn target(&mut self, disp: i32) {
+        let target = (self.offset as i64 + disp as i64) as u32;
+        self.operands.push(format!("{:#x}", target));
+    }
+
+    /// Decode the instruction, adding its operands. Returns the mnemonic.
+    fn decode(&mut self, is_64bit: bool) -> Option<String> {
+        // The two low bits are 11 for all non-compressed instructions.
+        if self.word & 3 != 3 {
+            return None;
+        }
+        let (rd, rs1, rs2) = (self.rd(), self.rs1(), self.rs2());
+        let funct3 = self.funct3();
+        let mnemonic = match self.field(2, 5) {
+            // LOAD
+            0b00000 => {
+                let name = match funct3 {
+                    0b000 => "lb",
+                    0b001 => "lh",
+                    0b010 => "lw",
+                    0b011 if is_64bit => "ld",
+                    0b100 => "lbu",
+                    0b101 => "lhu",
+                    0b110 if is_64bit => "lwu",
+                    _ => return None,
+                };
+                self.gpr(rd);
+                let imm = self.imm_i();
+                self.mem(imm);
+                name
+            }
+            // LOAD-FP
+            0b00001 => {
+                let name = match funct3 {
+                    0b010 => "flw",
+                    0b011 => "fld",
+                    _ => return None,
+                };
+                self.fpr(rd);
+                let imm = self.imm_i();
+                self.mem(imm);
+                name
+            }
+            // MISC-MEM
+            0b00011 => {
+                match funct3 {
+                    0b000 => {
+                        let (pred, succ) = (self.field(24, 4), self.field(20, 4));
+                        self.operands.push(fence_set(pred));
+                        self.operands.push(fence_set(succ));
+                        "fence"
+                    }
+                    0b001 => "fence.i",
+                    _ => return None,
+                }
+            }
+            // OP-IMM
+            0b00100 => {
+                let imm = self.imm_i();
+                let (name, imm) = match funct3 {
+                    0b000 => ("addi", imm),
+                    0b010 => ("slti", imm),
+                    0b011 => ("sltiu", imm),
+                    0b100 => ("xori", imm),
+                    0b110 => ("ori", imm),
+                    0b111 => ("andi", imm),
+                    _ => self.shift_imm(is_64bit, "slli", "srli", "srai")?,
+                };
+                self.gpr(rd);
+                self.gpr(rs1);
+                self.imm(imm);
+                name
+            }
+            // AUIPC
+            0b00101 => {
+                self.gpr(rd);
+                self.operands.push(format!("{:#x}", self.word >> 12));
+                "auipc"
+            }
+            // OP-IMM-32
+            0b00110 if is_64bit => {
+                let (name, imm) = match funct3 {
+                    0b000 => ("addiw", self.imm_i()),
+                    _ => self.shift_imm(false, "slliw", "srliw", "sraiw")?,
+                };
+                self.gpr(rd);
+                self.gpr(rs1);
+                self.imm(imm);
+                name
+            }
+            // STORE
+            0b01000 => {
+                let name = match funct3 {
+                    0b000 => "sb",
+                    0b001 => "sh",
+                    0b010 => "sw",
+                    0b011 if is_64bit => "sd",
+                    _ => return None,
+                };
+                self.gpr(rs2);
+                let imm = self.imm_s();
+                self.mem(imm);
+                name
+            }
+            // STORE-FP
+            0b01001 => {
+                let name = match funct3 {
+                    0b010 => "fsw",
+                    0b011 => "fsd",
+                    _ => return None,
+                };
+                self.fpr(rs2);
+                let imm = self.imm_s();
+                self.mem(imm);
+                name
+            }
+            // AMO
+            0b01011 => return self.decode_amo(is_64bit),
+            // OP
+            0b01100 => {
+                let name = match (self.funct7(), funct3) {
+                    (0b0000000, 0b000) => "add",
+                    (0b0100000, 0b000) => "sub",
+                    (0b0000000, 0b001) => "sll",
+                    (0b0000000, 0b010) => "slt",
+                    (0b0000000, 0b011) => "sltu",
+                    (0b0000000, 0b100) => "xor",
+                    (0b0000000, 0b101) => "srl",
+                    (0b0100000, 0b101) => "sra",
+                    (0b0000000, 0b110) => "or",
+                    (0b0000000, 0b111) => "and",
+                    (0b0000001, 0b000) => "mul",
+                    (0b0000001, 0b001) => "mulh",
+                    (0b0000001, 0b010) => "mulhsu",
+                    (0b0000001, 0b011) => "mulhu",
+                    (0b0000001, 0b100) => "div",
+                    (0b0000001, 0b101) => "divu",
+                    (0b0000001, 0b110) => "rem",
+                    (0b0000001, 0b111) => "remu",
+                    _ => return None,
+                };
+                self.gpr(rd);
+                self.gpr(rs1);
+                self.gpr(rs2);
+                name
+            }
+            // LUI
+            0b01101 => {
+                self.gpr(rd);
+                self.operands.push(format!("{:#x}", self.word >> 12));
+                "lui"
+            }
+            // OP-32
+            0b01110 if is_64bit => {
+                let name = match (self.funct7(), funct3) {
+                    (0b0000000, 0b000) => "addw",
+                    (0b0100000, 0b000) => "subw",
+                    (0b0000000, 0b001) => "sllw",
+                    (0b0000000, 0b101) => "srlw",
+                    (0b0100000, 0b101) => "sraw",
+                    (0b0000001, 0b000) => "mulw",
+                    (0b0000001, 0b100) => "divw",
+                    (0b0000001, 0b101) => "divuw",
+                    (0b0000001, 0b110) => "remw",
+                    (0b0000001, 0b111) => "remuw",
+                    _ => return None,
+                };
+                self.gpr(rd);
+                self.gpr(rs1);
+                self.gpr(rs2);
+                name
+            }
+            // BRANCH
+            0b11000 => {
+                let name = match funct3 {
+                    0b000 => "beq",
+                    0b001 => "bne",
+                    0b100 => "blt",
+                    0b101 => "bge",
+                    0b110 => "bltu",
+                    0b111 => "bgeu",
+                    _ => return None,
+                };
+                self.gpr(rs1);
+                self.gpr(rs2);
+                let disp = self.imm_sb();
+                self.target(disp);
+                name
+            }
+            // JALR
+            0b11001 if funct3 == 0 => {
+                self.gpr(rd);
+                self.gpr(rs1);
+                let imm = self.imm_i();
+                self.imm(imm);
+                "jalr"
+            }
+            // JAL
+            0b11011 => {
+                self.gpr(rd);
+                let disp = self.imm_uj();
+                self.target(disp);
+                "jal"
+            }
+            // SYSTEM
+            0b11100 => {
+                match self.word {
+                    0x00000073 => "ecall",
+                    0x00100073 => "ebreak",
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(mnemonic.to_string())
+    }
+
+    /// Decode the shift amount of an immediate shift.
+    ///
+    /// The shift amount has 6 bits in RV64 and 5 bits otherwise, and the remaining high bits
+    /// select the shift kind.
+    fn shift_imm(&self,
+                 wide: bool,
+                 sll: &'static str,
+                 srl: &'static str,
+                 sra: &'static str)
+                 -> Option<(&'static str, i32)> {
+        let shamt_bits = if wide { 6 } else { 5 };
+        let shamt = self.field(20, shamt_bits) as i32;
+        let high = self.word >> (20 + shamt_bits);
+        let arith = 0b0100000 >> (shamt_bits - 5);
+        match (self.funct3(), high) {
+            (0b001, 0) => Some((sll, shamt)),
+            (0b101, 0) => Some((srl, shamt)),
+            (0b101, h) if h == arith => Some((sra, shamt)),
+            _ => None,
+        }
+    }
+
+    /// Decode an instruction from the A extension.
+    fn decode_amo(&mut self, is_64bit: bool) -> Option<String> {
+        let width = match self.funct3() {
+            0b010 => "w",
+            0b011 if is_64bit => "d",
+            _ => return None,
+        };
+        let order = match self.field(25, 2) {
+            0b00 => "",
+            0b01 => ".rl",
+            0b10 => ".aq",
+            _ => ".aqrl",
+        };
+        let (rd, rs1, rs2) = (self.rd(), self.rs1(), self.rs2());
+        let name = match self.field(27, 5) {
+            0b00010 if rs2 == 0 => {
+                self.gpr(rd);
+                self.operands.push(format!("(x{})", rs1));
+                self.regs.push(rs1);
+                return Some(format!("lr.{}{}", width, order));
+            }
+            0b00011 => "sc",
+            0b00001 => "amoswap",
+            0b00000 => "amoadd",
+            0b00100 => "amoxor",
+            0b01100 => "amoand",
+            0b01000 => "amoor",
+            0b10000 => "amomin",
+            0b10100 => "amomax",
+            0b11000 => "amominu",
+            0b11100 => "amomaxu",
+            _ => return None,
+        };
+        self.gpr(rd);
+        self.gpr(rs2);
+        self.operands.push(format!("(x{})", rs1));
+        self.regs.push(rs1);
+        Some(format!("{}.{}{}", name, width, order))
+    }
+}
+
+/// Format the predecessor or successor set of a fence instruction.
+fn fence_set(bits: u32) -> String {
+    let set = "iorw"
+        .chars()
+        .enumerate()
+        .filter(|&(n, _)| bits & (8 >> n) != 0)
+        .map(|(_, c)| c)
+        .collect::<String>();
+    if set.is_empty() {
+        "0".to_string()
+    } else {
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dis(word: u32, is_64bit: bool) -> String {
+        let code = [word as u8, (word >> 8) as u8, (word >> 16) as u8, (word >> 24) as u8];
+        match (Disasm { is_64bit }).decode(&code, 0x100) {
+            Some(inst) => {
+                assert_eq!(inst.size, 4);
+                inst.to_string()
+            }
+            None => "-".to_string(),
+        }
+    }
+
+    #[test]
+    fn rv32() {
+        // Expected output from filetests/isa/riscv/binary32.cton.
+        assert_eq!(dis(0x015503b3, false), "add x7, x10, x21");
+        assert_eq!(dis(0x40aa8833, false), "sub x16, x21, x10");
+        assert_eq!(dis(0x415553b3, false), "sra x7, x10, x21");
+        assert_eq!(dis(0x00aab833, false), "sltu x16, x21, x10");
+        assert_eq!(dis(0x3e850393, false), "addi x7, x10, 1000");
+        assert_eq!(dis(0xc77af813, false), "andi x16, x21, -905");
+        assert_eq!(dis(0x01f51393, false), "slli x7, x10, 31");
+        assert_eq!(dis(0x41f55393, false), "srai x7, x10, 31");
+        assert_eq!(dis(0x00000073, false), "ecall");
+        assert_eq!(dis(0x0000006f, false), "jal x0, 0x100");
+        assert_eq!(dis(0x00008067, false), "jalr x0, x1, 0");
+        assert_eq!(dis(0xfe0508e3, false), "beq x10, x0, 0xf0");
+        assert_eq!(dis(0x00a52623, false), "sw x10, 12(x10)");
+        assert_eq!(dis(0xffc52383, false), "lw x7, -4(x10)");
+        assert_eq!(dis(0x0230000f, false), "fence r, rw");
+        assert_eq!(dis(0x075523af, false), "amoadd.w.aqrl x7, x21, (x10)");
+        assert_eq!(dis(0x160aa3af, false), "lr.w.aqrl x7, (x21)");
+        assert_eq!(dis(0x123453b7, false), "lui x7, 0x12345");
+    }
+
+    #[test]
+    fn rv64() {
+        assert_eq!(dis(0x00053383, false), "-");
+        assert_eq!(dis(0x00053383, true), "ld x7, 0(x10)");
+        assert_eq!(dis(0x03f51393, false), "-");
+        assert_eq!(dis(0x03f51393, true), "slli x7, x10, 63");
+        assert_eq!(dis(0x0005039b, true), "addiw x7, x10, 0");
+        assert_eq!(dis(0x015503bb, true), "addw x7, x10, x21");
+    }
+
+    #[test]
+    fn invalid() {
+        // Compressed instructions and unknown opcodes.
+        assert_eq!(dis(0x00000001, false), "-");
+        assert_eq!(dis(0xffffffff, false), "-");
+        assert_eq!(dis(0x00002067, false), "-");
+        assert!((Disasm { is_64bit: false }).decode(&[0x13, 0, 0], 0).is_none());
+    }
+}
@@ -3,11 +3,12 @@
 pub mod settings;
 mod abi;
 mod binemit;
+mod disasm;
 mod enc_tables;
 mod registers;
 
 use super::super::settings as shared_settings;
-use binemit::CodeSink;
+use binemit::{CodeSink, CodeOffset, MemoryCodeSink, Reloc, Disassembler, emit_function};
 use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, general_encoding};
 use isa::Builder as IsaBuilder;
 use isa::{TargetIsa, RegInfo, RegClass, EncInfo, Encoding, Legalize};
@@ -79,6 +80,10 @@ impl TargetIsa for Isa {
                 })
     }
 
+    fn supports_call_conv(&self, call_conv: ir::CallConv) -> bool {
+        abi::supports_call_conv(call_conv)
+    }
+
     fn legalize_signature(&self, sig: &mut ir::Signature, current: bool) {
         abi::legalize_signature(sig, &self.shared_flags, &self.isa_flags, current)
     }
@@ -95,6 +100,18 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, sink)
     }
 
+    fn emit_function(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
+        emit_function(func, binemit::emit_inst, sink)
+    }
+
+    fn apply_reloc(&self, reloc: Reloc, code: &mut [u8], offset: CodeOffset, target: CodeOffset) {
+        binemit::apply_reloc(reloc, code, offset, target)
+    }
+
+    fn disassembler(&self) -> Option<Box<Disassembler>> {
+        Some(Box::new(disasm::Disasm { is_64bit: self.shared_flags.is_64bit() }))
+    }
+
     fn reloc_names(&self) -> &'static [&'static str] {
         &binemit::RELOC_NAMES
     }
@@ -102,10 +119,13 @@ impl TargetIsa for Isa {
 
 #[cfg(test)]
 mod tests {
+    use binemit;
     use settings::{self, Configurable};
     use isa;
-    use ir::{DataFlowGraph, InstructionData, Opcode};
+    use ir::{DataFlowGraph, InstructionData, Opcode, CallConv};
     use ir::{types, immediates};
+    use result::CtonError;
+    use Context;
 
     fn encstr(isa: &isa::TargetIsa, enc: isa::Encoding) -> String {
         isa.encoding_info().display(enc).to_string()
@@ -238,4 +258,45 @@ mod tests {
         assert_eq!(encstr(&*isa, isa.encode(&dfg, &mul32, types::I32).unwrap()),
                    "R#10c");
     }
+
+    #[test]
+    fn test_apply_reloc() {
+        let shared_flags = settings::Flags::new(&settings::builder());
+        let isa = isa::lookup("riscv").unwrap().finish(shared_flags);
+
+        // Two `jal x1, 0` instructions.
+        let mut code = [0xef, 0, 0, 0, 0xef, 0, 0, 0];
+        isa.apply_reloc(binemit::Reloc(0), &mut code, 0, 8);
+        isa.apply_reloc(binemit::Reloc(0), &mut code, 4, 0);
+        // jal x1, 8 and jal x1, -4.
+        assert_eq!(code, [0xef, 0x00, 0x80, 0x00, 0xef, 0xf0, 0xdf, 0xff]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't resolve ConstPcrel relocation locally")]
+    fn test_apply_reloc_const() {
+        let shared_flags = settings::Flags::new(&settings::builder());
+        let isa = isa::lookup("riscv").unwrap().finish(shared_flags);
+        let mut code = [0; 4];
+        isa.apply_reloc(binemit::Reloc(1), &mut code, 0, 8);
+    }
+
+    #[test]
+    fn test_unsupported_call_conv() {
+        let shared_flags = settings::Flags::new(&settings::builder());
+        let isa = isa::lookup("riscv").unwrap().finish(shared_flags);
+        assert!(isa.supports_call_conv(CallConv::SystemV));
+        assert!(!isa.supports_call_conv(CallConv::WindowsFastcall));
+
+        // The legalizer rejects the function instead of panicking.
+        let mut ctx = Context::new();
+        ctx.func.signature.call_conv = CallConv::WindowsFastcall;
+        ctx.flowgraph();
+        match ctx.legalize(&*isa) {
+            Err(CtonError::Unsupported(msg)) => {
+                assert_eq!(msg, "the windows_fastcall calling convention on riscv")
+            }
+            _ => panic!("windows_fastcall should be rejected"),
+        }
+    }
 }
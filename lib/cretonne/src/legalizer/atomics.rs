@@ -0,0 +1,118 @@
+//! Legalization of atomic memory instructions.
+//!
+//! This module exports the `expand_atomic` function which transforms an `atomic_rmw` or
+//! `atomic_cas` instruction that the target ISA can't encode into a retry loop. Targets that can
+//! encode `load_reserved` and `store_conditional` get an LR/SC loop, other targets get a loop
+//! around `atomic_cas`.
+
+use ir::{DataFlowGraph, Cursor, InstBuilder, InstructionData, Opcode, Ebb, Inst, Value,
+         AtomicRmwOp};
+use ir::condcodes::IntCC;
+use isa::TargetIsa;
+
+/// Expand the atomic instruction under the cursor if `isa` can't encode it.
+///
+/// The expansion splits the current EBB, so the new EBBs that need to be legalized are pushed
+/// onto `new_ebbs`: First the continuation EBB, then the loop EBB.
+///
+/// Return `true` if the instruction was expanded.
+pub fn expand_atomic(dfg: &mut DataFlowGraph,
+                     pos: &mut Cursor,
+                     isa: &TargetIsa,
+                     new_ebbs: &mut Vec<Ebb>)
+                     -> bool {
+    let inst = pos.current_inst()
+        .expect("Cursor must point to an atomic instruction");
+    let ty = dfg.ctrl_typevar(inst);
+    if isa.encode(dfg, &dfg[inst], ty).is_ok() {
+        return false;
+    }
+
+    let (op, addr, expected, arg) = match dfg[inst] {
+        InstructionData::AtomicRmw { op, args, .. } => (Some(op), args[0], None, args[1]),
+        InstructionData::Ternary {
+            opcode: Opcode::AtomicCas,
+            args,
+        } => (None, args[0], Some(args[1]), args[2]),
+        _ => panic!("Wanted atomic instruction: {}", dfg.display_inst(inst)),
+    };
+
+    let llsc = InstructionData::Unary {
+        opcode: Opcode::LoadReserved,
+        arg: addr,
+    };
+    let have_llsc = isa.encode(dfg, &llsc, ty).is_ok();
+
+    // A compare-and-swap can only be expanded in terms of LR/SC.
+    if op.is_none() && !have_llsc {
+        return false;
+    }
+
+    // Split the EBB before `inst` so it becomes the first instruction in `done`, and insert the
+    // loop EBB in between.
+    let loop_ebb = dfg.make_ebb();
+    let done = dfg.make_ebb();
+    let old_ebb = pos.current_ebb().expect("Cursor must be in an EBB");
+    pos.layout.split_ebb(done, inst);
+    pos.layout.insert_ebb(loop_ebb, done);
+
+    pos.goto_bottom(old_ebb);
+    let old = if have_llsc {
+        dfg.ins(pos).jump(loop_ebb, &[]);
+        pos.goto_bottom(loop_ebb);
+        let old = dfg.ins(pos).load_reserved(ty, addr);
+        let new = match (op, expected) {
+            (Some(op), _) => rmw_value(dfg, pos, op, old, arg),
+            (None, Some(expected)) => {
+                dfg.ins(pos).br_icmp(IntCC::NotEqual, old, expected, done, &[]);
+                arg
+            }
+            (None, None) => unreachable!(),
+        };
+        let fail = dfg.ins(pos).store_conditional(new, addr);
+        dfg.ins(pos).brnz(fail, loop_ebb, &[]);
+        old
+    } else {
+        let first = dfg.ins(pos).atomic_load(ty, addr);
+        dfg.ins(pos).jump(loop_ebb, &[first]);
+        let cur = dfg.append_ebb_arg(loop_ebb, ty);
+        pos.goto_bottom(loop_ebb);
+        let new = rmw_value(dfg, pos, op.expect("atomic_rmw"), cur, arg);
+        let prev = dfg.ins(pos).atomic_cas(addr, cur, new);
+        dfg.ins(pos).br_icmp(IntCC::NotEqual, prev, cur, loop_ebb, &[prev]);
+        cur
+    };
+    dfg.ins(pos).jump(done, &[]);
+
+    replace_with_alias(dfg, pos, inst, old);
+
+    new_ebbs.push(done);
+    new_ebbs.push(loop_ebb);
+    true
+}
+
+/// Compute the value to be stored by a read-modify-write operation.
+fn rmw_value(dfg: &mut DataFlowGraph,
+             pos: &mut Cursor,
+             op: AtomicRmwOp,
+             old: Value,
+             arg: Value)
+             -> Value {
+    match op {
+        AtomicRmwOp::Add => dfg.ins(pos).iadd(old, arg),
+        AtomicRmwOp::Sub => dfg.ins(pos).isub(old, arg),
+        AtomicRmwOp::And => dfg.ins(pos).band(old, arg),
+        AtomicRmwOp::Or => dfg.ins(pos).bor(old, arg),
+        AtomicRmwOp::Xor => dfg.ins(pos).bxor(old, arg),
+        AtomicRmwOp::Xchg => arg,
+    }
+}
+
+/// Remove `inst` from the layout and turn its result into an alias of `value`.
+fn replace_with_alias(dfg: &mut DataFlowGraph, pos: &mut Cursor, inst: Inst, value: Value) {
+    let result = dfg.first_result(inst);
+    dfg.clear_results(inst);
+    pos.layout.remove_inst(inst);
+    dfg.change_to_alias(result, value);
+}
+
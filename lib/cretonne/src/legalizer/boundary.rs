@@ -23,6 +23,7 @@ use ir::{Function, Cursor, DataFlowGraph, Inst, InstBuilder, Ebb, Type, Value, S
          ArgumentType, ArgumentPurpose};
 use ir::instructions::CallInfo;
 use isa::TargetIsa;
+use result::{CtonError, CtonResult};
 use legalizer::split::{isplit, vsplit};
 
 /// Legalize all the function signatures in `func`.
@@ -30,7 +31,18 @@ use legalizer::split::{isplit, vsplit};
 /// This changes all signatures to be ABI-compliant with full `ArgumentLoc` annotations. It doesn't
 /// change the entry block arguments, calls, or return instructions, so this can leave the function
 /// in a state with type discrepancies.
-pub fn legalize_signatures(func: &mut Function, isa: &TargetIsa) {
+///
+/// Returns an error if a signature uses a calling convention that `isa` doesn't support.
+pub fn legalize_signatures(func: &mut Function, isa: &TargetIsa) -> CtonResult {
+    let sigs = func.dfg.signatures.keys().map(|sig| &func.dfg.signatures[sig]);
+    for sig in Some(&func.signature).into_iter().chain(sigs) {
+        if !isa.supports_call_conv(sig.call_conv) {
+            return Err(CtonError::Unsupported(format!("the {} calling convention on {}",
+                                                      sig.call_conv,
+                                                      isa.name())));
+        }
+    }
+
     isa.legalize_signature(&mut func.signature, true);
     for sig in func.dfg.signatures.keys() {
         isa.legalize_signature(&mut func.dfg.signatures[sig], false);
@@ -39,6 +51,7 @@ pub fn legalize_signatures(func: &mut Function, isa: &TargetIsa) {
     if let Some(entry) = func.layout.entry_block() {
         legalize_entry_arguments(func, entry);
     }
+    Ok(())
 }
 
 /// Legalize the entry block arguments after `func`'s signature has been legalized.
@@ -77,7 +90,8 @@ fn legalize_entry_arguments(func: &mut Function, entry: Ebb) {
             // Just use the original EBB argument value. This is the most common case.
             func.dfg.attach_ebb_arg(entry, arg);
             match abi_types[abi_arg].purpose {
-                ArgumentPurpose::Normal => {}
+                ArgumentPurpose::Normal |
+                ArgumentPurpose::VMContext => {}
                 ArgumentPurpose::StructReturn => {
                     assert!(!has_sret, "Multiple sret arguments found");
                     has_sret = true;
@@ -112,7 +126,8 @@ fn legalize_entry_arguments(func: &mut Function, entry: Ebb) {
     for &arg in &abi_types[abi_arg..] {
         match arg.purpose {
             // Any normal arguments should have been processed above.
-            ArgumentPurpose::Normal => {
+            ArgumentPurpose::Normal |
+            ArgumentPurpose::VMContext => {
                 panic!("Leftover arg: {}", arg);
             }
             // The callee-save arguments should not appear until after register allocation is
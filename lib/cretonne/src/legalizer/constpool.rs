@@ -0,0 +1,56 @@
+//! Legalization of constants.
+//!
+//! This module exports the `expand_constant` function which moves a constant that can't be
+//! encoded as an instruction immediate into the function's constant pool and replaces the
+//! instruction with a load from the pool.
+
+use ir::{DataFlowGraph, Cursor, InstBuilder, InstructionData, ConstantData, ConstantPool,
+         MemFlags};
+use ir::types::{I32, I64};
+use isa::{TargetIsa, Legalize};
+
+/// Expand the `iconst`, `f32const`, or `f64const` instruction under the cursor into a constant
+/// pool load if `isa` can't encode it.
+///
+/// Constants that need to be narrowed are left alone. Their narrow parts will be revisited.
+///
+/// Return `true` if the instruction was expanded.
+pub fn expand_constant(dfg: &mut DataFlowGraph,
+                       pos: &mut Cursor,
+                       constants: &mut ConstantPool,
+                       isa: &TargetIsa)
+                       -> bool {
+    let inst = pos.current_inst()
+        .expect("Cursor must point to a constant instruction");
+    let ty = dfg.ctrl_typevar(inst);
+    match isa.encode(dfg, &dfg[inst], ty) {
+        Err(Legalize::Expand) => {}
+        _ => return false,
+    }
+
+    // Vector constants would need a splat of the loaded lane.
+    if !ty.is_scalar() {
+        return false;
+    }
+
+    let data = match dfg[inst] {
+        InstructionData::UnaryImm { imm, .. } => {
+            if ty.bits() > 64 {
+                return false;
+            }
+            ConstantData::int(ty, imm)
+        }
+        InstructionData::UnaryIeee32 { imm, .. } => ConstantData::f32(imm),
+        InstructionData::UnaryIeee64 { imm, .. } => ConstantData::f64(imm),
+        _ => panic!("Wanted constant: {}", dfg.display_inst(inst)),
+    };
+    let constant = constants.insert(data);
+
+    let addr_ty = if isa.flags().is_64bit() { I64 } else { I32 };
+    let addr = dfg.ins(pos).const_addr(addr_ty, constant);
+    let mut mflags = MemFlags::new();
+    mflags.set_notrap();
+    mflags.set_aligned();
+    dfg.replace(inst).load(ty, mflags, addr, 0);
+    true
+}
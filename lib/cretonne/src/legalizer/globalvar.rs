@@ -0,0 +1,76 @@
+//! Legalization of global variables.
+//!
+//! This module exports the `expand_global_addr` function which transforms a `global_addr`
+//! instruction into code that depends on the kind of global variable referenced.
+
+use entity_map::EntityMap;
+use ir::{DataFlowGraph, Cursor, InstBuilder, InstructionData, Opcode, Signature, ArgumentPurpose,
+         Value, GlobalVar, GlobalVarData, MemFlags};
+use ir::immediates::Offset32;
+
+/// Expand the `global_addr` instruction under the cursor.
+pub fn expand_global_addr(dfg: &mut DataFlowGraph,
+                          pos: &mut Cursor,
+                          global_vars: &EntityMap<GlobalVar, GlobalVarData>,
+                          sig: &Signature) {
+    let inst = pos.current_inst()
+        .expect("Cursor must point to a global_addr instruction");
+    let gv = match dfg[inst] {
+        InstructionData::UnaryGlobalVar { opcode, global_var } => {
+            debug_assert_eq!(opcode, Opcode::GlobalAddr);
+            global_var
+        }
+        _ => panic!("Wanted global_addr: {}", dfg.display_inst(inst)),
+    };
+
+    match global_vars[gv] {
+        GlobalVarData::VmCtx { offset } => vmctx_addr(dfg, pos, sig, offset),
+        GlobalVarData::Deref { base, offset } => deref_addr(dfg, pos, base, offset),
+        GlobalVarData::Sym { .. } => {
+            let addr_ty = dfg.ctrl_typevar(inst);
+            dfg.replace(inst).globalsym_addr(addr_ty, gv);
+        }
+    }
+}
+
+/// Expand a `global_addr` instruction for a vmctx global.
+fn vmctx_addr(dfg: &mut DataFlowGraph, pos: &mut Cursor, sig: &Signature, offset: Offset32) {
+    let inst = pos.current_inst().unwrap();
+    let vmctx = vmctx_arg(dfg, pos, sig);
+    let offset: i32 = offset.into();
+    dfg.replace(inst).iadd_imm(vmctx, offset as i64);
+}
+
+/// Expand a `global_addr` instruction for a deref global.
+fn deref_addr(dfg: &mut DataFlowGraph, pos: &mut Cursor, base: GlobalVar, offset: Offset32) {
+    let inst = pos.current_inst().unwrap();
+    let addr_ty = dfg.ctrl_typevar(inst);
+
+    // We need to load a pointer from the `base` global variable, so insert a new `global_addr`
+    // instruction. This depends on the iterative legalization loop. Note that the IL verifier
+    // detects any cycles in the `deref` globals.
+    let base_addr = dfg.ins(pos).global_addr(addr_ty, base);
+
+    let mut mflags = MemFlags::new();
+    // Deref globals are required to be accessible and aligned.
+    mflags.set_notrap();
+    mflags.set_aligned();
+    let base_ptr = dfg.ins(pos).load(addr_ty, mflags, base_addr, 0);
+    let offset: i32 = offset.into();
+    dfg.replace(inst).iadd_imm(base_ptr, offset as i64);
+}
+
+/// Find the entry block argument holding the VM context pointer.
+///
+/// The function signature must have an argument with the `vmctx` purpose. The verifier rejects
+/// functions that use the VM context without one.
+pub fn vmctx_arg(dfg: &DataFlowGraph, pos: &Cursor, sig: &Signature) -> Value {
+    let idx = sig.argument_types
+        .iter()
+        .position(|arg| arg.purpose == ArgumentPurpose::VMContext)
+        .expect("Missing vmctx argument");
+    let entry = pos.layout
+        .entry_block()
+        .expect("Function has no entry block");
+    dfg.ebb_args(entry)[idx]
+}
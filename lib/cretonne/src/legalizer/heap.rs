@@ -0,0 +1,142 @@
+//! Legalization of heaps.
+//!
+//! This module exports the `expand_heap_addr` function which transforms a `heap_addr`
+//! instruction into explicit bounds checks and address arithmetic, depending on the style of the
+//! heap it references.
+
+use entity_map::EntityMap;
+use ir::{DataFlowGraph, Cursor, InstBuilder, InstructionData, Opcode, Signature, Value, Heap,
+         HeapData, HeapBase, HeapStyle, MemFlags, TrapCode};
+use ir::condcodes::IntCC;
+use ir::immediates::Offset32;
+use std::u64;
+use super::globalvar::vmctx_arg;
+
+/// Expand the `heap_addr` instruction under the cursor.
+///
+/// The heap base address and any dynamic bound are loaded from the VM context, which must be
+/// passed as a `vmctx` argument to the function.
+pub fn expand_heap_addr(dfg: &mut DataFlowGraph,
+                        pos: &mut Cursor,
+                        heaps: &EntityMap<Heap, HeapData>,
+                        sig: &Signature) {
+    let inst = pos.current_inst()
+        .expect("Cursor must point to a heap_addr instruction");
+    let (heap, offset, size) = match dfg[inst] {
+        InstructionData::HeapAddr {
+            opcode,
+            heap,
+            arg,
+            imm,
+        } => {
+            debug_assert_eq!(opcode, Opcode::HeapAddr);
+            (&heaps[heap], arg, imm)
+        }
+        _ => panic!("Wanted heap_addr: {}", dfg.display_inst(inst)),
+    };
+    let addr_ty = dfg.ctrl_typevar(inst);
+    let vmctx = vmctx_arg(dfg, pos, sig);
+
+    // Zero-extend the offset to the address type before doing any arithmetic.
+    assert!(heap.index_type.bits() <= addr_ty.bits(),
+            "Heap index type {} is wider than the address type {}",
+            heap.index_type,
+            addr_ty);
+    let offset = if heap.index_type == addr_ty {
+        offset
+    } else {
+        dfg.ins(pos).uextend(addr_ty, offset)
+    };
+
+    match heap.style {
+        HeapStyle::Dynamic { bound } => {
+            dynamic_bounds_check(dfg, pos, heap, vmctx, offset, size, bound)
+        }
+        HeapStyle::Static { bound } => {
+            static_bounds_check(dfg, pos, heap, offset, size, bound.into())
+        }
+    }
+
+    // The base address of a static heap never changes, so it can be hoisted out of loops.
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+    flags.set_aligned();
+    if let HeapStyle::Static { .. } = heap.style {
+        flags.set_readonly();
+    }
+    let base = match heap.base {
+        HeapBase::VmCtx(base_offset) => dfg.ins(pos).load(addr_ty, flags, vmctx, base_offset),
+    };
+    dfg.replace(inst).iadd(base, offset);
+}
+
+/// Trap if the access of `size` bytes at `offset` goes beyond the current bound of a dynamic heap.
+///
+/// The `offset` has already been extended to the address type.
+fn dynamic_bounds_check(dfg: &mut DataFlowGraph,
+                        pos: &mut Cursor,
+                        heap: &HeapData,
+                        vmctx: Value,
+                        offset: Value,
+                        size: u32,
+                        bound_offset: Offset32) {
+    let addr_ty = dfg.value_type(offset);
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+    flags.set_aligned();
+    let bound = dfg.ins(pos).load(addr_ty, flags, vmctx, bound_offset);
+
+    let guard_size: i64 = heap.guard_size.into();
+    if size as i64 <= guard_size {
+        // Any access starting below the bound ends in the guard pages at worst.
+        let oob = dfg.ins(pos)
+            .icmp(IntCC::UnsignedGreaterThanOrEqual, offset, bound);
+        dfg.ins(pos).trapnz(oob, TrapCode::HeapOutOfBounds);
+    } else {
+        let end = dfg.ins(pos).iadd_imm(offset, size as i64);
+        let oob = dfg.ins(pos).icmp(IntCC::UnsignedGreaterThan, end, bound);
+        dfg.ins(pos).trapnz(oob, TrapCode::HeapOutOfBounds);
+
+        // The addition can only overflow when the offset wasn't extended.
+        if heap.index_type == addr_ty {
+            let overflow = dfg.ins(pos).icmp(IntCC::UnsignedLessThan, end, offset);
+            dfg.ins(pos).trapnz(overflow, TrapCode::HeapOutOfBounds);
+        }
+    }
+}
+
+/// Trap if the access of `size` bytes at `offset` goes beyond the bound of a static heap.
+///
+/// No code is generated when the guard pages cover all the offsets that are representable in the
+/// heap index type. The `offset` has already been extended to the address type.
+fn static_bounds_check(dfg: &mut DataFlowGraph,
+                       pos: &mut Cursor,
+                       heap: &HeapData,
+                       offset: Value,
+                       size: u32,
+                       bound: i64) {
+    let addr_ty = dfg.value_type(offset);
+    let bound = bound as u64;
+    let size = size as u64;
+    let guard_size: i64 = heap.guard_size.into();
+
+    if size > bound {
+        // This access is always out of bounds.
+        let one = dfg.ins(pos).iconst(addr_ty, 1);
+        dfg.ins(pos).trapnz(one, TrapCode::HeapOutOfBounds);
+        return;
+    }
+
+    let limit = bound - size;
+    let index_max = match heap.index_type.bits() {
+        64 => u64::MAX,
+        bits => (1 << bits) - 1,
+    };
+    if limit.saturating_add(guard_size as u64) >= index_max {
+        return;
+    }
+
+    let oob = dfg.ins(pos)
+        .icmp_imm(IntCC::UnsignedGreaterThan, offset, limit as i64);
+    dfg.ins(pos).trapnz(oob, TrapCode::HeapOutOfBounds);
+}
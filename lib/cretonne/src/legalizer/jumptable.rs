@@ -0,0 +1,117 @@
+//! Legalization of jump tables.
+//!
+//! This module exports the `expand_br_table` function which transforms a `br_table` instruction
+//! into a bounds check followed by an indirect branch through the jump table. The jump table is
+//! emitted after the function body as a sequence of 32-bit offsets relative to the start of the
+//! table.
+
+use entity_map::EntityMap;
+use ir::{DataFlowGraph, Cursor, InstBuilder, InstructionData, Opcode, Ebb, JumpTable,
+         JumpTableData, MemFlags};
+use ir::condcodes::IntCC;
+use ir::types::{I32, I64};
+use isa::TargetIsa;
+use result::CtonError;
+
+/// Expand the `br_table` instruction under the cursor if `isa` can't encode it.
+///
+/// The instructions following the `br_table` are split off into a new EBB which is reached by an
+/// out-of-bounds index and by the missing entries in the jump table. The new EBB is pushed onto
+/// `new_ebbs` so it will be legalized.
+///
+/// Return `true` if the instruction was expanded, or an error if `isa` can't encode the indirect
+/// branch through the jump table either.
+pub fn expand_br_table(dfg: &mut DataFlowGraph,
+                       pos: &mut Cursor,
+                       jump_tables: &mut EntityMap<JumpTable, JumpTableData>,
+                       isa: &TargetIsa,
+                       new_ebbs: &mut Vec<Ebb>)
+                       -> Result<bool, CtonError> {
+    let inst = pos.current_inst()
+        .expect("Cursor must point to a br_table instruction");
+    if isa.encode(dfg, &dfg[inst], dfg.ctrl_typevar(inst)).is_ok() {
+        return Ok(false);
+    }
+
+    let (arg, table) = match dfg[inst] {
+        InstructionData::BranchTable { opcode, arg, table } => {
+            debug_assert_eq!(opcode, Opcode::BrTable);
+            (arg, table)
+        }
+        _ => panic!("Wanted br_table: {}", dfg.display_inst(inst)),
+    };
+
+    // The jump table address and the indirect branch can't be legalized any further, so make
+    // sure they can be encoded before changing anything.
+    let addr_ty = if isa.flags().is_64bit() { I64 } else { I32 };
+    let jt_base = InstructionData::BranchTableBase {
+        opcode: Opcode::JumpTableBase,
+        table,
+    };
+    let jt_br = InstructionData::BranchTable {
+        opcode: Opcode::IndirectJumpTableBr,
+        arg,
+        table,
+    };
+    if isa.encode(dfg, &jt_base, addr_ty).is_err() || isa.encode(dfg, &jt_br, addr_ty).is_err() {
+        return Err(CtonError::Unsupported(format!("jump tables on {}", isa.name())));
+    }
+
+    // Split the EBB after `inst` so the fall-through code gets an EBB we can branch to.
+    let next = pos.next_inst()
+        .expect("br_table can't be the last instruction in an EBB");
+    let fall = dfg.make_ebb();
+    pos.layout.split_ebb(fall, next);
+    pos.goto_inst(inst);
+
+    // Missing entries fall through. The jump table may be shared with other `br_table`
+    // instructions, so fill in the holes in a copy.
+    let len = jump_tables[table].len();
+    let table = if (0..len).all(|idx| jump_tables[table].get_entry(idx).is_some()) {
+        table
+    } else {
+        let mut data = jump_tables[table].clone();
+        for idx in 0..len {
+            if data.get_entry(idx).is_none() {
+                data.set_entry(idx, fall);
+            }
+        }
+        jump_tables.push(data)
+    };
+
+    // Sign-extend a narrow index before the bounds check so the comparison happens in a register
+    // sized type. Negative indexes become large unsigned numbers which fail the bounds check.
+    let mut idx = arg;
+    if dfg.value_type(idx).bits() < addr_ty.bits() {
+        idx = dfg.ins(pos).sextend(addr_ty, idx);
+    }
+
+    // Out-of-bounds indexes fall through too.
+    let idx_ty = dfg.value_type(idx);
+    let bound = dfg.ins(pos).iconst(idx_ty, len as i64);
+    dfg.ins(pos)
+        .br_icmp(IntCC::UnsignedGreaterThanOrEqual, idx, bound, fall, &[]);
+
+    // The bounds check guarantees that a wide index fits in the address type.
+    if idx_ty.bits() > addr_ty.bits() {
+        idx = dfg.ins(pos).ireduce(addr_ty, idx);
+    }
+
+    // Load the 32-bit entry and add it to the table address.
+    let base = dfg.ins(pos).jump_table_base(addr_ty, table);
+    let scaled = dfg.ins(pos).ishl_imm(idx, 2);
+    let entry_addr = dfg.ins(pos).iadd(base, scaled);
+    let mut mflags = MemFlags::new();
+    mflags.set_notrap();
+    mflags.set_aligned();
+    let entry = if addr_ty == I64 {
+        dfg.ins(pos).sload32(I64, mflags, entry_addr, 0)
+    } else {
+        dfg.ins(pos).load(I32, mflags, entry_addr, 0)
+    };
+    let dest = dfg.ins(pos).iadd(base, entry);
+    dfg.replace(inst).indirect_jump_table_br(dest, table);
+
+    new_ebbs.push(fall);
+    Ok(true)
+}
@@ -15,31 +15,45 @@
 
 use dominator_tree::DominatorTree;
 use flowgraph::ControlFlowGraph;
-use ir::{Function, Cursor, DataFlowGraph, InstructionData, Opcode, InstBuilder};
+use ir::{self, Function, Cursor, DataFlowGraph, InstructionData, Opcode, InstBuilder};
 use ir::condcodes::IntCC;
 use isa::{TargetIsa, Legalize};
+use result::CtonResult;
 
+mod atomics;
 mod boundary;
+mod constpool;
+mod globalvar;
+mod heap;
+mod jumptable;
 mod split;
+mod wideint;
 
 /// Legalize `func` for `isa`.
 ///
 /// - Transform any instructions that don't have a legal representation in `isa`.
 /// - Fill out `func.encodings`.
 ///
+/// If new EBBs are created, `cfg` and `domtree` are recomputed.
+///
+/// Returns an error if `func` uses a feature that `isa` doesn't support.
 pub fn legalize_function(func: &mut Function,
                          cfg: &mut ControlFlowGraph,
-                         domtree: &DominatorTree,
-                         isa: &TargetIsa) {
-    boundary::legalize_signatures(func, isa);
+                         domtree: &mut DominatorTree,
+                         isa: &TargetIsa)
+                         -> CtonResult {
+    boundary::legalize_signatures(func, isa)?;
 
     func.encodings.resize(func.dfg.num_insts());
 
+    // Process EBBs in a reverse post-order. This minimizes the number of split instructions we
+    // need. EBBs created by the legalizer are pushed onto the stack and processed next.
+    let mut ebbs = domtree.cfg_postorder().to_vec();
+    let num_ebbs = func.dfg.num_ebbs();
+
     let mut pos = Cursor::new(&mut func.layout);
 
-    // Process EBBs in a reverse post-order. This minimizes the number of split instructions we
-    // need.
-    for &ebb in domtree.cfg_postorder().iter().rev() {
+    while let Some(ebb) = ebbs.pop() {
         pos.goto_top(ebb);
 
         // Keep track of the cursor position before the instruction being processed, so we can
@@ -49,8 +63,12 @@ pub fn legalize_function(func: &mut Function,
         while let Some(inst) = pos.next_inst() {
             let opcode = func.dfg[inst].opcode();
 
+            // Instructions inserted while legalizing `inst` get its source location.
+            let first_new = func.dfg.num_insts();
+
             // Check for ABI boundaries that need to be converted to the legalized signature.
             if opcode.is_call() && boundary::handle_call_abi(&mut func.dfg, cfg, &mut pos) {
+                ir::sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
                 // Go back and legalize the inserted argument conversion instructions.
                 pos.set_position(prev_pos);
                 continue;
@@ -58,6 +76,7 @@ pub fn legalize_function(func: &mut Function,
 
             if opcode.is_return() &&
                boundary::handle_return_abi(&mut func.dfg, cfg, &mut pos, &func.signature) {
+                ir::sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
                 // Go back and legalize the inserted return value conversion instructions.
                 pos.set_position(prev_pos);
                 continue;
@@ -67,6 +86,54 @@ pub fn legalize_function(func: &mut Function,
                 split::simplify_branch_arguments(&mut func.dfg, inst);
             }
 
+            if opcode == Opcode::GlobalAddr {
+                globalvar::expand_global_addr(&mut func.dfg,
+                                              &mut pos,
+                                              &func.global_vars,
+                                              &func.signature);
+                ir::sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
+                // Go back and legalize the expanded address computation.
+                pos.set_position(prev_pos);
+                continue;
+            }
+
+            if opcode == Opcode::HeapAddr {
+                heap::expand_heap_addr(&mut func.dfg, &mut pos, &func.heaps, &func.signature);
+                ir::sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
+                // Go back and legalize the bounds checks and address computation.
+                pos.set_position(prev_pos);
+                continue;
+            }
+
+            if (opcode == Opcode::Iconst || opcode == Opcode::F32const ||
+                opcode == Opcode::F64const) &&
+               constpool::expand_constant(&mut func.dfg, &mut pos, &mut func.constants, isa) {
+                ir::sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
+                // Go back and legalize the constant pool address computation.
+                pos.set_position(prev_pos);
+                continue;
+            }
+
+            if opcode == Opcode::BrTable &&
+               jumptable::expand_br_table(&mut func.dfg,
+                                          &mut pos,
+                                          &mut func.jump_tables,
+                                          isa,
+                                          &mut ebbs)? {
+                ir::sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
+                // Go back and legalize the bounds check and the address computation.
+                pos.set_position(prev_pos);
+                continue;
+            }
+
+            if (opcode == Opcode::AtomicRmw || opcode == Opcode::AtomicCas) &&
+               atomics::expand_atomic(&mut func.dfg, &mut pos, isa, &mut ebbs) {
+                ir::sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
+                // Go back and legalize the jump to the new loop.
+                pos.set_position(prev_pos);
+                continue;
+            }
+
             match isa.encode(&func.dfg, &func.dfg[inst], func.dfg.ctrl_typevar(inst)) {
                 Ok(encoding) => *func.encodings.ensure(inst) = encoding,
                 Err(action) => {
@@ -75,8 +142,9 @@ pub fn legalize_function(func: &mut Function,
                     // 1. Legalize::Expand: Expand instruction into sequence of legal instructions.
                     //    Possibly iteratively. ()
                     // 2. Legalize::Narrow: Split the controlling type variable into high and low
-                    //    parts. This applies both to SIMD vector types which can be halved and to
-                    //    integer types such as `i64` used on a 32-bit ISA. ().
+                    //    parts. This applies to integer types such as `i64` used on a 32-bit ISA.
+                    //    Legalize::SplitVector does the same for SIMD vector types that are
+                    //    wider than the SIMD registers.
                     // 3. TODO: Promote the controlling type variable to a larger type. This
                     //    typically means expressing `i8` and `i16` arithmetic in terms if `i32`
                     //    operations on RISC targets. (It may or may not be beneficial to promote
@@ -85,7 +153,11 @@ pub fn legalize_function(func: &mut Function,
                     //    an ISA with no IEEE 754 support.
                     let changed = match action {
                         Legalize::Expand => expand(&mut func.dfg, cfg, &mut pos),
-                        Legalize::Narrow => narrow(&mut func.dfg, cfg, &mut pos),
+                        Legalize::Narrow => {
+                            narrow(&mut func.dfg, cfg, &mut pos) ||
+                            wideint::narrow(&mut func.dfg, cfg, &mut pos)
+                        }
+                        Legalize::SplitVector => split_vector(&mut func.dfg, cfg, &mut pos),
                     };
                     // If the current instruction was replaced, we need to double back and revisit
                     // the expanded sequence. This is both to assign encodings and possible to
@@ -93,6 +165,7 @@ pub fn legalize_function(func: &mut Function,
                     // There's a risk of infinite looping here if the legalization patterns are
                     // unsound. Should we attempt to detect that?
                     if changed {
+                        ir::sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
                         pos.set_position(prev_pos);
                         continue;
                     }
@@ -104,10 +177,16 @@ pub fn legalize_function(func: &mut Function,
         }
     }
     func.encodings.resize(func.dfg.num_insts());
+
+    if func.dfg.num_ebbs() != num_ebbs {
+        cfg.compute(func);
+        domtree.compute(func, cfg);
+    }
+    Ok(())
 }
 
 // Include legalization patterns that were generated by `gen_legalizer.py` from the `XForms` in
 // `meta/cretonne/legalize.py`.
 //
-// Concretely, this defines private functions `narrow()`, and `expand()`.
+// Concretely, this defines private functions `narrow()`, `expand()`, and `split_vector()`.
 include!(concat!(env!("OUT_DIR"), "/legalizer.rs"));
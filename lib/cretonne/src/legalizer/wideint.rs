@@ -0,0 +1,148 @@
+//! Narrowing of wide integer shifts and comparisons.
+//!
+//! Most integer operations on types that are too wide for the target are narrowed by the
+//! generated patterns in the `narrow` group. Shifts and comparisons depend on the half-width of
+//! the type and the condition code, so they can't be expressed as XForm patterns. They are
+//! narrowed here instead.
+
+use flowgraph::ControlFlowGraph;
+use ir::{DataFlowGraph, Cursor, InstBuilder, InstructionData, Opcode, Value};
+use ir::condcodes::IntCC;
+use super::split;
+
+/// Narrow the shift or comparison under the cursor by splitting its integer operands into halves.
+///
+/// Return `true` if the instruction was narrowed.
+pub fn narrow(dfg: &mut DataFlowGraph, cfg: &mut ControlFlowGraph, pos: &mut Cursor) -> bool {
+    let inst = pos.current_inst().expect("need instruction");
+    let changed = match dfg[inst] {
+        InstructionData::Binary { opcode, args } => {
+            match opcode {
+                Opcode::Ishl | Opcode::Ushr | Opcode::Sshr => {
+                    let x = dfg.resolve_aliases(args[0]);
+                    let y = dfg.resolve_aliases(args[1]);
+                    let (lo, hi) = narrow_shift(dfg, cfg, pos, opcode, x, y);
+                    dfg.replace(inst).iconcat(lo, hi);
+                    true
+                }
+                _ => false,
+            }
+        }
+        InstructionData::IntCompare { cond, args, .. } => {
+            let x = dfg.resolve_aliases(args[0]);
+            let y = dfg.resolve_aliases(args[1]);
+            let (xl, xh) = split::isplit(dfg, cfg, pos, x);
+            let (yl, yh) = split::isplit(dfg, cfg, pos, y);
+            match cond {
+                IntCC::Equal => {
+                    let lo = dfg.ins(pos).icmp(cond, xl, yl);
+                    let hi = dfg.ins(pos).icmp(cond, xh, yh);
+                    dfg.replace(inst).band(lo, hi);
+                }
+                IntCC::NotEqual => {
+                    let lo = dfg.ins(pos).icmp(cond, xl, yl);
+                    let hi = dfg.ins(pos).icmp(cond, xh, yh);
+                    dfg.replace(inst).bor(lo, hi);
+                }
+                _ => {
+                    // The high halves decide the comparison unless they are equal. Then the low
+                    // halves are compared as unsigned numbers.
+                    let hi = dfg.ins(pos).icmp(strict(cond), xh, yh);
+                    let hi_eq = dfg.ins(pos).icmp(IntCC::Equal, xh, yh);
+                    let lo = dfg.ins(pos).icmp(unsigned(cond), xl, yl);
+                    let lo_dec = dfg.ins(pos).band(hi_eq, lo);
+                    dfg.replace(inst).bor(hi, lo_dec);
+                }
+            }
+            true
+        }
+        _ => false,
+    };
+    if changed && pos.current_inst() == Some(inst) {
+        pos.next_inst();
+    }
+    changed
+}
+
+/// Compute the low and high halves of the shift `opcode(x, y)`.
+///
+/// With `H` bits in each half and the shift amount `s = y mod 2H`, a left shift computes:
+///
+/// - `s < H`: `lo = xl << s` and `hi = (xh << s) | (xl >> (H - s))`.
+/// - `s >= H`: `lo = 0` and `hi = xl << (s - H)`.
+///
+/// The right shifts are mirror images. The half-width shifts mask their shift amount, so
+/// `xl << s` computes `xl << (s - H)` when `s >= H`. The `xl >> (H - s)` term is computed as
+/// `(xl >> 1) >> ((H - 1) ^ s)` to avoid shifting by `H` when `s = 0`.
+fn narrow_shift(dfg: &mut DataFlowGraph,
+                cfg: &mut ControlFlowGraph,
+                pos: &mut Cursor,
+                opcode: Opcode,
+                x: Value,
+                y: Value)
+                -> (Value, Value) {
+    let (xl, xh) = split::isplit(dfg, cfg, pos, x);
+    let half_ty = dfg.value_type(xl);
+    let half_bits = half_ty.lane_bits() as i64;
+
+    // Only the low bits of the shift amount matter.
+    let mut s = y;
+    while dfg.value_type(s).bits() > half_ty.bits() {
+        s = split::isplit(dfg, cfg, pos, s).0;
+    }
+
+    let big = dfg.ins(pos).band_imm(s, half_bits);
+    let big = dfg.ins(pos).icmp_imm(IntCC::NotEqual, big, 0);
+    let rev = dfg.ins(pos).bxor_imm(s, half_bits - 1);
+
+    match opcode {
+        Opcode::Ishl => {
+            let lo = dfg.ins(pos).ishl(xl, s);
+            let hi = dfg.ins(pos).ishl(xh, s);
+            let carry = dfg.ins(pos).ushr_imm(xl, 1);
+            let carry = dfg.ins(pos).ushr(carry, rev);
+            let hi = dfg.ins(pos).bor(hi, carry);
+            let zero = dfg.ins(pos).iconst(half_ty, 0);
+            let al = dfg.ins(pos).select(big, zero, lo);
+            let ah = dfg.ins(pos).select(big, lo, hi);
+            (al, ah)
+        }
+        Opcode::Ushr | Opcode::Sshr => {
+            let lo = dfg.ins(pos).ushr(xl, s);
+            let carry = dfg.ins(pos).ishl_imm(xh, 1);
+            let carry = dfg.ins(pos).ishl(carry, rev);
+            let lo = dfg.ins(pos).bor(lo, carry);
+            let (hi, fill) = if opcode == Opcode::Ushr {
+                (dfg.ins(pos).ushr(xh, s), dfg.ins(pos).iconst(half_ty, 0))
+            } else {
+                (dfg.ins(pos).sshr(xh, s), dfg.ins(pos).sshr_imm(xh, half_bits - 1))
+            };
+            let al = dfg.ins(pos).select(big, hi, lo);
+            let ah = dfg.ins(pos).select(big, fill, hi);
+            (al, ah)
+        }
+        _ => panic!("Not a shift: {}", opcode),
+    }
+}
+
+/// Get the condition code that compares the high halves without the equal case.
+fn strict(cond: IntCC) -> IntCC {
+    match cond {
+        IntCC::SignedLessThanOrEqual => IntCC::SignedLessThan,
+        IntCC::SignedGreaterThanOrEqual => IntCC::SignedGreaterThan,
+        IntCC::UnsignedLessThanOrEqual => IntCC::UnsignedLessThan,
+        IntCC::UnsignedGreaterThanOrEqual => IntCC::UnsignedGreaterThan,
+        _ => cond,
+    }
+}
+
+/// Get the unsigned condition code used to compare the low halves.
+fn unsigned(cond: IntCC) -> IntCC {
+    match cond {
+        IntCC::SignedLessThan => IntCC::UnsignedLessThan,
+        IntCC::SignedGreaterThanOrEqual => IntCC::UnsignedGreaterThanOrEqual,
+        IntCC::SignedGreaterThan => IntCC::UnsignedGreaterThan,
+        IntCC::SignedLessThanOrEqual => IntCC::UnsignedLessThanOrEqual,
+        _ => cond,
+    }
+}
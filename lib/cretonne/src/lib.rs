@@ -4,8 +4,9 @@
 
 pub use context::Context;
 pub use legalizer::legalize_function;
+pub use module::Module;
 pub use verifier::verify_function;
-pub use write::write_function;
+pub use write::{write_function, write_data};
 
 /// Version number of the cretonne crate.
 pub const VERSION: &'static str = env!("CARGO_PKG_VERSION");
@@ -21,15 +22,19 @@ pub mod flowgraph;
 pub mod ir;
 pub mod isa;
 pub mod loop_analysis;
+pub mod module;
 pub mod regalloc;
 pub mod result;
 pub mod settings;
 pub mod sparse_map;
+pub mod stats;
 pub mod verifier;
 
 mod abi;
 mod constant_hash;
 mod context;
+mod if_conversion;
+mod induction;
 mod iterators;
 mod legalizer;
 mod licm;
@@ -38,5 +43,10 @@ mod partition_slice;
 mod predicates;
 mod ref_slice;
 mod simple_gvn;
+mod redundant_ebb_args;
+mod sccp;
+mod simplify_cfg;
+mod strength_reduction;
 mod topo_order;
+mod unroll;
 mod write;
@@ -1,8 +1,10 @@
 //! A Loop Invariant Code Motion optimization pass
 
-use ir::{Function, Ebb, Inst, Value, Cursor, Type, InstBuilder, Layout};
+use ir::{Function, Ebb, Inst, Value, Cursor, Type, InstBuilder, Layout, InstructionData, Opcode,
+         DataFlowGraph};
+use ir::instructions::BranchInfo;
 use flowgraph::ControlFlowGraph;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use dominator_tree::DominatorTree;
 use entity_list::{EntityList, ListPool};
 use loop_analysis::{Loop, LoopAnalysis};
@@ -15,24 +17,41 @@ pub fn do_licm(func: &mut Function,
                domtree: &mut DominatorTree,
                loop_analysis: &mut LoopAnalysis) {
     loop_analysis.compute(func, cfg, domtree);
-    for lp in loop_analysis.loops() {
+    // The loop exits are collected before any pre-headers are inserted since the loop analysis
+    // doesn't know about the new EBBs.
+    let loops: Vec<(Loop, Vec<Inst>)> = loop_analysis
+        .loops()
+        .map(|lp| (lp, loop_exits(lp, func, cfg, loop_analysis)))
+        .collect();
+    for (lp, exits) in loops {
+        let header = loop_analysis.loop_header(lp);
+        let natural_pre_header = has_pre_header(&func.layout, cfg, domtree, header);
+        // Instructions hoisted into a natural pre-header that can branch to the header without
+        // jumping there unconditionally are executed even when the loop is not entered.
+        let speculative = match natural_pre_header {
+            Some((pre_header, last_inst)) => {
+                func.dfg[last_inst].opcode() != Opcode::Jump ||
+                func.layout.last_inst(pre_header) != Some(last_inst)
+            }
+            None => false,
+        };
         // For each loop that we want to optimize we determine the set of loop-invariant
         // instructions
-        let invariant_inst = remove_loop_invariant_instructions(lp, func, cfg, loop_analysis);
+        let invariant_inst = remove_loop_invariant_instructions(lp,
+                                                                &exits,
+                                                                speculative,
+                                                                func,
+                                                                cfg,
+                                                                domtree,
+                                                                loop_analysis);
         // Then we create the loop's pre-header and fill it with the invariant instructions
         // Then we remove the invariant instructions from the loop body
         if invariant_inst.len() > 0 {
             // If the loop has a natural pre-header we use it, otherwise we create it.
             let mut pos;
-            match has_pre_header(&func.layout,
-                                 cfg,
-                                 domtree,
-                                 loop_analysis.loop_header(lp).clone()) {
+            match natural_pre_header {
                 None => {
-                    let pre_header = create_pre_header(loop_analysis.loop_header(lp).clone(),
-                                                       func,
-                                                       cfg,
-                                                       domtree);
+                    let pre_header = create_pre_header(header, func, cfg, domtree);
                     pos = Cursor::new(&mut func.layout);
                     pos.goto_bottom(pre_header);
                     pos.prev_inst();
@@ -131,30 +150,130 @@ fn change_branch_jump_destination(inst: Inst, new_ebb: Ebb, func: &mut Function)
     }
 }
 
+// Test whether the given opcode must never be moved out of a loop.
+fn trivially_unsafe_for_licm(opcode: Opcode) -> bool {
+    opcode.is_call() || opcode.is_branch() || opcode.is_terminator() || opcode.is_return() ||
+    opcode.can_store()
+}
+
+// Test whether all the arguments of `inst` are defined outside the loop, and it is safe to move
+// it as long as its arguments are available.
+fn is_loop_invariant(inst: Inst, loop_values: &HashSet<Value>, dfg: &DataFlowGraph) -> bool {
+    if !dfg.has_results(inst) || trivially_unsafe_for_licm(dfg[inst].opcode()) {
+        return false;
+    }
+    dfg.inst_args(inst)
+        .into_iter()
+        .all(|arg| !loop_values.contains(arg))
+}
+
+// Test whether `inst` can trap. Loads can trap unless they have the `notrap` flag.
+fn can_trap(dfg: &DataFlowGraph, inst: Inst) -> bool {
+    match dfg[inst] {
+        InstructionData::Load { flags, .. } => !flags.notrap(),
+        ref data => data.opcode().can_trap(),
+    }
+}
+
+// Test whether the memory read by the load `inst` can be written by an instruction in the loop.
+//
+// There is no alias analysis, so unless the load is marked `readonly`, any store or call in the
+// loop is assumed to clobber it.
+fn load_is_clobbered(dfg: &DataFlowGraph, inst: Inst, loop_writes_memory: bool) -> bool {
+    match dfg[inst] {
+        InstructionData::Load { flags, .. } if flags.readonly() => false,
+        _ => loop_writes_memory,
+    }
+}
+
+// Collects the instructions where control can leave the loop or go back to its header: branches
+// to EBBs outside the loop, back edges, and other terminators.
+//
+// An instruction in the loop that dominates all of these is executed at least once whenever the
+// loop is entered.
+fn loop_exits(lp: Loop,
+              func: &Function,
+              cfg: &ControlFlowGraph,
+              loop_analysis: &LoopAnalysis)
+              -> Vec<Inst> {
+    let header = loop_analysis.loop_header(lp);
+    let leaves_loop = |dest: Ebb| dest == header || !loop_analysis.is_in_loop(dest, lp);
+    let mut exits = Vec::new();
+    for ebb in postorder_ebbs_loop(loop_analysis, cfg, lp) {
+        for inst in func.layout.ebb_insts(ebb) {
+            let is_exit = match func.dfg[inst].analyze_branch(&func.dfg.value_lists) {
+                BranchInfo::SingleDest(dest, _) => leaves_loop(dest),
+                BranchInfo::Table(jt) => {
+                    func.jump_tables[jt]
+                        .entries()
+                        .any(|(_, dest)| leaves_loop(dest))
+                }
+                BranchInfo::NotABranch => func.dfg[inst].opcode().is_terminator(),
+            };
+            if is_exit {
+                exits.push(inst);
+            }
+        }
+    }
+    exits
+}
+
 // Traverses a loop in reverse post-order from a header EBB and identify loop-invariant
 // instructions. These loop-invariant instructions are then removed from the code and returned
 // (in reverse post-order) for later use.
+//
+// Loads are only considered invariant when nothing in the loop can write the memory they read,
+// and instructions that can trap are only hoisted when they dominate all the `exits` of the loop
+// so they are guaranteed to execute. They must also be the first side effect on every path from
+// the loop header: a trap can't be moved ahead of a store, a call, or another trapping instruction
+// that stays in the loop. When the hoisted instructions are `speculative`, they may execute
+// without entering the loop, and instructions that can trap are never hoisted.
 fn remove_loop_invariant_instructions(lp: Loop,
+                                      exits: &[Inst],
+                                      speculative: bool,
                                       func: &mut Function,
                                       cfg: &ControlFlowGraph,
+                                      domtree: &DominatorTree,
                                       loop_analysis: &LoopAnalysis)
                                       -> Vec<Inst> {
     let mut loop_values: HashSet<Value> = HashSet::new();
     let mut invariant_inst: Vec<Inst> = Vec::new();
+    let ebbs = postorder_ebbs_loop(loop_analysis, cfg, lp);
+    let loop_writes_memory = ebbs.iter().any(|&ebb| {
+        func.layout.ebb_insts(ebb).any(|inst| {
+            let opcode = func.dfg[inst].opcode();
+            opcode.can_store() || opcode.is_call()
+        })
+    });
+    let header = loop_analysis.loop_header(lp);
+    // Whether a side effect may have happened when leaving each EBB visited so far.
+    let mut side_effects: HashMap<Ebb, bool> = HashMap::new();
     let mut pos = Cursor::new(&mut func.layout);
     // We traverse the loop EBB in reverse post-order.
-    for ebb in postorder_ebbs_loop(loop_analysis, cfg, lp).iter().rev() {
+    for ebb in ebbs.iter().rev() {
         // Arguments of the EBB are loop values
         for val in func.dfg.ebb_args(*ebb) {
             loop_values.insert(val.clone());
         }
+        // The paths start at the header. Predecessors that haven't been visited yet are the
+        // latches of inner loops, and they are assumed to have side effects.
+        let mut side_effect = *ebb != header &&
+                              cfg.get_predecessors(*ebb)
+                                  .iter()
+                                  .filter(|&&(pred, _)| loop_analysis.is_in_loop(pred, lp))
+                                  .any(|&(pred, _)| *side_effects.get(&pred).unwrap_or(&true));
         pos.goto_top(*ebb);
         while let Some(inst) = pos.next_inst() {
-            if func.dfg.has_results(inst) &&
-               func.dfg
-                   .inst_args(inst)
-                   .into_iter()
-                   .all(|arg| !loop_values.contains(arg)) {
+            // Instructions that can trap must be executed whenever the loop is entered.
+            let hoistable = is_loop_invariant(inst, &loop_values, &func.dfg) &&
+                            !(func.dfg[inst].opcode().can_load() &&
+                              load_is_clobbered(&func.dfg, inst, loop_writes_memory)) &&
+                            (!can_trap(&func.dfg, inst) ||
+                             (!speculative && !side_effect &&
+                              exits
+                                  .iter()
+                                  .all(|&exit| domtree.dominates(inst, exit, &pos.layout))));
+            if hoistable {
                 // If all the instruction's argument are defined outside the loop
                 // then this instruction is loop-invariant
                 invariant_inst.push(inst);
@@ -166,8 +285,13 @@ fn remove_loop_invariant_instructions(lp: Loop,
                 for out in func.dfg.inst_results(inst) {
                     loop_values.insert(out.clone());
                 }
+                let opcode = func.dfg[inst].opcode();
+                if opcode.can_store() || opcode.is_call() || can_trap(&func.dfg, inst) {
+                    side_effect = true;
+                }
             }
         }
+        side_effects.insert(*ebb, side_effect);
     }
     invariant_inst
 }
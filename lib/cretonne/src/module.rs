@@ -0,0 +1,574 @@
+//! Modules of functions that are compiled and linked together.
+//!
+//! Cretonne compiles functions independently, and the functions called from a function are only
+//! identified by their `FunctionName` in the `ExtFuncData` declarations. A `Module` collects a set
+//! of functions and keeps track of how they relate: Each name in the module is declared once with
+//! a signature, and every use of the name must agree with that signature.
+//!
+//! Names that are used by functions in the module without being defined there are imports which
+//! must be resolved when the module is linked.
+//!
+//! A module can also contain data objects. Functions refer to them by name through `globalsym`
+//! global variables, and data objects can contain pointers to other symbols.
+//!
+//! The module drives the compilation of its functions and hands the compiled code to a `Backend`
+//! which is responsible for linking, for example by writing an object file or by placing the code
+//! in executable memory.
+
+use binemit::CodeOffset;
+use context::Context;
+use entity_map::{EntityMap, EntityRef, PrimaryEntityData, Keys};
+use ir::{Function, FunctionName, FuncRef, Signature, ArgumentType};
+use isa::TargetIsa;
+use result::CtonError;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt;
+use std::u32;
+use write::write_data;
+
+/// A reference to a function declared in a module.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FuncId(u32);
+
+impl EntityRef for FuncId {
+    fn new(index: usize) -> FuncId {
+        assert!(index < (u32::MAX as usize));
+        FuncId(index as u32)
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A reference to a data object defined in a module.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DataId(u32);
+
+impl EntityRef for DataId {
+    fn new(index: usize) -> DataId {
+        assert!(index < (u32::MAX as usize));
+        DataId(index as u32)
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A function declared in a module.
+pub struct FunctionDecl {
+    /// The name of the function.
+    pub name: FunctionName,
+
+    /// The signature that all uses of the function must agree with.
+    pub signature: Signature,
+
+    /// The function body, or `None` if the function is imported.
+    pub body: Option<Function>,
+}
+
+impl PrimaryEntityData for FunctionDecl {}
+
+/// A static data object.
+///
+/// In the text format, a data object is declared outside functions as:
+///
+/// ```text
+/// data %table = [#01020304, %func, 5], align 8, readonly
+/// ```
+///
+/// The contents are a list of hexadecimal byte sequences, single bytes, and pointers to named
+/// symbols. The alignment defaults to 1, and data is read-only unless it is declared `writable`.
+#[derive(Clone, Debug)]
+pub struct DataObject {
+    /// The name of the data object.
+    pub name: FunctionName,
+
+    /// The initial contents of the data object.
+    ///
+    /// The pointer fields are filled in when the data is linked.
+    pub contents: Vec<u8>,
+
+    /// Pointers to other symbols stored in `contents`, ordered by offset.
+    pub pointers: Vec<DataPointer>,
+
+    /// The required alignment in bytes. This is a power of two.
+    pub align: u32,
+
+    /// Can the data be modified at run time?
+    pub writable: bool,
+}
+
+/// A pointer to a symbol stored in a data object.
+#[derive(Clone, Debug)]
+pub struct DataPointer {
+    /// Offset of the pointer in the data object.
+    pub offset: u32,
+
+    /// Size of the pointer in bytes: 4 or 8.
+    pub size: u8,
+
+    /// The symbol pointed to.
+    pub target: FunctionName,
+}
+
+impl PrimaryEntityData for DataObject {}
+
+impl fmt::Display for DataObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_data(f, self)
+    }
+}
+
+/// A backend that links the compiled functions of a module.
+pub trait Backend {
+    /// Add a compiled function.
+    ///
+    /// The function has been prepared for code emission by `Context::compile()` which also
+    /// returned its `code_size`.
+    fn define_function(&mut self, func: &Function, code_size: CodeOffset) -> Result<(), String>;
+
+    /// Add a data object.
+    fn define_data(&mut self, data: &DataObject) -> Result<(), String>;
+}
+
+/// An error building or compiling a module.
+#[derive(Debug)]
+pub enum ModuleError {
+    /// A function without a name can't be declared in a module.
+    Anonymous,
+
+    /// A function or data object was defined more than once.
+    DuplicateDefinition(FunctionName),
+
+    /// A name was declared as both a function and a data object.
+    IncompatibleDeclaration(FunctionName),
+
+    /// A function was used with a signature that doesn't agree with its declaration.
+    IncompatibleSignature {
+        /// The name of the function.
+        name: FunctionName,
+        /// The signature in the declaration.
+        declared: Signature,
+        /// The incompatible signature.
+        used: Signature,
+    },
+
+    /// Compiling a function failed.
+    Compilation(FunctionName, CtonError),
+
+    /// The backend failed to link a function or data object.
+    Backend(FunctionName, String),
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ModuleError::Anonymous => f.write_str("Anonymous function in module"),
+            ModuleError::DuplicateDefinition(ref name) => {
+                write!(f, "Duplicate definition of {}", name)
+            }
+            ModuleError::IncompatibleDeclaration(ref name) => {
+                write!(f, "{} is declared as both a function and data", name)
+            }
+            ModuleError::IncompatibleSignature {
+                ref name,
+                ref declared,
+                ref used,
+            } => write!(f, "{} is declared as {}, but used as {}", name, declared, used),
+            ModuleError::Compilation(ref name, ref e) => write!(f, "{}: {}", name, e),
+            ModuleError::Backend(ref name, ref e) => write!(f, "{}: {}", name, e),
+        }
+    }
+}
+
+impl StdError for ModuleError {}
+
+/// A module result.
+pub type ModuleResult<T> = Result<T, ModuleError>;
+
+/// Check if the signature used for a function call agrees with the declared signature.
+///
+/// The value types, argument purposes and extensions, and the calling convention must match. The
+/// ABI locations are ignored since they are assigned independently when each function is
+/// legalized.
+fn signatures_agree(a: &Signature, b: &Signature) -> bool {
+    fn same(a: &[ArgumentType], b: &[ArgumentType]) -> bool {
+        a.len() == b.len() &&
+        a.iter()
+            .zip(b)
+            .all(|(x, y)| {
+                     x.value_type == y.value_type && x.purpose == y.purpose &&
+                     x.extension == y.extension
+                 })
+    }
+    a.call_conv == b.call_conv && same(&a.argument_types, &b.argument_types) &&
+    same(&a.return_types, &b.return_types)
+}
+
+/// What a name in a module refers to.
+#[derive(Copy, Clone)]
+enum Decl {
+    Function(FuncId),
+    Data(DataId),
+}
+
+/// A set of functions and data objects that are compiled and linked together.
+pub struct Module {
+    functions: EntityMap<FuncId, FunctionDecl>,
+    data: EntityMap<DataId, DataObject>,
+    names: HashMap<FunctionName, Decl>,
+}
+
+impl Module {
+    /// Create a new empty module.
+    pub fn new() -> Module {
+        Module {
+            functions: EntityMap::new(),
+            data: EntityMap::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Check that the function `name` can be declared with the signature `sig`.
+    ///
+    /// Return the existing declaration of `name`, if any, without modifying the module.
+    fn check_declaration(&self,
+                         name: &FunctionName,
+                         sig: &Signature)
+                         -> ModuleResult<Option<FuncId>> {
+        if name.as_ref().is_empty() {
+            return Err(ModuleError::Anonymous);
+        }
+        let id = match self.names.get(name) {
+            Some(&Decl::Function(id)) => id,
+            Some(&Decl::Data(_)) => {
+                return Err(ModuleError::IncompatibleDeclaration(name.clone()))
+            }
+            None => return Ok(None),
+        };
+        let decl = &self.functions[id];
+        if !signatures_agree(&decl.signature, sig) {
+            return Err(ModuleError::IncompatibleSignature {
+                           name: name.clone(),
+                           declared: decl.signature.clone(),
+                           used: sig.clone(),
+                       });
+        }
+        Ok(Some(id))
+    }
+
+    /// Declare the function `name` with the signature `sig`.
+    ///
+    /// If the function has already been declared, check that `sig` agrees with the existing
+    /// declaration.
+    pub fn declare_function(&mut self,
+                            name: &FunctionName,
+                            sig: &Signature)
+                            -> ModuleResult<FuncId> {
+        if let Some(id) = self.check_declaration(name, sig)? {
+            return Ok(id);
+        }
+        let id = self.functions
+            .push(FunctionDecl {
+                      name: name.clone(),
+                      signature: sig.clone(),
+                      body: None,
+                  });
+        self.names.insert(name.clone(), Decl::Function(id));
+        Ok(id)
+    }
+
+    /// Add the definition of `func` to the module.
+    ///
+    /// The function's own signature and the signatures of all the functions it calls are checked
+    /// against the existing declarations, and the called functions are declared if necessary.
+    /// The module is not modified if any of the checks fail.
+    pub fn define_function(&mut self, func: Function) -> ModuleResult<FuncId> {
+        if let Some(id) = self.check_declaration(&func.name, &func.signature)? {
+            if self.functions[id].body.is_some() {
+                return Err(ModuleError::DuplicateDefinition(func.name.clone()));
+            }
+        }
+
+        // The called functions that are not declared yet must also agree with each other.
+        let mut uses = HashMap::new();
+        uses.insert(&func.name, &func.signature);
+        for fref in func.dfg.ext_funcs.keys() {
+            let ext = &func.dfg.ext_funcs[fref];
+            let sig = &func.dfg.signatures[ext.signature];
+            self.check_declaration(&ext.name, sig)?;
+            if let Some(used) = uses.insert(&ext.name, sig) {
+                if !signatures_agree(used, sig) {
+                    return Err(ModuleError::IncompatibleSignature {
+                                   name: ext.name.clone(),
+                                   declared: used.clone(),
+                                   used: sig.clone(),
+                               });
+                }
+            }
+        }
+
+        let id = self.declare_function(&func.name, &func.signature)?;
+        for fref in func.dfg.ext_funcs.keys() {
+            let ext = &func.dfg.ext_funcs[fref];
+            self.declare_function(&ext.name, &func.dfg.signatures[ext.signature])?;
+        }
+        self.functions[id].body = Some(func);
+        Ok(id)
+    }
+
+    /// Add the data object `data` to the module.
+    pub fn define_data(&mut self, data: DataObject) -> ModuleResult<DataId> {
+        if data.name.as_ref().is_empty() {
+            return Err(ModuleError::Anonymous);
+        }
+        match self.names.get(&data.name) {
+            Some(&Decl::Function(_)) => {
+                return Err(ModuleError::IncompatibleDeclaration(data.name.clone()))
+            }
+            Some(&Decl::Data(_)) => return Err(ModuleError::DuplicateDefinition(data.name.clone())),
+            None => {}
+        }
+        let name = data.name.clone();
+        let id = self.data.push(data);
+        self.names.insert(name, Decl::Data(id));
+        Ok(id)
+    }
+
+    /// Look up a function by name.
+    pub fn get_name(&self, name: &FunctionName) -> Option<FuncId> {
+        match self.names.get(name) {
+            Some(&Decl::Function(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Look up a data object by name.
+    pub fn get_data(&self, name: &FunctionName) -> Option<DataId> {
+        match self.names.get(name) {
+            Some(&Decl::Data(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Get the data object `id`.
+    pub fn data(&self, id: DataId) -> &DataObject {
+        &self.data[id]
+    }
+
+    /// Iterate over all the data objects.
+    pub fn data_objects(&self) -> Keys<DataId> {
+        self.data.keys()
+    }
+
+    /// Get the declaration of the function `id`.
+    pub fn declaration(&self, id: FuncId) -> &FunctionDecl {
+        &self.functions[id]
+    }
+
+    /// Resolve the function called through `fref` in `func` to its declaration in the module.
+    pub fn callee(&self, func: &Function, fref: FuncRef) -> Option<FuncId> {
+        self.get_name(&func.dfg.ext_funcs[fref].name)
+    }
+
+    /// Iterate over all the declared functions, including the imported ones.
+    pub fn functions(&self) -> Keys<FuncId> {
+        self.functions.keys()
+    }
+
+    /// Is the function `id` imported?
+    pub fn is_import(&self, id: FuncId) -> bool {
+        self.functions[id].body.is_none()
+    }
+
+    /// Compile all the functions defined in the module for `isa`, and pass them to `backend`
+    /// along with the data objects.
+    ///
+    /// The functions in the module are not modified, so the same module can be compiled for
+    /// multiple targets.
+    pub fn compile<B: Backend>(&self, isa: &TargetIsa, backend: &mut B) -> ModuleResult<()> {
+        let mut ctx = Context::new();
+        for id in self.functions.keys() {
+            let decl = &self.functions[id];
+            if let Some(ref func) = decl.body {
+                ctx.func = func.clone();
+                let size = ctx.compile(isa)
+                    .map_err(|e| ModuleError::Compilation(decl.name.clone(), e))?;
+                backend
+                    .define_function(&ctx.func, size)
+                    .map_err(|e| ModuleError::Backend(decl.name.clone(), e))?;
+            }
+        }
+        for id in self.data.keys() {
+            let data = &self.data[id];
+            backend
+                .define_data(data)
+                .map_err(|e| ModuleError::Backend(data.name.clone(), e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ir::{Function, FunctionName, Signature, ArgumentType, ExtFuncData, InstBuilder, Cursor};
+    use ir::types::I32;
+    use isa;
+    use settings;
+
+    fn sig(args: usize) -> Signature {
+        let mut sig = Signature::new();
+        for _ in 0..args {
+            sig.argument_types.push(ArgumentType::new(I32));
+        }
+        sig
+    }
+
+    // Create a function named `name` that calls `callee` with the signature `callee_sig`.
+    fn caller(name: &str, callee: &str, callee_sig: Signature) -> Function {
+        let mut func = Function::with_name_signature(FunctionName::new(name), sig(0));
+        let sigref = func.dfg.signatures.push(callee_sig);
+        let fref = func.dfg
+            .ext_funcs
+            .push(ExtFuncData {
+                      name: FunctionName::new(callee),
+                      signature: sigref,
+                  });
+        let ebb = func.dfg.make_ebb();
+        let pos = &mut Cursor::new(&mut func.layout);
+        pos.insert_ebb(ebb);
+        func.dfg.ins(pos).call(fref, &[]);
+        func.dfg.ins(pos).return_(&[]);
+        func
+    }
+
+    #[test]
+    fn declarations() {
+        let mut module = Module::new();
+        let f = FunctionName::new("f");
+        let id = module.declare_function(&f, &sig(1)).unwrap();
+        assert_eq!(module.get_name(&f), Some(id));
+        assert!(module.is_import(id));
+        assert_eq!(module.declare_function(&f, &sig(1)).unwrap(), id);
+        assert_eq!(module.declare_function(&f, &sig(2)).unwrap_err().to_string(),
+                   "%f is declared as (i32), but used as (i32, i32)");
+        assert_eq!(module.declare_function(&FunctionName::new(""), &sig(0))
+                       .unwrap_err()
+                       .to_string(),
+                   "Anonymous function in module");
+    }
+
+    #[test]
+    fn definitions() {
+        let mut module = Module::new();
+        let a = module.define_function(caller("a", "b", sig(0))).unwrap();
+        let b = module.get_name(&FunctionName::new("b")).unwrap();
+        assert!(!module.is_import(a));
+        assert!(module.is_import(b));
+        assert_eq!(module.functions().collect::<Vec<_>>(), [a, b]);
+
+        {
+            let body = module.declaration(a).body.as_ref().unwrap();
+            let fref = body.dfg.ext_funcs.keys().next().unwrap();
+            assert_eq!(module.callee(body, fref), Some(b));
+        }
+
+        // Defining `b` with a different signature than the one used by `a` fails.
+        let wrong_b = Function::with_name_signature(FunctionName::new("b"), sig(1));
+        assert_eq!(module.define_function(wrong_b).unwrap_err().to_string(),
+                   "%b is declared as (), but used as (i32)");
+        assert!(module.is_import(b));
+
+        // `c` calls `a` with the wrong signature. Nothing is declared when the definition fails.
+        assert_eq!(module.define_function(caller("c", "a", sig(1))).unwrap_err().to_string(),
+                   "%a is declared as (), but used as (i32)");
+        assert_eq!(module.get_name(&FunctionName::new("c")), None);
+
+        // `d` calls the undeclared `e` with two different signatures.
+        let mut d = caller("d", "e", sig(0));
+        let sigref = d.dfg.signatures.push(sig(1));
+        d.dfg
+            .ext_funcs
+            .push(ExtFuncData {
+                      name: FunctionName::new("e"),
+                      signature: sigref,
+                  });
+        assert_eq!(module.define_function(d).unwrap_err().to_string(),
+                   "%e is declared as (), but used as (i32)");
+        assert_eq!(module.get_name(&FunctionName::new("d")), None);
+        assert_eq!(module.get_name(&FunctionName::new("e")), None);
+        assert_eq!(module.functions().count(), 2);
+
+        assert_eq!(module.define_function(caller("a", "b", sig(0))).unwrap_err().to_string(),
+                   "Duplicate definition of %a");
+    }
+
+    fn data(name: &str) -> DataObject {
+        DataObject {
+            name: FunctionName::new(name),
+            contents: vec![1, 2, 0, 0, 0, 0, 3],
+            pointers: vec![DataPointer {
+                               offset: 2,
+                               size: 4,
+                               target: FunctionName::new("a"),
+                           }],
+            align: 4,
+            writable: false,
+        }
+    }
+
+    #[test]
+    fn data_objects() {
+        let mut module = Module::new();
+        module.define_function(caller("a", "b", sig(0))).unwrap();
+        let d = module.define_data(data("d")).unwrap();
+        assert_eq!(module.get_data(&FunctionName::new("d")), Some(d));
+        assert_eq!(module.get_name(&FunctionName::new("d")), None);
+        assert_eq!(module.data(d).to_string(),
+                   "data %d = [#0102, %a, #03], align 4");
+
+        assert_eq!(module.define_data(data("d")).unwrap_err().to_string(),
+                   "Duplicate definition of %d");
+        assert_eq!(module.define_data(data("b")).unwrap_err().to_string(),
+                   "%b is declared as both a function and data");
+        assert_eq!(module.declare_function(&FunctionName::new("d"), &sig(0))
+                       .unwrap_err()
+                       .to_string(),
+                   "%d is declared as both a function and data");
+    }
+
+    struct Sizes(Vec<(FunctionName, CodeOffset)>);
+
+    impl Backend for Sizes {
+        fn define_function(&mut self, func: &Function, size: CodeOffset) -> Result<(), String> {
+            self.0.push((func.name.clone(), size));
+            Ok(())
+        }
+
+        fn define_data(&mut self, data: &DataObject) -> Result<(), String> {
+            self.0.push((data.name.clone(), data.contents.len() as CodeOffset));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn compile() {
+        let mut module = Module::new();
+        module.define_function(caller("a", "b", sig(0))).unwrap();
+        module.define_function(caller("c", "a", sig(0))).unwrap();
+        module.define_data(data("d")).unwrap();
+
+        let shared_flags = settings::Flags::new(&settings::builder());
+        let isa = isa::lookup("riscv").unwrap().finish(shared_flags);
+
+        let mut sizes = Sizes(Vec::new());
+        module.compile(&*isa, &mut sizes).unwrap();
+        let names = sizes.0.iter().map(|s| s.0.to_string()).collect::<Vec<_>>();
+        assert_eq!(names, ["%a", "%c", "%d"]);
+        assert!(sizes.0.iter().all(|s| s.1 > 0));
+    }
+}
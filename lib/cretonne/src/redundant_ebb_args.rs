@@ -0,0 +1,100 @@
+//! A pass that removes redundant EBB arguments.
+//!
+//! An EBB argument is redundant when every predecessor passes it the same value, not counting
+//! back edges passing the argument to itself. Such an argument is replaced by the common value
+//! everywhere it is used, and it is removed from the EBB and from all the branches to the EBB.
+//!
+//! Removing an argument can make other arguments redundant, so the pass runs until nothing
+//! changes.
+
+use flowgraph::ControlFlowGraph;
+use ir::{Function, Ebb, Value, ValueDef};
+use ir::instructions::BranchInfo;
+
+/// Remove the redundant EBB arguments in `func`.
+///
+/// Only branch arguments are changed, so the control flow graph stays valid.
+pub fn do_remove_redundant_ebb_args(func: &mut Function, cfg: &ControlFlowGraph) {
+    let entry = func.layout.entry_block();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+        for ebb in ebbs {
+            // The entry block arguments are the function arguments.
+            if Some(ebb) == entry {
+                continue;
+            }
+            // Visit the arguments backwards so removing one doesn't change the position of the
+            // arguments that are still to be visited.
+            for num in (0..func.dfg.num_ebb_args(ebb)).rev() {
+                let arg = func.dfg.ebb_args(ebb)[num];
+                if let Some(common) = common_value(func, cfg, ebb, arg) {
+                    remove_ebb_arg(func, cfg, ebb, arg, common);
+                    changed = true;
+                }
+            }
+        }
+    }
+}
+
+/// If all the predecessors of `ebb` pass the same value for `arg` apart from `arg` itself,
+/// return that value.
+fn common_value(func: &Function, cfg: &ControlFlowGraph, ebb: Ebb, arg: Value) -> Option<Value> {
+    let num = match func.dfg.value_def(arg) {
+        ValueDef::Arg(_, num) => num,
+        ValueDef::Res(..) => panic!("{} must be an EBB argument", arg),
+    };
+    let mut common = None;
+    for &(_, branch) in cfg.get_predecessors(ebb) {
+        let value = match func.dfg[branch].analyze_branch(&func.dfg.value_lists) {
+            BranchInfo::SingleDest(_, args) => func.dfg.resolve_aliases(args[num]),
+            // EBBs reached through a jump table can't have arguments.
+            _ => return None,
+        };
+        if value == arg || Some(value) == common {
+            continue;
+        }
+        if common.is_some() {
+            return None;
+        }
+        common = Some(value);
+    }
+
+    let common = match common {
+        Some(common) => common,
+        None => return None,
+    };
+    // In unreachable code, the only value passed could be defined in `ebb` itself.
+    let def_ebb = match func.dfg.value_def(common) {
+        ValueDef::Res(inst, _) => func.layout.inst_ebb(inst),
+        ValueDef::Arg(def_ebb, _) => Some(def_ebb),
+    };
+    if def_ebb == Some(ebb) {
+        None
+    } else {
+        Some(common)
+    }
+}
+
+/// Replace `arg` with an alias of `common`, and remove it from `ebb` and the branches to `ebb`.
+fn remove_ebb_arg(func: &mut Function,
+                  cfg: &ControlFlowGraph,
+                  ebb: Ebb,
+                  arg: Value,
+                  common: Value) {
+    let num = func.dfg.remove_ebb_arg(arg);
+    func.dfg.change_to_alias(arg, common);
+
+    for &(_, branch) in cfg.get_predecessors(ebb) {
+        let num_fixed = func.dfg[branch]
+            .opcode()
+            .constraints()
+            .fixed_value_arguments();
+        let mut args = func.dfg[branch]
+            .take_value_list()
+            .expect("Branches must have value lists.");
+        args.remove(num_fixed + num, &mut func.dfg.value_lists);
+        func.dfg[branch].put_value_list(args);
+    }
+}
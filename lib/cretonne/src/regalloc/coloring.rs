@@ -31,10 +31,11 @@
 //! defined by the instruction and only consider the colors of other values that are live at the
 //! instruction.
 
-use entity_map::EntityMap;
+use entity_map::{EntityMap, EntityRef};
 use dominator_tree::DominatorTree;
 use ir::{Ebb, Inst, Value, Function, Cursor, ValueLoc, DataFlowGraph};
 use ir::{InstBuilder, Signature, ArgumentType, ArgumentLoc};
+use ir::sourceloc;
 use isa::{TargetIsa, Encoding, EncInfo, OperandConstraint, ConstraintKind};
 use isa::{RegUnit, RegClass, RegInfo, regs_overlap};
 use regalloc::affinity::Affinity;
@@ -43,6 +44,7 @@ use regalloc::live_value_tracker::{LiveValue, LiveValueTracker};
 use regalloc::liveness::Liveness;
 use regalloc::solver::Solver;
 use regalloc::RegDiversions;
+use result::{CtonError, CtonResult};
 use topo_order::TopoOrder;
 
 
@@ -63,6 +65,8 @@ pub struct Coloring {
 /// Immutable context information and mutable references that don't need to be borrowed across
 /// method calls should go in this struct.
 struct Context<'a> {
+    isa: &'a TargetIsa,
+
     // Cached ISA information.
     // We save it here to avoid frequent virtual function calls on the `TargetIsa` trait object.
     reginfo: RegInfo,
@@ -94,14 +98,18 @@ impl Coloring {
     }
 
     /// Run the coloring algorithm over `func`.
+    ///
+    /// Fails if a register move inserted by the allocator can't be encoded.
     pub fn run(&mut self,
                isa: &TargetIsa,
                func: &mut Function,
                domtree: &DominatorTree,
                liveness: &mut Liveness,
                topo: &mut TopoOrder,
-               tracker: &mut LiveValueTracker) {
+               tracker: &mut LiveValueTracker)
+               -> CtonResult {
         let mut ctx = Context {
+            isa,
             reginfo: isa.register_info(),
             encinfo: isa.encoding_info(),
             domtree,
@@ -117,17 +125,22 @@ impl Coloring {
 
 impl<'a> Context<'a> {
     /// Run the coloring algorithm.
-    fn run(&mut self, func: &mut Function, tracker: &mut LiveValueTracker) {
+    fn run(&mut self, func: &mut Function, tracker: &mut LiveValueTracker) -> CtonResult {
         // Just visit blocks in layout order, letting `self.topo` enforce a topological ordering.
         // TODO: Once we have a loop tree, we could visit hot blocks first.
         self.topo.reset(func.layout.ebbs());
         while let Some(ebb) = self.topo.next(&func.layout, self.domtree) {
-            self.visit_ebb(ebb, func, tracker);
+            self.visit_ebb(ebb, func, tracker)?;
         }
+        Ok(())
     }
 
     /// Visit `ebb`, assuming that the immediate dominator has already been visited.
-    fn visit_ebb(&mut self, ebb: Ebb, func: &mut Function, tracker: &mut LiveValueTracker) {
+    fn visit_ebb(&mut self,
+                 ebb: Ebb,
+                 func: &mut Function,
+                 tracker: &mut LiveValueTracker)
+                 -> CtonResult {
         dbg!("Coloring {}:", ebb);
         let mut regs = self.visit_ebb_header(ebb, func, tracker);
         tracker.drop_dead_args();
@@ -139,6 +152,7 @@ impl<'a> Context<'a> {
         while let Some(inst) = pos.next_inst() {
             let encoding = func.encodings[inst];
             assert!(encoding.is_legal(), "Illegal: {}", func.dfg[inst].opcode());
+            let first_new = func.dfg.num_insts();
             self.visit_inst(inst,
                             encoding,
                             &mut pos,
@@ -147,9 +161,25 @@ impl<'a> Context<'a> {
                             &mut regs,
                             &mut func.locations,
                             &func.signature);
+            // Register moves inserted before `inst` get its source location.
+            sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
+            // Encode them too.
+            for num in first_new..func.dfg.num_insts() {
+                let mv = Inst::new(num);
+                let ctrl_type = func.dfg.ctrl_typevar(mv);
+                match self.isa.encode(&func.dfg, &func.dfg[mv], ctrl_type) {
+                    Ok(enc) => *func.encodings.ensure(mv) = enc,
+                    Err(_) => {
+                        return Err(CtonError::Unsupported(format!("{}.{} on {}",
+                                                                  func.dfg[mv].opcode(),
+                                                                  ctrl_type,
+                                                                  self.isa.name())))
+                    }
+                }
+            }
             tracker.drop_dead(inst);
         }
-
+        Ok(())
     }
 
     /// Visit the `ebb` header.
@@ -356,7 +386,12 @@ impl<'a> Context<'a> {
             let abi = &dfg.signatures[sig].return_types;
             self.program_output_abi(abi, defs, throughs, locations);
         }
-        self.program_output_constraints(inst, constraints.outs, defs, dfg, locations);
+        self.program_output_constraints(inst,
+                                        constraints.outs,
+                                        defs,
+                                        throughs,
+                                        dfg,
+                                        locations);
 
         // Finally, we've fully programmed the constraint solver.
         // We expect a quick solution in most cases.
@@ -535,11 +570,12 @@ impl<'a> Context<'a> {
     ///
     /// It is assumed that all fixed outputs have already been handled.
     fn program_output_constraints(&mut self,
-                                  _inst: Inst,
+                                  inst: Inst,
                                   constraints: &[OperandConstraint],
                                   defs: &[LiveValue],
-                                  _dfg: &mut DataFlowGraph,
-                                  _locations: &mut EntityMap<Value, ValueLoc>) {
+                                  throughs: &[LiveValue],
+                                  dfg: &mut DataFlowGraph,
+                                  locations: &mut EntityMap<Value, ValueLoc>) {
         for (op, lv) in constraints.iter().zip(defs) {
             match op.kind {
                 ConstraintKind::FixedReg(_) |
@@ -547,7 +583,13 @@ impl<'a> Context<'a> {
                 ConstraintKind::Reg => {
                     self.solver.add_def(lv.value, op.regclass);
                 }
-                ConstraintKind::Tied(_) => unimplemented!(),
+                ConstraintKind::Tied(num) => {
+                    // The reload pass made sure that the tied input is killed by `inst`, so the
+                    // output can take over its register.
+                    let arg = dfg.inst_args(inst)[num as usize];
+                    let reg = self.divert.reg(arg, locations);
+                    self.add_fixed_output(lv.value, op.regclass, reg, throughs, locations);
+                }
             }
         }
     }
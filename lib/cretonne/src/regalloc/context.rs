@@ -71,7 +71,7 @@ impl Context {
                  domtree,
                  &mut self.liveness,
                  &mut self.topo,
-                 &mut self.tracker);
+                 &mut self.tracker)?;
 
         if isa.flags().enable_verifier() {
             verify_context(func, cfg, domtree, Some(isa))?;
@@ -85,7 +85,7 @@ impl Context {
                  domtree,
                  &mut self.liveness,
                  &mut self.topo,
-                 &mut self.tracker);
+                 &mut self.tracker)?;
 
         if isa.flags().enable_verifier() {
             verify_context(func, cfg, domtree, Some(isa))?;
@@ -204,13 +204,17 @@ fn get_or_create<'a>(lrset: &'a mut LiveRangeSet,
         match func.dfg.value_def(value) {
             ValueDef::Res(inst, rnum) => {
                 def = inst.into();
-                // Initialize the affinity from the defining instruction's result constraints.
-                // Don't do this for call return values which are always tied to a single register.
-                affinity = enc_info
-                    .operand_constraints(func.encodings[inst])
-                    .and_then(|rc| rc.outs.get(rnum))
-                    .map(Affinity::new)
-                    .unwrap_or_default();
+                if let Some(sig) = func.dfg.call_signature(inst) {
+                    // Call return values are tied to the registers given by the signature.
+                    affinity = Affinity::abi(&func.dfg.signatures[sig].return_types[rnum], isa);
+                } else {
+                    // Initialize the affinity from the defining instruction's result constraints.
+                    affinity = enc_info
+                        .operand_constraints(func.encodings[inst])
+                        .and_then(|rc| rc.outs.get(rnum))
+                        .map(Affinity::new)
+                        .unwrap_or_default();
+                }
             }
             ValueDef::Arg(ebb, num) => {
                 def = ebb.into();
@@ -8,16 +8,23 @@
 //! The secondary responsibility of the reload pass is to reuse values in registers as much as
 //! possible to minimize the number of `fill` instructions needed. This must not cause the register
 //! pressure limits to be exceeded.
+//!
+//! The reload pass also makes sure that the input operands that are tied to an output are killed
+//! by the instruction. A tied input value that is still live after the instruction is replaced
+//! with a `copy` so the output can take over the register of the copy.
 
 use dominator_tree::DominatorTree;
+use entity_map::EntityMap;
 use ir::{Ebb, Inst, Value, Function, DataFlowGraph};
 use ir::layout::{Cursor, CursorPosition};
 use ir::{InstBuilder, ArgumentLoc};
+use ir::sourceloc;
 use isa::RegClass;
 use isa::{TargetIsa, Encoding, EncInfo, ConstraintKind};
 use regalloc::affinity::Affinity;
 use regalloc::live_value_tracker::{LiveValue, LiveValueTracker};
 use regalloc::liveness::Liveness;
+use result::{CtonError, CtonResult};
 use sparse_map::{SparseMap, SparseMapValue};
 use topo_order::TopoOrder;
 
@@ -29,6 +36,8 @@ pub struct Reload {
 
 /// Context data structure that gets instantiated once per pass.
 struct Context<'a> {
+    isa: &'a TargetIsa,
+
     // Cached ISA information.
     // We save it here to avoid frequent virtual function calls on the `TargetIsa` trait object.
     encinfo: EncInfo,
@@ -52,14 +61,18 @@ impl Reload {
     }
 
     /// Run the reload algorithm over `func`.
+    ///
+    /// Fails if a copy of a tied operand can't be encoded.
     pub fn run(&mut self,
                isa: &TargetIsa,
                func: &mut Function,
                domtree: &DominatorTree,
                liveness: &mut Liveness,
                topo: &mut TopoOrder,
-               tracker: &mut LiveValueTracker) {
+               tracker: &mut LiveValueTracker)
+               -> CtonResult {
         let mut ctx = Context {
+            isa,
             encinfo: isa.encoding_info(),
             domtree,
             liveness,
@@ -95,14 +108,19 @@ impl SparseMapValue<Value> for ReloadedValue {
 }
 
 impl<'a> Context<'a> {
-    fn run(&mut self, func: &mut Function, tracker: &mut LiveValueTracker) {
+    fn run(&mut self, func: &mut Function, tracker: &mut LiveValueTracker) -> CtonResult {
         self.topo.reset(func.layout.ebbs());
         while let Some(ebb) = self.topo.next(&func.layout, self.domtree) {
-            self.visit_ebb(ebb, func, tracker);
+            self.visit_ebb(ebb, func, tracker)?;
         }
+        Ok(())
     }
 
-    fn visit_ebb(&mut self, ebb: Ebb, func: &mut Function, tracker: &mut LiveValueTracker) {
+    fn visit_ebb(&mut self,
+                 ebb: Ebb,
+                 func: &mut Function,
+                 tracker: &mut LiveValueTracker)
+                 -> CtonResult {
         dbg!("Reloading {}:", ebb);
         let start_from = self.visit_ebb_header(ebb, func, tracker);
         tracker.drop_dead_args();
@@ -112,12 +130,22 @@ impl<'a> Context<'a> {
         while let Some(inst) = pos.current_inst() {
             let encoding = func.encodings[inst];
             if encoding.is_legal() {
-                self.visit_inst(ebb, inst, encoding, &mut pos, &mut func.dfg, tracker);
+                let first_new = func.dfg.num_insts();
+                self.visit_inst(ebb,
+                                inst,
+                                encoding,
+                                &mut pos,
+                                &mut func.dfg,
+                                &mut func.encodings,
+                                tracker)?;
+                // Fills and spills inserted around `inst` get its source location.
+                sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
                 tracker.drop_dead(inst);
             } else {
                 pos.next_inst();
             }
         }
+        Ok(())
     }
 
     /// Process the EBB parameters. Return the next instruction in the EBB to be processed
@@ -184,7 +212,9 @@ impl<'a> Context<'a> {
                   encoding: Encoding,
                   pos: &mut Cursor,
                   dfg: &mut DataFlowGraph,
-                  tracker: &mut LiveValueTracker) {
+                  encodings: &mut EntityMap<Inst, Encoding>,
+                  tracker: &mut LiveValueTracker)
+                  -> CtonResult {
         // Get the operand constraints for `inst` that we are trying to satisfy.
         let constraints = self.encinfo
             .operand_constraints(encoding)
@@ -235,6 +265,34 @@ impl<'a> Context<'a> {
         // TODO: Reuse reloads for future instructions.
         self.reloads.clear();
 
+        // Copy tied inputs that are live after `inst`.
+        for (num, op) in constraints.ins.iter().enumerate() {
+            if let ConstraintKind::Tied(_) = op.kind {
+                let arg = dfg.inst_args(inst)[num];
+                if tracker
+                       .live()
+                       .iter()
+                       .any(|lv| lv.value == arg && lv.endpoint != inst) {
+                    let copy = dfg.ins(pos).copy(arg);
+                    let copy_inst = dfg.value_def(copy).unwrap_inst();
+                    let ctrl_type = dfg.ctrl_typevar(copy_inst);
+                    match self.isa.encode(dfg, &dfg[copy_inst], ctrl_type) {
+                        Ok(enc) => *encodings.ensure(copy_inst) = enc,
+                        Err(_) => {
+                            return Err(CtonError::Unsupported(format!("copy.{} on {}",
+                                                                      ctrl_type,
+                                                                      self.isa.name())))
+                        }
+                    }
+
+                    let affinity = Affinity::Reg(op.regclass.into());
+                    self.liveness.create_dead(copy, copy_inst, affinity);
+                    self.liveness.extend_locally(copy, ebb, inst, &pos.layout);
+                    dfg.inst_args_mut(inst)[num] = copy;
+                }
+            }
+        }
+
         let (_throughs, _kills, defs) = tracker.process_inst(inst, dfg, self.liveness);
 
         // Advance to the next instruction so we can insert any spills after the instruction.
@@ -265,5 +323,6 @@ impl<'a> Context<'a> {
                 self.liveness.move_def_locally(lv.value, spill);
             }
         }
+        Ok(())
     }
 }
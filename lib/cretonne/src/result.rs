@@ -29,6 +29,11 @@ pub enum CtonError {
     /// Different target ISAs may impose a limit on the size of a compiled function. If that limit
     /// is exceeded, compilation fails.
     CodeTooLarge,
+
+    /// The function uses a feature that isn't supported by the target ISA.
+    ///
+    /// For example, not all ISAs support all calling conventions.
+    Unsupported(String),
 }
 
 /// A Cretonne compilation result.
@@ -38,6 +43,7 @@ impl fmt::Display for CtonError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             CtonError::Verifier(ref e) => write!(f, "Verifier error: {}", e),
+            CtonError::Unsupported(ref msg) => write!(f, "Unsupported: {}", msg),
             CtonError::ImplLimitExceeded |
             CtonError::CodeTooLarge => f.write_str(self.description()),
         }
@@ -50,13 +56,15 @@ impl StdError for CtonError {
             CtonError::Verifier(ref e) => &e.message,
             CtonError::ImplLimitExceeded => "Implementation limit exceeded",
             CtonError::CodeTooLarge => "Code for function is too large",
+            CtonError::Unsupported(ref msg) => msg,
         }
     }
     fn cause(&self) -> Option<&StdError> {
         match *self {
             CtonError::Verifier(ref e) => Some(e),
             CtonError::ImplLimitExceeded |
-            CtonError::CodeTooLarge => None,
+            CtonError::CodeTooLarge |
+            CtonError::Unsupported(_) => None,
         }
     }
 }
@@ -0,0 +1,477 @@
+//! A sparse conditional constant propagation pass.
+//!
+//! Cretonne passes values between EBBs as EBB arguments instead of using phi nodes. This pass
+//! tracks a lattice value for every SSA value, and it only follows the control flow edges that
+//! can be executed given the constants known so far. The value of an EBB argument is the meet of
+//! the values passed along the executable edges to the EBB.
+//!
+//! When the analysis has reached a fixed point, the function is rewritten:
+//!
+//! - Integer values that are known constants are computed with `iconst` instead.
+//! - Branches with a known condition are turned into jumps or removed.
+//! - EBBs that are no longer reachable are deleted.
+
+use dominator_tree::DominatorTree;
+use entity_map::EntityMap;
+use flowgraph::ControlFlowGraph;
+use ir::{Function, DataFlowGraph, Cursor, Ebb, Inst, Value, Type, InstructionData, Opcode,
+         InstBuilder};
+use ir::condcodes::IntCC;
+use ir::instructions::BranchInfo;
+use simplify_cfg::remove_unreachable_ebbs;
+use std::collections::HashSet;
+
+/// A lattice value tracked for each SSA value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LatticeValue {
+    /// No executable definition has been seen yet.
+    Top,
+    /// The value is always this constant.
+    ///
+    /// Integers are stored sign-extended from their type's width, and booleans as 0 or 1.
+    Const(i64),
+    /// The value is not a constant.
+    Bottom,
+}
+
+impl Default for LatticeValue {
+    fn default() -> LatticeValue {
+        LatticeValue::Top
+    }
+}
+
+impl LatticeValue {
+    /// Compute the greatest lower bound of two lattice values.
+    fn meet(self, other: LatticeValue) -> LatticeValue {
+        use self::LatticeValue::*;
+        match (self, other) {
+            (Top, x) | (x, Top) => x,
+            (Const(a), Const(b)) if a == b => Const(a),
+            _ => Bottom,
+        }
+    }
+
+    /// Apply `f` to a constant operand.
+    fn map<F>(self, f: F) -> LatticeValue
+        where F: FnOnce(i64) -> i64
+    {
+        match self {
+            LatticeValue::Const(x) => LatticeValue::Const(f(x)),
+            lv => lv,
+        }
+    }
+
+    /// Apply `f` to constant operands.
+    fn map2<F>(self, other: LatticeValue, f: F) -> LatticeValue
+        where F: FnOnce(i64, i64) -> i64
+    {
+        use self::LatticeValue::*;
+        match (self, other) {
+            (Bottom, _) | (_, Bottom) => Bottom,
+            (Top, _) | (_, Top) => Top,
+            (Const(a), Const(b)) => Const(f(a, b)),
+        }
+    }
+}
+
+/// What is known about the direction of a conditional branch.
+enum BranchDirection {
+    /// The condition is still `Top`. Nothing after the branch has been executed yet.
+    Unknown,
+    /// The branch is always taken.
+    Taken,
+    /// The branch is never taken.
+    NotTaken,
+    /// The branch may or may not be taken.
+    Either,
+}
+
+/// Perform sparse conditional constant propagation on `func`.
+///
+/// The control flow graph and the dominator tree are recomputed.
+pub fn do_sccp(func: &mut Function, cfg: &mut ControlFlowGraph, domtree: &mut DominatorTree) {
+    let mut sccp = Sccp::new(func);
+    sccp.solve(func, cfg);
+    // Branches are folded first since the lattice values of EBB arguments can't be found through
+    // the aliases created by `rewrite_constants`.
+    sccp.fold_branches(func);
+    sccp.rewrite_constants(func);
+
+    cfg.compute(func);
+    remove_unreachable_ebbs(func, cfg);
+    domtree.compute(func, cfg);
+}
+
+struct Sccp {
+    values: EntityMap<Value, LatticeValue>,
+    executable_ebbs: EntityMap<Ebb, bool>,
+    executable_edges: HashSet<(Inst, Ebb)>,
+    changed: bool,
+}
+
+impl Sccp {
+    fn new(func: &Function) -> Sccp {
+        Sccp {
+            values: EntityMap::new(),
+            executable_ebbs: EntityMap::with_capacity(func.dfg.num_ebbs()),
+            executable_edges: HashSet::new(),
+            changed: false,
+        }
+    }
+
+    /// Get the lattice value of `value`.
+    fn value(&self, dfg: &DataFlowGraph, value: Value) -> LatticeValue {
+        self.values.get_or_default(dfg.resolve_aliases(value))
+    }
+
+    /// Lower the lattice value of `value` to `lv`.
+    fn lower_value(&mut self, value: Value, lv: LatticeValue) {
+        let old = self.values.get_or_default(value);
+        let new = old.meet(lv);
+        if new != old {
+            *self.values.ensure(value) = new;
+            self.changed = true;
+        }
+    }
+
+    /// Mark the edge from `branch` to `dest` as executable.
+    fn mark_edge(&mut self, branch: Inst, dest: Ebb) {
+        if self.executable_edges.insert((branch, dest)) {
+            self.changed = true;
+        }
+        if !self.executable_ebbs[dest] {
+            self.executable_ebbs[dest] = true;
+            self.changed = true;
+        }
+    }
+
+    /// Visit the executable EBBs until the lattice values stop changing.
+    fn solve(&mut self, func: &Function, cfg: &ControlFlowGraph) {
+        let entry = match func.layout.entry_block() {
+            Some(entry) => entry,
+            None => return,
+        };
+        self.executable_ebbs[entry] = true;
+        for &arg in func.dfg.ebb_args(entry) {
+            self.lower_value(arg, LatticeValue::Bottom);
+        }
+
+        self.changed = true;
+        while self.changed {
+            self.changed = false;
+            for ebb in func.layout.ebbs() {
+                if self.executable_ebbs[ebb] {
+                    self.visit_ebb(func, cfg, ebb);
+                }
+            }
+        }
+    }
+
+    fn visit_ebb(&mut self, func: &Function, cfg: &ControlFlowGraph, ebb: Ebb) {
+        // The EBB arguments are the meet of the values passed along the executable edges.
+        for (num, &arg) in func.dfg.ebb_args(ebb).iter().enumerate() {
+            for &(_, branch) in cfg.get_predecessors(ebb) {
+                if !self.executable_edges.contains(&(branch, ebb)) {
+                    continue;
+                }
+                if let BranchInfo::SingleDest(_, args) =
+                    func.dfg[branch].analyze_branch(&func.dfg.value_lists) {
+                    let lv = self.value(&func.dfg, args[num]);
+                    self.lower_value(arg, lv);
+                }
+            }
+        }
+
+        for inst in func.layout.ebb_insts(ebb) {
+            let opcode = func.dfg[inst].opcode();
+            match func.dfg[inst].analyze_branch(&func.dfg.value_lists) {
+                BranchInfo::SingleDest(dest, _) => {
+                    match self.branch_direction(&func.dfg, inst) {
+                        BranchDirection::Unknown => return,
+                        BranchDirection::Taken => {
+                            self.mark_edge(inst, dest);
+                            return;
+                        }
+                        BranchDirection::NotTaken => {}
+                        BranchDirection::Either => self.mark_edge(inst, dest),
+                    }
+                }
+                BranchInfo::Table(jt) => {
+                    let arg = func.dfg.inst_args(inst)[0];
+                    match self.value(&func.dfg, arg) {
+                        LatticeValue::Top => return,
+                        LatticeValue::Const(idx) => {
+                            let idx = unsigned(idx, func.dfg.value_type(arg)) as usize;
+                            if let Some(dest) = func.jump_tables[jt].get_entry(idx) {
+                                self.mark_edge(inst, dest);
+                                return;
+                            }
+                        }
+                        LatticeValue::Bottom => {
+                            for (_, dest) in func.jump_tables[jt].entries() {
+                                self.mark_edge(inst, dest);
+                            }
+                        }
+                    }
+                }
+                BranchInfo::NotABranch => {
+                    if opcode.is_terminator() {
+                        return;
+                    }
+                    if func.dfg.inst_results(inst).len() == 1 {
+                        let lv = self.evaluate(&func.dfg, inst);
+                        self.lower_value(func.dfg.first_result(inst), lv);
+                    } else {
+                        for &res in func.dfg.inst_results(inst) {
+                            self.lower_value(res, LatticeValue::Bottom);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Determine the direction of the single destination branch `inst`.
+    fn branch_direction(&self, dfg: &DataFlowGraph, inst: Inst) -> BranchDirection {
+        let taken = match dfg[inst] {
+            InstructionData::Jump { .. } => return BranchDirection::Taken,
+            InstructionData::Branch { opcode, .. } => {
+                let is_brz = opcode == Opcode::Brz;
+                self.value(dfg, dfg.inst_fixed_args(inst)[0])
+                    .map(|c| ((c == 0) == is_brz) as i64)
+            }
+            InstructionData::BranchIcmp { cond, .. } => {
+                let args = dfg.inst_fixed_args(inst);
+                let ty = dfg.value_type(args[0]);
+                self.value(dfg, args[0])
+                    .map2(self.value(dfg, args[1]),
+                          |x, y| icmp(cond, x, y, ty) as i64)
+            }
+            _ => LatticeValue::Bottom,
+        };
+        match taken {
+            LatticeValue::Top => BranchDirection::Unknown,
+            LatticeValue::Const(0) => BranchDirection::NotTaken,
+            LatticeValue::Const(_) => BranchDirection::Taken,
+            LatticeValue::Bottom => BranchDirection::Either,
+        }
+    }
+
+    /// Compute the lattice value of the single result of `inst`.
+    fn evaluate(&self, dfg: &DataFlowGraph, inst: Inst) -> LatticeValue {
+        use self::LatticeValue::*;
+        let ty = dfg.value_type(dfg.first_result(inst));
+        // Constants are tracked as `i64`, so wider types are never constant.
+        if (!ty.is_int() && !ty.is_bool()) || ty.bits() > 64 {
+            return Bottom;
+        }
+        let lv = match dfg[inst] {
+            InstructionData::UnaryImm {
+                opcode: Opcode::Iconst,
+                imm,
+            } => Const(imm.into()),
+            InstructionData::Unary { opcode, arg } => {
+                let x = self.value(dfg, arg);
+                match opcode {
+                    Opcode::Copy => x,
+                    Opcode::Bnot if ty.is_int() => x.map(|x| !x),
+                    Opcode::Bint => x,
+                    _ => Bottom,
+                }
+            }
+            InstructionData::Binary { opcode, args } if ty.is_int() => {
+                let x = self.value(dfg, args[0]);
+                let y = self.value(dfg, args[1]);
+                match binary_op(opcode) {
+                    Some(op) => x.map2(y, op),
+                    None => Bottom,
+                }
+            }
+            InstructionData::BinaryImm { opcode, arg, imm } if ty.is_int() => {
+                let x = self.value(dfg, arg);
+                let imm = Const(imm.into());
+                match opcode {
+                    Opcode::IrsubImm => imm.map2(x, i64::wrapping_sub),
+                    _ => {
+                        match binary_op(opcode) {
+                            Some(op) => x.map2(imm, op),
+                            None => Bottom,
+                        }
+                    }
+                }
+            }
+            InstructionData::IntCompare { cond, args, .. } => {
+                let arg_ty = dfg.value_type(args[0]);
+                self.value(dfg, args[0])
+                    .map2(self.value(dfg, args[1]),
+                          |x, y| icmp(cond, x, y, arg_ty) as i64)
+            }
+            InstructionData::IntCompareImm { cond, arg, imm, .. } => {
+                let arg_ty = dfg.value_type(arg);
+                self.value(dfg, arg)
+                    .map2(Const(imm.into()), |x, y| icmp(cond, x, y, arg_ty) as i64)
+            }
+            _ => Bottom,
+        };
+        match lv {
+            Const(x) if ty.is_int() => Const(sign_extend(x, ty)),
+            lv => lv,
+        }
+    }
+
+    /// Replace instructions computing integer constants with `iconst`, and EBB arguments that are
+    /// always the same constant with an `iconst` at the top of the EBB.
+    fn rewrite_constants(&self, func: &mut Function) {
+        let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+        let entry = func.layout.entry_block();
+        for ebb in ebbs {
+            if !self.executable_ebbs[ebb] {
+                continue;
+            }
+            let mut pos = Cursor::new(&mut func.layout);
+
+            if Some(ebb) != entry {
+                pos.goto_top(ebb);
+                pos.next_inst();
+                let args = func.dfg.ebb_args(ebb).to_vec();
+                for arg in args {
+                    let ty = func.dfg.value_type(arg);
+                    let imm = match self.value(&func.dfg, arg) {
+                        LatticeValue::Const(imm) if ty.is_int() => imm,
+                        _ => continue,
+                    };
+                    // The argument stays on the EBB so the branches don't need to change.
+                    func.dfg.replace_ebb_arg(arg, ty);
+                    let c = func.dfg.ins(&mut pos).iconst(ty, imm);
+                    func.dfg.change_to_alias(arg, c);
+                }
+            }
+
+            pos.goto_top(ebb);
+            while let Some(inst) = pos.next_inst() {
+                if let Some(imm) = self.foldable_inst(&func.dfg, inst) {
+                    let ty = func.dfg.value_type(func.dfg.first_result(inst));
+                    func.dfg.replace(inst).iconst(ty, imm);
+                }
+            }
+        }
+    }
+
+    /// If `inst` computes an integer constant without side effects and isn't already an `iconst`,
+    /// return the constant.
+    fn foldable_inst(&self, dfg: &DataFlowGraph, inst: Inst) -> Option<i64> {
+        let opcode = dfg[inst].opcode();
+        if opcode == Opcode::Iconst || opcode.is_call() || opcode.is_branch() ||
+           opcode.is_terminator() || opcode.can_trap() || opcode.can_load() ||
+           opcode.can_store() || dfg.inst_results(inst).len() != 1 {
+            return None;
+        }
+        let res = dfg.first_result(inst);
+        match self.value(dfg, res) {
+            LatticeValue::Const(imm) if dfg.value_type(res).is_int() => Some(imm),
+            _ => None,
+        }
+    }
+
+    /// Turn branches with a known direction into jumps, or remove them.
+    fn fold_branches(&self, func: &mut Function) {
+        let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+        for ebb in ebbs {
+            if !self.executable_ebbs[ebb] {
+                continue;
+            }
+            let mut pos = Cursor::new(&mut func.layout);
+            pos.goto_top(ebb);
+            while let Some(inst) = pos.next_inst() {
+                let taken = match func.dfg[inst].analyze_branch(&func.dfg.value_lists) {
+                    BranchInfo::SingleDest(dest, args) => {
+                        match self.branch_direction(&func.dfg, inst) {
+                            BranchDirection::Taken => Some((dest, args.to_vec())),
+                            BranchDirection::NotTaken => None,
+                            _ => continue,
+                        }
+                    }
+                    BranchInfo::Table(jt) => {
+                        let arg = func.dfg.inst_args(inst)[0];
+                        match self.value(&func.dfg, arg) {
+                            LatticeValue::Const(idx) => {
+                                let idx = unsigned(idx, func.dfg.value_type(arg)) as usize;
+                                func.jump_tables[jt]
+                                    .get_entry(idx)
+                                    .map(|dest| (dest, Vec::new()))
+                            }
+                            _ => continue,
+                        }
+                    }
+                    BranchInfo::NotABranch => continue,
+                };
+
+                match taken {
+                    Some((dest, args)) => {
+                        if func.dfg[inst].opcode() != Opcode::Jump {
+                            func.dfg.replace(inst).jump(dest, &args);
+                        }
+                        // Everything after a branch that is always taken is dead.
+                        while let Some(_) = pos.next_inst() {
+                            pos.remove_inst_and_step_back();
+                        }
+                    }
+                    None => {
+                        pos.remove_inst_and_step_back();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Get the binary operation computed by `opcode`, or its `_imm` variant.
+fn binary_op(opcode: Opcode) -> Option<fn(i64, i64) -> i64> {
+    match opcode {
+        Opcode::Iadd | Opcode::IaddImm => Some(i64::wrapping_add),
+        Opcode::Isub => Some(i64::wrapping_sub),
+        Opcode::Imul | Opcode::ImulImm => Some(i64::wrapping_mul),
+        Opcode::Band | Opcode::BandImm => Some(|x, y| x & y),
+        Opcode::Bor | Opcode::BorImm => Some(|x, y| x | y),
+        Opcode::Bxor | Opcode::BxorImm => Some(|x, y| x ^ y),
+        _ => None,
+    }
+}
+
+/// Sign-extend `x` from the width of the integer type `ty`.
+fn sign_extend(x: i64, ty: Type) -> i64 {
+    if ty.bits() >= 64 {
+        x
+    } else {
+        let shift = 64 - ty.bits() as u32;
+        (x << shift) >> shift
+    }
+}
+
+/// Zero-extend `x` from the width of the integer type `ty`.
+fn unsigned(x: i64, ty: Type) -> u64 {
+    if ty.bits() >= 64 {
+        x as u64
+    } else {
+        let shift = 64 - ty.bits() as u32;
+        ((x as u64) << shift) >> shift
+    }
+}
+
+/// Compare two integer constants of type `ty`.
+fn icmp(cond: IntCC, x: i64, y: i64, ty: Type) -> bool {
+    let (sx, sy) = (sign_extend(x, ty), sign_extend(y, ty));
+    let (ux, uy) = (unsigned(x, ty), unsigned(y, ty));
+    match cond {
+        IntCC::Equal => ux == uy,
+        IntCC::NotEqual => ux != uy,
+        IntCC::SignedLessThan => sx < sy,
+        IntCC::SignedGreaterThanOrEqual => sx >= sy,
+        IntCC::SignedGreaterThan => sx > sy,
+        IntCC::SignedLessThanOrEqual => sx <= sy,
+        IntCC::UnsignedLessThan => ux < uy,
+        IntCC::UnsignedGreaterThanOrEqual => ux >= uy,
+        IntCC::UnsignedGreaterThan => ux > uy,
+        IntCC::UnsignedLessThanOrEqual => ux <= uy,
+    }
+}
@@ -276,9 +276,13 @@ mod tests {
                     is_compressed = false\n\
                     enable_float = true\n\
                     enable_simd = true\n\
-                    enable_atomics = true\n");
+                    enable_atomics = true\n\
+                    loop_unroll_factor = 1\n\
+                    if_conversion_cost = 4\n");
         assert_eq!(f.opt_level(), super::OptLevel::Default);
         assert_eq!(f.enable_simd(), true);
+        assert_eq!(f.loop_unroll_factor(), 1);
+        assert_eq!(f.if_conversion_cost(), 4);
     }
 
     #[test]
@@ -301,9 +305,12 @@ mod tests {
         assert_eq!(b.set("opt_level", "true"), Err(BadValue));
         assert_eq!(b.set("opt_level", "best"), Ok(()));
         assert_eq!(b.set("enable_simd", "0"), Ok(()));
+        assert_eq!(b.set("loop_unroll_factor", "true"), Err(BadValue));
+        assert_eq!(b.set("loop_unroll_factor", "4"), Ok(()));
 
         let f = Flags::new(&b);
         assert_eq!(f.enable_simd(), false);
         assert_eq!(f.opt_level(), super::OptLevel::Best);
+        assert_eq!(f.loop_unroll_factor(), 4);
     }
 }
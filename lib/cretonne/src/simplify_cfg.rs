@@ -0,0 +1,311 @@
+//! A control flow graph simplification pass.
+//!
+//! Frontends tend to generate long chains of EBBs connected by unconditional jumps, as well as
+//! trivial EBBs that do nothing but forward their arguments to another EBB. This pass cleans up
+//! the control flow graph by applying the following transformations until nothing changes:
+//!
+//! - Fold `brz` and `brnz` branches on constant values into jumps, or delete them when they are
+//!   never taken.
+//! - Thread jumps through empty forwarding EBBs, rewriting the EBB arguments passed by the
+//!   predecessors.
+//! - Merge an EBB into its single predecessor when the predecessor ends in a jump to it.
+//! - Delete EBBs that are unreachable from the entry block.
+//!
+//! The control flow graph is updated incrementally as the function is modified, and the dominator
+//! tree is recomputed at the end.
+
+use dominator_tree::DominatorTree;
+use entity_map::EntityMap;
+use flowgraph::{ControlFlowGraph, BasicBlock};
+use ir::{Function, DataFlowGraph, Cursor, Ebb, Inst, Value, ValueDef, InstructionData, Opcode,
+         InstBuilder, ValueList};
+use ir::instructions::BranchInfo;
+
+/// Simplify the control flow graph of `func`.
+///
+/// Both `cfg` and `domtree` are kept up to date.
+pub fn do_simplify_cfg(func: &mut Function,
+                       cfg: &mut ControlFlowGraph,
+                       domtree: &mut DominatorTree) {
+    let mut changed = true;
+    while changed {
+        changed = remove_unreachable_ebbs(func, cfg);
+        changed |= fold_constant_branches(func, cfg);
+        changed |= thread_jumps(func, cfg);
+        changed |= merge_ebbs(func, cfg);
+    }
+    domtree.compute(func, cfg);
+}
+
+/// Delete all the EBBs that can't be reached from the entry block.
+///
+/// The control flow graph must be up to date, and it is kept up to date.
+///
+/// Returns `true` if any EBBs were removed.
+pub fn remove_unreachable_ebbs(func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
+    let mut reachable = EntityMap::<Ebb, bool>::with_capacity(func.dfg.num_ebbs());
+    let mut stack = Vec::new();
+    if let Some(entry) = func.layout.entry_block() {
+        reachable[entry] = true;
+        stack.push(entry);
+    }
+    while let Some(ebb) = stack.pop() {
+        for &succ in cfg.get_successors(ebb) {
+            if !reachable[succ] {
+                reachable[succ] = true;
+                stack.push(succ);
+            }
+        }
+    }
+
+    let mut changed = false;
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    for ebb in ebbs {
+        if reachable[ebb] {
+            continue;
+        }
+        while let Some(inst) = func.layout.first_inst(ebb) {
+            func.layout.remove_inst(inst);
+        }
+        // With its instructions gone, this removes all the outgoing edges of `ebb`.
+        cfg.recompute_ebb(func, ebb);
+        func.layout.remove_ebb(ebb);
+        changed = true;
+    }
+    changed
+}
+
+/// If `value` is defined by an `iconst` instruction, return `Some(true)` when it is zero.
+fn constant_is_zero(dfg: &DataFlowGraph, value: Value) -> Option<bool> {
+    if let ValueDef::Res(inst, 0) = dfg.value_def(dfg.resolve_aliases(value)) {
+        if let InstructionData::UnaryImm {
+                   opcode: Opcode::Iconst,
+                   imm,
+               } = dfg[inst] {
+            let imm: i64 = imm.into();
+            return Some(imm == 0);
+        }
+    }
+    None
+}
+
+/// Fold `brz` and `brnz` instructions whose controlling value is a known constant.
+///
+/// A branch that is always taken is turned into a `jump`, and the dead instructions following it
+/// are deleted. A branch that is never taken is simply removed.
+///
+/// Returns `true` if any branches were folded.
+fn fold_constant_branches(func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
+    let mut changed = false;
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    for ebb in ebbs {
+        let mut folded = false;
+        {
+            let mut pos = Cursor::new(&mut func.layout);
+            pos.goto_top(ebb);
+            while let Some(inst) = pos.next_inst() {
+                let opcode = func.dfg[inst].opcode();
+                if opcode != Opcode::Brz && opcode != Opcode::Brnz {
+                    continue;
+                }
+                let is_zero = match constant_is_zero(&func.dfg, func.dfg.inst_fixed_args(inst)[0]) {
+                    Some(z) => z,
+                    None => continue,
+                };
+                folded = true;
+
+                if is_zero == (opcode == Opcode::Brz) {
+                    // The branch is always taken. Everything after it is dead.
+                    let dest = func.dfg[inst].branch_destination().unwrap();
+                    let args = func.dfg.inst_variable_args(inst).to_vec();
+                    func.dfg.replace(inst).jump(dest, &args);
+                    while let Some(_) = pos.next_inst() {
+                        pos.remove_inst_and_step_back();
+                    }
+                } else {
+                    // The branch is never taken.
+                    pos.remove_inst_and_step_back();
+                }
+            }
+        }
+        if folded {
+            cfg.recompute_ebb(func, ebb);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// If `ebb` contains nothing but a `jump`, return the jump instruction and its destination.
+fn forwarding_jump(func: &Function, ebb: Ebb) -> Option<(Inst, Ebb)> {
+    let inst = match func.layout.first_inst(ebb) {
+        Some(inst) => inst,
+        None => return None,
+    };
+    if func.layout.last_inst(ebb) != Some(inst) {
+        return None;
+    }
+    match func.dfg[inst] {
+        InstructionData::Jump {
+            opcode: Opcode::Jump,
+            destination,
+            ..
+        } => Some((inst, destination)),
+        _ => None,
+    }
+}
+
+/// Redirect branches to empty forwarding EBBs so they go directly to the final destination.
+///
+/// The forwarding EBBs are left without predecessors. They will be deleted as unreachable.
+///
+/// Returns `true` if any branches were redirected.
+fn thread_jumps(func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
+    let mut changed = false;
+    let entry = func.layout.entry_block();
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    for ebb in ebbs {
+        if Some(ebb) == entry {
+            continue;
+        }
+        let (jump, dest) = match forwarding_jump(func, ebb) {
+            Some(fwd) => fwd,
+            None => continue,
+        };
+        if dest == ebb {
+            // An empty infinite loop.
+            continue;
+        }
+
+        let params = func.dfg.ebb_args(ebb).to_vec();
+        let args = func.dfg.inst_variable_args(jump).to_vec();
+
+        // The CFG can list the same branch more than once when a jump table has multiple entries
+        // for `ebb`.
+        let mut preds: Vec<BasicBlock> = Vec::new();
+        for &pred in cfg.get_predecessors(ebb) {
+            if !preds.contains(&pred) {
+                preds.push(pred);
+            }
+        }
+
+        for (pred_ebb, branch) in preds {
+            if redirect_branch(func, branch, ebb, dest, &params, &args) {
+                cfg.recompute_ebb(func, pred_ebb);
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// Redirect `branch` from `old_dest` to `new_dest`.
+///
+/// The forwarding EBB `old_dest` has the arguments `params`, and it passes `args` to `new_dest`.
+/// Rewrite the arguments passed by `branch` so the effect is the same as going through
+/// `old_dest`.
+///
+/// Returns `false` if `branch` can't be redirected.
+fn redirect_branch(func: &mut Function,
+                   branch: Inst,
+                   old_dest: Ebb,
+                   new_dest: Ebb,
+                   params: &[Value],
+                   args: &[Value])
+                   -> bool {
+    if let BranchInfo::Table(jt) = func.dfg[branch].analyze_branch(&func.dfg.value_lists) {
+        // Jump tables can't pass EBB arguments.
+        if !args.is_empty() {
+            return false;
+        }
+        for entry in func.jump_tables[jt].as_mut_slice() {
+            if entry.expand() == Some(old_dest) {
+                *entry = new_dest.into();
+            }
+        }
+        return true;
+    }
+
+    let num_fixed = func.dfg[branch]
+        .opcode()
+        .constraints()
+        .fixed_value_arguments();
+    let mut old_list = func.dfg[branch]
+        .take_value_list()
+        .expect("Branches must have value lists.");
+    let mut new_list = ValueList::default();
+    {
+        let old_args = old_list.as_slice(&func.dfg.value_lists).to_vec();
+        new_list.extend(old_args[0..num_fixed].iter().cloned(),
+                        &mut func.dfg.value_lists);
+        for &arg in args {
+            // Arguments of the forwarding EBB are replaced by the values passed by `branch`.
+            // Other values are defined outside `old_dest`, and so they dominate `branch` too.
+            let resolved = func.dfg.resolve_aliases(arg);
+            let arg = match params.iter().position(|&p| p == resolved) {
+                Some(num) => old_args[num_fixed + num],
+                None => arg,
+            };
+            new_list.push(arg, &mut func.dfg.value_lists);
+        }
+        old_list.clear(&mut func.dfg.value_lists);
+    }
+    func.dfg[branch].put_value_list(new_list);
+    *func.dfg[branch]
+         .branch_destination_mut()
+         .expect("Expected a single destination branch") = new_dest;
+    true
+}
+
+/// Merge EBBs into their single predecessor when it ends in an unconditional jump.
+///
+/// The EBB arguments of the merged EBB become aliases of the values passed by the jump.
+///
+/// Returns `true` if any EBBs were merged.
+fn merge_ebbs(func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
+    let mut changed = false;
+    let entry = func.layout.entry_block();
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    for ebb in ebbs {
+        if Some(ebb) == entry || !func.layout.is_ebb_inserted(ebb) {
+            continue;
+        }
+        let (pred, jump) = {
+            let preds = cfg.get_predecessors(ebb);
+            if preds.len() != 1 {
+                continue;
+            }
+            preds[0]
+        };
+        if pred == ebb || func.dfg[jump].opcode() != Opcode::Jump {
+            continue;
+        }
+        debug_assert_eq!(func.layout.last_inst(pred), Some(jump));
+
+        let params = func.dfg.ebb_args(ebb).to_vec();
+        let args = func.dfg.inst_variable_args(jump).to_vec();
+        // In unreachable code, an EBB could be passing its own arguments back to itself.
+        if args.iter()
+               .any(|&arg| params.contains(&func.dfg.resolve_aliases(arg))) {
+            continue;
+        }
+
+        func.dfg.detach_ebb_args(ebb);
+        for (&param, &arg) in params.iter().zip(&args) {
+            func.dfg.change_to_alias(param, arg);
+        }
+
+        func.layout.remove_inst(jump);
+        while let Some(inst) = func.layout.first_inst(ebb) {
+            func.layout.remove_inst(inst);
+            func.layout.append_inst(inst, pred);
+        }
+        func.layout.remove_ebb(ebb);
+
+        // The outgoing edges of `ebb` now belong to `pred`.
+        cfg.recompute_ebb(func, pred);
+        cfg.recompute_ebb(func, ebb);
+        changed = true;
+    }
+    changed
+}
@@ -0,0 +1,324 @@
+//! Statistics about compiled functions.
+//!
+//! Comparing the code generated by different versions of Cretonne requires numbers that can be
+//! collected in bulk and processed by other tools. `Context::compile_with_stats()` compiles a
+//! function like `Context::compile()` while counting the instructions between the passes, and a
+//! `Stats` report collects the statistics for many functions and writes them as JSON or CSV.
+
+use binemit::CodeOffset;
+use ir::{Function, FunctionName, Opcode};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+/// The number of instructions with each opcode in a function.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OpcodeCounts {
+    counts: HashMap<Opcode, usize>,
+}
+
+impl OpcodeCounts {
+    /// Count the instructions in the layout of `func`.
+    pub fn of(func: &Function) -> OpcodeCounts {
+        let mut counts = OpcodeCounts::default();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                *counts.counts.entry(func.dfg[inst].opcode()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Get the number of instructions with `opcode`.
+    pub fn get(&self, opcode: Opcode) -> usize {
+        self.counts.get(&opcode).cloned().unwrap_or(0)
+    }
+
+    /// Get the total number of instructions.
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// Add the counts in `other` to these counts.
+    pub fn add(&mut self, other: &OpcodeCounts) {
+        for (&opcode, &n) in &other.counts {
+            *self.counts.entry(opcode).or_insert(0) += n;
+        }
+    }
+
+    /// Get the opcodes and their counts sorted by opcode name.
+    pub fn sorted(&self) -> Vec<(Opcode, usize)> {
+        let mut v: Vec<_> = self.counts.iter().map(|(&op, &n)| (op, n)).collect();
+        v.sort_by_key(|&(op, _)| op.to_string());
+        v
+    }
+}
+
+/// Statistics about the code generated for one or more functions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodeStats {
+    /// Instructions by opcode in the input to the legalizer.
+    pub before_legalization: OpcodeCounts,
+
+    /// Instructions by opcode in the legalized function.
+    pub after_legalization: OpcodeCounts,
+
+    /// Number of `regmove` instructions inserted by the register allocator.
+    pub regmoves: usize,
+
+    /// Number of `spill` instructions inserted by the register allocator.
+    pub spills: usize,
+
+    /// Number of `fill` instructions inserted by the register allocator.
+    pub fills: usize,
+
+    /// Size of the stack frame in bytes.
+    ///
+    /// This is the total size of the function's stack slots, including the spill slots created by
+    /// the register allocator.
+    pub frame_size: u32,
+
+    /// Size of the final machine code in bytes, including jump tables and constants.
+    pub code_size: CodeOffset,
+}
+
+impl CodeStats {
+    /// Add the statistics in `other` to these statistics.
+    pub fn add(&mut self, other: &CodeStats) {
+        self.before_legalization.add(&other.before_legalization);
+        self.after_legalization.add(&other.after_legalization);
+        self.regmoves += other.regmoves;
+        self.spills += other.spills;
+        self.fills += other.fills;
+        self.frame_size += other.frame_size;
+        self.code_size += other.code_size;
+    }
+
+    /// Write the statistics as the members of a JSON object.
+    fn write_json(&self, w: &mut Write) -> fmt::Result {
+        write!(w,
+               "\"code_size\": {}, \"frame_size\": {}, \"regmoves\": {}, \"spills\": {}, \
+                \"fills\": {}, ",
+               self.code_size,
+               self.frame_size,
+               self.regmoves,
+               self.spills,
+               self.fills)?;
+        write!(w, "\"before_legalization\": ")?;
+        write_counts_json(w, &self.before_legalization)?;
+        write!(w, ", \"after_legalization\": ")?;
+        write_counts_json(w, &self.after_legalization)
+    }
+
+    /// Write the statistics as CSV rows starting with `isa` and `function`.
+    fn write_csv(&self, w: &mut Write, isa: &str, function: &str) -> fmt::Result {
+        for &(stat, value) in &[("code_size", self.code_size as usize),
+                                ("frame_size", self.frame_size as usize),
+                                ("regmoves", self.regmoves),
+                                ("spills", self.spills),
+                                ("fills", self.fills),
+                                ("insts_before_legalization", self.before_legalization.total()),
+                                ("insts_after_legalization", self.after_legalization.total())] {
+            writeln!(w, "{},{},{},{}", isa, function, stat, value)?;
+        }
+        for (opcode, n) in self.before_legalization.sorted() {
+            writeln!(w, "{},{},before_legalization.{},{}", isa, function, opcode, n)?;
+        }
+        for (opcode, n) in self.after_legalization.sorted() {
+            writeln!(w, "{},{},after_legalization.{},{}", isa, function, opcode, n)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write opcode counts as a JSON object.
+fn write_counts_json(w: &mut Write, counts: &OpcodeCounts) -> fmt::Result {
+    write!(w, "{{")?;
+    for (i, (opcode, n)) in counts.sorted().into_iter().enumerate() {
+        if i != 0 {
+            write!(w, ", ")?;
+        }
+        write!(w, "\"{}\": {}", opcode, n)?;
+    }
+    write!(w, "}}")
+}
+
+/// Statistics about a function compiled for an ISA.
+#[derive(Clone, Debug)]
+pub struct FunctionStats {
+    /// The name of the function.
+    pub name: FunctionName,
+
+    /// The name of the ISA the function was compiled for.
+    pub isa: &'static str,
+
+    /// The statistics for the function.
+    pub stats: CodeStats,
+}
+
+/// The statistics for all the functions compiled for an ISA.
+#[derive(Clone, Debug)]
+pub struct IsaTotals {
+    /// The name of the ISA.
+    pub isa: &'static str,
+
+    /// The number of functions.
+    pub functions: usize,
+
+    /// The sum of the function statistics.
+    pub stats: CodeStats,
+}
+
+/// A report of the statistics for a set of compiled functions.
+///
+/// A function can appear more than once if it was compiled for multiple ISAs.
+#[derive(Clone, Debug, Default)]
+pub struct Stats {
+    /// The per-function statistics in the order they were added.
+    pub functions: Vec<FunctionStats>,
+}
+
+impl Stats {
+    /// Create an empty report.
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    /// Add the statistics for a function.
+    pub fn add(&mut self, stats: FunctionStats) {
+        self.functions.push(stats);
+    }
+
+    /// Get the aggregate statistics for each ISA, in the order the ISAs first appear.
+    pub fn totals(&self) -> Vec<IsaTotals> {
+        let mut totals: Vec<IsaTotals> = Vec::new();
+        for f in &self.functions {
+            let i = match totals.iter().position(|t| t.isa == f.isa) {
+                Some(i) => i,
+                None => {
+                    totals.push(IsaTotals {
+                                    isa: f.isa,
+                                    functions: 0,
+                                    stats: CodeStats::default(),
+                                });
+                    totals.len() - 1
+                }
+            };
+            totals[i].functions += 1;
+            totals[i].stats.add(&f.stats);
+        }
+        totals
+    }
+
+    /// Write the report as a JSON object with `functions` and `totals` arrays.
+    pub fn write_json(&self, w: &mut Write) -> fmt::Result {
+        writeln!(w, "{{")?;
+        writeln!(w, "  \"functions\": [")?;
+        for (i, f) in self.functions.iter().enumerate() {
+            write!(w, "    {{\"isa\": \"{}\", \"function\": \"{}\", ", f.isa, f.name)?;
+            f.stats.write_json(w)?;
+            writeln!(w,
+                     "}}{}",
+                     if i + 1 < self.functions.len() { "," } else { "" })?;
+        }
+        writeln!(w, "  ],")?;
+        writeln!(w, "  \"totals\": [")?;
+        let totals = self.totals();
+        for (i, t) in totals.iter().enumerate() {
+            write!(w, "    {{\"isa\": \"{}\", \"functions\": {}, ", t.isa, t.functions)?;
+            t.stats.write_json(w)?;
+            writeln!(w, "}}{}", if i + 1 < totals.len() { "," } else { "" })?;
+        }
+        writeln!(w, "  ]")?;
+        writeln!(w, "}}")
+    }
+
+    /// Write the report as CSV with the columns `isa,function,stat,value`.
+    ///
+    /// The per-ISA totals use `*` as the function name. Opcode counts have the stat names
+    /// `before_legalization.<opcode>` and `after_legalization.<opcode>`.
+    pub fn write_csv(&self, w: &mut Write) -> fmt::Result {
+        writeln!(w, "isa,function,stat,value")?;
+        for f in &self.functions {
+            f.stats.write_csv(w, f.isa, &f.name.to_string())?;
+        }
+        for t in self.totals() {
+            writeln!(w, "{},*,functions,{}", t.isa, t.functions)?;
+            t.stats.write_csv(w, t.isa, "*")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use context::Context;
+    use ir::{ArgumentType, Cursor, Function, FunctionName, InstBuilder, Signature, types};
+    use isa;
+    use settings::{self, Configurable};
+
+    fn function() -> Function {
+        let mut sig = Signature::new();
+        sig.argument_types.push(ArgumentType::new(types::I32));
+        sig.return_types.push(ArgumentType::new(types::I32));
+        let mut func = Function::with_name_signature(FunctionName::new("f"), sig);
+        let ebb = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_arg(ebb, types::I32);
+        {
+            let dfg = &mut func.dfg;
+            let cur = &mut Cursor::new(&mut func.layout);
+            cur.insert_ebb(ebb);
+            let v = dfg.ins(cur).iadd_imm(arg, 100000);
+            dfg.ins(cur).return_(&[v]);
+        }
+        func
+    }
+
+    #[test]
+    fn compile() {
+        let mut builder = settings::builder();
+        builder.set_bool("is_64bit", false).unwrap();
+        let isa = isa::lookup("riscv")
+            .unwrap()
+            .finish(settings::Flags::new(&builder));
+
+        let mut ctx = Context::new();
+        ctx.func = function();
+        let fstats = ctx.compile_with_stats(&*isa).unwrap();
+        assert_eq!(fstats.isa, "riscv");
+        let stats = fstats.stats.clone();
+        assert_eq!(stats.before_legalization.total(), 2);
+        assert_eq!(stats.before_legalization.get(Opcode::IaddImm), 1);
+        // The immediate doesn't fit in `addi`, so it is loaded from the constant pool.
+        assert_eq!(stats.after_legalization.get(Opcode::IaddImm), 0);
+        assert_eq!(stats.after_legalization.get(Opcode::ConstAddr), 1);
+        assert_eq!(stats.after_legalization.get(Opcode::Load), 1);
+        assert_eq!(stats.after_legalization.get(Opcode::Iadd), 1);
+        assert_eq!(stats.after_legalization.total(), 4);
+        // The sum is moved into the return value register.
+        assert_eq!(stats.regmoves, 1);
+        assert_eq!(stats.spills, 0);
+        assert_eq!(stats.fills, 0);
+        assert_eq!(stats.frame_size, 0);
+        // The `const_addr` is `auipc` + `addi`, and the 4-byte constant follows the code.
+        assert_eq!(stats.code_size, 4 * 6 + 4);
+
+        let mut report = Stats::new();
+        report.add(fstats.clone());
+        report.add(fstats);
+        let totals = report.totals();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].functions, 2);
+        assert_eq!(totals[0].stats.code_size, 2 * stats.code_size);
+
+        let mut csv = String::new();
+        report.write_csv(&mut csv).unwrap();
+        assert!(csv.starts_with("isa,function,stat,value\nriscv,%f,code_size,"));
+        assert!(csv.contains("\nriscv,*,before_legalization.iadd_imm,2\n"));
+
+        let mut json = String::new();
+        report.write_json(&mut json).unwrap();
+        assert!(json.contains("{\"isa\": \"riscv\", \"function\": \"%f\", \"code_size\": "));
+        assert!(json.contains("\"before_legalization\": {\"iadd_imm\": 1, \"return\": 1}"));
+    }
+}
@@ -0,0 +1,133 @@
+//! A loop strength reduction pass.
+//!
+//! Multiplications of a basic induction variable by a constant are replaced with a new induction
+//! variable that is incremented by the product of the step and the constant on every iteration.
+
+use dominator_tree::DominatorTree;
+use flowgraph::ControlFlowGraph;
+use induction::{InductionVar, basic_induction_vars, iconst_value};
+use ir::{Function, Ebb, Inst, Value, Cursor, InstructionData, Opcode, InstBuilder};
+use loop_analysis::{Loop, LoopAnalysis};
+use std::collections::HashMap;
+
+/// Performs strength reduction on all the loops in `func`.
+///
+/// New instructions are only inserted before existing branches, so the control flow graph and the
+/// dominator tree remain valid.
+pub fn do_strength_reduction(func: &mut Function,
+                             cfg: &ControlFlowGraph,
+                             domtree: &DominatorTree,
+                             loop_analysis: &mut LoopAnalysis) {
+    loop_analysis.compute(func, cfg, domtree);
+    for lp in loop_analysis.loops() {
+        let ivs = basic_induction_vars(func, cfg, loop_analysis, lp);
+        if !ivs.is_empty() {
+            reduce_loop(func, cfg, loop_analysis, lp, &ivs);
+        }
+    }
+}
+
+// Replace multiplications of the induction variables `ivs` by constants in `lp`.
+fn reduce_loop(func: &mut Function,
+               cfg: &ControlFlowGraph,
+               loop_analysis: &LoopAnalysis,
+               lp: Loop,
+               ivs: &[InductionVar]) {
+    let ebbs: Vec<Ebb> = func.layout
+        .ebbs()
+        .filter(|&ebb| loop_analysis.is_in_loop(ebb, lp))
+        .collect();
+
+    let mut candidates = Vec::new();
+    for ebb in ebbs {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let Some((value, factor)) = multiplication_by_constant(func, inst) {
+                if let Some(iv) = ivs.iter().find(|iv| iv.value == value) {
+                    candidates.push((inst, *iv, factor));
+                }
+            }
+        }
+    }
+
+    // Multiplications of the same induction variable by the same factor share the new variable.
+    let mut derived_ivs: HashMap<(Value, i64), Value> = HashMap::new();
+    for (inst, iv, factor) in candidates {
+        let derived = match derived_ivs.get(&(iv.value, factor)).cloned() {
+            Some(derived) => derived,
+            None => {
+                let derived = make_derived_iv(func, cfg, loop_analysis, lp, iv, factor);
+                derived_ivs.insert((iv.value, factor), derived);
+                derived
+            }
+        };
+        let result = func.dfg.first_result(inst);
+        func.dfg.clear_results(inst);
+        func.layout.remove_inst(inst);
+        func.dfg.change_to_alias(result, derived);
+    }
+}
+
+// If `inst` multiplies a value by a constant, return the value and the constant.
+fn multiplication_by_constant(func: &Function, inst: Inst) -> Option<(Value, i64)> {
+    let dfg = &func.dfg;
+    match dfg[inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::ImulImm,
+            arg,
+            imm,
+        } => Some((dfg.resolve_aliases(arg), imm.into())),
+        InstructionData::Binary {
+            opcode: Opcode::Imul,
+            args,
+        } => {
+            if let Some(factor) = iconst_value(dfg, args[1]) {
+                Some((dfg.resolve_aliases(args[0]), factor))
+            } else if let Some(factor) = iconst_value(dfg, args[0]) {
+                Some((dfg.resolve_aliases(args[1]), factor))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// Add a new argument to the header of `lp` holding `iv * factor`.
+//
+// The entry edges compute the initial value with an `imul_imm`, and the back edges increment it
+// with an `iadd_imm`.
+fn make_derived_iv(func: &mut Function,
+                   cfg: &ControlFlowGraph,
+                   loop_analysis: &LoopAnalysis,
+                   lp: Loop,
+                   iv: InductionVar,
+                   factor: i64)
+                   -> Value {
+    let header = loop_analysis.loop_header(lp);
+    let num = func.dfg
+        .ebb_args(header)
+        .iter()
+        .position(|&arg| arg == iv.value)
+        .expect("Induction variable must be a header argument");
+    let derived = {
+        let ty = func.dfg.value_type(iv.value);
+        func.dfg.append_ebb_arg(header, ty)
+    };
+
+    for &(pred, branch) in cfg.get_predecessors(header) {
+        let new_arg = {
+            let mut pos = Cursor::new(&mut func.layout);
+            pos.goto_inst(branch);
+            if loop_analysis.is_in_loop(pred, lp) {
+                func.dfg
+                    .ins(&mut pos)
+                    .iadd_imm(derived, iv.step.wrapping_mul(factor))
+            } else {
+                let init = func.dfg.inst_variable_args(branch)[num];
+                func.dfg.ins(&mut pos).imul_imm(init, factor)
+            }
+        };
+        func.dfg.append_inst_arg(branch, new_arg);
+    }
+    derived
+}
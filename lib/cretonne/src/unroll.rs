@@ -0,0 +1,151 @@
+//! A loop unrolling pass.
+//!
+//! Small counted loops consisting of a single EBB are unrolled by appending copies of the loop
+//! body to the EBB. Each copy keeps its branches out of the loop, so no remainder loop is needed.
+//! The arguments passed by the jump back to the header in one copy become the header arguments of
+//! the next copy.
+
+use dominator_tree::DominatorTree;
+use flowgraph::ControlFlowGraph;
+use induction::basic_induction_vars;
+use ir::{Function, Ebb, Inst, Value, Opcode, ValueList};
+use ir::sourceloc;
+use loop_analysis::{Loop, LoopAnalysis};
+use std::collections::{HashMap, HashSet};
+
+/// Don't unroll loops that would end up with more instructions than this.
+const MAX_UNROLLED_INSTS: usize = 64;
+
+/// Unroll the small counted loops in `func` so they contain `factor` copies of the loop body.
+///
+/// A `factor` of 0 or 1 leaves the function unchanged.
+pub fn do_unroll_loops(func: &mut Function,
+                       cfg: &mut ControlFlowGraph,
+                       domtree: &mut DominatorTree,
+                       loop_analysis: &mut LoopAnalysis,
+                       factor: u8) {
+    if factor <= 1 {
+        return;
+    }
+    loop_analysis.compute(func, cfg, domtree);
+    let mut changed = false;
+    for lp in loop_analysis.loops() {
+        let header = loop_analysis.loop_header(lp);
+        if is_unrollable(func, cfg, loop_analysis, lp, factor as usize) {
+            unroll_ebb(func, header, factor as usize);
+            cfg.recompute_ebb(func, header);
+            changed = true;
+        }
+    }
+    if changed {
+        domtree.compute(func, cfg);
+    }
+}
+
+// Check if the loop `lp` is a small counted loop that can be unrolled.
+fn is_unrollable(func: &Function,
+                 cfg: &ControlFlowGraph,
+                 loop_analysis: &LoopAnalysis,
+                 lp: Loop,
+                 factor: usize)
+                 -> bool {
+    let header = loop_analysis.loop_header(lp);
+
+    // The loop must be a single EBB.
+    if func.layout
+           .ebbs()
+           .any(|ebb| ebb != header && loop_analysis.is_in_loop(ebb, lp)) {
+        return false;
+    }
+
+    // It must end with the jump back to the header.
+    let last_inst = match func.layout.last_inst(header) {
+        Some(inst) => inst,
+        None => return false,
+    };
+    if func.dfg[last_inst].opcode() != Opcode::Jump ||
+       func.dfg[last_inst].branch_destination() != Some(header) {
+        return false;
+    }
+
+    let num_insts = func.layout.ebb_insts(header).count();
+    if num_insts * factor > MAX_UNROLLED_INSTS {
+        return false;
+    }
+
+    // Only counted loops are unrolled.
+    if basic_induction_vars(func, cfg, loop_analysis, lp).is_empty() {
+        return false;
+    }
+
+    // Values defined in the loop can only leave it as EBB arguments. Any other use outside the
+    // loop would see the value computed by the first copy of the body instead of the last.
+    let mut loop_values: HashSet<Value> = func.dfg.ebb_args(header).iter().cloned().collect();
+    for inst in func.layout.ebb_insts(header) {
+        loop_values.extend(func.dfg.inst_results(inst));
+    }
+    for ebb in func.layout.ebbs().filter(|&ebb| ebb != header) {
+        for inst in func.layout.ebb_insts(ebb) {
+            if func.dfg
+                   .inst_args(inst)
+                   .iter()
+                   .any(|&arg| loop_values.contains(&func.dfg.resolve_aliases(arg))) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Append `factor - 1` copies of the loop body in `ebb` to itself.
+fn unroll_ebb(func: &mut Function, ebb: Ebb, factor: usize) {
+    let body: Vec<Inst> = func.layout.ebb_insts(ebb).collect();
+    let params = func.dfg.ebb_args(ebb).to_vec();
+    for _ in 1..factor {
+        // Replace the jump back to the header with the next copy of the body.
+        let jump = func.layout.last_inst(ebb).unwrap();
+        let mut map: HashMap<Value, Value> = params
+            .iter()
+            .cloned()
+            .zip(func.dfg.inst_variable_args(jump).iter().cloned())
+            .collect();
+        func.layout.remove_inst(jump);
+        for &inst in &body {
+            let copy = copy_inst(func, inst, &mut map);
+            func.layout.append_inst(copy, ebb);
+        }
+    }
+}
+
+// Create a copy of `inst`, replacing its arguments according to `map`.
+//
+// The results of `inst` are mapped to the results of the copy.
+fn copy_inst(func: &mut Function, inst: Inst, map: &mut HashMap<Value, Value>) -> Inst {
+    let ctrl_typevar = func.dfg.ctrl_typevar(inst);
+    let mut data = func.dfg[inst].clone();
+    // The cloned instruction data still refers to the value list of `inst`.
+    if let Some(list) = data.take_value_list() {
+        let mut new_list = ValueList::default();
+        let args = list.as_slice(&func.dfg.value_lists).to_vec();
+        new_list.extend(args, &mut func.dfg.value_lists);
+        data.put_value_list(new_list);
+    }
+    let first_new = func.dfg.num_insts();
+    let copy = func.dfg.make_inst(data);
+    func.dfg.make_inst_results(copy, ctrl_typevar);
+    sourceloc::inherit(&mut func.srclocs, &func.dfg, inst, first_new);
+
+    func.dfg.resolve_aliases_in_arguments(copy);
+    for arg in func.dfg.inst_args_mut(copy) {
+        if let Some(&new_arg) = map.get(arg) {
+            *arg = new_arg;
+        }
+    }
+    for (&old, &new) in func.dfg
+            .inst_results(inst)
+            .iter()
+            .zip(func.dfg.inst_results(copy)) {
+        map.insert(old, new);
+    }
+    copy
+}
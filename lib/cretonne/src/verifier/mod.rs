@@ -15,6 +15,11 @@
 //!    - All result values must be created for multi-valued instructions.
 //!    - All referenced entities must exist. (Values, EBBs, stack slots, ...)
 //!
+//!   Global variables
+//!
+//!    - Detect cycles in deref(base) declarations.
+//!    - Functions using globals or heaps in the VM context must have a `vmctx` argument.
+//!
 //!   SSA form
 //!
 //!    - Values must be defined by an instruction that exists and that is inserted in
@@ -39,6 +44,7 @@
 //!      function.
 //!    - All return instructions must have return value operands matching the current
 //!      function signature.
+//!    - The offset operand of a `heap_addr` must have the index type of the heap.
 //!
 //! TODO:
 //!   Ad hoc checking
@@ -55,9 +61,10 @@
 use dominator_tree::DominatorTree;
 use flowgraph::ControlFlowGraph;
 use ir::entities::AnyEntity;
-use ir::instructions::{InstructionFormat, BranchInfo, ResolvedConstraint, CallInfo};
+use ir::instructions::{InstructionData, InstructionFormat, BranchInfo, ResolvedConstraint,
+                       CallInfo};
 use ir::{types, Function, ValueDef, Ebb, Inst, SigRef, FuncRef, ValueList, JumpTable, StackSlot,
-         Value, Type};
+         GlobalVar, GlobalVarData, Heap, Constant, Value, Type, ArgumentPurpose};
 use isa::TargetIsa;
 use std::error as std_error;
 use std::fmt::{self, Display, Formatter};
@@ -251,7 +258,8 @@ impl<'a> Verifier<'a> {
                 self.verify_ebb(inst, destination)?;
                 self.verify_value_list(inst, args)?;
             }
-            &BranchTable { table, .. } => {
+            &BranchTable { table, .. } |
+            &BranchTableBase { table, .. } => {
                 self.verify_jump_table(inst, table)?;
             }
             &Call { func_ref, ref args, .. } => {
@@ -266,6 +274,20 @@ impl<'a> Verifier<'a> {
             &StackStore { stack_slot, .. } => {
                 self.verify_stack_slot(inst, stack_slot)?;
             }
+            &UnaryGlobalVar { global_var, .. } => {
+                self.verify_global_var(inst, global_var)?;
+                if self.global_var_uses_vmctx(global_var) {
+                    self.verify_vmctx_arg(inst)?;
+                }
+            }
+            &UnaryConst { constant, .. } => {
+                self.verify_constant(inst, constant)?;
+            }
+            &HeapAddr { heap, .. } => {
+                self.verify_heap(inst, heap)?;
+                // All heap bases are currently found in the VM context.
+                self.verify_vmctx_arg(inst)?;
+            }
 
             // Exhaustive list so we can't forget to add new formats
             &Nullary { .. } |
@@ -281,11 +303,14 @@ impl<'a> Verifier<'a> {
             &IntCompare { .. } |
             &IntCompareImm { .. } |
             &FloatCompare { .. } |
-            &HeapLoad { .. } |
-            &HeapStore { .. } |
             &Load { .. } |
             &Store { .. } |
-            &RegMove { .. } => {}
+            &AtomicRmw { .. } |
+            &Fence { .. } |
+            &RegMove { .. } |
+            &Trap { .. } |
+            &CondTrap { .. } |
+            &BinaryTrap { .. } => {}
         }
 
         Ok(())
@@ -323,6 +348,49 @@ impl<'a> Verifier<'a> {
         }
     }
 
+    fn verify_global_var(&self, inst: Inst, gv: GlobalVar) -> Result {
+        if !self.func.global_vars.is_valid(gv) {
+            err!(inst, "invalid global variable {}", gv)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn verify_heap(&self, inst: Inst, heap: Heap) -> Result {
+        if !self.func.heaps.is_valid(heap) {
+            err!(inst, "invalid heap {}", heap)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Is `gv` addressed relative to the VM context pointer, directly or through `deref` bases?
+    //
+    // This relies on `verify_global_vars` having rejected cycles and invalid bases.
+    fn global_var_uses_vmctx(&self, gv: GlobalVar) -> bool {
+        let mut cur = gv;
+        loop {
+            match self.func.global_vars[cur] {
+                GlobalVarData::VmCtx { .. } => return true,
+                GlobalVarData::Deref { base, .. } => cur = base,
+                GlobalVarData::Sym { .. } => return false,
+            }
+        }
+    }
+
+    // Instructions using the VM context require a `vmctx` argument to find it.
+    fn verify_vmctx_arg(&self, inst: Inst) -> Result {
+        if self.func
+               .signature
+               .argument_types
+               .iter()
+               .any(|arg| arg.purpose == ArgumentPurpose::VMContext) {
+            Ok(())
+        } else {
+            err!(inst, "uses the VM context, but the function has no vmctx argument")
+        }
+    }
+
     fn verify_value_list(&self, inst: Inst, l: &ValueList) -> Result {
         if !l.is_valid(&self.func.dfg.value_lists) {
             err!(inst, "invalid value list reference {:?}", l)
@@ -331,6 +399,14 @@ impl<'a> Verifier<'a> {
         }
     }
 
+    fn verify_constant(&self, inst: Inst, c: Constant) -> Result {
+        if !self.func.constants.is_valid(c) {
+            err!(inst, "invalid constant reference {}", c)
+        } else {
+            Ok(())
+        }
+    }
+
     fn verify_jump_table(&self, inst: Inst, j: JumpTable) -> Result {
         if !self.func.jump_tables.is_valid(j) {
             err!(inst, "invalid jump table reference {}", j)
@@ -453,6 +529,7 @@ impl<'a> Verifier<'a> {
         self.typecheck_fixed_args(inst, ctrl_type)?;
         self.typecheck_variable_args(inst)?;
         self.typecheck_return(inst)?;
+        self.typecheck_heap_addr(inst)?;
 
         Ok(())
     }
@@ -616,6 +693,22 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    // The offset of a `heap_addr` must have the index type of the heap.
+    fn typecheck_heap_addr(&self, inst: Inst) -> Result {
+        if let InstructionData::HeapAddr { heap, arg, .. } = self.func.dfg[inst] {
+            let index_type = self.func.heaps[heap].index_type;
+            let arg_type = self.func.dfg.value_type(arg);
+            if arg_type != index_type {
+                return err!(inst,
+                            "index type {} doesn't match {} index type {}",
+                            arg_type,
+                            heap,
+                            index_type);
+            }
+        }
+        Ok(())
+    }
+
     fn cfg_integrity(&self, cfg: &ControlFlowGraph) -> Result {
         let mut expected_succs = BTreeSet::<Ebb>::new();
         let mut got_succs = BTreeSet::<Ebb>::new();
@@ -693,7 +786,32 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    // Check for cycles in the global variable declarations.
+    fn verify_global_vars(&self) -> Result {
+        let num_gvs = self.func.global_vars.len();
+
+        for gv in self.func.global_vars.keys() {
+            // A chain of `deref` bases that is longer than the number of global variables must
+            // visit one of them twice.
+            let mut cur = gv;
+            let mut steps = 0;
+            while let GlobalVarData::Deref { base, .. } = self.func.global_vars[cur] {
+                if !self.func.global_vars.is_valid(base) {
+                    return err!(cur, "invalid base global variable {}", base);
+                }
+                steps += 1;
+                if steps > num_gvs {
+                    return err!(gv, "deref cycle involving {}", gv);
+                }
+                cur = base;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn run(&self) -> Result {
+        self.verify_global_vars()?;
         self.typecheck_entry_block_arguments()?;
         for ebb in self.func.layout.ebbs() {
             for inst in self.func.layout.ebb_insts(ebb) {
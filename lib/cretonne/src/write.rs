@@ -1,11 +1,12 @@
 //! Converting Cretonne IL to text.
 //!
 //! The `write` module provides the `write_function` function which converts an IL `Function` to an
-//! equivalent textual representation. This textual representation can be read back by the
-//! `cretonne-reader` crate.
+//! equivalent textual representation, and the `write_data` function which does the same for a
+//! `DataObject`. This textual representation can be read back by the `cretonne-reader` crate.
 
 use ir::{Function, DataFlowGraph, Ebb, Inst, Value, ValueDef, Type};
 use isa::{TargetIsa, RegInfo};
+use module::DataObject;
 use std::fmt::{self, Result, Error, Write};
 use std::result;
 
@@ -28,6 +29,39 @@ pub fn write_function(w: &mut Write, func: &Function, isa: Option<&TargetIsa>) -
     writeln!(w, "}}")
 }
 
+/// Write the data object `data` to `w` as an equivalent `data` declaration.
+///
+/// The declaration is written on a single line without a trailing newline. The pointers are
+/// written as symbol names, so the declaration must be read back with the same ISA.
+pub fn write_data(w: &mut Write, data: &DataObject) -> Result {
+    write!(w, "data {} = [", data.name)?;
+    let mut pos = 0;
+    let mut sep = "";
+    for ptr in data.pointers.iter().map(Some).chain(Some(None)) {
+        let end = ptr.map_or(data.contents.len(), |p| p.offset as usize);
+        if end > pos {
+            write!(w, "{}#", sep)?;
+            for byte in &data.contents[pos..end] {
+                write!(w, "{:02x}", byte)?;
+            }
+            sep = ", ";
+        }
+        if let Some(ptr) = ptr {
+            write!(w, "{}{}", sep, ptr.target)?;
+            sep = ", ";
+            pos = end + ptr.size as usize;
+        }
+    }
+    write!(w, "]")?;
+    if data.align > 1 {
+        write!(w, ", align {}", data.align)?;
+    }
+    if data.writable {
+        write!(w, ", writable")?;
+    }
+    Ok(())
+}
+
 // ====--------------------------------------------------------------------------------------====//
 //
 // Function spec.
@@ -69,6 +103,21 @@ fn write_preamble(w: &mut Write,
         writeln!(w, "    {} = {}", jt, func.jump_tables[jt])?;
     }
 
+    for c in func.constants.keys() {
+        any = true;
+        writeln!(w, "    {} = {}", c, func.constants[c])?;
+    }
+
+    for gv in func.global_vars.keys() {
+        any = true;
+        writeln!(w, "    {} = {}", gv, func.global_vars[gv])?;
+    }
+
+    for heap in func.heaps.keys() {
+        any = true;
+        writeln!(w, "    {} = {}", heap, func.heaps[heap])?;
+    }
+
     Ok(any)
 }
 
@@ -174,15 +223,27 @@ fn write_instruction(w: &mut Write,
                      isa: Option<&TargetIsa>,
                      inst: Inst)
                      -> Result {
-    // Indent all instructions to col 24 if any encodings are present.
-    let indent = if func.encodings.is_empty() { 4 } else { 24 };
+    // Indent all instructions to col 24 if any encodings or source locations are present.
+    let indent = if func.encodings.is_empty() && func.srclocs.is_empty() {
+        4
+    } else {
+        24
+    };
 
     // Value aliases come out on lines before the instruction using them.
     write_value_aliases(w, func, inst, indent)?;
 
+    // Prefix containing source location and encoding info.
+    let mut s = String::with_capacity(16);
+
+    // Source location goes first.
+    let srcloc = func.srclocs.get_or_default(inst);
+    if !srcloc.is_default() {
+        write!(s, "{} ", srcloc)?;
+    }
+
     // Write out encoding info.
     if let Some(enc) = func.encodings.get(inst).cloned() {
-        let mut s = String::with_capacity(16);
         if let Some(isa) = isa {
             write!(s, "[{}", isa.encoding_info().display(enc))?;
             // Write value locations, if we have them.
@@ -196,11 +257,14 @@ fn write_instruction(w: &mut Write,
         } else {
             write!(s, "[{}]", enc)?;
         }
-        // Align instruction following ISA annotation to col 24.
-        write!(w, "{:23} ", s)?;
-    } else {
+    }
+
+    if s.is_empty() {
         // No annotations, simply indent.
         write!(w, "{1:0$}", indent, "")?;
+    } else {
+        // Align instruction following the annotations to col 24.
+        write!(w, "{:1$} ", s, indent - 1)?;
     }
 
     // Write out the result values, if any.
@@ -298,6 +362,7 @@ pub fn write_operands(w: &mut Write,
             Ok(())
         }
         BranchTable { arg, table, .. } => write!(w, " {}, {}", arg, table),
+        BranchTableBase { table, .. } => write!(w, " {}", table),
         Call { func_ref, ref args, .. } => {
             write!(w, " {}({})", func_ref, DisplayValues(args.as_slice(pool)))
         }
@@ -316,8 +381,9 @@ pub fn write_operands(w: &mut Write,
             offset,
             ..
         } => write!(w, " {}, {}{}", arg, stack_slot, offset),
-        HeapLoad { arg, offset, .. } => write!(w, " {}{}", arg, offset),
-        HeapStore { args, offset, .. } => write!(w, " {}, {}{}", args[0], args[1], offset),
+        UnaryGlobalVar { global_var, .. } => write!(w, " {}", global_var),
+        UnaryConst { constant, .. } => write!(w, " {}", constant),
+        HeapAddr { heap, arg, imm, .. } => write!(w, " {}, {}, {}", heap, arg, imm),
         Load { flags, arg, offset, .. } => write!(w, "{} {}{}", flags, arg, offset),
         Store {
             flags,
@@ -325,6 +391,8 @@ pub fn write_operands(w: &mut Write,
             offset,
             ..
         } => write!(w, "{} {}, {}{}", flags, args[0], args[1], offset),
+        AtomicRmw { op, args, .. } => write!(w, " {} {}, {}", op, args[0], args[1]),
+        Fence { ordering, .. } => write!(w, " {}", ordering),
         RegMove { arg, src, dst, .. } => {
             if let Some(isa) = isa {
                 let regs = isa.register_info();
@@ -337,6 +405,9 @@ pub fn write_operands(w: &mut Write,
                 write!(w, " {}, %{} -> %{}", arg, src, dst)
             }
         }
+        Trap { code, .. } => write!(w, " {}", code),
+        CondTrap { arg, code, .. } => write!(w, " {}, {}", arg, code),
+        BinaryTrap { args, code, .. } => write!(w, " {}, {}, {}", args[0], args[1], code),
 
     }
 }
@@ -0,0 +1,566 @@
+//! Interpreting functions.
+
+use cretonne::ir::{Ebb, Function, FunctionName, Inst, InstructionData, Opcode, StackSlot, TrapCode,
+                   Type, Value};
+use cretonne::ir::immediates::Offset32;
+use ops;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+use std::result;
+use value::{DataValue, zext};
+
+/// Default size of the heap part of the sandboxed memory.
+const DEFAULT_HEAP_SIZE: usize = 64 * 1024;
+
+/// Default size of the memory reserved for stack slots.
+const DEFAULT_STACK_SIZE: usize = 64 * 1024;
+
+/// Maximum depth of nested calls before the interpreter reports a stack overflow.
+///
+/// Each interpreted call uses the native stack, so this can't be too large.
+const MAX_CALL_DEPTH: usize = 256;
+
+/// An error that stopped the interpreter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The function trapped.
+    Trap(TrapCode),
+
+    /// A call to a function that hasn't been added to the interpreter.
+    UnknownFunction(FunctionName),
+
+    /// The arguments don't match the signature of the called function.
+    BadArguments(String),
+
+    /// The function contains an instruction the interpreter can't execute.
+    Unsupported(String),
+
+    /// The function executed more instructions than the step limit allows.
+    StepLimit,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Error::Trap(code) => write!(f, "trap {}", code),
+            Error::UnknownFunction(ref name) => write!(f, "unknown function {}", name),
+            Error::BadArguments(ref msg) => write!(f, "bad arguments: {}", msg),
+            Error::Unsupported(ref msg) => write!(f, "unsupported instruction {}", msg),
+            Error::StepLimit => write!(f, "step limit reached"),
+        }
+    }
+}
+
+/// A result from the interpreter.
+pub type Result<T> = result::Result<T, Error>;
+
+/// An interpreter for Cretonne functions.
+///
+/// The interpreter owns a sandboxed memory which is accessed by loads and stores. Addresses are
+/// offsets into this memory, and accesses outside of it trap with `heap_oob`. The memory starts
+/// with the heap which can be accessed by the host with `heap()` and `heap_mut()`. The stack slots
+/// of the active calls are allocated after the heap.
+pub struct Interpreter<'a> {
+    functions: HashMap<FunctionName, &'a Function>,
+    memory: Vec<u8>,
+    heap_size: usize,
+    stack_pointer: usize,
+    depth: usize,
+    steps: u64,
+    step_limit: Option<u64>,
+}
+
+/// Where to go after executing an instruction.
+enum Control {
+    /// Continue with the next instruction.
+    Continue,
+    /// Jump to an EBB with arguments.
+    Jump(Ebb, Vec<DataValue>),
+    /// Return from the function with values.
+    Return(Vec<DataValue>),
+}
+
+/// The state of an active call.
+struct Frame<'f> {
+    func: &'f Function,
+    values: HashMap<Value, DataValue>,
+    slots: HashMap<StackSlot, usize>,
+}
+
+impl<'f> Frame<'f> {
+    fn get(&self, v: Value) -> DataValue {
+        let v = self.func.dfg.resolve_aliases(v);
+        match self.values.get(&v) {
+            Some(x) => x.clone(),
+            None => panic!("{} used before it was defined", v),
+        }
+    }
+
+    fn get_all(&self, vs: &[Value]) -> Vec<DataValue> {
+        vs.iter().map(|&v| self.get(v)).collect()
+    }
+
+    fn set(&mut self, v: Value, x: DataValue) {
+        self.values.insert(v, x);
+    }
+}
+
+impl<'a> Default for Interpreter<'a> {
+    fn default() -> Interpreter<'a> {
+        Interpreter::new()
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    /// Create an interpreter with the default memory sizes.
+    pub fn new() -> Interpreter<'a> {
+        Interpreter::with_memory(DEFAULT_HEAP_SIZE, DEFAULT_STACK_SIZE)
+    }
+
+    /// Create an interpreter with `heap_size` bytes of heap and `stack_size` bytes of memory for
+    /// stack slots.
+    pub fn with_memory(heap_size: usize, stack_size: usize) -> Interpreter<'a> {
+        Interpreter {
+            functions: HashMap::new(),
+            memory: vec![0; heap_size + stack_size],
+            heap_size,
+            stack_pointer: heap_size,
+            depth: 0,
+            steps: 0,
+            step_limit: None,
+        }
+    }
+
+    /// Add a function that can be called by name.
+    pub fn add_function(&mut self, func: &'a Function) -> result::Result<(), String> {
+        if self.functions.contains_key(&func.name) {
+            return Err(format!("duplicate definition of {}", func.name));
+        }
+        self.functions.insert(func.name.clone(), func);
+        Ok(())
+    }
+
+    /// Limit the number of instructions executed by each call from the host.
+    ///
+    /// Exceeding the limit stops the interpreter with `Error::StepLimit`. This guards against
+    /// functions that never return.
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.step_limit = limit;
+    }
+
+    /// Get the heap.
+    pub fn heap(&self) -> &[u8] {
+        &self.memory[0..self.heap_size]
+    }
+
+    /// Get the heap for modification.
+    pub fn heap_mut(&mut self) -> &mut [u8] {
+        &mut self.memory[0..self.heap_size]
+    }
+
+    /// Call the function named `name` with `args` and return the values it returns.
+    pub fn call(&mut self, name: &FunctionName, args: &[DataValue]) -> Result<Vec<DataValue>> {
+        let func = self.lookup(name)?;
+        self.run(func, args)
+    }
+
+    /// Run `func` with `args` and return the values it returns.
+    ///
+    /// The function doesn't need to be added to the interpreter, but any functions it calls do.
+    pub fn run(&mut self, func: &Function, args: &[DataValue]) -> Result<Vec<DataValue>> {
+        self.steps = 0;
+        self.invoke(func, args)
+    }
+
+    fn lookup(&self, name: &FunctionName) -> Result<&'a Function> {
+        self.functions
+            .get(name)
+            .cloned()
+            .ok_or_else(|| Error::UnknownFunction(name.clone()))
+    }
+
+    /// Call `func` after checking the arguments.
+    fn invoke(&mut self, func: &Function, args: &[DataValue]) -> Result<Vec<DataValue>> {
+        let types = &func.signature.argument_types;
+        if args.len() != types.len() {
+            return Err(Error::BadArguments(format!("{} takes {} arguments, got {}",
+                                                   func.name,
+                                                   types.len(),
+                                                   args.len())));
+        }
+        for (arg, at) in args.iter().zip(types) {
+            if !arg.is_of_type(at.value_type) {
+                return Err(Error::BadArguments(format!("{} is not a valid {} argument to {}",
+                                                       arg,
+                                                       at.value_type,
+                                                       func.name)));
+            }
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(Error::Trap(TrapCode::StackOverflow));
+        }
+
+        self.depth += 1;
+        let saved_stack_pointer = self.stack_pointer;
+        let result = self.execute(func, args);
+        self.stack_pointer = saved_stack_pointer;
+        self.depth -= 1;
+        result
+    }
+
+    /// Execute the body of `func`.
+    fn execute(&mut self, func: &Function, args: &[DataValue]) -> Result<Vec<DataValue>> {
+        let mut frame = Frame {
+            func,
+            values: HashMap::new(),
+            slots: HashMap::new(),
+        };
+        for slot in func.stack_slots.keys() {
+            let addr = self.allocate(func.stack_slots[slot].size as usize)?;
+            frame.slots.insert(slot, addr);
+        }
+
+        let mut ebb = func.layout
+            .entry_block()
+            .ok_or_else(|| Error::Unsupported(format!("{} has no entry block", func.name)))?;
+        let mut ebb_args = args.to_vec();
+        'ebbs: loop {
+            for (&arg, x) in func.dfg.ebb_args(ebb).iter().zip(ebb_args) {
+                frame.set(arg, x);
+            }
+            for inst in func.layout.ebb_insts(ebb) {
+                self.steps += 1;
+                if self.step_limit.map_or(false, |limit| self.steps > limit) {
+                    return Err(Error::StepLimit);
+                }
+                match self.step(&mut frame, inst)? {
+                    Control::Continue => {}
+                    Control::Jump(dest, xs) => {
+                        ebb = dest;
+                        ebb_args = xs;
+                        continue 'ebbs;
+                    }
+                    Control::Return(xs) => return Ok(xs),
+                }
+            }
+            panic!("{} doesn't end in a terminator", ebb);
+        }
+    }
+
+    /// Allocate `size` bytes of stack memory and return the address.
+    fn allocate(&mut self, size: usize) -> Result<usize> {
+        let addr = self.stack_pointer;
+        let end = addr + ((size + 7) & !7);
+        if end > self.memory.len() {
+            return Err(Error::Trap(TrapCode::StackOverflow));
+        }
+        self.stack_pointer = end;
+        Ok(addr)
+    }
+
+    /// Execute a single instruction.
+    fn step(&mut self, frame: &mut Frame, inst: Inst) -> Result<Control> {
+        let func = frame.func;
+        let dfg = &func.dfg;
+        let args = frame.get_all(dfg.inst_args(inst));
+        let jump = |dest, frame: &Frame| {
+            Ok(Control::Jump(dest, frame.get_all(dfg.inst_variable_args(inst))))
+        };
+
+        match dfg[inst] {
+            InstructionData::Jump { destination, .. } => return jump(destination, frame),
+            InstructionData::Branch {
+                opcode,
+                destination,
+                ..
+            } => {
+                if args[0].as_bool() == (opcode == Opcode::Brnz) {
+                    return jump(destination, frame);
+                }
+                return Ok(Control::Continue);
+            }
+            InstructionData::BranchIcmp {
+                cond, destination, ..
+            } => {
+                if ops::icmp(cond, dfg.ctrl_typevar(inst), &args[0], &args[1]) {
+                    return jump(destination, frame);
+                }
+                return Ok(Control::Continue);
+            }
+            InstructionData::BranchTable { table, .. } => {
+                let index = zext(args[0].as_int(), dfg.ctrl_typevar(inst).lane_bits());
+                let jt = &func.jump_tables[table];
+                return Ok(match jt.get_entry(index as usize) {
+                              Some(dest) if index < jt.len() as u64 => {
+                                  Control::Jump(dest, Vec::new())
+                              }
+                              _ => Control::Continue,
+                          });
+            }
+            InstructionData::Trap { code, .. } => return Err(Error::Trap(code)),
+            InstructionData::CondTrap { opcode, code, .. } => {
+                if args[0].as_bool() == (opcode == Opcode::Trapnz) {
+                    return Err(Error::Trap(code));
+                }
+                return Ok(Control::Continue);
+            }
+            InstructionData::MultiAry { opcode: Opcode::Return, .. } => {
+                return Ok(Control::Return(args))
+            }
+            _ => {}
+        }
+
+        let results = self.compute(frame, inst, &args)?;
+        for (&v, x) in dfg.inst_results(inst).iter().zip(results) {
+            frame.set(v, x);
+        }
+        Ok(Control::Continue)
+    }
+
+    /// Compute the results of an instruction that doesn't affect control flow.
+    fn compute(&mut self, frame: &Frame, inst: Inst, args: &[DataValue]) -> Result<Vec<DataValue>> {
+        let dfg = &frame.func.dfg;
+        let opcode = dfg[inst].opcode();
+        let ctrl_ty = dfg.ctrl_typevar(inst);
+        let result_tys: Vec<Type> = dfg.inst_results(inst)
+            .iter()
+            .map(|&v| dfg.value_type(v))
+            .collect();
+        let arg_tys: Vec<Type> = dfg.inst_args(inst)
+            .iter()
+            .map(|&v| dfg.value_type(v))
+            .collect();
+
+        let x = match dfg[inst] {
+            InstructionData::UnaryImm { imm, .. } => DataValue::int(imm.into(), ctrl_ty),
+            InstructionData::UnaryIeee32 { imm, .. } => DataValue::F32(imm),
+            InstructionData::UnaryIeee64 { imm, .. } => DataValue::F64(imm),
+            InstructionData::BinaryImm { imm, .. } => {
+                let imm = imm.into();
+                ops::lanewise(ctrl_ty,
+                              args,
+                              |ty, xs| ops::binary_imm(opcode, ty, &xs[0], imm))?
+            }
+            InstructionData::IntCompare { cond, .. } => {
+                ops::lanewise(result_tys[0], args, |_, xs| {
+                    Ok(DataValue::Bool(ops::icmp(cond, ctrl_ty.lane_type(), &xs[0], &xs[1])))
+                })?
+            }
+            InstructionData::IntCompareImm { cond, imm, .. } => {
+                let imm = DataValue::int(imm.into(), ctrl_ty.lane_type());
+                ops::lanewise(result_tys[0], args, |_, xs| {
+                    Ok(DataValue::Bool(ops::icmp(cond, ctrl_ty.lane_type(), &xs[0], &imm)))
+                })?
+            }
+            InstructionData::FloatCompare { cond, .. } => {
+                ops::lanewise(result_tys[0],
+                              args,
+                              |_, xs| Ok(DataValue::Bool(ops::fcmp(cond, &xs[0], &xs[1]))))?
+            }
+            InstructionData::InsertLane { lane, .. } => {
+                let mut lanes = args[0].lanes().to_vec();
+                lanes[lane as usize] = args[1].clone();
+                DataValue::Vector(lanes)
+            }
+            InstructionData::ExtractLane { lane, .. } => args[0].lanes()[lane as usize].clone(),
+            InstructionData::Load { offset, .. } => {
+                let addr = address(&args[0], arg_tys[0], offset);
+                self.load(opcode, result_tys[0], addr)?
+            }
+            InstructionData::Store { offset, .. } => {
+                let addr = address(&args[1], arg_tys[1], offset);
+                self.store(opcode, arg_tys[0], &args[0], addr)?;
+                return Ok(Vec::new());
+            }
+            InstructionData::StackLoad {
+                stack_slot, offset, ..
+            } => {
+                let offset: i64 = offset.into();
+                let addr = frame.slots[&stack_slot] as i64 + offset;
+                if opcode == Opcode::StackAddr {
+                    DataValue::int(addr, result_tys[0])
+                } else {
+                    self.load(opcode, result_tys[0], addr)?
+                }
+            }
+            InstructionData::StackStore {
+                stack_slot, offset, ..
+            } => {
+                let offset: i64 = offset.into();
+                let addr = frame.slots[&stack_slot] as i64 + offset;
+                self.store(opcode, arg_tys[0], &args[0], addr)?;
+                return Ok(Vec::new());
+            }
+            InstructionData::BinaryTrap { code, .. } => {
+                let (x, of) = ops::overflow(opcode, ctrl_ty, &args[0], &args[1])?;
+                if of {
+                    return Err(Error::Trap(code));
+                }
+                x
+            }
+            InstructionData::Call { func_ref, .. } => {
+                let callee = self.lookup(&dfg.ext_funcs[func_ref].name)?;
+                return self.invoke(callee, args);
+            }
+            _ => return compute(opcode, ctrl_ty, &arg_tys, &result_tys, args),
+        };
+        Ok(vec![x])
+    }
+
+    /// Get the memory range for accessing `size` bytes at `addr`.
+    fn range(&self, addr: i64, size: usize) -> Result<Range<usize>> {
+        if addr < 0 || addr as u64 + size as u64 > self.memory.len() as u64 {
+            return Err(Error::Trap(TrapCode::HeapOutOfBounds));
+        }
+        Ok(addr as usize..addr as usize + size)
+    }
+
+    /// Load a value of type `ty` from `addr`.
+    fn load(&self, opcode: Opcode, ty: Type, addr: i64) -> Result<DataValue> {
+        let mem_ty = match opcode {
+            Opcode::Uload8 | Opcode::Sload8 => Type::int(8),
+            Opcode::Uload16 | Opcode::Sload16 => Type::int(16),
+            Opcode::Uload32 | Opcode::Sload32 => Type::int(32),
+            _ => Some(ty),
+        }
+                .expect("memory type");
+        let range = self.range(addr, mem_ty.bits() as usize / 8)?;
+        let x = DataValue::read_bytes(mem_ty, &self.memory[range])
+            .ok_or_else(|| ops::unsupported(opcode, ty))?;
+        Ok(match opcode {
+               Opcode::Uload8 | Opcode::Uload16 | Opcode::Uload32 => {
+                   DataValue::int(zext(x.as_int(), mem_ty.lane_bits()) as i64, ty)
+               }
+               Opcode::Sload8 | Opcode::Sload16 | Opcode::Sload32 => {
+                   DataValue::int(x.as_int(), ty)
+               }
+               _ => x,
+           })
+    }
+
+    /// Store `x` of type `ty` at `addr`.
+    fn store(&mut self, opcode: Opcode, ty: Type, x: &DataValue, addr: i64) -> Result<()> {
+        let mem_ty = match opcode {
+            Opcode::Istore8 => Type::int(8),
+            Opcode::Istore16 => Type::int(16),
+            Opcode::Istore32 => Type::int(32),
+            _ => Some(ty),
+        }
+                .expect("memory type");
+        let range = self.range(addr, mem_ty.bits() as usize / 8)?;
+        x.write_bytes(mem_ty, &mut self.memory[range])
+            .ok_or_else(|| ops::unsupported(opcode, ty))
+    }
+}
+
+/// Compute the address for a memory access.
+fn address(base: &DataValue, ty: Type, offset: Offset32) -> i64 {
+    (zext(base.as_int(), ty.lane_bits()) as i64).wrapping_add(offset.into())
+}
+
+/// Compute the results of an instruction that doesn't have immediate operands or touch memory.
+fn compute(opcode: Opcode,
+           ctrl_ty: Type,
+           arg_tys: &[Type],
+           result_tys: &[Type],
+           args: &[DataValue])
+           -> Result<Vec<DataValue>> {
+    let x = match opcode {
+        Opcode::Copy | Opcode::Spill | Opcode::Fill | Opcode::Regmove => args[0].clone(),
+        Opcode::Select => {
+            if args[0].as_bool() {
+                args[1].clone()
+            } else {
+                args[2].clone()
+            }
+        }
+        Opcode::Vselect => {
+            ops::lanewise(ctrl_ty, args, |_, xs| {
+                Ok(if xs[0].as_bool() {
+                       xs[1].clone()
+                   } else {
+                       xs[2].clone()
+                   })
+            })?
+        }
+        Opcode::Splat => DataValue::Vector(vec![args[0].clone(); ctrl_ty.lane_count() as usize]),
+        Opcode::Vsplit => {
+            let lanes = args[0].lanes();
+            let (lo, hi) = lanes.split_at(lanes.len() / 2);
+            let half = |l: &[DataValue]| if l.len() == 1 {
+                l[0].clone()
+            } else {
+                DataValue::Vector(l.to_vec())
+            };
+            return Ok(vec![half(lo), half(hi)]);
+        }
+        Opcode::Vconcat => {
+            let mut lanes = Vec::new();
+            for x in args {
+                match *x {
+                    DataValue::Vector(ref l) => lanes.extend_from_slice(l),
+                    ref scalar => lanes.push(scalar.clone()),
+                }
+            }
+            DataValue::Vector(lanes)
+        }
+        Opcode::Iadd | Opcode::Isub | Opcode::Imul | Opcode::Umulhi | Opcode::Smulhi |
+        Opcode::Udiv | Opcode::Sdiv | Opcode::Urem | Opcode::Srem | Opcode::Band | Opcode::Bor |
+        Opcode::Bxor | Opcode::Rotl | Opcode::Rotr | Opcode::Ishl | Opcode::Ushr |
+        Opcode::Sshr | Opcode::Fadd | Opcode::Fsub | Opcode::Fmul | Opcode::Fdiv |
+        Opcode::Fcopysign | Opcode::Fmin | Opcode::Fminnum | Opcode::Fmax | Opcode::Fmaxnum => {
+            ops::lanewise(ctrl_ty, args, |ty, xs| ops::binary(opcode, ty, &xs[0], &xs[1]))?
+        }
+        Opcode::Bnot | Opcode::Clz | Opcode::Cls | Opcode::Ctz | Opcode::Popcnt |
+        Opcode::Fneg | Opcode::Fabs | Opcode::Sqrt | Opcode::Ceil | Opcode::Floor |
+        Opcode::Trunc | Opcode::Nearest => {
+            ops::lanewise(ctrl_ty, args, |ty, xs| ops::unary(opcode, ty, &xs[0]))?
+        }
+        Opcode::Fma => ops::lanewise(ctrl_ty, args, ops::fma)?,
+        Opcode::IaddCin | Opcode::IaddCout | Opcode::IaddCarry | Opcode::IsubBin |
+        Opcode::IsubBout | Opcode::IsubBorrow => {
+            let (x, c) = ops::carry(opcode, ctrl_ty, args)?;
+            let mut xs = vec![x, DataValue::Bool(c)];
+            xs.truncate(result_tys.len());
+            return Ok(xs);
+        }
+        Opcode::IaddOv | Opcode::IsubOv | Opcode::ImulOv => {
+            let (x, of) = ops::overflow(opcode, ctrl_ty, &args[0], &args[1])?;
+            return Ok(vec![x, DataValue::Bool(of)]);
+        }
+        Opcode::Bitcast => {
+            let mut bytes = vec![0; arg_tys[0].bits() as usize / 8];
+            args[0]
+                .write_bytes(arg_tys[0], &mut bytes)
+                .and_then(|_| DataValue::read_bytes(result_tys[0], &bytes))
+                .ok_or_else(|| ops::unsupported(opcode, result_tys[0]))?
+        }
+        Opcode::Breduce | Opcode::Bextend | Opcode::Bint | Opcode::Bmask | Opcode::Ireduce |
+        Opcode::Uextend | Opcode::Sextend | Opcode::Fpromote | Opcode::Fdemote |
+        Opcode::FcvtToUint | Opcode::FcvtToSint | Opcode::FcvtFromUint |
+        Opcode::FcvtFromSint => {
+            let from = arg_tys[0].lane_type();
+            ops::lanewise(result_tys[0],
+                          args,
+                          |to, xs| ops::convert(opcode, from, to, &xs[0]))?
+        }
+        Opcode::Isplit => {
+            let from = arg_tys[0].lane_type();
+            let lo = ops::lanewise(result_tys[0],
+                                   args,
+                                   |_, xs| ops::isplit(from, &xs[0]).map(|r| r.0))?;
+            let hi = ops::lanewise(result_tys[1],
+                                   args,
+                                   |_, xs| ops::isplit(from, &xs[0]).map(|r| r.1))?;
+            return Ok(vec![lo, hi]);
+        }
+        Opcode::Iconcat => {
+            ops::lanewise(result_tys[0],
+                          args,
+                          |ty, xs| ops::iconcat(ty, &xs[0], &xs[1]))?
+        }
+        _ => return Err(ops::unsupported(opcode, ctrl_ty)),
+    };
+    Ok(vec![x])
+}
@@ -0,0 +1,25 @@
+//! Cretonne IL interpreter.
+//!
+//! The cton_interpreter library executes Cretonne functions directly from their data flow graph
+//! and layout, without compiling them for a target ISA. This makes it possible to check that an
+//! optimization pass preserves the semantics of a function by running it before and after the
+//! pass.
+//!
+//! Functions are added to an `Interpreter` which can then call them by name with a list of
+//! `DataValue` arguments. Calls between the added functions are supported, and loads and stores
+//! access a sandboxed memory owned by the interpreter. Stack slots are allocated in the same
+//! memory.
+//!
+//! The interpreted functions must pass the verifier. Integer types wider than 64 bits are not
+//! supported.
+
+#![deny(missing_docs)]
+
+extern crate cretonne;
+
+pub use interpreter::{Interpreter, Error};
+pub use value::DataValue;
+
+mod interpreter;
+mod ops;
+mod value;
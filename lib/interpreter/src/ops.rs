@@ -0,0 +1,497 @@
+//! Evaluation of the instructions that compute values.
+//!
+//! The functions in this module work on scalar values of a lane type. Vector instructions are
+//! evaluated lane by lane with `lanewise()`.
+
+use cretonne::ir::{Opcode, TrapCode, Type};
+use cretonne::ir::condcodes::{IntCC, FloatCC};
+use interpreter::{Error, Result};
+use value::{DataValue, sext, zext};
+
+/// Evaluate `f` on each lane of `args`, where `ty` is the type of the result.
+///
+/// Scalar arguments are passed unchanged to every lane, which handles shift amounts and
+/// immediates. If `ty` is a scalar, `f` is called once with the arguments.
+pub fn lanewise<F>(ty: Type, args: &[DataValue], mut f: F) -> Result<DataValue>
+    where F: FnMut(Type, &[DataValue]) -> Result<DataValue>
+{
+    if ty.is_scalar() {
+        return f(ty, args);
+    }
+    let mut lanes = Vec::with_capacity(ty.lane_count() as usize);
+    for i in 0..ty.lane_count() as usize {
+        let lane_args: Vec<DataValue> = args.iter()
+            .map(|arg| match *arg {
+                     DataValue::Vector(ref l) => l[i].clone(),
+                     ref scalar => scalar.clone(),
+                 })
+            .collect();
+        lanes.push(f(ty.lane_type(), &lane_args)?);
+    }
+    Ok(DataValue::Vector(lanes))
+}
+
+/// Evaluate a unary instruction on a lane of type `ty`.
+pub fn unary(opcode: Opcode, ty: Type, x: &DataValue) -> Result<DataValue> {
+    if ty.is_float() && opcode != Opcode::Bnot {
+        return float(opcode, ty, &[x.clone()]);
+    }
+    if !ty.is_int() {
+        return match opcode {
+                   Opcode::Bnot => bitwise(opcode, ty, x, x),
+                   _ => Err(unsupported(opcode, ty)),
+               };
+    }
+    let bits = ty.lane_bits();
+    let (a, ua) = (x.as_int(), zext(x.as_int(), bits));
+    // Number of unused high bits in the 64-bit representation.
+    let unused = 64 - bits as u32;
+    let r = match opcode {
+        Opcode::Bnot => !a,
+        Opcode::Clz => (ua.leading_zeros() - unused) as i64,
+        Opcode::Cls => {
+            let s = if a < 0 { !a } else { a };
+            (s.leading_zeros() - unused - 1) as i64
+        }
+        Opcode::Ctz => {
+            if ua == 0 {
+                bits as i64
+            } else {
+                ua.trailing_zeros() as i64
+            }
+        }
+        Opcode::Popcnt => ua.count_ones() as i64,
+        _ => return Err(unsupported(opcode, ty)),
+    };
+    Ok(DataValue::int(r, ty))
+}
+
+/// Evaluate a binary instruction on a lane of type `ty`.
+pub fn binary(opcode: Opcode, ty: Type, x: &DataValue, y: &DataValue) -> Result<DataValue> {
+    match opcode {
+        Opcode::Band | Opcode::Bor | Opcode::Bxor if !ty.is_int() => {
+            return bitwise(opcode, ty, x, y)
+        }
+        _ if ty.is_float() => return float(opcode, ty, &[x.clone(), y.clone()]),
+        _ if !ty.is_int() => return Err(unsupported(opcode, ty)),
+        _ => {}
+    }
+    let bits = ty.lane_bits();
+    let (a, b) = (x.as_int(), y.as_int());
+    let (ua, ub) = (zext(a, bits), zext(b, bits));
+    // Shift and rotate amounts are taken modulo the number of bits.
+    let s = (ub % bits as u64) as u32;
+    let r = match opcode {
+        Opcode::Iadd => a.wrapping_add(b),
+        Opcode::Isub => a.wrapping_sub(b),
+        Opcode::Imul => a.wrapping_mul(b),
+        Opcode::Umulhi => ((ua as u128 * ub as u128) >> bits) as i64,
+        Opcode::Smulhi => ((a as i128 * b as i128) >> bits) as i64,
+        Opcode::Udiv | Opcode::Urem => {
+            if ub == 0 {
+                return Err(Error::Trap(TrapCode::IntegerDivisionByZero));
+            }
+            if opcode == Opcode::Udiv {
+                (ua / ub) as i64
+            } else {
+                (ua % ub) as i64
+            }
+        }
+        Opcode::Sdiv => {
+            if b == 0 {
+                return Err(Error::Trap(TrapCode::IntegerDivisionByZero));
+            }
+            if b == -1 && a == sext(1 << (bits - 1), bits) {
+                return Err(Error::Trap(TrapCode::IntegerOverflow));
+            }
+            a / b
+        }
+        Opcode::Srem => {
+            if b == 0 {
+                return Err(Error::Trap(TrapCode::IntegerDivisionByZero));
+            }
+            // The remainder is 0 when the quotient would overflow.
+            if b == -1 { 0 } else { a % b }
+        }
+        Opcode::Band => a & b,
+        Opcode::Bor => a | b,
+        Opcode::Bxor => a ^ b,
+        Opcode::Rotl if s == 0 => a,
+        Opcode::Rotl => ((ua << s) | (ua >> (bits as u32 - s))) as i64,
+        Opcode::Rotr if s == 0 => a,
+        Opcode::Rotr => ((ua >> s) | (ua << (bits as u32 - s))) as i64,
+        Opcode::Ishl => (ua << s) as i64,
+        Opcode::Ushr => (ua >> s) as i64,
+        Opcode::Sshr => a >> s,
+        _ => return Err(unsupported(opcode, ty)),
+    };
+    Ok(DataValue::int(r, ty))
+}
+
+/// Evaluate a binary instruction with an immediate operand on a lane of type `ty`.
+pub fn binary_imm(opcode: Opcode, ty: Type, x: &DataValue, imm: i64) -> Result<DataValue> {
+    let imm = DataValue::int(imm, ty);
+    let (op, x, y) = match opcode {
+        Opcode::IaddImm => (Opcode::Iadd, x, &imm),
+        Opcode::ImulImm => (Opcode::Imul, x, &imm),
+        Opcode::UdivImm => (Opcode::Udiv, x, &imm),
+        Opcode::SdivImm => (Opcode::Sdiv, x, &imm),
+        Opcode::UremImm => (Opcode::Urem, x, &imm),
+        Opcode::SremImm => (Opcode::Srem, x, &imm),
+        Opcode::IrsubImm => (Opcode::Isub, &imm, x),
+        Opcode::BandImm => (Opcode::Band, x, &imm),
+        Opcode::BorImm => (Opcode::Bor, x, &imm),
+        Opcode::BxorImm => (Opcode::Bxor, x, &imm),
+        Opcode::RotlImm => (Opcode::Rotl, x, &imm),
+        Opcode::RotrImm => (Opcode::Rotr, x, &imm),
+        Opcode::IshlImm => (Opcode::Ishl, x, &imm),
+        Opcode::UshrImm => (Opcode::Ushr, x, &imm),
+        Opcode::SshrImm => (Opcode::Sshr, x, &imm),
+        _ => return Err(unsupported(opcode, ty)),
+    };
+    binary(op, ty, x, y)
+}
+
+/// Evaluate an addition or subtraction with carry or borrow flags.
+///
+/// Returns the result and the carry or borrow out.
+pub fn carry(opcode: Opcode, ty: Type, args: &[DataValue]) -> Result<(DataValue, bool)> {
+    let bits = ty.lane_bits();
+    let x = zext(args[0].as_int(), bits) as u128;
+    let y = zext(args[1].as_int(), bits) as u128;
+    let c = args.get(2).map_or(0, |c| c.as_bool() as u128);
+    match opcode {
+        Opcode::IaddCin | Opcode::IaddCout | Opcode::IaddCarry => {
+            let sum = x + y + c;
+            Ok((DataValue::int(sum as i64, ty), sum >> bits != 0))
+        }
+        Opcode::IsubBin | Opcode::IsubBout | Opcode::IsubBorrow => {
+            Ok((DataValue::int(x.wrapping_sub(y + c) as i64, ty), x < y + c))
+        }
+        _ => Err(unsupported(opcode, ty)),
+    }
+}
+
+/// Evaluate an arithmetic instruction with signed overflow detection.
+///
+/// Returns the result and the overflow flag.
+pub fn overflow(opcode: Opcode,
+                ty: Type,
+                x: &DataValue,
+                y: &DataValue)
+                -> Result<(DataValue, bool)> {
+    let (a, b) = (x.as_int() as i128, y.as_int() as i128);
+    let r = match opcode {
+        Opcode::IaddOv | Opcode::IaddOvTrap => a + b,
+        Opcode::IsubOv | Opcode::IsubOvTrap => a - b,
+        Opcode::ImulOv | Opcode::ImulOvTrap => a * b,
+        _ => return Err(unsupported(opcode, ty)),
+    };
+    let v = DataValue::int(r as i64, ty);
+    let of = v.as_int() as i128 != r;
+    Ok((v, of))
+}
+
+/// Evaluate a bitwise instruction on the bits of a non-integer lane.
+fn bitwise(opcode: Opcode, ty: Type, x: &DataValue, y: &DataValue) -> Result<DataValue> {
+    let (a, b) = match (x.to_bits(ty), y.to_bits(ty)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Err(unsupported(opcode, ty)),
+    };
+    let r = match opcode {
+        Opcode::Band => a & b,
+        Opcode::Bor => a | b,
+        Opcode::Bxor => a ^ b,
+        Opcode::Bnot => !a,
+        _ => return Err(unsupported(opcode, ty)),
+    };
+    DataValue::from_bits(r, ty).ok_or_else(|| unsupported(opcode, ty))
+}
+
+macro_rules! float_op {
+    ($name:ident, $t:ident) => {
+        /// Evaluate a floating point instruction, or return `None` for other opcodes.
+        fn $name(opcode: Opcode, args: &[$t]) -> Option<$t> {
+            let min = |a: $t, b: $t| if a == b {
+                // Only zeros with different signs compare equal and can be told apart.
+                if a.is_sign_negative() { a } else { b }
+            } else {
+                a.min(b)
+            };
+            let max = |a: $t, b: $t| if a == b {
+                if a.is_sign_positive() { a } else { b }
+            } else {
+                a.max(b)
+            };
+            let (a, b) = (args[0], args.get(1).cloned().unwrap_or(0.0));
+            Some(match opcode {
+                Opcode::Fadd => a + b,
+                Opcode::Fsub => a - b,
+                Opcode::Fmul => a * b,
+                Opcode::Fdiv => a / b,
+                Opcode::Fma => a.mul_add(b, args[2]),
+                Opcode::Fcopysign => a.copysign(b),
+                Opcode::Fmin if a.is_nan() || b.is_nan() => a + b,
+                Opcode::Fmin | Opcode::Fminnum => min(a, b),
+                Opcode::Fmax if a.is_nan() || b.is_nan() => a + b,
+                Opcode::Fmax | Opcode::Fmaxnum => max(a, b),
+                Opcode::Fneg => -a,
+                Opcode::Fabs => a.abs(),
+                Opcode::Sqrt => a.sqrt(),
+                Opcode::Ceil => a.ceil(),
+                Opcode::Floor => a.floor(),
+                Opcode::Trunc => a.trunc(),
+                Opcode::Nearest => {
+                    // `round()` rounds ties away from zero, but we want ties to even.
+                    let r = a.round();
+                    if (r - a).abs() == 0.5 {
+                        2.0 * (a / 2.0).round()
+                    } else {
+                        r
+                    }
+                }
+                _ => return None,
+            })
+        }
+    }
+}
+
+float_op!(float32, f32);
+float_op!(float64, f64);
+
+/// Evaluate a floating point instruction on a lane of type `ty`.
+fn float(opcode: Opcode, ty: Type, args: &[DataValue]) -> Result<DataValue> {
+    let r = if ty.bits() == 32 {
+        let args: Vec<f32> = args.iter().map(DataValue::as_f32).collect();
+        float32(opcode, &args).map(DataValue::f32)
+    } else {
+        let args: Vec<f64> = args.iter().map(DataValue::as_f64).collect();
+        float64(opcode, &args).map(DataValue::f64)
+    };
+    r.ok_or_else(|| unsupported(opcode, ty))
+}
+
+/// Evaluate a fused multiply-add on a lane of type `ty`.
+pub fn fma(ty: Type, args: &[DataValue]) -> Result<DataValue> {
+    float(Opcode::Fma, ty, args)
+}
+
+/// Compare two integer lanes of type `ty`.
+pub fn icmp(cond: IntCC, ty: Type, x: &DataValue, y: &DataValue) -> bool {
+    let bits = ty.lane_bits();
+    let (a, b) = (x.as_int(), y.as_int());
+    let (ua, ub) = (zext(a, bits), zext(b, bits));
+    match cond {
+        IntCC::Equal => a == b,
+        IntCC::NotEqual => a != b,
+        IntCC::SignedLessThan => a < b,
+        IntCC::SignedGreaterThanOrEqual => a >= b,
+        IntCC::SignedGreaterThan => a > b,
+        IntCC::SignedLessThanOrEqual => a <= b,
+        IntCC::UnsignedLessThan => ua < ub,
+        IntCC::UnsignedGreaterThanOrEqual => ua >= ub,
+        IntCC::UnsignedGreaterThan => ua > ub,
+        IntCC::UnsignedLessThanOrEqual => ua <= ub,
+    }
+}
+
+/// Compare two floating point lanes.
+pub fn fcmp(cond: FloatCC, x: &DataValue, y: &DataValue) -> bool {
+    // Promoting an `f32` to `f64` is exact.
+    let (a, b) = match (x, y) {
+        (&DataValue::F32(_), &DataValue::F32(_)) => (x.as_f32() as f64, y.as_f32() as f64),
+        _ => (x.as_f64(), y.as_f64()),
+    };
+    let uno = a.is_nan() || b.is_nan();
+    match cond {
+        FloatCC::Ordered => !uno,
+        FloatCC::Unordered => uno,
+        FloatCC::Equal => a == b,
+        FloatCC::NotEqual => a != b,
+        FloatCC::OrderedNotEqual => !uno && a != b,
+        FloatCC::UnorderedOrEqual => uno || a == b,
+        FloatCC::LessThan => a < b,
+        FloatCC::LessThanOrEqual => a <= b,
+        FloatCC::GreaterThan => a > b,
+        FloatCC::GreaterThanOrEqual => a >= b,
+        FloatCC::UnorderedOrLessThan => uno || a < b,
+        FloatCC::UnorderedOrLessThanOrEqual => uno || a <= b,
+        FloatCC::UnorderedOrGreaterThan => uno || a > b,
+        FloatCC::UnorderedOrGreaterThanOrEqual => uno || a >= b,
+    }
+}
+
+/// Convert a lane of type `from` to a lane of type `to`.
+pub fn convert(opcode: Opcode, from: Type, to: Type, x: &DataValue) -> Result<DataValue> {
+    Ok(match opcode {
+           Opcode::Breduce | Opcode::Bextend => DataValue::Bool(x.as_bool()),
+           Opcode::Bint => DataValue::int(x.as_bool() as i64, to),
+           Opcode::Bmask => DataValue::int(if x.as_bool() { -1 } else { 0 }, to),
+           Opcode::Ireduce | Opcode::Sextend => DataValue::int(x.as_int(), to),
+           Opcode::Uextend => DataValue::int(zext(x.as_int(), from.lane_bits()) as i64, to),
+           Opcode::Fpromote => DataValue::f64(x.as_f32() as f64),
+           Opcode::Fdemote => DataValue::f32(x.as_f64() as f32),
+           Opcode::FcvtToUint | Opcode::FcvtToSint => {
+               let f = if from.bits() == 32 {
+                   x.as_f32() as f64
+               } else {
+                   x.as_f64()
+               };
+               let t = f.trunc();
+               let bits = to.lane_bits() as i32;
+               let signed = opcode == Opcode::FcvtToSint;
+               let (min, max) = if signed {
+                   (-2f64.powi(bits - 1), 2f64.powi(bits - 1))
+               } else {
+                   (0.0, 2f64.powi(bits))
+               };
+               // This also catches NaN.
+               if !(t >= min && t < max) {
+                   return Err(Error::Trap(TrapCode::IntegerOverflow));
+               }
+               DataValue::int(if signed { t as i64 } else { t as u64 as i64 }, to)
+           }
+           Opcode::FcvtFromUint | Opcode::FcvtFromSint => {
+               let a = x.as_int();
+               let u = zext(a, from.lane_bits());
+               let unsigned = opcode == Opcode::FcvtFromUint;
+               if to.bits() == 32 {
+                   DataValue::f32(if unsigned { u as f32 } else { a as f32 })
+               } else {
+                   DataValue::f64(if unsigned { u as f64 } else { a as f64 })
+               }
+           }
+           _ => return Err(unsupported(opcode, to)),
+       })
+}
+
+/// Split an integer lane of type `ty` into its low and high halves.
+pub fn isplit(ty: Type, x: &DataValue) -> Result<(DataValue, DataValue)> {
+    let half = ty.half_width().ok_or_else(|| unsupported(Opcode::Isplit, ty))?;
+    let a = x.as_int();
+    Ok((DataValue::int(a, half), DataValue::int(a >> half.lane_bits(), half)))
+}
+
+/// Concatenate the low and high halves of an integer lane of type `ty`.
+pub fn iconcat(ty: Type, lo: &DataValue, hi: &DataValue) -> Result<DataValue> {
+    let half = ty.half_width().ok_or_else(|| unsupported(Opcode::Iconcat, ty))?;
+    let bits = half.lane_bits();
+    let r = zext(lo.as_int(), bits) | (zext(hi.as_int(), bits) << bits);
+    Ok(DataValue::int(r as i64, ty))
+}
+
+/// Get the error for an instruction the interpreter can't evaluate.
+pub fn unsupported(opcode: Opcode, ty: Type) -> Error {
+    Error::Unsupported(format!("{}.{}", opcode, ty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne::ir::types::*;
+
+    fn int(x: i64) -> DataValue {
+        DataValue::Int(x)
+    }
+
+    #[test]
+    fn integer() {
+        let bin = |op, ty, x, y| binary(op, ty, &int(x), &int(y));
+        assert_eq!(bin(Opcode::Iadd, I8, 127, 1), Ok(int(-128)));
+        assert_eq!(bin(Opcode::Umulhi, I32, -1, -1), Ok(int(-2)));
+        assert_eq!(bin(Opcode::Smulhi, I32, -1, -1), Ok(int(0)));
+        assert_eq!(bin(Opcode::Udiv, I16, -2, 2), Ok(int(0x7fff)));
+        assert_eq!(bin(Opcode::Sdiv, I16, -7, 2), Ok(int(-3)));
+        assert_eq!(bin(Opcode::Srem, I16, -7, 2), Ok(int(-1)));
+        assert_eq!(bin(Opcode::Urem, I64, 7, 0),
+                   Err(Error::Trap(TrapCode::IntegerDivisionByZero)));
+        assert_eq!(bin(Opcode::Sdiv, I32, -0x8000_0000, -1),
+                   Err(Error::Trap(TrapCode::IntegerOverflow)));
+        assert_eq!(bin(Opcode::Sdiv, I64, i64::min_value(), -1),
+                   Err(Error::Trap(TrapCode::IntegerOverflow)));
+        assert_eq!(bin(Opcode::Srem, I64, i64::min_value(), -1), Ok(int(0)));
+        assert_eq!(bin(Opcode::Rotl, I8, 0x81, 1), Ok(int(3)));
+        assert_eq!(bin(Opcode::Rotr, I8, 3, 9), Ok(int(-127)));
+        assert_eq!(bin(Opcode::Ishl, I32, 1, 33), Ok(int(2)));
+        assert_eq!(bin(Opcode::Ushr, I8, -128, 7), Ok(int(1)));
+        assert_eq!(bin(Opcode::Sshr, I8, -128, 7), Ok(int(-1)));
+        assert_eq!(binary_imm(Opcode::IrsubImm, I32, &int(3), 10), Ok(int(7)));
+
+        assert_eq!(unary(Opcode::Clz, I32, &int(1)), Ok(int(31)));
+        assert_eq!(unary(Opcode::Cls, I16, &int(0)), Ok(int(15)));
+        assert_eq!(unary(Opcode::Cls, I16, &int(-2)), Ok(int(14)));
+        assert_eq!(unary(Opcode::Ctz, I8, &int(0)), Ok(int(8)));
+        assert_eq!(unary(Opcode::Popcnt, I16, &int(-1)), Ok(int(16)));
+        assert_eq!(unary(Opcode::Bnot, B1, &DataValue::Bool(true)),
+                   Ok(DataValue::Bool(false)));
+
+        assert_eq!(carry(Opcode::IaddCout, I8, &[int(-1), int(1)]),
+                   Ok((int(0), true)));
+        assert_eq!(carry(Opcode::IsubBorrow, I8, &[int(1), int(1), DataValue::Bool(true)]),
+                   Ok((int(-1), true)));
+        assert_eq!(overflow(Opcode::IaddOv, I32, &int(0x7fff_ffff), &int(1)),
+                   Ok((int(-0x8000_0000), true)));
+        assert_eq!(overflow(Opcode::ImulOv, I64, &int(1 << 32), &int(1 << 31)),
+                   Ok((int(i64::min_value()), true)));
+        assert_eq!(overflow(Opcode::IsubOv, I16, &int(5), &int(7)), Ok((int(-2), false)));
+
+        assert!(icmp(IntCC::UnsignedLessThan, I8, &int(1), &int(-1)));
+        assert!(!icmp(IntCC::SignedLessThan, I8, &int(1), &int(-1)));
+    }
+
+    #[test]
+    fn floats() {
+        let f = |x| DataValue::f64(x);
+        let bin = |op, x, y| binary(op, F64, &f(x), &f(y));
+        assert_eq!(bin(Opcode::Fadd, 1.5, 2.0), Ok(f(3.5)));
+        assert_eq!(bin(Opcode::Fmin, -0.0, 0.0), Ok(f(-0.0)));
+        assert_eq!(bin(Opcode::Fmax, -0.0, 0.0), Ok(f(0.0)));
+        assert_eq!(bin(Opcode::Fminnum, ::std::f64::NAN, 1.0), Ok(f(1.0)));
+        assert!(bin(Opcode::Fmin, ::std::f64::NAN, 1.0).unwrap().as_f64().is_nan());
+        assert_eq!(bin(Opcode::Fcopysign, 2.0, -0.0), Ok(f(-2.0)));
+        assert_eq!(unary(Opcode::Nearest, F64, &f(2.5)), Ok(f(2.0)));
+        assert_eq!(unary(Opcode::Nearest, F64, &f(-3.5)), Ok(f(-4.0)));
+        assert_eq!(unary(Opcode::Nearest, F64, &f(2.4)), Ok(f(2.0)));
+        assert_eq!(unary(Opcode::Bnot, F32, &DataValue::f32(0.0)),
+                   Ok(DataValue::F32(::cretonne::ir::immediates::Ieee32::with_bits(!0))));
+        assert_eq!(fma(F32, &[DataValue::f32(2.0), DataValue::f32(3.0), DataValue::f32(1.0)]),
+                   Ok(DataValue::f32(7.0)));
+
+        let nan = f(::std::f64::NAN);
+        assert!(fcmp(FloatCC::Unordered, &nan, &f(1.0)));
+        assert!(fcmp(FloatCC::NotEqual, &nan, &nan));
+        assert!(!fcmp(FloatCC::OrderedNotEqual, &nan, &f(1.0)));
+        assert!(fcmp(FloatCC::UnorderedOrLessThan, &nan, &f(1.0)));
+        assert!(fcmp(FloatCC::LessThan, &f(-1.0), &f(1.0)));
+    }
+
+    #[test]
+    fn conversions() {
+        let f = |x| DataValue::f64(x);
+        assert_eq!(convert(Opcode::Uextend, I8, I32, &int(-1)), Ok(int(255)));
+        assert_eq!(convert(Opcode::Sextend, I8, I32, &int(-1)), Ok(int(-1)));
+        assert_eq!(convert(Opcode::Ireduce, I32, I8, &int(0x1ff)), Ok(int(-1)));
+        assert_eq!(convert(Opcode::Bmask, B1, I16, &DataValue::Bool(true)), Ok(int(-1)));
+        assert_eq!(convert(Opcode::Bint, B1, I16, &DataValue::Bool(true)), Ok(int(1)));
+        assert_eq!(convert(Opcode::FcvtToSint, F64, I32, &f(-2.9)), Ok(int(-2)));
+        assert_eq!(convert(Opcode::FcvtToUint, F64, I32, &f(-0.5)), Ok(int(0)));
+        assert_eq!(convert(Opcode::FcvtToUint, F64, I8, &f(255.5)), Ok(int(-1)));
+        assert_eq!(convert(Opcode::FcvtToUint, F64, I8, &f(256.0)),
+                   Err(Error::Trap(TrapCode::IntegerOverflow)));
+        assert_eq!(convert(Opcode::FcvtToSint, F64, I64, &f(::std::f64::NAN)),
+                   Err(Error::Trap(TrapCode::IntegerOverflow)));
+        assert_eq!(convert(Opcode::FcvtFromUint, I32, F64, &int(-1)), Ok(f(4294967295.0)));
+        assert_eq!(convert(Opcode::FcvtFromSint, I32, F64, &int(-1)), Ok(f(-1.0)));
+        assert_eq!(isplit(I64, &int(0x1_0000_0002)), Ok((int(2), int(1))));
+        assert_eq!(iconcat(I64, &int(-1), &int(1)), Ok(int(0x1_ffff_ffff)));
+    }
+
+    #[test]
+    fn lanes() {
+        let v = DataValue::Vector(vec![int(1), int(-1)]);
+        let r = lanewise(I64X2,
+                         &[v, int(1)],
+                         |ty, xs| binary(Opcode::Ishl, ty, &xs[0], &xs[1]));
+        assert_eq!(r, Ok(DataValue::Vector(vec![int(2), int(-2)])));
+    }
+}
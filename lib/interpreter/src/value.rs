@@ -0,0 +1,311 @@
+//! Values computed by the interpreter.
+
+use cretonne::ir::Type;
+use cretonne::ir::immediates::{Imm64, Ieee32, Ieee64};
+use std::fmt::{self, Display, Formatter};
+
+/// A value of a Cretonne type.
+///
+/// Integers of all widths are stored sign-extended to 64 bits, so two values of the same type are
+/// equal exactly when they have the same bits. Floating point values are stored as their bit
+/// patterns for the same reason.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DataValue {
+    /// A boolean of any width.
+    Bool(bool),
+    /// An integer of at most 64 bits.
+    Int(i64),
+    /// A 32-bit floating point number.
+    F32(Ieee32),
+    /// A 64-bit floating point number.
+    F64(Ieee64),
+    /// A vector of lanes.
+    Vector(Vec<DataValue>),
+}
+
+impl DataValue {
+    /// Create an integer value of type `ty` from the low bits of `x`.
+    pub fn int(x: i64, ty: Type) -> DataValue {
+        DataValue::Int(sext(x, ty.lane_bits()))
+    }
+
+    /// Create a 32-bit float value.
+    pub fn f32(x: f32) -> DataValue {
+        DataValue::F32(Ieee32::new(x))
+    }
+
+    /// Create a 64-bit float value.
+    pub fn f64(x: f64) -> DataValue {
+        DataValue::F64(Ieee64::new(x))
+    }
+
+    /// Parse a value of type `ty`.
+    ///
+    /// Integers and floats use the syntax of the `iconst`, `f32const`, and `f64const` immediates,
+    /// booleans are `true` or `false`, and vectors are written as a list of lanes in brackets,
+    /// like `[1 2 3 4]`.
+    pub fn parse(text: &str, ty: Type) -> Result<DataValue, String> {
+        let text = text.trim();
+        if ty.is_scalar() {
+            return parse_scalar(text, ty);
+        }
+        if !text.starts_with('[') || !text.ends_with(']') {
+            return Err(format!("expected a {} vector in brackets, got '{}'", ty, text));
+        }
+        let lanes = text[1..text.len() - 1]
+            .split_whitespace()
+            .map(|lane| parse_scalar(lane, ty.lane_type()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if lanes.len() != ty.lane_count() as usize {
+            return Err(format!("expected {} lanes for {}, got {}",
+                               ty.lane_count(),
+                               ty,
+                               lanes.len()));
+        }
+        Ok(DataValue::Vector(lanes))
+    }
+
+    /// Is this a valid value of type `ty`?
+    pub fn is_of_type(&self, ty: Type) -> bool {
+        match *self {
+            DataValue::Bool(_) => ty.is_scalar() && ty.is_bool(),
+            DataValue::Int(x) => {
+                ty.is_scalar() && ty.is_int() && ty.bits() <= 64 && x == sext(x, ty.lane_bits())
+            }
+            DataValue::F32(_) => ty == ::cretonne::ir::types::F32,
+            DataValue::F64(_) => ty == ::cretonne::ir::types::F64,
+            DataValue::Vector(ref lanes) => {
+                !ty.is_scalar() && lanes.len() == ty.lane_count() as usize &&
+                lanes.iter().all(|l| l.is_of_type(ty.lane_type()))
+            }
+        }
+    }
+
+    /// Get the value of a boolean, or the truth of an integer.
+    pub fn as_bool(&self) -> bool {
+        match *self {
+            DataValue::Bool(b) => b,
+            DataValue::Int(x) => x != 0,
+            _ => panic!("expected a boolean, got {}", self),
+        }
+    }
+
+    /// Get the sign-extended value of an integer.
+    pub fn as_int(&self) -> i64 {
+        match *self {
+            DataValue::Int(x) => x,
+            _ => panic!("expected an integer, got {}", self),
+        }
+    }
+
+    /// Get the value of a 32-bit float.
+    pub fn as_f32(&self) -> f32 {
+        match *self {
+            DataValue::F32(x) => f32::from_bits(x.bits()),
+            _ => panic!("expected an f32, got {}", self),
+        }
+    }
+
+    /// Get the value of a 64-bit float.
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            DataValue::F64(x) => f64::from_bits(x.bits()),
+            _ => panic!("expected an f64, got {}", self),
+        }
+    }
+
+    /// Get the lanes of a vector.
+    pub fn lanes(&self) -> &[DataValue] {
+        match *self {
+            DataValue::Vector(ref lanes) => lanes,
+            _ => panic!("expected a vector, got {}", self),
+        }
+    }
+
+    /// Get the bits of a scalar of type `ty`, zero-extended to 64 bits.
+    ///
+    /// Booleans are all ones when true. Returns `None` for types that aren't scalars of at most 64
+    /// bits.
+    pub fn to_bits(&self, ty: Type) -> Option<u64> {
+        if !ty.is_scalar() || ty.bits() > 64 {
+            return None;
+        }
+        let bits = ty.lane_bits();
+        match *self {
+            DataValue::Bool(b) => Some(if b { zext(-1, bits) } else { 0 }),
+            DataValue::Int(x) => Some(zext(x, bits)),
+            DataValue::F32(x) => Some(x.bits() as u64),
+            DataValue::F64(x) => Some(x.bits()),
+            DataValue::Vector(_) => None,
+        }
+    }
+
+    /// Create a scalar of type `ty` from its bits.
+    ///
+    /// A boolean is true if any bit is set.
+    pub fn from_bits(bits: u64, ty: Type) -> Option<DataValue> {
+        if !ty.is_scalar() || ty.bits() > 64 {
+            None
+        } else if ty.is_int() {
+            Some(DataValue::int(bits as i64, ty))
+        } else if ty.is_bool() {
+            Some(DataValue::Bool(zext(bits as i64, ty.lane_bits()) != 0))
+        } else if ty.bits() == 32 {
+            Some(DataValue::F32(Ieee32::with_bits(bits as u32)))
+        } else {
+            Some(DataValue::F64(Ieee64::with_bits(bits)))
+        }
+    }
+
+    /// Encode this value of type `ty` as little-endian bytes in `bytes`.
+    ///
+    /// The length of `bytes` must be the size of `ty` in bytes.
+    pub fn write_bytes(&self, ty: Type, bytes: &mut [u8]) -> Option<()> {
+        if ty.is_scalar() {
+            let bits = self.to_bits(ty)?;
+            for (i, b) in bytes.iter_mut().enumerate() {
+                *b = (bits >> (8 * i)) as u8;
+            }
+            return Some(());
+        }
+        let lane_size = ty.lane_bits() as usize / 8;
+        for (lane, chunk) in self.lanes().iter().zip(bytes.chunks_mut(lane_size)) {
+            lane.write_bytes(ty.lane_type(), chunk)?;
+        }
+        Some(())
+    }
+
+    /// Decode a value of type `ty` from little-endian `bytes`.
+    pub fn read_bytes(ty: Type, bytes: &[u8]) -> Option<DataValue> {
+        if ty.is_scalar() {
+            let bits = bytes
+                .iter()
+                .rev()
+                .fold(0u64, |acc, &b| (acc << 8) | b as u64);
+            return DataValue::from_bits(bits, ty);
+        }
+        let lane_size = ty.lane_bits() as usize / 8;
+        bytes
+            .chunks(lane_size)
+            .map(|chunk| DataValue::read_bytes(ty.lane_type(), chunk))
+            .collect::<Option<Vec<_>>>()
+            .map(DataValue::Vector)
+    }
+}
+
+impl Display for DataValue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            DataValue::Bool(b) => write!(f, "{}", b),
+            DataValue::Int(x) => write!(f, "{}", x),
+            DataValue::F32(x) => write!(f, "{}", x),
+            DataValue::F64(x) => write!(f, "{}", x),
+            DataValue::Vector(ref lanes) => {
+                write!(f, "[")?;
+                for (i, lane) in lanes.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", lane)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Parse a scalar value of type `ty`.
+fn parse_scalar(text: &str, ty: Type) -> Result<DataValue, String> {
+    if ty.is_bool() {
+        match text {
+            "true" => Ok(DataValue::Bool(true)),
+            "false" => Ok(DataValue::Bool(false)),
+            _ => Err(format!("expected a boolean, got '{}'", text)),
+        }
+    } else if ty.is_int() {
+        if ty.bits() > 64 {
+            return Err(format!("{} values are not supported", ty));
+        }
+        let imm: Imm64 = text.parse()
+            .map_err(|e| format!("bad {} value '{}': {}", ty, text, e))?;
+        Ok(DataValue::int(imm.into(), ty))
+    } else if ty.is_float() && ty.bits() == 32 {
+        text.parse()
+            .map(DataValue::F32)
+            .map_err(|e| format!("bad f32 value '{}': {}", text, e))
+    } else if ty.is_float() {
+        text.parse()
+            .map(DataValue::F64)
+            .map_err(|e| format!("bad f64 value '{}': {}", text, e))
+    } else {
+        Err(format!("can't parse a value of type {}", ty))
+    }
+}
+
+/// Sign-extend the low `bits` bits of `x`.
+pub fn sext(x: i64, bits: u8) -> i64 {
+    if bits >= 64 {
+        x
+    } else {
+        let shift = 64 - bits as u32;
+        (x << shift) >> shift
+    }
+}
+
+/// Zero-extend the low `bits` bits of `x`.
+pub fn zext(x: i64, bits: u8) -> u64 {
+    if bits >= 64 {
+        x as u64
+    } else {
+        x as u64 & ((1 << bits) - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cretonne::ir::types::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(DataValue::parse("3", I32), Ok(DataValue::Int(3)));
+        assert_eq!(DataValue::parse("0xffff_ffff", I32), Ok(DataValue::Int(-1)));
+        assert_eq!(DataValue::parse("0xff", I8), Ok(DataValue::Int(-1)));
+        assert_eq!(DataValue::parse("true", B1), Ok(DataValue::Bool(true)));
+        assert_eq!(DataValue::parse("0x1.8p0", F32), Ok(DataValue::f32(1.5)));
+        assert_eq!(DataValue::parse("-0x1.0p1", F64), Ok(DataValue::f64(-2.0)));
+        assert_eq!(DataValue::parse("[1 2 -3 4]", I32X4),
+                   Ok(DataValue::Vector(vec![DataValue::Int(1),
+                                             DataValue::Int(2),
+                                             DataValue::Int(-3),
+                                             DataValue::Int(4)])));
+        assert!(DataValue::parse("[1 2 3]", I32X4).is_err());
+        assert!(DataValue::parse("1", I32X4).is_err());
+        assert!(DataValue::parse("yes", B1).is_err());
+        assert!(DataValue::parse("1", I128).is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(DataValue::Int(-7).to_string(), "-7");
+        assert_eq!(DataValue::f32(1.5).to_string(), "0x1.800000p0");
+        assert_eq!(DataValue::parse("[true false]", B64X2).unwrap().to_string(),
+                   "[true false]");
+    }
+
+    #[test]
+    fn bytes() {
+        let mut buf = [0; 8];
+        DataValue::Int(-2).write_bytes(I32, &mut buf[0..4]).unwrap();
+        assert_eq!(buf, [0xfe, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+        assert_eq!(DataValue::read_bytes(I32, &buf[0..4]), Some(DataValue::Int(-2)));
+        assert_eq!(DataValue::read_bytes(I16X4, &buf),
+                   Some(DataValue::Vector(vec![DataValue::Int(-2),
+                                               DataValue::Int(-1),
+                                               DataValue::Int(0),
+                                               DataValue::Int(0)])));
+        assert_eq!(DataValue::read_bytes(F32, &buf[4..8]), Some(DataValue::f32(0.0)));
+        DataValue::f64(1.0).write_bytes(F64, &mut buf).unwrap();
+        assert_eq!(DataValue::read_bytes(F64, &buf), Some(DataValue::f64(1.0)));
+    }
+}
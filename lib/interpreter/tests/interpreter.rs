@@ -0,0 +1,268 @@
+extern crate cretonne;
+extern crate cton_interpreter;
+extern crate cton_reader;
+
+use cretonne::ir::{Function, FunctionName, TrapCode};
+use cton_interpreter::{Interpreter, Error, DataValue};
+use cton_reader::parse_test;
+
+/// Parse all the functions in `source`.
+fn parse(source: &str) -> Vec<Function> {
+    parse_test(source)
+        .unwrap()
+        .functions
+        .into_iter()
+        .map(|(func, _)| func)
+        .collect()
+}
+
+/// Create an interpreter for `funcs`.
+fn interpreter(funcs: &[Function]) -> Interpreter {
+    let mut interp = Interpreter::new();
+    for func in funcs {
+        interp.add_function(func).unwrap();
+    }
+    interp
+}
+
+fn name(s: &str) -> FunctionName {
+    FunctionName::new(s)
+}
+
+fn ints(xs: &[i64]) -> Vec<DataValue> {
+    xs.iter().map(|&x| DataValue::Int(x)).collect()
+}
+
+#[test]
+fn arithmetic() {
+    let funcs = parse("
+        function %muladd(i32, i32, i32) -> i32 {
+        ebb0(v0: i32, v1: i32, v2: i32):
+            v3 = imul v0, v1
+            v4 = iadd v3, v2
+            return v4
+        }
+
+        function %narrow(i8) -> i8, b1 {
+        ebb0(v0: i8):
+            v1 = iadd_imm v0, 100
+            v2 = icmp_imm slt v1, 0
+            return v1, v2
+        }
+    ");
+    let mut interp = interpreter(&funcs);
+    assert_eq!(interp.call(&name("muladd"), &ints(&[3, 4, 5])), Ok(ints(&[17])));
+    assert_eq!(interp.call(&name("muladd"), &ints(&[0x10000, 0x10000, 1])),
+               Ok(ints(&[1])));
+    assert_eq!(interp.call(&name("narrow"), &ints(&[100])),
+               Ok(vec![DataValue::Int(-56), DataValue::Bool(true)]));
+}
+
+#[test]
+fn control_flow() {
+    let funcs = parse("
+        ; Sum the numbers from 1 to n.
+        function %sum(i32) -> i32 {
+        ebb0(v0: i32):
+            v1 = iconst.i32 0
+            jump ebb1(v0, v1)
+
+        ebb1(v2: i32, v3: i32):
+            brz v2, ebb2(v3)
+            v4 = iadd v3, v2
+            v5 = iadd_imm v2, -1
+            jump ebb1(v5, v4)
+
+        ebb2(v6: i32):
+            return v6
+        }
+
+        function %table(i32) -> i32 {
+            jt0 = jump_table ebb1, 0, ebb2
+        ebb0(v0: i32):
+            br_table v0, jt0
+            v1 = iconst.i32 -1
+            return v1
+        ebb1:
+            v2 = iconst.i32 10
+            return v2
+        ebb2:
+            v3 = iconst.i32 20
+            return v3
+        }
+    ");
+    let mut interp = interpreter(&funcs);
+    assert_eq!(interp.call(&name("sum"), &ints(&[0])), Ok(ints(&[0])));
+    assert_eq!(interp.call(&name("sum"), &ints(&[100])), Ok(ints(&[5050])));
+    assert_eq!(interp.call(&name("table"), &ints(&[0])), Ok(ints(&[10])));
+    assert_eq!(interp.call(&name("table"), &ints(&[1])), Ok(ints(&[-1])));
+    assert_eq!(interp.call(&name("table"), &ints(&[2])), Ok(ints(&[20])));
+    assert_eq!(interp.call(&name("table"), &ints(&[-1])), Ok(ints(&[-1])));
+
+    interp.set_step_limit(Some(100));
+    assert_eq!(interp.call(&name("sum"), &ints(&[100])), Err(Error::StepLimit));
+}
+
+#[test]
+fn calls() {
+    let funcs = parse("
+        function %fib(i64) -> i64 {
+            fn0 = function %fib(i64) -> i64
+        ebb0(v0: i64):
+            v1 = icmp_imm ult v0, 2
+            brnz v1, ebb1
+            v2 = iadd_imm v0, -1
+            v3 = call fn0(v2)
+            v4 = iadd_imm v0, -2
+            v5 = call fn0(v4)
+            v6 = iadd v3, v5
+            return v6
+        ebb1:
+            return v0
+        }
+
+        function %forever(i64) {
+            fn0 = function %forever(i64)
+        ebb0(v0: i64):
+            call fn0(v0)
+            return
+        }
+
+        function %missing() {
+            fn0 = function %nowhere()
+        ebb0:
+            call fn0()
+            return
+        }
+    ");
+    let mut interp = interpreter(&funcs);
+    assert_eq!(interp.call(&name("fib"), &ints(&[20])), Ok(ints(&[6765])));
+    assert_eq!(interp.call(&name("forever"), &ints(&[0])),
+               Err(Error::Trap(TrapCode::StackOverflow)));
+    assert_eq!(interp.call(&name("missing"), &[]),
+               Err(Error::UnknownFunction(name("nowhere"))));
+    assert_eq!(interp.call(&name("nothing"), &[]),
+               Err(Error::UnknownFunction(name("nothing"))));
+    assert!(interp.add_function(&funcs[0]).is_err());
+
+    // Argument checking.
+    match interp.call(&name("fib"), &[]) {
+        Err(Error::BadArguments(_)) => {}
+        r => panic!("unexpected {:?}", r),
+    }
+    match interp.call(&name("fib"), &[DataValue::f64(1.0)]) {
+        Err(Error::BadArguments(_)) => {}
+        r => panic!("unexpected {:?}", r),
+    }
+}
+
+#[test]
+fn memory() {
+    let funcs = parse("
+        function %swap(i32, i32) {
+        ebb0(v0: i32, v1: i32):
+            v2 = load.i32 v0
+            v3 = load.i32 v1
+            store v3, v0
+            store v2, v1
+            return
+        }
+
+        function %bytes(i64) -> i32, i32 {
+        ebb0(v0: i64):
+            v1 = uload8.i32 v0+1
+            v2 = sload16.i32 v0+2
+            return v1, v2
+        }
+
+        function %slots(i32) -> i32 {
+            ss0 = stack_slot 8
+        ebb0(v0: i32):
+            stack_store v0, ss0+4
+            v1 = stack_addr.i32 ss0
+            v2 = load.i32 v1+4
+            v3 = iadd v2, v2
+            return v3
+        }
+    ");
+    let mut interp = interpreter(&funcs);
+    interp.heap_mut()[0..8].copy_from_slice(&[1, 2, 0xfe, 0xff, 3, 4, 5, 6]);
+    assert_eq!(interp.call(&name("swap"), &ints(&[0, 4])), Ok(vec![]));
+    assert_eq!(&interp.heap()[0..8], &[3, 4, 5, 6, 1, 2, 0xfe, 0xff]);
+    assert_eq!(interp.call(&name("bytes"), &ints(&[4])), Ok(ints(&[2, -2])));
+
+    let end = interp.heap().len() as i64;
+    assert_eq!(interp.call(&name("bytes"), &ints(&[-2])),
+               Err(Error::Trap(TrapCode::HeapOutOfBounds)));
+    assert_eq!(interp.call(&name("swap"), &ints(&[0, 0x7fff_ffff])),
+               Err(Error::Trap(TrapCode::HeapOutOfBounds)));
+    // The stack slots follow the heap.
+    assert_eq!(interp.call(&name("bytes"), &ints(&[end])), Ok(ints(&[0, 0])));
+
+    assert_eq!(interp.call(&name("slots"), &ints(&[21])), Ok(ints(&[42])));
+}
+
+#[test]
+fn traps() {
+    let funcs = parse("
+        function %div(i32, i32) -> i32 {
+        ebb0(v0: i32, v1: i32):
+            v2 = sdiv v0, v1
+            return v2
+        }
+
+        function %check(i32) {
+        ebb0(v0: i32):
+            trapz v0, user7
+            return
+        }
+
+        function %convert(f64) -> i8 {
+        ebb0(v0: f64):
+            v1 = fcvt_to_sint.i8 v0
+            return v1
+        }
+    ");
+    let mut interp = interpreter(&funcs);
+    assert_eq!(interp.call(&name("div"), &ints(&[-7, 2])), Ok(ints(&[-3])));
+    assert_eq!(interp.call(&name("div"), &ints(&[1, 0])),
+               Err(Error::Trap(TrapCode::IntegerDivisionByZero)));
+    assert_eq!(interp.call(&name("div"), &ints(&[-0x8000_0000, -1])),
+               Err(Error::Trap(TrapCode::IntegerOverflow)));
+    assert_eq!(interp.call(&name("check"), &ints(&[1])), Ok(vec![]));
+    assert_eq!(interp.call(&name("check"), &ints(&[0])),
+               Err(Error::Trap(TrapCode::User(7))));
+    assert_eq!(interp.call(&name("convert"), &[DataValue::f64(-128.5)]),
+               Ok(ints(&[-128])));
+    assert_eq!(interp.call(&name("convert"), &[DataValue::f64(128.0)]),
+               Err(Error::Trap(TrapCode::IntegerOverflow)));
+}
+
+#[test]
+fn vectors() {
+    let funcs = parse("
+        function %vadd(i32x4, i32x4) -> i32x4, i32 {
+        ebb0(v0: i32x4, v1: i32x4):
+            v2 = iadd v0, v1
+            v3 = extractlane v2, 3
+            return v2, v3
+        }
+
+        function %build(f32) -> f32x4 {
+        ebb0(v0: f32):
+            v1 = splat.f32x4 v0
+            v2 = fadd v1, v1
+            v3 = insertlane v2, 0, v0
+            return v3
+        }
+    ");
+    let mut interp = interpreter(&funcs);
+    let i32x4 = |s| DataValue::parse(s, cretonne::ir::types::I32X4).unwrap();
+    assert_eq!(interp.call(&name("vadd"), &[i32x4("[1 2 3 4]"), i32x4("[10 20 30 -4]")]),
+               Ok(vec![i32x4("[11 22 33 0]"), DataValue::Int(0)]));
+    assert_eq!(interp.call(&name("build"), &[DataValue::f32(1.5)]),
+               Ok(vec![DataValue::Vector(vec![DataValue::f32(1.5),
+                                              DataValue::f32(3.0),
+                                              DataValue::f32(3.0),
+                                              DataValue::f32(3.0)])]));
+}
@@ -0,0 +1,414 @@
+//! Compiling and linking functions and data objects in memory.
+
+use cretonne::Context;
+use cretonne::binemit::{self, CodeOffset, Reloc, RelocSink, NullTrapSink};
+use cretonne::ir::{Function, FunctionName, FuncRef, GlobalVar, GlobalVarData, JumpTable, Constant};
+use cretonne::isa::TargetIsa;
+use cretonne::module::{Backend, DataObject};
+use memory::Memory;
+use std::collections::HashMap;
+use std::str;
+
+/// Alignment of the compiled functions in memory.
+const FUNCTION_ALIGNMENT: usize = 16;
+
+/// Size of a stub that jumps to a host function.
+///
+/// The stub is a `jmp *0(%rip)` instruction followed by the 8-byte absolute address of the host
+/// function, padded to 16 bytes. Direct calls only have a 32-bit displacement, so calls to the
+/// host go through a stub placed after the compiled code.
+const STUB_SIZE: usize = 16;
+
+/// JIT compiler for a set of functions.
+///
+/// Functions are compiled as they are added with `add_function()`, or they can be compiled by a
+/// `Module` which uses the JIT as its `Backend`. When all the functions have been added, `finish()`
+/// copies the code into executable memory and links it.
+///
+/// Data objects added with `add_data()` are copied into separate memory which is made read-only
+/// unless the data is writable.
+///
+/// Each function and data object is identified by its `FunctionName`. Calls to functions that have
+/// not been added to the JIT are resolved to host functions by the callback passed to `finish()`,
+/// and so are the addresses of `globalsym` global variables and pointers in data objects that
+/// don't refer to the added functions and data.
+///
+/// The register allocator doesn't support values that are live across calls or spilling yet, and
+/// no stack frames are created, so the stack pointer is not kept 16-byte aligned for calls to host
+/// functions.
+pub struct JIT<'a> {
+    isa: &'a TargetIsa,
+    functions: Vec<Emitted>,
+    index: HashMap<Vec<u8>, usize>,
+    data: Vec<DataObject>,
+    data_index: HashMap<Vec<u8>, usize>,
+}
+
+/// The machine code for a function that hasn't been linked yet.
+struct Emitted {
+    name: FunctionName,
+    code: Vec<u8>,
+    relocs: Vec<(CodeOffset, Reloc, Link)>,
+}
+
+/// What a relocation in emitted code refers to.
+enum Link {
+    /// A function which is either compiled by the JIT or provided by the host.
+    Func(Vec<u8>),
+    /// A data object or a host symbol.
+    Sym(Vec<u8>),
+    /// An offset into the function's own code.
+    Local(CodeOffset),
+}
+
+/// Compiled code for a set of functions in executable memory, along with their data.
+///
+/// The memory is released when the `Code` is dropped, so the pointers returned by `get()` must not
+/// be used after that.
+pub struct Code {
+    memory: Memory,
+    // The data memory is only kept mapped for the code to use.
+    _rodata: Memory,
+    _data: Memory,
+    symbols: HashMap<Vec<u8>, usize>,
+}
+
+/// Where a relocation is pointing.
+enum Target {
+    Func(FuncRef),
+    GlobalSym(GlobalVar),
+    JumpTable(JumpTable),
+    Constant(Constant),
+}
+
+/// A `RelocSink` that records all the relocations for a function.
+struct FunctionRelocs(Vec<(CodeOffset, Reloc, Target)>);
+
+impl RelocSink for FunctionRelocs {
+    fn reloc_ebb(&mut self, _offset: CodeOffset, _reloc: Reloc, _ebb_offset: CodeOffset) {
+        panic!("EBB relocations are resolved by emit_to_memory()");
+    }
+
+    fn reloc_func(&mut self, offset: CodeOffset, reloc: Reloc, func: FuncRef) {
+        self.0.push((offset, reloc, Target::Func(func)));
+    }
+
+    fn reloc_globalsym(&mut self, offset: CodeOffset, reloc: Reloc, gv: GlobalVar) {
+        self.0.push((offset, reloc, Target::GlobalSym(gv)));
+    }
+
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+        self.0.push((offset, reloc, Target::JumpTable(jt)));
+    }
+
+    fn reloc_constant(&mut self, offset: CodeOffset, reloc: Reloc, constant: Constant) {
+        self.0.push((offset, reloc, Target::Constant(constant)));
+    }
+}
+
+/// Get a symbol name as a string for the host callback and error messages.
+fn symbol_str(name: &[u8]) -> Result<&str, String> {
+    if name.is_empty() {
+        return Err("Can't link an anonymous symbol".to_string());
+    }
+    str::from_utf8(name).map_err(|_| format!("Invalid symbol name: {:?}", name))
+}
+
+impl<'a> JIT<'a> {
+    /// Create a new JIT compiler for functions compiled for `isa`.
+    ///
+    /// The ISA must be `intel` with the `is_64bit` setting enabled so the code can run on the
+    /// host.
+    pub fn new(isa: &'a TargetIsa) -> Result<JIT<'a>, String> {
+        if isa.name() != "intel" || !isa.flags().is_64bit() {
+            return Err(format!("Can't JIT compile for {}, need 64-bit intel", isa.name()));
+        }
+        Ok(JIT {
+               isa,
+               functions: Vec::new(),
+               index: HashMap::new(),
+               data: Vec::new(),
+               data_index: HashMap::new(),
+           })
+    }
+
+    /// Compile `func` and add it to the set of functions.
+    pub fn add_function(&mut self, func: Function) -> Result<(), String> {
+        let mut ctx = Context::new();
+        ctx.func = func;
+        let size = ctx.compile(self.isa)
+            .map_err(|e| format!("{}: {}", ctx.func.name, e))?;
+        self.define_function(&ctx.func, size)
+    }
+
+    /// Add a data object.
+    ///
+    /// The alignment of the data can't be larger than the page size.
+    pub fn add_data(&mut self, data: DataObject) -> Result<(), String> {
+        let name = data.name.as_ref().to_vec();
+        symbol_str(&name)?;
+        if self.is_defined(&name) {
+            return Err(format!("Duplicate definition of {}", data.name));
+        }
+        self.data_index.insert(name, self.data.len());
+        self.data.push(data);
+        Ok(())
+    }
+
+    /// Has a function or data object named `name` been added?
+    fn is_defined(&self, name: &[u8]) -> bool {
+        self.index.contains_key(name) || self.data_index.contains_key(name)
+    }
+
+    /// Copy the compiled functions into executable memory and the data objects into data memory,
+    /// and link them.
+    ///
+    /// The `lookup` callback is called with the name of every symbol referenced by the functions
+    /// and data that is not one of the compiled functions or data objects. It should return the
+    /// address of the host function or data with that name.
+    pub fn finish<F>(self, mut lookup: F) -> Result<Code, String>
+        where F: FnMut(&str) -> Option<*const u8>
+    {
+        // Lay out the functions, followed by a stub for each host function called.
+        let mut offsets = Vec::with_capacity(self.functions.len());
+        let mut size = 0;
+        for func in &self.functions {
+            size = (size + FUNCTION_ALIGNMENT - 1) & !(FUNCTION_ALIGNMENT - 1);
+            offsets.push(size);
+            size += func.code.len();
+        }
+        size = (size + STUB_SIZE - 1) & !(STUB_SIZE - 1);
+
+        let mut host = HashMap::new();
+        let mut stubs = HashMap::new();
+        let mut stub_addrs = Vec::new();
+        for func in &self.functions {
+            for &(_, _, ref link) in &func.relocs {
+                if let Link::Func(ref name) = *link {
+                    if self.index.contains_key(name) || stubs.contains_key(name) {
+                        continue;
+                    }
+                    let addr = resolve(&mut host, &mut lookup, name)?;
+                    stubs.insert(name.clone(), size + stub_addrs.len() * STUB_SIZE);
+                    stub_addrs.push(addr);
+                }
+            }
+        }
+
+        // Lay out the read-only and the writable data objects separately.
+        let mut data_offsets = Vec::with_capacity(self.data.len());
+        let mut rodata_size = 0;
+        let mut data_size = 0;
+        for data in &self.data {
+            let size = if data.writable {
+                &mut data_size
+            } else {
+                &mut rodata_size
+            };
+            let align = data.align as usize;
+            *size = (*size + align - 1) & !(align - 1);
+            data_offsets.push(*size);
+            *size += data.contents.len();
+        }
+
+        let mut memory = Memory::new(size + stub_addrs.len() * STUB_SIZE)?;
+        let mut rodata = Memory::new(rodata_size)?;
+        let mut data_memory = Memory::new(data_size)?;
+        let base = memory.addr();
+
+        let mut symbols = HashMap::new();
+        for (name, &idx) in &self.index {
+            symbols.insert(name.clone(), base + offsets[idx]);
+        }
+        for (name, &idx) in &self.data_index {
+            let addr = if self.data[idx].writable {
+                data_memory.addr()
+            } else {
+                rodata.addr()
+            };
+            symbols.insert(name.clone(), addr + data_offsets[idx]);
+        }
+
+        {
+            let mem = memory.as_mut_slice();
+            for (func, &offset) in self.functions.iter().zip(&offsets) {
+                let code = &mut mem[offset..offset + func.code.len()];
+                code.copy_from_slice(&func.code);
+
+                let func_addr = base + offset;
+                for &(at, ref reloc, ref link) in &func.relocs {
+                    let addr = match *link {
+                        Link::Func(ref name) => {
+                            match symbols.get(name) {
+                                Some(&addr) => addr,
+                                None => base + stubs[name],
+                            }
+                        }
+                        Link::Sym(ref name) => {
+                            match symbols.get(name) {
+                                Some(&addr) => addr,
+                                None => resolve(&mut host, &mut lookup, name)?,
+                            }
+                        }
+                        Link::Local(offset) => func_addr + offset as usize,
+                    };
+                    self.patch(&mut code[at as usize..], func_addr + at as usize, reloc, addr)
+                        .map_err(|e| format!("{}: {}", func.name, e))?;
+                }
+            }
+
+            for (n, &addr) in stub_addrs.iter().enumerate() {
+                let stub = &mut mem[size + n * STUB_SIZE..size + (n + 1) * STUB_SIZE];
+                stub[0..6].copy_from_slice(&[0xff, 0x25, 0, 0, 0, 0]);
+                put_le(&mut stub[6..14], addr as u64);
+            }
+        }
+
+        for (data, &offset) in self.data.iter().zip(&data_offsets) {
+            let mem = if data.writable {
+                data_memory.as_mut_slice()
+            } else {
+                rodata.as_mut_slice()
+            };
+            let contents = &mut mem[offset..offset + data.contents.len()];
+            contents.copy_from_slice(&data.contents);
+
+            for ptr in &data.pointers {
+                let name = ptr.target.as_ref();
+                let addr = match symbols.get(name) {
+                    Some(&addr) => addr,
+                    None => resolve(&mut host, &mut lookup, name)?,
+                };
+                if ptr.size == 4 && addr as u32 as usize != addr {
+                    return Err(format!("{}: Address {:#x} out of range for a 4-byte pointer",
+                                       data.name,
+                                       addr));
+                }
+                let at = ptr.offset as usize;
+                put_le(&mut contents[at..at + ptr.size as usize], addr as u64);
+            }
+        }
+
+        memory.make_executable()?;
+        rodata.make_readonly()?;
+        Ok(Code {
+               memory,
+               _rodata: rodata,
+               _data: data_memory,
+               symbols,
+           })
+    }
+
+    /// Patch the relocated `field` at address `at` so it refers to the address `target`.
+    fn patch(&self,
+             field: &mut [u8],
+             at: usize,
+             reloc: &Reloc,
+             target: usize)
+             -> Result<(), String> {
+        let value = match self.isa.reloc_names()[reloc.0 as usize] {
+            name @ "Abs4" | name @ "ConstAbs4" => {
+                if target as u32 as usize != target {
+                    return Err(format!("Address {:#x} out of range for {}", target, name));
+                }
+                target as u64
+            }
+            // PC-relative displacements are relative to the end of the 4-byte field.
+            name @ "Call" | name @ "ConstPcrel4" => {
+                let disp = (target as i64).wrapping_sub(at as i64 + 4);
+                if disp as i32 as i64 != disp {
+                    return Err(format!("Address {:#x} out of range for {}", target, name));
+                }
+                disp as u64
+            }
+            name => return Err(format!("Unsupported relocation: {}", name)),
+        };
+        put_le(&mut field[0..4], value);
+        Ok(())
+    }
+}
+
+impl<'a> Backend for JIT<'a> {
+    fn define_function(&mut self, func: &Function, code_size: CodeOffset) -> Result<(), String> {
+        let name = func.name.as_ref().to_vec();
+        symbol_str(&name)?;
+        if self.is_defined(&name) {
+            return Err(format!("Duplicate definition of {}", func.name));
+        }
+
+        let mut code = vec![0; code_size as usize];
+        let mut relocs = FunctionRelocs(Vec::new());
+        binemit::emit_to_memory(func, self.isa, &mut code, &mut relocs, &mut NullTrapSink {});
+
+        let mut links = Vec::with_capacity(relocs.0.len());
+        for (offset, reloc, target) in relocs.0 {
+            let link = match target {
+                Target::Func(fref) => Link::Func(func.dfg.ext_funcs[fref].name.as_ref().to_vec()),
+                Target::GlobalSym(gv) => {
+                    match func.global_vars[gv] {
+                        GlobalVarData::Sym { ref name } => Link::Sym(name.as_ref().to_vec()),
+                        ref gvdata => {
+                            return Err(format!("Can't relocate {} = {}", gv, gvdata));
+                        }
+                    }
+                }
+                Target::JumpTable(jt) => Link::Local(func.jt_offsets[jt]),
+                Target::Constant(c) => Link::Local(func.const_offsets[c]),
+            };
+            links.push((offset, reloc, link));
+        }
+
+        self.index.insert(name, self.functions.len());
+        self.functions
+            .push(Emitted {
+                      name: func.name.clone(),
+                      code,
+                      relocs: links,
+                  });
+        Ok(())
+    }
+
+    fn define_data(&mut self, data: &DataObject) -> Result<(), String> {
+        self.add_data(data.clone())
+    }
+}
+
+/// Resolve the host symbol `name` with the `lookup` callback, caching the result in `host`.
+fn resolve<F>(host: &mut HashMap<Vec<u8>, usize>,
+              lookup: &mut F,
+              name: &[u8])
+              -> Result<usize, String>
+    where F: FnMut(&str) -> Option<*const u8>
+{
+    if let Some(&addr) = host.get(name) {
+        return Ok(addr);
+    }
+    let sym = symbol_str(name)?;
+    let addr = lookup(sym).ok_or_else(|| format!("Undefined symbol: {}", sym))? as usize;
+    host.insert(name.to_vec(), addr);
+    Ok(addr)
+}
+
+/// Write the low bytes of `value` into `field` in little-endian order.
+fn put_le(field: &mut [u8], value: u64) {
+    for (n, byte) in field.iter_mut().enumerate() {
+        *byte = (value >> (8 * n)) as u8;
+    }
+}
+
+impl Code {
+    /// Get a pointer to the compiled function or data object `name`.
+    ///
+    /// A function pointer can be transmuted to an `extern "C"` function pointer with the
+    /// function's signature.
+    pub fn get(&self, name: &str) -> Option<*const u8> {
+        self.symbols
+            .get(name.as_bytes())
+            .map(|&addr| addr as *const u8)
+    }
+
+    /// Get the total size of the executable memory, including padding and stubs.
+    pub fn size(&self) -> usize {
+        self.memory.len()
+    }
+}
@@ -0,0 +1,24 @@
+//! Cretonne JIT compiler.
+//!
+//! The cton_jit library compiles a set of Cretonne functions into executable memory in the
+//! current process, links them together, and hands back pointers to the compiled functions that
+//! can be called directly.
+//!
+//! Functions are added to a `JIT` which compiles them with `cretonne::Context::compile()`. The
+//! `JIT::finish()` method lays out the code in memory, resolves the calls between the functions,
+//! and resolves references to other symbols by asking a callback provided by the host program.
+//!
+//! A `JIT` can also be used as the backend for compiling a `cretonne::Module`.
+//!
+//! Only Linux x86-64 hosts are supported, and the functions must be compiled for the `intel` ISA
+//! with the `is_64bit` setting enabled.
+
+#![deny(missing_docs)]
+
+extern crate cretonne;
+extern crate libc;
+
+pub use jit::{JIT, Code};
+
+mod jit;
+mod memory;
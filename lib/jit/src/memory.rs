@@ -0,0 +1,80 @@
+//! Memory for compiled code and data.
+
+use libc;
+use std::io;
+use std::ptr;
+use std::slice;
+
+/// A block of memory mapped with `mmap()`.
+///
+/// The memory is writable when it is created, and it can be made executable or read-only once the
+/// code or data has been written. It is mapped in the low 2 GB of the address space so it can be
+/// addressed with 32-bit absolute relocations. The memory is unmapped when it is dropped.
+pub struct Memory {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Memory {
+    /// Map `size` bytes of writable memory.
+    pub fn new(size: usize) -> Result<Memory, String> {
+        // A zero-sized mapping is an error.
+        let len = if size == 0 { 1 } else { size };
+        let ptr = unsafe {
+            libc::mmap(ptr::null_mut(),
+                       len,
+                       libc::PROT_READ | libc::PROT_WRITE,
+                       libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_32BIT,
+                       -1,
+                       0)
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(format!("mmap failed: {}", io::Error::last_os_error()));
+        }
+        Ok(Memory {
+               ptr: ptr as *mut u8,
+               len,
+           })
+    }
+
+    /// Get the address of the first byte.
+    pub fn addr(&self) -> usize {
+        self.ptr as usize
+    }
+
+    /// Get the size of the memory in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the memory as a slice that can be written.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+
+    /// Make the memory read-only and executable.
+    pub fn make_executable(&mut self) -> Result<(), String> {
+        self.protect(libc::PROT_READ | libc::PROT_EXEC)
+    }
+
+    /// Make the memory read-only.
+    pub fn make_readonly(&mut self) -> Result<(), String> {
+        self.protect(libc::PROT_READ)
+    }
+
+    fn protect(&mut self, prot: libc::c_int) -> Result<(), String> {
+        let res = unsafe { libc::mprotect(self.ptr as *mut libc::c_void, self.len, prot) };
+        if res != 0 {
+            return Err(format!("mprotect failed: {}", io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+extern crate cretonne;
+extern crate cton_jit;
+extern crate cton_reader;
+
+use cretonne::Module;
+use cton_jit::{JIT, Code};
+use cton_reader::parse_test;
+use std::mem;
+
+/// Compile and link all the functions in `source`, resolving host symbols with `lookup`.
+fn jit<F>(source: &str, lookup: F) -> Result<Code, String>
+    where F: FnMut(&str) -> Option<*const u8>
+{
+    let test = parse_test(source).unwrap();
+    let isa = test.isa_spec.unique_isa().expect("Need an ISA");
+    let mut jit = JIT::new(isa)?;
+    for (func, _) in test.functions {
+        jit.add_function(func)?;
+    }
+    jit.finish(lookup)
+}
+
+/// Host symbol lookup for functions that don't call out.
+fn no_host(name: &str) -> Option<*const u8> {
+    panic!("Unexpected host symbol: {}", name)
+}
+
+extern "C" fn triple(x: i32) -> i32 {
+    3 * x
+}
+
+#[test]
+fn arithmetic() {
+    let code = jit("
+        set is_64bit=1
+        isa intel
+
+        function %add(i32, i32) -> i32 {
+        ebb0(v0: i32, v1: i32):
+            v2 = iadd v0, v1
+            return v2
+        }
+
+        function %sub_and(i32, i32) -> i32 {
+        ebb0(v0: i32, v1: i32):
+            v2 = isub v0, v1
+            v3 = band v2, v0
+            return v3
+        }
+    ",
+                   no_host)
+            .unwrap();
+
+    let add: extern "C" fn(i32, i32) -> i32 = unsafe { mem::transmute(code.get("add").unwrap()) };
+    assert_eq!(add(2, 3), 5);
+    assert_eq!(add(-7, 3), -4);
+
+    let sub_and: extern "C" fn(i32, i32) -> i32 =
+        unsafe { mem::transmute(code.get("sub_and").unwrap()) };
+    assert_eq!(sub_and(15, 3), 12 & 15);
+    assert_eq!(sub_and(100, 1), 99 & 100);
+
+    assert_eq!(code.get("missing"), None);
+}
+
+#[test]
+fn jump_tables() {
+    let code = jit("
+        set is_64bit=1
+        isa intel
+
+        function %switch(i32) -> i32 {
+            jt0 = jump_table ebb1, 0, ebb2
+
+        ebb0(v0: i32):
+            br_table v0, jt0
+            v1 = iconst.i32 10
+            return v1
+
+        ebb1:
+            v2 = iconst.i32 11
+            return v2
+
+        ebb2:
+            v3 = iconst.i32 12
+            return v3
+        }
+    ",
+                   no_host)
+            .unwrap();
+
+    let switch: extern "C" fn(i32) -> i32 =
+        unsafe { mem::transmute(code.get("switch").unwrap()) };
+    assert_eq!(switch(0), 11);
+    assert_eq!(switch(1), 10);
+    assert_eq!(switch(2), 12);
+    assert_eq!(switch(3), 10);
+    assert_eq!(switch(-1), 10);
+}
+
+#[test]
+fn constants() {
+    let code = jit("
+        set is_64bit=1
+        isa intel
+
+        function %wide(i64) -> i64 {
+        ebb0(v0: i64):
+            v1 = iconst.i64 0x1234_5678_9abc_def0
+            v2 = iadd v0, v1
+            return v2
+        }
+    ",
+                   no_host)
+            .unwrap();
+
+    let wide: extern "C" fn(i64) -> i64 = unsafe { mem::transmute(code.get("wide").unwrap()) };
+    assert_eq!(wide(0), 0x1234_5678_9abc_def0);
+    assert_eq!(wide(1), 0x1234_5678_9abc_def1);
+}
+
+#[test]
+fn calls() {
+    let code = jit("
+        set is_64bit=1
+        isa intel
+
+        function %add(i32, i32) -> i32 {
+        ebb0(v0: i32, v1: i32):
+            v2 = iadd v0, v1
+            return v2
+        }
+
+        function %caller(i32) -> i32 {
+            fn0 = function %add(i32, i32) -> i32
+            fn1 = function %triple(i32) -> i32
+        ebb0(v0: i32):
+            v1 = iconst.i32 10
+            v2 = call fn0(v0, v1)
+            v3 = call fn1(v2)
+            return v3
+        }
+    ",
+                   |name| match name {
+                       "triple" => Some(triple as *const u8),
+                       _ => None,
+                   })
+            .unwrap();
+
+    let caller: extern "C" fn(i32) -> i32 =
+        unsafe { mem::transmute(code.get("caller").unwrap()) };
+    assert_eq!(caller(5), 45);
+    assert_eq!(caller(-10), 0);
+}
+
+#[test]
+fn undefined_symbol() {
+    let err = jit("
+        set is_64bit=1
+        isa intel
+
+        function %caller(i32) -> i32 {
+            fn0 = function %nowhere(i32) -> i32
+        ebb0(v0: i32):
+            v1 = call fn0(v0)
+            return v1
+        }
+    ",
+                  |_| None)
+            .err()
+            .unwrap();
+    assert_eq!(err, "Undefined symbol: nowhere");
+}
+
+#[test]
+fn wrong_isa() {
+    let test = parse_test("isa intel").unwrap();
+    let isa = test.isa_spec.unique_isa().unwrap();
+    assert!(JIT::new(isa).is_err());
+}
+
+#[test]
+fn module() {
+    let test = parse_test("
+        set is_64bit=1
+        isa intel
+
+        function %caller(i32) -> i32 {
+            fn0 = function %double(i32) -> i32
+        ebb0(v0: i32):
+            v1 = call fn0(v0)
+            return v1
+        }
+
+        function %double(i32) -> i32 {
+        ebb0(v0: i32):
+            v1 = iadd v0, v0
+            return v1
+        }
+    ")
+            .unwrap();
+    let isa = test.isa_spec.unique_isa().unwrap();
+    let mut module = Module::new();
+    for (func, _) in test.functions {
+        module.define_function(func).unwrap();
+    }
+
+    let mut jit = JIT::new(isa).unwrap();
+    module.compile(isa, &mut jit).unwrap();
+    let code = jit.finish(no_host).unwrap();
+    let caller: extern "C" fn(i32) -> i32 =
+        unsafe { mem::transmute(code.get("caller").unwrap()) };
+    assert_eq!(caller(21), 42);
+}
+
+#[test]
+fn data() {
+    let test = parse_test("
+        set is_64bit=1
+        isa intel
+
+        data %counter = [#05000000], align 4, writable
+        data %table = [%counter, %increment, %host_value], align 8
+
+        function %increment() -> i32 {
+            gv0 = globalsym %counter
+        ebb0:
+            v0 = global_addr.i64 gv0
+            v1 = load.i32 v0
+            v2 = iadd_imm v1, 1
+            store v2, v0
+            return v2
+        }
+    ")
+            .unwrap();
+    let isa = test.isa_spec.unique_isa().unwrap();
+    let mut module = Module::new();
+    for (func, _) in test.functions {
+        module.define_function(func).unwrap();
+    }
+    for data in test.data {
+        module.define_data(data).unwrap();
+    }
+
+    static HOST_VALUE: i32 = 7;
+    let mut jit = JIT::new(isa).unwrap();
+    module.compile(isa, &mut jit).unwrap();
+    let code = jit.finish(|name| match name {
+                                "host_value" => Some(&HOST_VALUE as *const i32 as *const u8),
+                                _ => None,
+                            })
+        .unwrap();
+
+    let increment: extern "C" fn() -> i32 =
+        unsafe { mem::transmute(code.get("increment").unwrap()) };
+    assert_eq!(increment(), 6);
+    assert_eq!(increment(), 7);
+
+    let counter = code.get("counter").unwrap() as *const i32;
+    assert_eq!(unsafe { *counter }, 7);
+
+    let table = code.get("table").unwrap() as *const usize;
+    assert_eq!(table as usize % 8, 0);
+    let table = unsafe { std::slice::from_raw_parts(table, 3) };
+    assert_eq!(table[0], counter as usize);
+    assert_eq!(table[1], code.get("increment").unwrap() as usize);
+    assert_eq!(table[2], &HOST_VALUE as *const i32 as usize);
+}
+
+#[test]
+fn duplicate_data() {
+    let test = parse_test("
+        set is_64bit=1
+        isa intel
+
+        data %f = [1]
+
+        function %f() {
+        ebb0:
+            return
+        }
+    ")
+            .unwrap();
+    let isa = test.isa_spec.unique_isa().unwrap();
+    let mut jit = JIT::new(isa).unwrap();
+    for data in test.data {
+        jit.add_data(data).unwrap();
+    }
+    for (func, _) in test.functions {
+        assert_eq!(jit.add_function(func).unwrap_err(), "Duplicate definition of %f");
+    }
+}
@@ -0,0 +1,436 @@
+//! ELF relocatable object file encoding.
+//!
+//! This module knows how to lay out and encode the ELF file format. The `object` module decides
+//! what goes into the sections.
+//!
+//! The generated object files always have the same sections in the same order:
+//!
+//! 1. `.text` holds the machine code of all the functions.
+//! 2. `.rodata` holds the constant pools that were moved out of `.text`, and read-only data
+//!    objects. Jump tables always stay in `.text` with the code.
+//! 3. `.data` holds writable data objects.
+//! 4. `.rel.text` or `.rela.text` holds the relocations for `.text`.
+//! 5. `.rel.rodata` or `.rela.rodata` holds the relocations for `.rodata`.
+//! 6. `.rel.data` or `.rela.data` holds the relocations for `.data`.
+//! 7. `.symtab` is the symbol table.
+//! 8. `.strtab` holds the symbol names.
+//! 9. `.shstrtab` holds the section names.
+//!
+//! All supported targets are little-endian.
+
+/// Machine code for Intel 80386.
+pub const EM_386: u16 = 3;
+/// Machine code for AMD x86-64.
+pub const EM_X86_64: u16 = 62;
+/// Machine code for RISC-V.
+pub const EM_RISCV: u16 = 243;
+
+/// Direct 32-bit relocation for i386.
+pub const R_386_32: u32 = 1;
+/// Direct 64-bit relocation for x86-64.
+pub const R_X86_64_64: u32 = 1;
+/// PC-relative 32-bit relocation for i386.
+pub const R_386_PC32: u32 = 2;
+/// PC-relative 32-bit relocation for x86-64.
+pub const R_X86_64_PC32: u32 = 2;
+/// Direct zero-extended 32-bit relocation for x86-64.
+pub const R_X86_64_32: u32 = 10;
+/// Direct 32-bit relocation for RISC-V.
+pub const R_RISCV_32: u32 = 1;
+/// Direct 64-bit relocation for RISC-V.
+pub const R_RISCV_64: u32 = 2;
+/// 20-bit PC-relative jump offset of a RISC-V `jal` instruction.
+pub const R_RISCV_JAL: u32 = 17;
+
+/// Section header index of `.text`.
+pub const SHN_TEXT: u16 = 1;
+/// Section header index of `.rodata`.
+pub const SHN_RODATA: u16 = 2;
+/// Section header index of `.data`.
+pub const SHN_DATA: u16 = 3;
+const SHN_SYMTAB: u16 = 7;
+const SHN_STRTAB: u16 = 8;
+const SHN_SHSTRTAB: u16 = 9;
+const NUM_SECTIONS: u16 = 10;
+
+/// Symbol table index of the section symbol for `.text`.
+pub const SYM_TEXT: u32 = 1;
+/// Symbol table index of the section symbol for `.rodata`.
+pub const SYM_RODATA: u32 = 2;
+/// Symbol table index of the first global symbol.
+pub const SYM_FIRST_GLOBAL: u32 = 4;
+
+const ET_REL: u16 = 1;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_REL: u32 = 9;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_OBJECT: u8 = 1;
+const STT_FUNC: u8 = 2;
+const STT_SECTION: u8 = 3;
+
+/// A global symbol, either defined in one of the sections or undefined.
+pub struct Symbol {
+    /// The symbol name.
+    pub name: Vec<u8>,
+    /// The section header index, the offset into the section and the size of the function or
+    /// data object, or `None` for an undefined symbol.
+    pub def: Option<(u16, u64, u64)>,
+}
+
+/// A relocation in the `.text`, `.rodata`, or `.data` section.
+pub struct Relocation {
+    /// Section header index of the section containing the location to patch.
+    pub section: u16,
+    /// Offset into the section of the location to patch.
+    pub offset: u64,
+    /// Symbol table index of the relocation target.
+    pub symbol: u32,
+    /// ELF relocation type.
+    pub kind: u32,
+    /// Constant to add to the symbol value.
+    ///
+    /// For `.rel` sections without an explicit addend, the addend must already have been written
+    /// to the patched location.
+    pub addend: i64,
+}
+
+/// The contents of a relocatable object file.
+pub struct Object {
+    /// Produce a 64-bit ELF file instead of a 32-bit one.
+    pub is_64bit: bool,
+    /// Use `.rela` relocations with explicit addends instead of `.rel` relocations.
+    pub use_rela: bool,
+    /// The `e_machine` field of the ELF header.
+    pub machine: u16,
+    /// Alignment of the `.text` section.
+    pub text_align: u64,
+    /// Contents of the `.text` section.
+    pub text: Vec<u8>,
+    /// Alignment of the `.rodata` section.
+    pub rodata_align: u64,
+    /// Contents of the `.rodata` section.
+    pub rodata: Vec<u8>,
+    /// Alignment of the `.data` section.
+    pub data_align: u64,
+    /// Contents of the `.data` section.
+    pub data: Vec<u8>,
+    /// Global symbols. The symbol table index of `symbols[i]` is `SYM_FIRST_GLOBAL + i`.
+    pub symbols: Vec<Symbol>,
+    /// Relocations for all the sections.
+    pub relocs: Vec<Relocation>,
+}
+
+/// A string table section under construction.
+struct StringTable(Vec<u8>);
+
+impl StringTable {
+    fn new() -> StringTable {
+        // The first string is always the empty string.
+        StringTable(vec![0])
+    }
+
+    fn add(&mut self, s: &[u8]) -> u32 {
+        let idx = self.0.len() as u32;
+        self.0.extend_from_slice(s);
+        self.0.push(0);
+        idx
+    }
+}
+
+/// Little-endian output buffer which knows the size of target addresses.
+struct Buffer {
+    data: Vec<u8>,
+    is_64bit: bool,
+}
+
+impl Buffer {
+    fn u8(&mut self, x: u8) {
+        self.data.push(x);
+    }
+
+    fn u16(&mut self, x: u16) {
+        self.u8(x as u8);
+        self.u8((x >> 8) as u8);
+    }
+
+    fn u32(&mut self, x: u32) {
+        self.u16(x as u16);
+        self.u16((x >> 16) as u16);
+    }
+
+    fn u64(&mut self, x: u64) {
+        self.u32(x as u32);
+        self.u32((x >> 32) as u32);
+    }
+
+    /// Write an address-sized field.
+    fn addr(&mut self, x: u64) {
+        if self.is_64bit {
+            self.u64(x)
+        } else {
+            self.u32(x as u32)
+        }
+    }
+
+    /// Pad with zeros up to an `align` boundary.
+    fn align(&mut self, align: u64) {
+        while self.data.len() as u64 % align != 0 {
+            self.u8(0);
+        }
+    }
+
+    fn offset(&self) -> u64 {
+        self.data.len() as u64
+    }
+}
+
+/// A section header.
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl Object {
+    /// Encode this object as an ELF file.
+    pub fn write(&self) -> Vec<u8> {
+        let mut buf = Buffer {
+            data: Vec::new(),
+            is_64bit: self.is_64bit,
+        };
+        let ehsize = if self.is_64bit { 64 } else { 52 };
+        let shentsize = if self.is_64bit { 64 } else { 40 };
+        let symsize = if self.is_64bit { 24 } else { 16 };
+        let relsize = match (self.is_64bit, self.use_rela) {
+            (false, false) => 8,
+            (false, true) => 12,
+            (true, false) => 16,
+            (true, true) => 24,
+        };
+
+        let mut shstrtab = StringTable::new();
+        let mut strtab = StringTable::new();
+        let mut headers = Vec::new();
+
+        // Leave room for the ELF header which is written last.
+        buf.data.resize(ehsize, 0);
+
+        buf.align(self.text_align);
+        headers.push(SectionHeader {
+                         name: shstrtab.add(b".text"),
+                         kind: SHT_PROGBITS,
+                         flags: SHF_ALLOC | SHF_EXECINSTR,
+                         offset: buf.offset(),
+                         size: self.text.len() as u64,
+                         link: 0,
+                         info: 0,
+                         align: self.text_align,
+                         entsize: 0,
+                     });
+        buf.data.extend_from_slice(&self.text);
+
+        buf.align(self.rodata_align);
+        headers.push(SectionHeader {
+                         name: shstrtab.add(b".rodata"),
+                         kind: SHT_PROGBITS,
+                         flags: SHF_ALLOC,
+                         offset: buf.offset(),
+                         size: self.rodata.len() as u64,
+                         link: 0,
+                         info: 0,
+                         align: self.rodata_align,
+                         entsize: 0,
+                     });
+        buf.data.extend_from_slice(&self.rodata);
+
+        buf.align(self.data_align);
+        headers.push(SectionHeader {
+                         name: shstrtab.add(b".data"),
+                         kind: SHT_PROGBITS,
+                         flags: SHF_ALLOC | SHF_WRITE,
+                         offset: buf.offset(),
+                         size: self.data.len() as u64,
+                         link: 0,
+                         info: 0,
+                         align: self.data_align,
+                         entsize: 0,
+                     });
+        buf.data.extend_from_slice(&self.data);
+
+        let relocated = [(SHN_TEXT, "text"), (SHN_RODATA, "rodata"), (SHN_DATA, "data")];
+        for &(section, name) in &relocated {
+            buf.align(if self.is_64bit { 8 } else { 4 });
+            let rel_offset = buf.offset();
+            for rel in self.relocs.iter().filter(|rel| rel.section == section) {
+                buf.addr(rel.offset);
+                if self.is_64bit {
+                    buf.u64((rel.symbol as u64) << 32 | rel.kind as u64);
+                } else {
+                    buf.u32(rel.symbol << 8 | rel.kind);
+                }
+                if self.use_rela {
+                    buf.addr(rel.addend as u64);
+                }
+            }
+            let prefix = if self.use_rela { ".rela." } else { ".rel." };
+            headers.push(SectionHeader {
+                             name: shstrtab.add(format!("{}{}", prefix, name).as_bytes()),
+                             kind: if self.use_rela { SHT_RELA } else { SHT_REL },
+                             flags: SHF_INFO_LINK,
+                             offset: rel_offset,
+                             size: buf.offset() - rel_offset,
+                             link: SHN_SYMTAB as u32,
+                             info: section as u32,
+                             align: if self.is_64bit { 8 } else { 4 },
+                             entsize: relsize,
+                         });
+        }
+
+        // The symbol table starts with the null symbol and the section symbols, followed by the
+        // global symbols.
+        let sym_offset = buf.offset();
+        self.write_symbol(&mut buf, 0, 0, 0, 0, 0);
+        self.write_symbol(&mut buf, 0, 0, 0, STB_LOCAL << 4 | STT_SECTION, SHN_TEXT);
+        self.write_symbol(&mut buf, 0, 0, 0, STB_LOCAL << 4 | STT_SECTION, SHN_RODATA);
+        self.write_symbol(&mut buf, 0, 0, 0, STB_LOCAL << 4 | STT_SECTION, SHN_DATA);
+        for sym in &self.symbols {
+            let name = strtab.add(&sym.name);
+            match sym.def {
+                Some((section, value, size)) => {
+                    let kind = if section == SHN_TEXT {
+                        STT_FUNC
+                    } else {
+                        STT_OBJECT
+                    };
+                    self.write_symbol(&mut buf, name, value, size, STB_GLOBAL << 4 | kind, section)
+                }
+                None => self.write_symbol(&mut buf, name, 0, 0, STB_GLOBAL << 4 | STT_NOTYPE, 0),
+            }
+        }
+        headers.push(SectionHeader {
+                         name: shstrtab.add(b".symtab"),
+                         kind: SHT_SYMTAB,
+                         flags: 0,
+                         offset: sym_offset,
+                         size: buf.offset() - sym_offset,
+                         link: SHN_STRTAB as u32,
+                         info: SYM_FIRST_GLOBAL,
+                         align: if self.is_64bit { 8 } else { 4 },
+                         entsize: symsize,
+                     });
+
+        headers.push(SectionHeader {
+                         name: shstrtab.add(b".strtab"),
+                         kind: SHT_STRTAB,
+                         flags: 0,
+                         offset: buf.offset(),
+                         size: strtab.0.len() as u64,
+                         link: 0,
+                         info: 0,
+                         align: 1,
+                         entsize: 0,
+                     });
+        buf.data.extend_from_slice(&strtab.0);
+
+        let shstrtab_name = shstrtab.add(b".shstrtab");
+        headers.push(SectionHeader {
+                         name: shstrtab_name,
+                         kind: SHT_STRTAB,
+                         flags: 0,
+                         offset: buf.offset(),
+                         size: shstrtab.0.len() as u64,
+                         link: 0,
+                         info: 0,
+                         align: 1,
+                         entsize: 0,
+                     });
+        buf.data.extend_from_slice(&shstrtab.0);
+        debug_assert_eq!(headers.len(), (NUM_SECTIONS - 1) as usize);
+
+        // The section header table, starting with the null section.
+        buf.align(if self.is_64bit { 8 } else { 4 });
+        let shoff = buf.offset();
+        buf.data.resize(shoff as usize + shentsize, 0);
+        for sh in &headers {
+            buf.u32(sh.name);
+            buf.u32(sh.kind);
+            buf.addr(sh.flags);
+            buf.addr(0);
+            buf.addr(sh.offset);
+            buf.addr(sh.size);
+            buf.u32(sh.link);
+            buf.u32(sh.info);
+            buf.addr(sh.align);
+            buf.addr(sh.entsize);
+        }
+
+        // Finally go back and write the ELF header.
+        let mut eh = Buffer {
+            data: Vec::new(),
+            is_64bit: self.is_64bit,
+        };
+        eh.data.extend_from_slice(b"\x7fELF");
+        eh.u8(if self.is_64bit { 2 } else { 1 }); // EI_CLASS
+        eh.u8(1); // EI_DATA: little-endian.
+        eh.u8(1); // EI_VERSION
+        eh.align(16);
+        eh.u16(ET_REL);
+        eh.u16(self.machine);
+        eh.u32(1); // e_version
+        eh.addr(0); // e_entry
+        eh.addr(0); // e_phoff
+        eh.addr(shoff);
+        eh.u32(0); // e_flags
+        eh.u16(ehsize as u16);
+        eh.u16(0); // e_phentsize
+        eh.u16(0); // e_phnum
+        eh.u16(shentsize as u16);
+        eh.u16(NUM_SECTIONS);
+        eh.u16(SHN_SHSTRTAB);
+        debug_assert_eq!(eh.data.len(), ehsize);
+        buf.data[0..ehsize].copy_from_slice(&eh.data);
+
+        buf.data
+    }
+
+    fn write_symbol(&self,
+                    buf: &mut Buffer,
+                    name: u32,
+                    value: u64,
+                    size: u64,
+                    info: u8,
+                    shndx: u16) {
+        buf.u32(name);
+        if self.is_64bit {
+            buf.u8(info);
+            buf.u8(0);
+            buf.u16(shndx);
+            buf.u64(value);
+            buf.u64(size);
+        } else {
+            buf.u32(value as u32);
+            buf.u32(size as u32);
+            buf.u8(info);
+            buf.u8(0);
+            buf.u16(shndx);
+        }
+    }
+}
@@ -0,0 +1,17 @@
+//! Cretonne object file writer.
+//!
+//! The cton_obj library writes compiled Cretonne functions into relocatable object files that can
+//! be linked with other code by a system linker. Only ELF object files are supported.
+//!
+//! Functions are first compiled with `cretonne::Context::compile()`, and then added to an
+//! `ObjectBuilder` which lays out the sections and translates Cretonne's ISA-specific relocations
+//! into ELF relocations.
+
+#![deny(missing_docs)]
+
+extern crate cretonne;
+
+pub use object::ObjectBuilder;
+
+mod elf;
+mod object;
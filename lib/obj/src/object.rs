@@ -0,0 +1,374 @@
+//! Building object files from compiled functions and data objects.
+
+use cretonne::binemit::{self, CodeOffset, Reloc, RelocSink, NullTrapSink};
+use cretonne::ir::{Function, FunctionName, FuncRef, GlobalVar, GlobalVarData, JumpTable, Constant};
+use cretonne::isa::TargetIsa;
+use cretonne::module::{Backend, DataObject};
+use elf;
+use std::collections::HashMap;
+
+/// Alignment of functions in the `.text` section.
+const FUNCTION_ALIGNMENT: usize = 16;
+
+/// Alignment of the read-only data of each function in the `.rodata` section.
+///
+/// This is the largest alignment required by constant pool entries.
+const RODATA_ALIGNMENT: usize = 8;
+
+/// Builder for a relocatable object file.
+///
+/// Compiled functions and data objects are added one at a time, and the object file is produced
+/// by `finish()`. The builder can also be used as the `Backend` for compiling a `Module`.
+///
+/// Each function is given a global symbol in the `.text` section named after its `FunctionName`.
+/// Data objects are placed in `.data` if they are writable, and in `.rodata` otherwise. References
+/// to external functions and `globalsym` global variables that are not defined in the object file
+/// become undefined symbols.
+///
+/// The jump tables that are emitted after a function's code stay in `.text` because their entries
+/// are relative to the table. The constant pool that follows them is moved into the `.rodata`
+/// section when all the references to it can be relocated. Otherwise, it stays in `.text`
+/// immediately after the jump tables.
+pub struct ObjectBuilder<'a> {
+    isa: &'a TargetIsa,
+    text: Vec<u8>,
+    rodata: Vec<u8>,
+    rodata_align: usize,
+    data: Vec<u8>,
+    data_align: usize,
+    symbols: Vec<elf::Symbol>,
+    symbol_index: HashMap<Vec<u8>, usize>,
+    relocs: Vec<elf::Relocation>,
+}
+
+/// Where a relocation is pointing.
+enum Target {
+    Func(FuncRef),
+    GlobalSym(GlobalVar),
+    JumpTable(JumpTable),
+    Constant(Constant),
+}
+
+/// A `RelocSink` that records all the relocations for a function.
+struct FunctionRelocs(Vec<(CodeOffset, Reloc, Target)>);
+
+impl RelocSink for FunctionRelocs {
+    fn reloc_ebb(&mut self, _offset: CodeOffset, _reloc: Reloc, _ebb_offset: CodeOffset) {
+        panic!("EBB relocations are resolved by emit_to_memory()");
+    }
+
+    fn reloc_func(&mut self, offset: CodeOffset, reloc: Reloc, func: FuncRef) {
+        self.0.push((offset, reloc, Target::Func(func)));
+    }
+
+    fn reloc_globalsym(&mut self, offset: CodeOffset, reloc: Reloc, gv: GlobalVar) {
+        self.0.push((offset, reloc, Target::GlobalSym(gv)));
+    }
+
+    fn reloc_jt(&mut self, offset: CodeOffset, reloc: Reloc, jt: JumpTable) {
+        self.0.push((offset, reloc, Target::JumpTable(jt)));
+    }
+
+    fn reloc_constant(&mut self, offset: CodeOffset, reloc: Reloc, constant: Constant) {
+        self.0.push((offset, reloc, Target::Constant(constant)));
+    }
+}
+
+impl<'a> ObjectBuilder<'a> {
+    /// Create a new object file builder for functions compiled for `isa`.
+    ///
+    /// The ELF class is selected by the `is_64bit` setting.
+    pub fn new(isa: &'a TargetIsa) -> Result<ObjectBuilder<'a>, String> {
+        match isa.name() {
+            "intel" | "riscv" => {}
+            name => return Err(format!("No object file support for {}", name)),
+        }
+        Ok(ObjectBuilder {
+               isa,
+               text: Vec::new(),
+               rodata: Vec::new(),
+               rodata_align: RODATA_ALIGNMENT,
+               data: Vec::new(),
+               data_align: 1,
+               symbols: Vec::new(),
+               symbol_index: HashMap::new(),
+               relocs: Vec::new(),
+           })
+    }
+
+    /// Add a compiled function to the object file.
+    ///
+    /// The function must have been prepared for code emission by `Context::compile()` which also
+    /// returns its `code_size`.
+    pub fn add_function(&mut self, func: &Function, code_size: CodeOffset) -> Result<(), String> {
+        let mut code = vec![0; code_size as usize];
+        let mut relocs = FunctionRelocs(Vec::new());
+        binemit::emit_to_memory(func, self.isa, &mut code, &mut relocs, &mut NullTrapSink {});
+
+        // The constant pool is emitted after the code and the jump tables. The jump table entries
+        // are relative to the table itself, so the tables must stay in `.text` with the code. The
+        // constants are moved into `.rodata` if all the references to them can be relocated.
+        let const_start = func.constants
+            .keys()
+            .map(|c| func.const_offsets[c] as usize)
+            .min();
+        let moved = const_start.is_some() &&
+                    relocs
+                        .0
+                        .iter()
+                        .all(|&(_, ref reloc, ref target)| match *target {
+                                 Target::Constant(_) => self.elf_reloc(reloc).is_ok(),
+                                 _ => true,
+                             });
+        let text_end = if moved {
+            const_start.unwrap()
+        } else {
+            code.len()
+        };
+
+        while self.text.len() % FUNCTION_ALIGNMENT != 0 {
+            self.text.push(0);
+        }
+        let text_offset = self.text.len();
+        self.define(&func.name, elf::SHN_TEXT, text_offset as u64, text_end as u64)?;
+
+        // Pad `.rodata` so the constants keep the alignment they had after the code.
+        let rodata_offset = if moved {
+            while self.rodata.len() % RODATA_ALIGNMENT != text_end % RODATA_ALIGNMENT {
+                self.rodata.push(0);
+            }
+            let offset = self.rodata.len();
+            self.rodata.extend_from_slice(&code[text_end..]);
+            offset
+        } else {
+            0
+        };
+
+        for (offset, reloc, target) in relocs.0 {
+            // Find the symbol and the addend for the target. References to data that stays in
+            // `.text` are relative to the `.text` section symbol, except for the PC-relative
+            // references to constants which the emitter has already resolved.
+            let (symbol, addend) = match target {
+                Target::Func(fref) => {
+                    let name = &func.dfg.ext_funcs[fref].name;
+                    (self.declare(name.as_ref())?, 0)
+                }
+                Target::GlobalSym(gv) => {
+                    match func.global_vars[gv] {
+                        GlobalVarData::Sym { ref name } => (self.declare(name.as_ref())?, 0),
+                        ref gvdata => {
+                            return Err(format!("Can't relocate {} = {}", gv, gvdata));
+                        }
+                    }
+                }
+                Target::JumpTable(jt) => {
+                    (elf::SYM_TEXT, (text_offset + func.jt_offsets[jt] as usize) as i64)
+                }
+                Target::Constant(c) if moved => {
+                    (elf::SYM_RODATA,
+                     (rodata_offset + func.const_offsets[c] as usize - text_end) as i64)
+                }
+                Target::Constant(_) if self.resolved_pcrel(&reloc) => continue,
+                Target::Constant(c) => {
+                    (elf::SYM_TEXT, (text_offset + func.const_offsets[c] as usize) as i64)
+                }
+            };
+            let (kind, bias) = self.elf_reloc(&reloc)?;
+            self.add_reloc(&mut code[offset as usize..],
+                           elf::SHN_TEXT,
+                           (text_offset + offset as usize) as u64,
+                           symbol,
+                           kind,
+                           addend + bias);
+        }
+
+        self.text.extend_from_slice(&code[0..text_end]);
+        Ok(())
+    }
+
+    /// Add a data object to the object file.
+    pub fn add_data(&mut self, data: &DataObject) -> Result<(), String> {
+        let align = data.align as usize;
+        let (section, offset) = if data.writable {
+            self.data_align = self.data_align.max(align);
+            while self.data.len() % align != 0 {
+                self.data.push(0);
+            }
+            (elf::SHN_DATA, self.data.len())
+        } else {
+            self.rodata_align = self.rodata_align.max(align);
+            while self.rodata.len() % align != 0 {
+                self.rodata.push(0);
+            }
+            (elf::SHN_RODATA, self.rodata.len())
+        };
+        self.define(&data.name, section, offset as u64, data.contents.len() as u64)?;
+
+        let mut contents = data.contents.clone();
+        for ptr in &data.pointers {
+            let symbol = self.declare(ptr.target.as_ref())?;
+            let kind = self.pointer_reloc(ptr.size)?;
+            self.add_reloc(&mut contents[ptr.offset as usize..],
+                           section,
+                           (offset + ptr.offset as usize) as u64,
+                           symbol,
+                           kind,
+                           0);
+        }
+
+        if data.writable {
+            self.data.extend_from_slice(&contents);
+        } else {
+            self.rodata.extend_from_slice(&contents);
+        }
+        Ok(())
+    }
+
+    /// Produce the final object file.
+    pub fn finish(self) -> Vec<u8> {
+        let machine = match (self.isa.name(), self.isa.flags().is_64bit()) {
+            ("intel", false) => elf::EM_386,
+            ("intel", true) => elf::EM_X86_64,
+            _ => elf::EM_RISCV,
+        };
+        elf::Object {
+                is_64bit: self.isa.flags().is_64bit(),
+                use_rela: self.use_rela(),
+                machine,
+                text_align: FUNCTION_ALIGNMENT as u64,
+                text: self.text,
+                rodata_align: self.rodata_align as u64,
+                rodata: self.rodata,
+                data_align: self.data_align as u64,
+                data: self.data,
+                symbols: self.symbols,
+                relocs: self.relocs,
+            }
+            .write()
+    }
+
+    /// Get the ELF relocation type and the addend bias for `reloc`.
+    fn elf_reloc(&self, reloc: &Reloc) -> Result<(u32, i64), String> {
+        let name = self.isa.reloc_names()[reloc.0 as usize];
+        let is_64bit = self.isa.flags().is_64bit();
+        match (self.isa.name(), name) {
+            // The x86 PC-relative displacements are relative to the end of the 4-byte field.
+            ("intel", "Abs4") |
+            ("intel", "ConstAbs4") if is_64bit => Ok((elf::R_X86_64_32, 0)),
+            ("intel", "Call") |
+            ("intel", "ConstPcrel4") if is_64bit => Ok((elf::R_X86_64_PC32, -4)),
+            ("intel", "Abs4") |
+            ("intel", "ConstAbs4") => Ok((elf::R_386_32, 0)),
+            ("intel", "Call") => Ok((elf::R_386_PC32, -4)),
+            ("riscv", "Call") => Ok((elf::R_RISCV_JAL, 0)),
+            (isa, _) => Err(format!("Unsupported {} relocation: {}", isa, name)),
+        }
+    }
+
+    /// Is `reloc` a PC-relative reference to the constant pool that was resolved when emitted?
+    ///
+    /// These only need a relocation when the constant pool is moved out of `.text`.
+    fn resolved_pcrel(&self, reloc: &Reloc) -> bool {
+        match (self.isa.name(), self.isa.reloc_names()[reloc.0 as usize]) {
+            ("intel", "ConstPcrel4") |
+            ("riscv", "ConstPcrel") => true,
+            _ => false,
+        }
+    }
+
+    /// Get the ELF relocation type for a pointer of `size` bytes in a data object.
+    fn pointer_reloc(&self, size: u8) -> Result<u32, String> {
+        match (self.isa.name(), self.isa.flags().is_64bit(), size) {
+            ("intel", true, 8) => Ok(elf::R_X86_64_64),
+            ("intel", true, 4) => Ok(elf::R_X86_64_32),
+            ("intel", false, 4) => Ok(elf::R_386_32),
+            ("riscv", _, 4) => Ok(elf::R_RISCV_32),
+            ("riscv", _, 8) => Ok(elf::R_RISCV_64),
+            (isa, _, _) => Err(format!("Unsupported {} pointer size: {}", isa, size)),
+        }
+    }
+
+    /// Should relocations have explicit addends?
+    ///
+    /// The i386 ABI uses `.rel` sections where the addend is stored in the patched location.
+    fn use_rela(&self) -> bool {
+        self.isa.name() != "intel" || self.isa.flags().is_64bit()
+    }
+
+    /// Add a relocation of the `field` at `offset` in `section`.
+    fn add_reloc(&mut self,
+                 field: &mut [u8],
+                 section: u16,
+                 offset: u64,
+                 symbol: u32,
+                 kind: u32,
+                 addend: i64) {
+        let addend = if self.use_rela() {
+            addend
+        } else {
+            // All the i386 relocations patch a 4-byte field.
+            let mut x = 0u32;
+            for (n, byte) in field[0..4].iter().enumerate() {
+                x |= (*byte as u32) << (8 * n);
+            }
+            x = x.wrapping_add(addend as u32);
+            for (n, byte) in field[0..4].iter_mut().enumerate() {
+                *byte = (x >> (8 * n)) as u8;
+            }
+            0
+        };
+        self.relocs
+            .push(elf::Relocation {
+                      section,
+                      offset,
+                      symbol,
+                      kind,
+                      addend,
+                  });
+    }
+
+    /// Get the symbol table index of the global symbol `name`, declaring it if necessary.
+    fn declare(&mut self, name: &[u8]) -> Result<u32, String> {
+        if name.is_empty() {
+            return Err("Can't reference an anonymous symbol".to_string());
+        }
+        let symbols = &mut self.symbols;
+        let idx = *self.symbol_index
+                       .entry(name.to_vec())
+                       .or_insert_with(|| {
+                                           symbols.push(elf::Symbol {
+                                                            name: name.to_vec(),
+                                                            def: None,
+                                                        });
+                                           symbols.len() - 1
+                                       });
+        Ok(elf::SYM_FIRST_GLOBAL + idx as u32)
+    }
+
+    /// Define the global symbol `name` for a function or data object in `section`.
+    fn define(&mut self,
+              name: &FunctionName,
+              section: u16,
+              offset: u64,
+              size: u64)
+              -> Result<(), String> {
+        let sym = self.declare(name.as_ref())
+            .map_err(|_| "Symbols need a name in an object file".to_string())?;
+        let sym = &mut self.symbols[(sym - elf::SYM_FIRST_GLOBAL) as usize];
+        if sym.def.is_some() {
+            return Err(format!("Duplicate definition of {}", name));
+        }
+        sym.def = Some((section, offset, size));
+        Ok(())
+    }
+}
+
+impl<'a> Backend for ObjectBuilder<'a> {
+    fn define_function(&mut self, func: &Function, code_size: CodeOffset) -> Result<(), String> {
+        self.add_function(func, code_size)
+    }
+
+    fn define_data(&mut self, data: &DataObject) -> Result<(), String> {
+        self.add_data(data)
+    }
+}
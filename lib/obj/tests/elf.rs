@@ -0,0 +1,515 @@
+extern crate cretonne;
+extern crate cton_obj;
+extern crate cton_reader;
+
+use cretonne::Context;
+use cretonne::binemit;
+use cton_obj::ObjectBuilder;
+use cton_reader::parse_test;
+
+/// Compile all the functions and data objects in `source` into an object file.
+fn compile(source: &str) -> Vec<u8> {
+    let test = parse_test(source).unwrap();
+    let isa = test.isa_spec.unique_isa().expect("Need an ISA");
+    let mut obj = ObjectBuilder::new(isa).unwrap();
+    for (func, _) in test.functions {
+        let mut ctx = Context::new();
+        ctx.func = func;
+        let size = ctx.compile(isa).unwrap();
+        obj.add_function(&ctx.func, size).unwrap();
+    }
+    for data in &test.data {
+        obj.add_data(data).unwrap();
+    }
+    obj.finish()
+}
+
+/// Build an object file from functions in `source` that have already been register allocated.
+///
+/// Instructions without an encoding are given the default encoding for the ISA.
+fn assemble(source: &str) -> Vec<u8> {
+    let test = parse_test(source).unwrap();
+    let isa = test.isa_spec.unique_isa().expect("Need an ISA");
+    let mut obj = ObjectBuilder::new(isa).unwrap();
+    for (mut func, _) in test.functions {
+        let insts = func.layout
+            .ebbs()
+            .flat_map(|ebb| func.layout.ebb_insts(ebb))
+            .collect::<Vec<_>>();
+        for inst in insts {
+            if !func.encodings.get_or_default(inst).is_legal() {
+                let ctrl_type = func.dfg.ctrl_typevar(inst);
+                let enc = isa.encode(&func.dfg, &func.dfg[inst], ctrl_type)
+                    .expect("No encoding");
+                *func.encodings.ensure(inst) = enc;
+            }
+        }
+        let size = binemit::relax_branches(&mut func, isa);
+        obj.add_function(&func, size).unwrap();
+    }
+    obj.finish()
+}
+
+/// Minimal little-endian ELF reader for checking the generated object files.
+struct Elf<'a> {
+    data: &'a [u8],
+    is_64bit: bool,
+}
+
+#[derive(Debug)]
+struct Section<'a> {
+    name: String,
+    kind: u32,
+    link: u32,
+    info: u32,
+    offset: usize,
+    data: &'a [u8],
+}
+
+#[derive(Debug, PartialEq)]
+struct Symbol {
+    name: String,
+    value: u64,
+    size: u64,
+    info: u8,
+    shndx: u16,
+}
+
+#[derive(Debug, PartialEq)]
+struct Reloc {
+    offset: u64,
+    symbol: u32,
+    kind: u32,
+    addend: Option<i64>,
+}
+
+impl<'a> Elf<'a> {
+    fn new(data: &'a [u8]) -> Elf<'a> {
+        assert_eq!(&data[0..4], b"\x7fELF");
+        assert_eq!(data[5], 1, "Not little-endian");
+        let elf = Elf {
+            data,
+            is_64bit: data[4] == 2,
+        };
+        assert_eq!(elf.u16(16), 1, "Not a relocatable object");
+        elf
+    }
+
+    fn u16(&self, at: usize) -> u16 {
+        self.data[at] as u16 | (self.data[at + 1] as u16) << 8
+    }
+
+    fn u32(&self, at: usize) -> u32 {
+        self.u16(at) as u32 | (self.u16(at + 2) as u32) << 16
+    }
+
+    fn u64(&self, at: usize) -> u64 {
+        self.u32(at) as u64 | (self.u32(at + 4) as u64) << 32
+    }
+
+    fn addr(&self, at: usize) -> u64 {
+        if self.is_64bit {
+            self.u64(at)
+        } else {
+            self.u32(at) as u64
+        }
+    }
+
+    fn addr_size(&self) -> usize {
+        if self.is_64bit { 8 } else { 4 }
+    }
+
+    fn machine(&self) -> u16 {
+        self.u16(18)
+    }
+
+    fn string(&self, strtab: &[u8], at: u32) -> String {
+        let s = &strtab[at as usize..];
+        let len = s.iter().position(|&b| b == 0).unwrap();
+        String::from_utf8(s[0..len].to_vec()).unwrap()
+    }
+
+    fn sections(&self) -> Vec<Section<'a>> {
+        let a = self.addr_size();
+        let shoff = self.addr(24 + 2 * a) as usize;
+        let shentsize = self.u16(34 + 3 * a) as usize;
+        let shnum = self.u16(36 + 3 * a) as usize;
+        let shstrndx = self.u16(38 + 3 * a) as usize;
+
+        let raw = (0..shnum)
+            .map(|i| {
+                let sh = shoff + i * shentsize;
+                let offset = self.addr(sh + 8 + 2 * a) as usize;
+                let size = self.addr(sh + 8 + 3 * a) as usize;
+                (self.u32(sh),
+                 self.u32(sh + 4),
+                 self.u32(sh + 8 + 4 * a),
+                 self.u32(sh + 12 + 4 * a),
+                 offset,
+                 &self.data[offset..offset + size])
+            })
+            .collect::<Vec<_>>();
+        let shstrtab = raw[shstrndx].5;
+        raw.iter()
+            .map(|&(name, kind, link, info, offset, data)| {
+                     Section {
+                         name: self.string(shstrtab, name),
+                         kind,
+                         link,
+                         info,
+                         offset,
+                         data,
+                     }
+                 })
+            .collect()
+    }
+
+    fn section(&self, name: &str) -> Section<'a> {
+        self.sections()
+            .into_iter()
+            .find(|s| s.name == name)
+            .expect(name)
+    }
+
+    fn symbols(&self) -> Vec<Symbol> {
+        let sections = self.sections();
+        let symtab = sections.iter().find(|s| s.kind == 2).unwrap();
+        let strtab = sections[symtab.link as usize].data;
+        let entsize = if self.is_64bit { 24 } else { 16 };
+        (0..symtab.data.len() / entsize)
+            .map(|i| {
+                let at = symtab.offset + i * entsize;
+                let (value, size, info, shndx) = if self.is_64bit {
+                    (self.u64(at + 8), self.u64(at + 16), self.data[at + 4], self.u16(at + 6))
+                } else {
+                    (self.u32(at + 4) as u64,
+                     self.u32(at + 8) as u64,
+                     self.data[at + 12],
+                     self.u16(at + 14))
+                };
+                Symbol {
+                    name: self.string(strtab, self.u32(at)),
+                    value,
+                    size,
+                    info,
+                    shndx,
+                }
+            })
+            .collect()
+    }
+
+    fn relocs(&self, section: &str) -> Vec<Reloc> {
+        let sections = self.sections();
+        let rel = sections
+            .iter()
+            .find(|s| (s.kind == 4 || s.kind == 9) && sections[s.info as usize].name == section)
+            .unwrap();
+        let rela = rel.kind == 4;
+        let a = self.addr_size();
+        let entsize = if rela { 3 * a } else { 2 * a };
+        (0..rel.data.len() / entsize)
+            .map(|i| {
+                let at = rel.offset + i * entsize;
+                let info = self.addr(at + a);
+                let (symbol, kind) = if self.is_64bit {
+                    ((info >> 32) as u32, info as u32)
+                } else {
+                    ((info >> 8) as u32, info as u32 & 0xff)
+                };
+                let addend = if !rela {
+                    None
+                } else if self.is_64bit {
+                    Some(self.u64(at + 2 * a) as i64)
+                } else {
+                    Some(self.u32(at + 2 * a) as i32 as i64)
+                };
+                Reloc {
+                    offset: self.addr(at),
+                    symbol,
+                    kind,
+                    addend,
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn riscv_calls() {
+    let obj = compile("
+        isa riscv
+
+        function %callee() {
+        ebb0:
+            return
+        }
+
+        function %caller(i32) -> i32 {
+            fn0 = function %callee()
+            fn1 = function %external()
+        ebb0(v0: i32):
+            call fn1()
+            call fn0()
+            return v0
+        }
+    ");
+    let elf = Elf::new(&obj);
+    assert!(!elf.is_64bit);
+    assert_eq!(elf.machine(), 243);
+
+    let names = elf.sections()
+        .iter()
+        .map(|s| s.name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(names,
+               ["",
+                ".text",
+                ".rodata",
+                ".data",
+                ".rela.text",
+                ".rela.rodata",
+                ".rela.data",
+                ".symtab",
+                ".strtab",
+                ".shstrtab"]);
+
+    let syms = elf.symbols();
+    assert_eq!(syms.len(), 7);
+    assert_eq!(syms[4].name, "callee");
+    assert_eq!((syms[4].value, syms[4].info, syms[4].shndx), (0, 0x12, 1));
+    assert_eq!(syms[5].name, "caller");
+    assert_eq!((syms[5].value % 16, syms[5].info, syms[5].shndx), (0, 0x12, 1));
+    assert_eq!(syms[6].name, "external");
+    assert_eq!((syms[6].value, syms[6].info, syms[6].shndx), (0, 0x10, 0));
+
+    // Two `jal` instructions with R_RISCV_JAL relocations.
+    let text = elf.section(".text").data;
+    let relocs = elf.relocs(".text");
+    assert_eq!(relocs.len(), 2);
+    assert_eq!((relocs[0].symbol, relocs[0].kind, relocs[0].addend), (6, 17, Some(0)));
+    assert_eq!((relocs[1].symbol, relocs[1].kind, relocs[1].addend), (4, 17, Some(0)));
+    for rel in &relocs {
+        assert!(rel.offset >= syms[5].value);
+        assert_eq!(text[rel.offset as usize] & 0x7f, 0x6f, "Not a jal");
+    }
+}
+
+#[test]
+fn riscv_branches() {
+    let obj = compile("
+        isa riscv
+
+        function %sum(i32) -> i32 {
+        ebb0(v0: i32):
+            v1 = iconst.i32 0
+            brnz v0, ebb1(v0, v1)
+            return v1
+
+        ebb1(v2: i32, v3: i32):
+            v4 = iadd v3, v2
+            v5 = iadd_imm v2, -1
+            brnz v5, ebb1(v5, v4)
+            return v4
+        }
+    ");
+    let elf = Elf::new(&obj);
+
+    // Branches between EBBs are resolved when the code is emitted, so they need no relocations.
+    assert_eq!(elf.relocs(".text").len(), 0);
+
+    // Both `bne` instructions branch to `ebb1`, which follows the first `return`. One branch is
+    // forwards and the other backwards.
+    let text = elf.section(".text");
+    let mut targets = Vec::new();
+    for at in (0..text.data.len()).filter(|at| at % 4 == 0) {
+        let i = elf.u32(text.offset + at);
+        if i & 0x7f != 0x63 {
+            continue;
+        }
+        // Unscramble the SB-type displacement.
+        let mut imm = ((i >> 7) & 0x1e) | ((i >> 20) & 0x7e0) | ((i << 4) & 0x800);
+        if i & 0x8000_0000 != 0 {
+            imm |= 0xffff_f000;
+        }
+        targets.push(((at as u32).wrapping_add(imm), imm as i32 > 0));
+    }
+    assert_eq!(targets, [(0x10, true), (0x10, false)]);
+}
+
+#[test]
+fn intel_jump_table() {
+    let obj = assemble("
+        isa intel
+
+        function %first() {
+        ebb0:
+            trap user0
+        }
+
+        function %switch() {
+            jt0 = jump_table ebb1, ebb2
+        ebb0:
+            [-,%rdx] v1 = jump_table_base.i32 jt0
+            indirect_jump_table_br v1, jt0
+        ebb1:
+            trap user1
+        ebb2:
+            trap user2
+        }
+    ");
+    let elf = Elf::new(&obj);
+    assert!(!elf.is_64bit);
+    assert_eq!(elf.machine(), 3);
+
+    let syms = elf.symbols();
+    assert_eq!(syms.len(), 6);
+    assert_eq!(syms[1].info & 0xf, 3, "Section symbol");
+    assert_eq!(syms[1].shndx, 1, ".text");
+    assert_eq!(syms[5].name, "switch");
+    let switch = syms[5].value;
+
+    // The jump table stays in `.text` after the code, and it is addressed with an R_386_32
+    // relocation against the `.text` section symbol. The addend is stored in the code.
+    let text = elf.section(".text");
+    assert_eq!(text.data.len() as u64, switch + syms[5].size);
+    assert_eq!(elf.section(".rodata").data.len(), 0);
+    let relocs = elf.relocs(".text");
+    assert_eq!(relocs.len(), 1);
+    let rel = &relocs[0];
+    assert_eq!((rel.symbol, rel.kind, rel.addend), (1, 1, None));
+    assert!(rel.offset > switch);
+    let table = elf.u32(text.offset + rel.offset as usize) as usize;
+    assert_eq!(table % 4, 0);
+    assert_eq!(text.data.len(), table + 8);
+
+    // The table entries are relative to the table. The code is `movl $jt0, %edx` and `jmp *%edx`
+    // followed by the 2-byte `trap` instructions in `ebb1` and `ebb2`.
+    let ebb1 = table as i64 + elf.u32(text.offset + table) as i32 as i64;
+    let ebb2 = table as i64 + elf.u32(text.offset + table + 4) as i32 as i64;
+    assert_eq!(ebb1, switch as i64 + 7);
+    assert_eq!(ebb2, switch as i64 + 9);
+    assert_eq!(&text.data[ebb1 as usize..ebb1 as usize + 2], [0x0f, 0x0b]);
+}
+
+#[test]
+fn intel_constants() {
+    let obj = assemble("
+        isa intel
+
+        function %first() {
+        ebb0:
+            trap user0
+        }
+
+        function %load() -> i32 {
+            const0 = constant.i32 0x1234_5678
+        ebb0:
+            [-,%rcx] v1 = const_addr.i32 const0
+            [-,%rax] v2 = load.i32 notrap aligned v1
+            return v2
+        }
+    ");
+    let elf = Elf::new(&obj);
+    let syms = elf.symbols();
+    assert_eq!(syms.len(), 6);
+    assert_eq!(syms[2].info & 0xf, 3, "Section symbol");
+    assert_eq!(syms[2].shndx, 2, ".rodata");
+    assert_eq!(syms[5].name, "load");
+
+    // The constant pool is moved to `.rodata`, and it is addressed with an R_386_32 relocation
+    // against the `.rodata` section symbol.
+    let text = elf.section(".text");
+    assert_eq!(text.data.len() as u64, syms[5].value + syms[5].size);
+    let relocs = elf.relocs(".text");
+    assert_eq!(relocs.len(), 1);
+    let rel = &relocs[0];
+    assert_eq!((rel.symbol, rel.kind, rel.addend), (2, 1, None));
+    let constant = elf.u32(text.offset + rel.offset as usize) as usize;
+    assert_eq!(constant % 4, 0);
+    let rodata = elf.section(".rodata");
+    assert_eq!(rodata.data.len(), constant + 4);
+    assert_eq!(elf.u32(rodata.offset + constant), 0x1234_5678);
+}
+
+#[test]
+fn riscv_constants() {
+    let obj = assemble("
+        isa riscv
+
+        function %load() -> i32 {
+            const0 = constant.i32 0x1234_5678
+        ebb0:
+            [-,%x5] v1 = const_addr.i32 const0
+            [-,%x10] v2 = load.i32 notrap aligned v1
+            return v2
+        }
+    ");
+    let elf = Elf::new(&obj);
+
+    // The `auipc` + `addi` sequence is resolved when emitted, so the constant pool stays in
+    // `.text` after the code and needs no relocation.
+    assert_eq!(elf.relocs(".text").len(), 0);
+    assert_eq!(elf.section(".rodata").data.len(), 0);
+    let text = elf.section(".text");
+    let auipc = elf.u32(text.offset);
+    let addi = elf.u32(text.offset + 4);
+    assert_eq!(auipc & 0x7f, 0x17, "Not an auipc");
+    let constant = ((auipc & 0xffff_f000) as i32 + ((addi as i32) >> 20)) as usize;
+    assert_eq!(text.data.len(), constant + 4);
+    assert_eq!(elf.u32(text.offset + constant), 0x1234_5678);
+}
+
+#[test]
+fn intel_data() {
+    let obj = compile("
+        set is_64bit=1
+        isa intel
+
+        data %counter = [#00000000], align 4, writable
+
+        function %get() -> i32 {
+            gv0 = globalsym %counter
+        ebb0:
+            v0 = global_addr.i64 gv0
+            v1 = load.i32 v0
+            return v1
+        }
+
+        data %table = [#01, %get, %counter, %external], align 8
+    ");
+    let elf = Elf::new(&obj);
+    assert!(elf.is_64bit);
+    assert_eq!(elf.machine(), 62);
+
+    let syms = elf.symbols();
+    assert_eq!(syms.len(), 8);
+    assert_eq!(syms[4].name, "get");
+    assert_eq!((syms[4].info, syms[4].shndx), (0x12, 1));
+    assert_eq!(syms[5].name, "counter");
+    assert_eq!((syms[5].value, syms[5].size, syms[5].info, syms[5].shndx), (0, 4, 0x11, 3));
+    assert_eq!(syms[6].name, "table");
+    assert_eq!((syms[6].value % 8, syms[6].size, syms[6].info, syms[6].shndx),
+               (0, 25, 0x11, 2));
+    assert_eq!(syms[7].name, "external");
+    assert_eq!((syms[7].value, syms[7].info, syms[7].shndx), (0, 0x10, 0));
+
+    // The function loads from `counter` through an R_X86_64_32 relocation.
+    let relocs = elf.relocs(".text");
+    assert_eq!(relocs.len(), 1);
+    assert_eq!((relocs[0].symbol, relocs[0].kind, relocs[0].addend), (5, 10, Some(0)));
+
+    // The pointers in `table` have R_X86_64_64 relocations.
+    let table = syms[6].value;
+    let relocs = elf.relocs(".rodata");
+    let relocs = relocs
+        .iter()
+        .map(|r| (r.offset - table, r.symbol, r.kind, r.addend))
+        .collect::<Vec<_>>();
+    assert_eq!(relocs,
+               [(1, 4, 1, Some(0)), (9, 5, 1, Some(0)), (17, 7, 1, Some(0))]);
+    assert_eq!(elf.relocs(".data").len(), 0);
+
+    let rodata = elf.section(".rodata").data;
+    assert_eq!(rodata[table as usize], 1);
+    assert_eq!(elf.section(".data").data, [0, 0, 0, 0]);
+}
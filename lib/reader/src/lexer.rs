@@ -37,11 +37,15 @@ pub enum Token<'a> {
     Value(Value), // v12, v7
     Ebb(Ebb), // ebb3
     StackSlot(u32), // ss3
+    GlobalVar(u32), // gv3
     JumpTable(u32), // jt2
     FuncRef(u32), // fn2
     SigRef(u32), // sig2
+    Heap(u32), // heap2
+    Constant(u32), // const2
     Name(&'a str), // %9arbitrary_alphanum, %x3, %0, %function ...
     HexSequence(&'a str), // #89AF
+    SourceLoc(&'a str), // @00c7
     Identifier(&'a str), // Unrecognized identifier (opcode, enumerator, ...)
 }
 
@@ -308,9 +312,12 @@ impl<'a> Lexer<'a> {
             "v" => Value::with_number(number).map(|v| Token::Value(v)),
             "ebb" => Ebb::with_number(number).map(|ebb| Token::Ebb(ebb)),
             "ss" => Some(Token::StackSlot(number)),
+            "gv" => Some(Token::GlobalVar(number)),
             "jt" => Some(Token::JumpTable(number)),
             "fn" => Some(Token::FuncRef(number)),
             "sig" => Some(Token::SigRef(number)),
+            "heap" => Some(Token::Heap(number)),
+            "const" => Some(Token::Constant(number)),
             _ => None,
         }
     }
@@ -328,6 +335,7 @@ impl<'a> Lexer<'a> {
             "i16" => types::I16,
             "i32" => types::I32,
             "i64" => types::I64,
+            "i128" => types::I128,
             "f32" => types::F32,
             "f64" => types::F64,
             "b1" => types::B1,
@@ -380,6 +388,22 @@ impl<'a> Lexer<'a> {
         token(Token::HexSequence(&self.source[begin..end]), loc)
     }
 
+    fn scan_srcloc(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
+        let loc = self.loc();
+        let begin = self.pos + 1;
+
+        assert!(self.lookahead == Some('@'));
+
+        while let Some(c) = self.next_ch() {
+            if !char::is_digit(c, 16) {
+                break;
+            }
+        }
+
+        let end = self.pos;
+        token(Token::SourceLoc(&self.source[begin..end]), loc)
+    }
+
     /// Get the next token or a lexical error.
     ///
     /// Return None when the end of the source is encountered.
@@ -411,6 +435,7 @@ impl<'a> Lexer<'a> {
                        Some(ch) if ch.is_alphabetic() => Some(self.scan_word()),
                        Some('%') => Some(self.scan_name()),
                        Some('#') => Some(self.scan_hex_sequence()),
+                       Some('@') => Some(self.scan_srcloc()),
                        Some(ch) if ch.is_whitespace() => {
                            self.next_ch();
                            continue;
@@ -555,6 +580,15 @@ mod tests {
         assert_eq!(lex.next(), token(Token::HexSequence("789"), 1));
     }
 
+    #[test]
+    fn lex_srclocs() {
+        let mut lex = Lexer::new("@0 @00c7 v1");
+
+        assert_eq!(lex.next(), token(Token::SourceLoc("0"), 1));
+        assert_eq!(lex.next(), token(Token::SourceLoc("00c7"), 1));
+        assert_eq!(lex.next(), token(Token::Value(Value::with_number(1).unwrap()), 1));
+    }
+
     #[test]
     fn lex_names() {
         let mut lex = Lexer::new("%0 %x3 %function %123_abc %ss0 %v3 %ebb11 %_");
@@ -11,12 +11,14 @@ use std::{u16, u32};
 use std::mem;
 use cretonne::ir::{Function, Ebb, Opcode, Value, Type, FunctionName, StackSlotData, JumpTable,
                    JumpTableData, Signature, ArgumentType, ArgumentExtension, ExtFuncData, SigRef,
-                   FuncRef, StackSlot, ValueLoc, ArgumentLoc, MemFlags};
-use cretonne::ir::types::VOID;
-use cretonne::ir::immediates::{Imm64, Offset32, Uoffset32, Ieee32, Ieee64};
+                   FuncRef, StackSlot, GlobalVar, GlobalVarData, Heap, HeapData, HeapBase,
+                   HeapStyle, Constant, ConstantData, ValueLoc, ArgumentLoc, MemFlags, SourceLoc};
+use cretonne::ir::types::{VOID, I32, I64, F32, F64};
+use cretonne::ir::immediates::{Imm64, Offset32, Ieee32, Ieee64};
 use cretonne::ir::entities::AnyEntity;
 use cretonne::ir::instructions::{InstructionFormat, InstructionData, VariableArgs};
 use cretonne::isa::{self, TargetIsa, Encoding, RegUnit};
+use cretonne::module::{DataObject, DataPointer};
 use cretonne::settings::{self, Configurable};
 use testfile::{TestFile, Details, Comment};
 use error::{Location, Error, Result};
@@ -43,13 +45,14 @@ pub fn parse_test<'a>(text: &'a str) -> Result<TestFile<'a>> {
     let commands = parser.parse_test_commands();
     let isa_spec = parser.parse_isa_specs()?;
     let preamble_comments = parser.take_comments();
-    let functions = parser.parse_function_list(isa_spec.unique_isa())?;
+    let (functions, data) = parser.parse_function_list(isa_spec.unique_isa())?;
 
     Ok(TestFile {
            commands,
            isa_spec,
            preamble_comments,
            functions,
+           data,
        })
 }
 
@@ -127,6 +130,20 @@ impl<'a> Context<'a> {
         }
     }
 
+    // Allocate a global variable slot and add a mapping number -> GlobalVar.
+    fn add_gv(&mut self, number: u32, data: GlobalVarData, loc: &Location) -> Result<()> {
+        self.map
+            .def_gv(number, self.function.global_vars.push(data), loc)
+    }
+
+    // Resolve a reference to a global variable.
+    fn get_gv(&self, number: u32, loc: &Location) -> Result<GlobalVar> {
+        match self.map.get_gv(number) {
+            Some(gv) => Ok(gv),
+            None => err!(loc, "undefined global variable gv{}", number),
+        }
+    }
+
     // Allocate a new signature and add a mapping number -> SigRef.
     fn add_sig(&mut self, number: u32, data: Signature, loc: &Location) -> Result<()> {
         self.map
@@ -169,6 +186,34 @@ impl<'a> Context<'a> {
         }
     }
 
+    // Allocate a new heap and add a mapping number -> Heap.
+    fn add_heap(&mut self, number: u32, data: HeapData, loc: &Location) -> Result<()> {
+        self.map
+            .def_heap(number, self.function.heaps.push(data), loc)
+    }
+
+    // Resolve a reference to a heap.
+    fn get_heap(&self, number: u32, loc: &Location) -> Result<Heap> {
+        match self.map.get_heap(number) {
+            Some(heap) => Ok(heap),
+            None => err!(loc, "undefined heap heap{}", number),
+        }
+    }
+
+    // Allocate a new constant and add a mapping number -> Constant.
+    fn add_constant(&mut self, number: u32, data: ConstantData, loc: &Location) -> Result<()> {
+        self.map
+            .def_constant(number, self.function.constants.push(data), loc)
+    }
+
+    // Resolve a reference to a constant.
+    fn get_constant(&self, number: u32, loc: &Location) -> Result<Constant> {
+        match self.map.get_constant(number) {
+            Some(c) => Ok(c),
+            None => err!(loc, "undefined constant const{}", number),
+        }
+    }
+
     // Allocate a new EBB and add a mapping src_ebb -> Ebb.
     fn add_ebb(&mut self, src_ebb: Ebb, loc: &Location) -> Result<Ebb> {
         let ebb = self.function.dfg.make_ebb();
@@ -344,6 +389,16 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Match and consume a global variable reference.
+    fn match_gv(&mut self, err_msg: &str) -> Result<u32> {
+        if let Some(Token::GlobalVar(gv)) = self.token() {
+            self.consume();
+            Ok(gv)
+        } else {
+            err!(self.loc, err_msg)
+        }
+    }
+
     // Match and consume a function reference.
     fn match_fn(&mut self, err_msg: &str) -> Result<u32> {
         if let Some(Token::FuncRef(fnref)) = self.token() {
@@ -374,6 +429,26 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Match and consume a heap reference.
+    fn match_heap(&mut self, err_msg: &str) -> Result<u32> {
+        if let Some(Token::Heap(heap)) = self.token() {
+            self.consume();
+            Ok(heap)
+        } else {
+            err!(self.loc, err_msg)
+        }
+    }
+
+    // Match and consume a constant reference.
+    fn match_constant(&mut self, err_msg: &str) -> Result<u32> {
+        if let Some(Token::Constant(c)) = self.token() {
+            self.consume();
+            Ok(c)
+        } else {
+            err!(self.loc, err_msg)
+        }
+    }
+
     // Match and consume an ebb reference.
     fn match_ebb(&mut self, err_msg: &str) -> Result<Ebb> {
         if let Some(Token::Ebb(ebb)) = self.token() {
@@ -458,22 +533,6 @@ impl<'a> Parser<'a> {
         }
     }
 
-    // Match and consume an optional uoffset32 immediate.
-    //
-    // Note that that this will match an empty string as an empty offset, and that if an offset is
-    // present, it must contain a `+` sign.
-    fn optional_uoffset32(&mut self) -> Result<Uoffset32> {
-        if let Some(Token::Integer(text)) = self.token() {
-            self.consume();
-            // Lexer just gives us raw text that looks like an integer.
-            // Parse it as a `Uoffset32` to check for overflow and other issues.
-            text.parse().map_err(|e| self.error(e))
-        } else {
-            // A uoffset32 operand can be absent.
-            Ok(Uoffset32::new(0))
-        }
-    }
-
     // Match and consume an Ieee32 immediate.
     fn match_ieee32(&mut self, err_msg: &str) -> Result<Ieee32> {
         if let Some(Token::Float(text)) = self.token() {
@@ -642,17 +701,117 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Parse a list of function definitions.
+    /// Parse a list of function definitions and data objects.
     ///
     /// This is the top-level parse function matching the whole contents of a file.
     pub fn parse_function_list(&mut self,
                                unique_isa: Option<&TargetIsa>)
-                               -> Result<Vec<(Function, Details<'a>)>> {
+                               -> Result<(Vec<(Function, Details<'a>)>, Vec<DataObject>)> {
         let mut list = Vec::new();
-        while self.token().is_some() {
-            list.push(self.parse_function(unique_isa)?);
+        let mut data = Vec::new();
+        while let Some(tok) = self.token() {
+            if tok == Token::Identifier("data") {
+                data.push(self.parse_data(unique_isa)?);
+            } else {
+                list.push(self.parse_function(unique_isa)?);
+            }
         }
-        Ok(list)
+        Ok((list, data))
+    }
+
+    // Parse a data object declaration.
+    //
+    // data-decl ::= * "data" name "=" "[" data-item { "," data-item } "]" { "," data-option }
+    // data-item ::= HexSequence | Integer | name
+    // data-option ::= "align" Integer | "readonly" | "writable"
+    //
+    fn parse_data(&mut self, unique_isa: Option<&TargetIsa>) -> Result<DataObject> {
+        self.match_identifier("data", "expected 'data'")?;
+        let name = self.parse_function_name()?;
+        self.match_token(Token::Equal, "expected '=' after data name")?;
+        self.match_token(Token::LBracket, "expected '[' before data contents")?;
+
+        let mut contents = Vec::new();
+        let mut pointers = Vec::new();
+        if !self.optional(Token::RBracket) {
+            loop {
+                match self.token() {
+                    Some(Token::HexSequence(s)) => {
+                        if s.len() % 2 != 0 {
+                            return err!(self.loc,
+                                        "expected data bytes to have length multiple of two");
+                        }
+                        for i in 0..s.len() / 2 {
+                            contents.push(u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap());
+                        }
+                        self.consume();
+                    }
+                    Some(Token::Integer(text)) => {
+                        let byte: i64 = Imm64::from_str(text)
+                            .map_err(|e| self.error(e))?
+                            .into();
+                        if byte < 0 || byte > 0xff {
+                            return err!(self.loc, "data byte out of range: {}", text);
+                        }
+                        self.consume();
+                        contents.push(byte as u8);
+                    }
+                    Some(Token::Name(_)) => {
+                        let size = match unique_isa {
+                            Some(isa) if isa.flags().is_64bit() => 8,
+                            Some(_) => 4,
+                            None => {
+                                return err!(self.loc,
+                                            "pointers in data require a unique ISA to be specified")
+                            }
+                        };
+                        let target = self.parse_function_name()?;
+                        pointers.push(DataPointer {
+                                          offset: contents.len() as u32,
+                                          size,
+                                          target,
+                                      });
+                        contents.resize(contents.len() + size as usize, 0);
+                    }
+                    _ => return err!(self.loc, "expected data bytes or symbol name"),
+                }
+                if !self.optional(Token::Comma) {
+                    break;
+                }
+            }
+            self.match_token(Token::RBracket, "expected ']' after data contents")?;
+        }
+
+        let mut align = 1;
+        let mut writable = false;
+        while self.optional(Token::Comma) {
+            match self.token() {
+                Some(Token::Identifier("align")) => {
+                    self.consume();
+                    align = self.match_uimm32("expected data alignment")?;
+                    if !align.is_power_of_two() {
+                        return err!(self.loc, "data alignment must be a power of two");
+                    }
+                }
+                Some(Token::Identifier("readonly")) => {
+                    self.consume();
+                    writable = false;
+                }
+                Some(Token::Identifier("writable")) => {
+                    self.consume();
+                    writable = true;
+                }
+                _ => return err!(self.loc, "expected 'align', 'readonly', or 'writable'"),
+            }
+        }
+
+        Ok(DataObject {
+               name,
+               contents,
+               pointers,
+               align,
+               writable,
+           })
     }
 
     // Parse a whole function definition.
@@ -748,13 +907,13 @@ impl<'a> Parser<'a> {
 
     // Parse a function signature.
     //
-    // signature ::=  * "(" [arglist] ")" ["->" retlist] [call_conv]
+    // signature ::=  * "(" [arglist] ")" ["->" retlist] [callconv]
     //
     fn parse_signature(&mut self, unique_isa: Option<&TargetIsa>) -> Result<Signature> {
         let mut sig = Signature::new();
 
         self.match_token(Token::LPar, "expected function signature: ( args... )")?;
-        // signature ::=  "(" * [arglist] ")" ["->" retlist] [call_conv]
+        // signature ::=  "(" * [arglist] ")" ["->" retlist] [callconv]
         if self.token() != Some(Token::RPar) {
             sig.argument_types = self.parse_argument_list(unique_isa)?;
         }
@@ -763,12 +922,21 @@ impl<'a> Parser<'a> {
             sig.return_types = self.parse_argument_list(unique_isa)?;
         }
 
+        // The calling convention is optional.
+        if let Some(Token::Identifier(text)) = self.token() {
+            match text.parse() {
+                Ok(cc) => {
+                    self.consume();
+                    sig.call_conv = cc;
+                }
+                _ => return err!(self.loc, "unknown calling convention: {}", text),
+            }
+        }
+
         if sig.argument_types.iter().all(|a| a.location.is_assigned()) {
             sig.compute_argument_bytes();
         }
 
-        // TBD: calling convention.
-
         Ok(sig)
     }
 
@@ -860,9 +1028,12 @@ impl<'a> Parser<'a> {
     //
     // preamble      ::= * { preamble-decl }
     // preamble-decl ::= * stack-slot-decl
+    //                   * global-var-decl
     //                   * function-decl
     //                   * signature-decl
     //                   * jump-table-decl
+    //                   * heap-decl
+    //                   * constant-decl
     //
     // The parsed decls are added to `ctx` rather than returned.
     fn parse_preamble(&mut self, ctx: &mut Context) -> Result<()> {
@@ -873,6 +1044,11 @@ impl<'a> Parser<'a> {
                     self.parse_stack_slot_decl()
                         .and_then(|(num, dat)| ctx.add_ss(num, dat, &self.loc))
                 }
+                Some(Token::GlobalVar(..)) => {
+                    self.gather_comments(ctx.function.global_vars.next_key());
+                    self.parse_global_var_decl(ctx)
+                        .and_then(|(num, dat)| ctx.add_gv(num, dat, &self.loc))
+                }
                 Some(Token::SigRef(..)) => {
                     self.gather_comments(ctx.function.dfg.signatures.next_key());
                     self.parse_signature_decl(ctx.unique_isa)
@@ -888,6 +1064,16 @@ impl<'a> Parser<'a> {
                     self.parse_jump_table_decl()
                         .and_then(|(num, dat)| ctx.add_jt(num, dat, &self.loc))
                 }
+                Some(Token::Heap(..)) => {
+                    self.gather_comments(ctx.function.heaps.next_key());
+                    self.parse_heap_decl()
+                        .and_then(|(num, dat)| ctx.add_heap(num, dat, &self.loc))
+                }
+                Some(Token::Constant(..)) => {
+                    self.gather_comments(ctx.function.constants.next_key());
+                    self.parse_constant_decl()
+                        .and_then(|(num, dat)| ctx.add_constant(num, dat, &self.loc))
+                }
                 // More to come..
                 _ => return Ok(()),
             }?;
@@ -917,6 +1103,41 @@ impl<'a> Parser<'a> {
         Ok((number, data))
     }
 
+    // Parse a global variable decl.
+    //
+    // global-var-decl ::= * GlobalVar(gv) "=" global-var-desc
+    // global-var-desc ::= "vmctx" [Offset32]
+    //                   | "deref" "(" GlobalVar(base) ")" [Offset32]
+    //                   | "globalsym" name
+    //
+    // The base of a `deref` global variable must be declared first.
+    fn parse_global_var_decl(&mut self, ctx: &Context) -> Result<(u32, GlobalVarData)> {
+        let number = self.match_gv("expected global variable number: gv«n»")?;
+        self.match_token(Token::Equal, "expected '=' in global variable decl")?;
+
+        let data = match self.match_any_identifier("expected global variable kind")? {
+            "vmctx" => {
+                let offset = self.optional_offset32()?;
+                GlobalVarData::VmCtx { offset }
+            }
+            "deref" => {
+                self.match_token(Token::LPar, "expected '(' in 'deref(global)'")?;
+                let base = self.match_gv("expected global variable: gv«n»")
+                    .and_then(|num| ctx.get_gv(num, &self.loc))?;
+                self.match_token(Token::RPar, "expected ')' in 'deref(global)'")?;
+                let offset = self.optional_offset32()?;
+                GlobalVarData::Deref { base, offset }
+            }
+            "globalsym" => {
+                let name = self.parse_function_name()?;
+                GlobalVarData::Sym { name }
+            }
+            other => return err!(self.loc, "unknown global variable kind '{}'", other),
+        };
+
+        Ok((number, data))
+    }
+
     // Parse a signature decl.
     //
     // signature-decl ::= SigRef(sigref) "=" "signature" signature
@@ -1011,6 +1232,85 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Parse a heap decl.
+    //
+    // heap-decl ::= * Heap(heap) "=" heap-style heap-base "," "bound" heap-bound
+    //                 "," "guard" Imm64(bytes) "," "index_type" type
+    // heap-style ::= "static" | "dynamic"
+    // heap-base ::= "vmctx" [Offset32]
+    // heap-bound ::= Imm64(bytes) | "vmctx" [Offset32]
+    //
+    // A static heap has a constant bound, a dynamic heap has its bound stored in the VM context.
+    fn parse_heap_decl(&mut self) -> Result<(u32, HeapData)> {
+        let number = self.match_heap("expected heap number: heap«n»")?;
+        self.match_token(Token::Equal, "expected '=' in heap decl")?;
+
+        let is_static = match self.match_any_identifier("expected 'static' or 'dynamic'")? {
+            "static" => true,
+            "dynamic" => false,
+            _ => return err!(self.loc, "expected 'static' or 'dynamic'"),
+        };
+
+        // heap-decl ::= Heap(heap) "=" heap-style * heap-base ...
+        self.match_identifier("vmctx", "expected heap base: vmctx")?;
+        let base = HeapBase::VmCtx(self.optional_offset32()?);
+
+        // heap-decl ::= Heap(heap) "=" heap-style heap-base * "," "bound" heap-bound ...
+        self.match_token(Token::Comma, "expected ',' after heap base")?;
+        self.match_identifier("bound", "expected 'bound'")?;
+        let style = if is_static {
+            let bound = self.match_imm64("expected static heap bound in bytes")?;
+            HeapStyle::Static { bound }
+        } else {
+            self.match_identifier("vmctx", "expected dynamic heap bound: vmctx")?;
+            HeapStyle::Dynamic { bound: self.optional_offset32()? }
+        };
+
+        // heap-decl ::= ... heap-bound * "," "guard" Imm64(bytes) "," "index_type" type
+        self.match_token(Token::Comma, "expected ',' after heap bound")?;
+        self.match_identifier("guard", "expected 'guard'")?;
+        let guard_size = self.match_imm64("expected guard size in bytes")?;
+        self.match_token(Token::Comma, "expected ',' after guard size")?;
+        self.match_identifier("index_type", "expected 'index_type'")?;
+        let index_type = self.match_type("expected heap index type")?;
+        if index_type != I32 && index_type != I64 {
+            return err!(self.loc, "heap index type must be i32 or i64");
+        }
+
+        let data = HeapData {
+            base,
+            guard_size,
+            style,
+            index_type,
+        };
+        Ok((number, data))
+    }
+
+    // Parse a constant decl.
+    //
+    // constant-decl ::= * Constant(c) "=" "constant" "." type constant-value
+    // constant-value ::= Imm64 | Ieee32 | Ieee64
+    //
+    // The format of the value depends on the type of the constant.
+    fn parse_constant_decl(&mut self) -> Result<(u32, ConstantData)> {
+        let number = self.match_constant("expected constant number: const«n»")?;
+        self.match_token(Token::Equal, "expected '=' in constant decl")?;
+        self.match_identifier("constant", "expected 'constant'")?;
+        self.match_token(Token::Dot, "expected '.' after 'constant'")?;
+        let ty = self.match_type("expected constant type")?;
+
+        // constant-decl ::= Constant(c) "=" "constant" "." type * constant-value
+        let data = match ty {
+            F32 => ConstantData::f32(self.match_ieee32("expected f32 constant value")?),
+            F64 => ConstantData::f64(self.match_ieee64("expected f64 constant value")?),
+            _ if ty.is_int() && ty.is_scalar() && ty.bits() <= 64 => {
+                ConstantData::int(ty, self.match_imm64("expected integer constant value")?)
+            }
+            _ => return err!(self.loc, "unsupported constant type {}", ty),
+        };
+        Ok((number, data))
+    }
+
     // Parse a function body, add contents to `ctx`.
     //
     // function-body ::= * { extended-basic-block }
@@ -1043,8 +1343,10 @@ impl<'a> Parser<'a> {
                   Some(Token::Value(_)) => true,
                   Some(Token::Identifier(_)) => true,
                   Some(Token::LBracket) => true,
+                  Some(Token::SourceLoc(_)) => true,
                   _ => false,
               } {
+            let srcloc = self.optional_srcloc()?;
             let (encoding, result_locations) = self.parse_instruction_encoding(ctx)?;
 
             // We need to parse instruction results here because they are shared
@@ -1060,10 +1362,12 @@ impl<'a> Parser<'a> {
                 }
                 Some(Token::Equal) => {
                     self.consume();
-                    self.parse_instruction(results, encoding, result_locations, ctx, ebb)?;
+                    self.parse_instruction(results, srcloc, encoding, result_locations, ctx, ebb)?;
                 }
                 _ if results.len() != 0 => return err!(self.loc, "expected -> or ="),
-                _ => self.parse_instruction(results, encoding, result_locations, ctx, ebb)?,
+                _ => {
+                    self.parse_instruction(results, srcloc, encoding, result_locations, ctx, ebb)?
+                }
             }
         }
 
@@ -1142,6 +1446,23 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Parse an optional source location prefix on an instruction.
+    //
+    // srcloc ::= * "@" HexSequence
+    fn optional_srcloc(&mut self) -> Result<SourceLoc> {
+        if let Some(Token::SourceLoc(text)) = self.token() {
+            match u32::from_str_radix(text, 16) {
+                Ok(num) => {
+                    self.consume();
+                    Ok(SourceLoc::new(num))
+                }
+                Err(_) => err!(self.loc, "invalid source location: @{}", text),
+            }
+        } else {
+            Ok(Default::default())
+        }
+    }
+
     fn parse_instruction_encoding(&mut self,
                                   ctx: &Context)
                                   -> Result<(Option<Encoding>, Option<Vec<ValueLoc>>)> {
@@ -1226,6 +1547,7 @@ impl<'a> Parser<'a> {
     //
     fn parse_instruction(&mut self,
                          results: Vec<Value>,
+                         srcloc: SourceLoc,
                          encoding: Option<Encoding>,
                          result_locations: Option<Vec<ValueLoc>>,
                          ctx: &mut Context,
@@ -1274,6 +1596,10 @@ impl<'a> Parser<'a> {
             *ctx.function.encodings.ensure(inst) = encoding;
         }
 
+        if !srcloc.is_default() {
+            *ctx.function.srclocs.ensure(inst) = srcloc;
+        }
+
         if results.len() != num_results {
             return err!(self.loc,
                         "instruction produces {} result values, {} given",
@@ -1563,6 +1889,43 @@ impl<'a> Parser<'a> {
                 let lane = self.match_uimm8("expected lane number")?;
                 InstructionData::ExtractLane { opcode, lane, arg }
             }
+            InstructionFormat::Trap => {
+                let code = self.match_enum("expected trap code")?;
+                InstructionData::Trap { opcode, code }
+            }
+            InstructionFormat::CondTrap => {
+                let arg = self.match_value("expected SSA value operand")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let code = self.match_enum("expected trap code")?;
+                InstructionData::CondTrap { opcode, arg, code }
+            }
+            InstructionFormat::BinaryTrap => {
+                let lhs = self.match_value("expected SSA value first operand")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let rhs = self.match_value("expected SSA value second operand")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let code = self.match_enum("expected trap code")?;
+                InstructionData::BinaryTrap {
+                    opcode,
+                    args: [lhs, rhs],
+                    code,
+                }
+            }
+            InstructionFormat::AtomicRmw => {
+                let op = self.match_enum("expected atomic rmw operation")?;
+                let addr = self.match_value("expected SSA value address")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let arg = self.match_value("expected SSA value operand")?;
+                InstructionData::AtomicRmw {
+                    opcode,
+                    op,
+                    args: [addr, arg],
+                }
+            }
+            InstructionFormat::Fence => {
+                let ordering = self.match_enum("expected memory ordering")?;
+                InstructionData::Fence { opcode, ordering }
+            }
             InstructionFormat::IntCompare => {
                 let cond = self.match_enum("expected intcc condition code")?;
                 let lhs = self.match_value("expected SSA value first operand")?;
@@ -1629,6 +1992,10 @@ impl<'a> Parser<'a> {
                 let table = self.match_jt().and_then(|num| ctx.get_jt(num, &self.loc))?;
                 InstructionData::BranchTable { opcode, arg, table }
             }
+            InstructionFormat::BranchTableBase => {
+                let table = self.match_jt().and_then(|num| ctx.get_jt(num, &self.loc))?;
+                InstructionData::BranchTableBase { opcode, table }
+            }
             InstructionFormat::StackLoad => {
                 let ss = self.match_ss("expected stack slot number: ss«n»")
                     .and_then(|num| ctx.get_ss(num, &self.loc))?;
@@ -1652,24 +2019,31 @@ impl<'a> Parser<'a> {
                     offset,
                 }
             }
-            InstructionFormat::HeapLoad => {
-                let addr = self.match_value("expected SSA value address")?;
-                let offset = self.optional_uoffset32()?;
-                InstructionData::HeapLoad {
+            InstructionFormat::UnaryGlobalVar => {
+                let gv = self.match_gv("expected global variable")
+                    .and_then(|num| ctx.get_gv(num, &self.loc))?;
+                InstructionData::UnaryGlobalVar {
                     opcode,
-                    arg: addr,
-                    offset,
+                    global_var: gv,
                 }
             }
-            InstructionFormat::HeapStore => {
-                let arg = self.match_value("expected SSA value operand")?;
+            InstructionFormat::UnaryConst => {
+                let constant = self.match_constant("expected constant: const«n»")
+                    .and_then(|num| ctx.get_constant(num, &self.loc))?;
+                InstructionData::UnaryConst { opcode, constant }
+            }
+            InstructionFormat::HeapAddr => {
+                let heap = self.match_heap("expected heap identifier")
+                    .and_then(|h| ctx.get_heap(h, &self.loc))?;
                 self.match_token(Token::Comma, "expected ',' between operands")?;
-                let addr = self.match_value("expected SSA value address")?;
-                let offset = self.optional_uoffset32()?;
-                InstructionData::HeapStore {
+                let arg = self.match_value("expected SSA value heap offset")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let imm = self.match_uimm32("expected u32 size immediate")?;
+                InstructionData::HeapAddr {
                     opcode,
-                    args: [arg, addr],
-                    offset,
+                    heap,
+                    arg,
+                    imm,
                 }
             }
             InstructionFormat::Load => {
@@ -1717,7 +2091,7 @@ impl<'a> Parser<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cretonne::ir::{ArgumentExtension, ArgumentPurpose};
+    use cretonne::ir::{ArgumentExtension, ArgumentPurpose, CallConv};
     use cretonne::ir::types;
     use cretonne::ir::entities::AnyEntity;
     use testfile::{Details, Comment};
@@ -1772,6 +2146,22 @@ mod tests {
         assert_eq!(sig2.to_string(),
                    "(i8 uext, f32, f64, i32 sret) -> i32 sext, f64");
 
+        let sig3 = Parser::new("(i32, f64) system_v")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig3.call_conv, CallConv::SystemV);
+        assert_eq!(sig3.to_string(), "(i32, f64) system_v");
+        let sig4 = Parser::new("() -> i32 sext windows_fastcall")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig4.call_conv, CallConv::WindowsFastcall);
+        assert_eq!(sig4.return_types[0].extension, ArgumentExtension::Sext);
+        assert_eq!(Parser::new("(i8) stdcall")
+                       .parse_signature(None)
+                       .unwrap_err()
+                       .to_string(),
+                   "1: unknown calling convention: stdcall");
+
         // `void` is not recognized as a type by the lexer. It should not appear in files.
         assert_eq!(Parser::new("() -> void")
                        .parse_signature(None)
@@ -1819,6 +2209,91 @@ mod tests {
                    "3: duplicate stack slot: ss1");
     }
 
+    #[test]
+    fn global_var_decl() {
+        let (func, _) = Parser::new("function %foo() {
+                                       gv3 = vmctx+16
+                                       gv1 = deref(gv3)-8
+                                       gv2 = globalsym %sym
+                                     }")
+                .parse_function(None)
+                .unwrap();
+        let mut iter = func.global_vars.keys();
+        let gv0 = iter.next().unwrap();
+        assert_eq!(func.global_vars[gv0].to_string(), "vmctx+16");
+        let gv1 = iter.next().unwrap();
+        assert_eq!(func.global_vars[gv1].to_string(), "deref(gv0)-8");
+        let gv2 = iter.next().unwrap();
+        assert_eq!(func.global_vars[gv2].to_string(), "globalsym %sym");
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(Parser::new("function %bar() {
+                                    gv0 = deref(gv1)
+                                    gv1 = vmctx
+                                }")
+                           .parse_function(None)
+                           .unwrap_err()
+                           .to_string(),
+                   "2: undefined global variable gv1");
+    }
+
+    #[test]
+    fn heap_decl() {
+        let (func, _) = Parser::new("function %foo() {
+                                       heap1 = static vmctx, bound 0x1000, guard 0, index_type i32
+                                       heap0 = dynamic vmctx+16, bound vmctx+24, guard 0, index_type i64
+                                     }")
+                .parse_function(None)
+                .unwrap();
+        let mut iter = func.heaps.keys();
+        let heap0 = iter.next().unwrap();
+        assert_eq!(func.heaps[heap0].to_string(),
+                   "static vmctx, bound 4096, guard 0, index_type i32");
+        let heap1 = iter.next().unwrap();
+        assert_eq!(func.heaps[heap1].to_string(),
+                   "dynamic vmctx+16, bound vmctx+24, guard 0, index_type i64");
+        assert_eq!(iter.next(), None);
+
+        assert_eq!(Parser::new("function %bar() {
+                                    heap0 = static vmctx, bound 4096, guard 0, index_type f32
+                                }")
+                           .parse_function(None)
+                           .unwrap_err()
+                           .to_string(),
+                   "2: heap index type must be i32 or i64");
+    }
+
+    #[test]
+    fn constant_decl() {
+        let (func, _) = Parser::new("function %foo() {
+                                       const3 = constant.f64 0x1.0p0
+                                       const1 = constant.i64 0x1234_5678_9abc
+                                     ebb0:
+                                       v0 = const_addr.i32 const1
+                                     }")
+                .parse_function(None)
+                .unwrap();
+        let mut iter = func.constants.keys();
+        let const0 = iter.next().unwrap();
+        assert_eq!(func.constants[const0].to_string(),
+                   "constant.f64 0x1.0000000000000p0");
+        let const1 = iter.next().unwrap();
+        assert_eq!(func.constants[const1].to_string(),
+                   "constant.i64 0x1234_5678_9abc");
+        assert_eq!(iter.next(), None);
+
+        let inst = func.layout.ebb_insts(func.layout.entry_block().unwrap()).next().unwrap();
+        assert_eq!(func.dfg.display_inst(inst).to_string(), "v0 = const_addr.i32 const1");
+
+        assert_eq!(Parser::new("function %bar() {
+                                    const0 = constant.b1 1
+                                }")
+                           .parse_function(None)
+                           .unwrap_err()
+                           .to_string(),
+                   "2: unsupported constant type b1");
+    }
+
     #[test]
     fn ebb_header() {
         let (func, _) = Parser::new("function %ebbs() {
@@ -1840,6 +2315,32 @@ mod tests {
         assert_eq!(func.dfg.value_type(ebb4_args[0]), types::I32);
     }
 
+    #[test]
+    fn source_locations() {
+        let (func, _) = Parser::new("function %srclocs(i32) {
+                                     ebb0(v0: i32):
+                                       @0042 v1 = iadd v0, v0
+                                       v2 = iadd v1, v1
+                                       @00c7 return
+                                     }")
+                .parse_function(None)
+                .unwrap();
+
+        let ebb0 = func.layout.entry_block().unwrap();
+        let locs: Vec<_> = func.layout
+            .ebb_insts(ebb0)
+            .map(|inst| func.srclocs.get_or_default(inst).to_string())
+            .collect();
+        assert_eq!(locs, ["@0042", "@-", "@00c7"]);
+
+        assert!(Parser::new("function %bad() {
+                             ebb0:
+                               @xyz return
+                             }")
+                        .parse_function(None)
+                        .is_err());
+    }
+
     #[test]
     fn comments() {
         let (func, Details { comments, .. }) = Parser::new("; before
@@ -1849,7 +2350,7 @@ mod tests {
                             jt10 = jump_table ebb0
                             ; Jumptable
                          ebb0: ; Basic block
-                         trap ; Instruction
+                         trap user42 ; Instruction
                          } ; Trailing.
                          ; More trailing.")
                 .parse_function(None)
@@ -1931,7 +2432,7 @@ mod tests {
         // Valid characters in the name.
         let func = Parser::new("function #1234567890AbCdEf() {
                                            ebb0:
-                                             trap
+                                             trap user0
                                            }")
                 .parse_function(None)
                 .unwrap()
@@ -1941,25 +2442,82 @@ mod tests {
         // Invalid characters in the name.
         let mut parser = Parser::new("function #12ww() {
                                            ebb0:
-                                             trap
+                                             trap user0
                                            }");
         assert!(parser.parse_function(None).is_err());
 
         // The length of binary function name should be multiple of two.
         let mut parser = Parser::new("function #1() {
                                            ebb0:
-                                             trap
+                                             trap user0
                                            }");
         assert!(parser.parse_function(None).is_err());
 
         // Empty binary function name should be valid.
         let func = Parser::new("function #() {
                                            ebb0:
-                                             trap
+                                             trap user0
                                            }")
                 .parse_function(None)
                 .unwrap()
                 .0;
         assert_eq!(func.name.to_string(), "%");
     }
+
+    #[test]
+    fn data_objects() {
+        let test = parse_test("isa riscv
+                               data %bytes = [#0102, 255, 0x10], align 4, writable
+                               function %foo() {
+                               ebb0:
+                                 trap user0
+                               }
+                               data %table = [%foo, #00, %bytes]")
+                .unwrap();
+        assert_eq!(test.functions.len(), 1);
+        assert_eq!(test.data.len(), 2);
+
+        let bytes = &test.data[0];
+        assert_eq!(bytes.contents, [1, 2, 255, 16]);
+        assert_eq!((bytes.align, bytes.writable), (4, true));
+        assert_eq!(bytes.to_string(),
+                   "data %bytes = [#0102ff10], align 4, writable");
+
+        let table = &test.data[1];
+        assert_eq!(table.contents.len(), 9);
+        assert_eq!(table.pointers.len(), 2);
+        assert_eq!((table.pointers[1].offset, table.pointers[1].size), (5, 4));
+        assert_eq!((table.align, table.writable), (1, false));
+        assert_eq!(table.to_string(), "data %table = [%foo, #00, %bytes]");
+
+        // Pointers need to know the ISA.
+        let err = parse_test("data %p = [%foo]").err().unwrap();
+        assert_eq!(err.to_string(),
+                   "1: pointers in data require a unique ISA to be specified");
+
+        let err = parse_test("data %p = [256]").err().unwrap();
+        assert_eq!(err.to_string(), "1: data byte out of range: 256");
+        let err = parse_test("data %p = [#012]").err().unwrap();
+        assert_eq!(err.to_string(),
+                   "1: expected data bytes to have length multiple of two");
+        let err = parse_test("data %p = [#01, #x]").err().unwrap();
+        assert_eq!(err.to_string(), "1: expected ']' after data contents");
+        let err = parse_test("data %p = [], align 3").err().unwrap();
+        assert_eq!(err.to_string(), "1: data alignment must be a power of two");
+        let err = parse_test("data %p = [], align 0").err().unwrap();
+        assert_eq!(err.to_string(), "1: data alignment must be a power of two");
+        let err = parse_test("data %p = [], align").err().unwrap();
+        assert_eq!(err.to_string(), "1: expected data alignment");
+        let err = parse_test("data %p = [], aligned 4").err().unwrap();
+        assert_eq!(err.to_string(), "1: expected 'align', 'readonly', or 'writable'");
+
+        // The printed data objects can be parsed again.
+        let text = format!("isa riscv\n{}\n{}\nfunction %foo() {{\nebb0:\n trap user0\n}}",
+                           bytes,
+                           table);
+        let test = parse_test(&text).unwrap();
+        assert_eq!(test.data[0].to_string(), bytes.to_string());
+        assert_eq!(test.data[1].contents, table.contents);
+        assert_eq!(test.data[1].to_string(), table.to_string());
+    }
 }
@@ -8,7 +8,7 @@
 //! clients.
 
 use std::collections::HashMap;
-use cretonne::ir::{StackSlot, JumpTable, Ebb, Value, SigRef, FuncRef};
+use cretonne::ir::{StackSlot, GlobalVar, JumpTable, Ebb, Value, SigRef, FuncRef, Heap, Constant};
 use cretonne::ir::entities::AnyEntity;
 use error::{Result, Location};
 use lexer::split_entity_name;
@@ -19,9 +19,12 @@ pub struct SourceMap {
     values: HashMap<Value, Value>, // vNN
     ebbs: HashMap<Ebb, Ebb>, // ebbNN
     stack_slots: HashMap<u32, StackSlot>, // ssNN
+    global_vars: HashMap<u32, GlobalVar>, // gvNN
     signatures: HashMap<u32, SigRef>, // sigNN
     functions: HashMap<u32, FuncRef>, // fnNN
     jump_tables: HashMap<u32, JumpTable>, // jtNN
+    heaps: HashMap<u32, Heap>, // heapNN
+    constants: HashMap<u32, Constant>, // constNN
 
     // Store locations for entities, including instructions.
     locations: HashMap<AnyEntity, Location>,
@@ -44,6 +47,11 @@ impl SourceMap {
         self.stack_slots.get(&src_num).cloned()
     }
 
+    /// Look up a global variable entity by its source number.
+    pub fn get_gv(&self, src_num: u32) -> Option<GlobalVar> {
+        self.global_vars.get(&src_num).cloned()
+    }
+
     /// Look up a signature entity by its source number.
     pub fn get_sig(&self, src_num: u32) -> Option<SigRef> {
         self.signatures.get(&src_num).cloned()
@@ -59,6 +67,16 @@ impl SourceMap {
         self.jump_tables.get(&src_num).cloned()
     }
 
+    /// Look up a heap entity by its source number.
+    pub fn get_heap(&self, src_num: u32) -> Option<Heap> {
+        self.heaps.get(&src_num).cloned()
+    }
+
+    /// Look up a constant entity by its source number.
+    pub fn get_constant(&self, src_num: u32) -> Option<Constant> {
+        self.constants.get(&src_num).cloned()
+    }
+
     /// Look up an entity by source name.
     /// Returns the entity reference corresponding to `name`, if it exists.
     pub fn lookup_str(&self, name: &str) -> Option<AnyEntity> {
@@ -74,9 +92,14 @@ impl SourceMap {
                                                      .map(AnyEntity::Ebb)
                                              }
                                              "ss" => self.get_ss(num).map(AnyEntity::StackSlot),
+                                             "gv" => self.get_gv(num).map(AnyEntity::GlobalVar),
                                              "sig" => self.get_sig(num).map(AnyEntity::SigRef),
                                              "fn" => self.get_fn(num).map(AnyEntity::FuncRef),
                                              "jt" => self.get_jt(num).map(AnyEntity::JumpTable),
+                                             "heap" => self.get_heap(num).map(AnyEntity::Heap),
+                                             "const" => {
+                                                 self.get_constant(num).map(AnyEntity::Constant)
+                                             }
                                              _ => None,
                                          })
     }
@@ -137,9 +160,12 @@ pub trait MutableSourceMap {
     fn def_value(&mut self, src: Value, entity: Value, loc: &Location) -> Result<()>;
     fn def_ebb(&mut self, src: Ebb, entity: Ebb, loc: &Location) -> Result<()>;
     fn def_ss(&mut self, src_num: u32, entity: StackSlot, loc: &Location) -> Result<()>;
+    fn def_gv(&mut self, src_num: u32, entity: GlobalVar, loc: &Location) -> Result<()>;
     fn def_sig(&mut self, src_num: u32, entity: SigRef, loc: &Location) -> Result<()>;
     fn def_fn(&mut self, src_num: u32, entity: FuncRef, loc: &Location) -> Result<()>;
     fn def_jt(&mut self, src_num: u32, entity: JumpTable, loc: &Location) -> Result<()>;
+    fn def_heap(&mut self, src_num: u32, entity: Heap, loc: &Location) -> Result<()>;
+    fn def_constant(&mut self, src_num: u32, entity: Constant, loc: &Location) -> Result<()>;
 
     /// Define an entity without an associated source number. This can be used for instructions
     /// whose numbers never appear in source, or implicitly defined signatures.
@@ -152,9 +178,12 @@ impl MutableSourceMap for SourceMap {
             values: HashMap::new(),
             ebbs: HashMap::new(),
             stack_slots: HashMap::new(),
+            global_vars: HashMap::new(),
             signatures: HashMap::new(),
             functions: HashMap::new(),
             jump_tables: HashMap::new(),
+            heaps: HashMap::new(),
+            constants: HashMap::new(),
             locations: HashMap::new(),
         }
     }
@@ -183,6 +212,14 @@ impl MutableSourceMap for SourceMap {
         }
     }
 
+    fn def_gv(&mut self, src_num: u32, entity: GlobalVar, loc: &Location) -> Result<()> {
+        if self.global_vars.insert(src_num, entity).is_some() {
+            err!(loc, "duplicate global variable: gv{}", src_num)
+        } else {
+            self.def_entity(entity.into(), loc)
+        }
+    }
+
     fn def_sig(&mut self, src_num: u32, entity: SigRef, loc: &Location) -> Result<()> {
         if self.signatures.insert(src_num, entity).is_some() {
             err!(loc, "duplicate signature: sig{}", src_num)
@@ -207,6 +244,22 @@ impl MutableSourceMap for SourceMap {
         }
     }
 
+    fn def_heap(&mut self, src_num: u32, entity: Heap, loc: &Location) -> Result<()> {
+        if self.heaps.insert(src_num, entity).is_some() {
+            err!(loc, "duplicate heap: heap{}", src_num)
+        } else {
+            self.def_entity(entity.into(), loc)
+        }
+    }
+
+    fn def_constant(&mut self, src_num: u32, entity: Constant, loc: &Location) -> Result<()> {
+        if self.constants.insert(src_num, entity).is_some() {
+            err!(loc, "duplicate constant: const{}", src_num)
+        } else {
+            self.def_entity(entity.into(), loc)
+        }
+    }
+
     fn def_entity(&mut self, entity: AnyEntity, loc: &Location) -> Result<()> {
         if self.locations.insert(entity, loc.clone()).is_some() {
             err!(loc, "duplicate entity: {}", entity)
@@ -225,6 +278,8 @@ mod tests {
         let tf = parse_test("function %detail() {
                                ss10 = stack_slot 13
                                jt10 = jump_table ebb0
+                               gv4 = vmctx+8
+                               heap3 = static vmctx, bound 0x1000, guard 0, index_type i32
                              ebb0(v4: i32, v7: i32):
                                v10 = iadd v4, v7
                              }")
@@ -235,6 +290,8 @@ mod tests {
         assert_eq!(map.lookup_str("ss1"), None);
         assert_eq!(map.lookup_str("ss10").unwrap().to_string(), "ss0");
         assert_eq!(map.lookup_str("jt10").unwrap().to_string(), "jt0");
+        assert_eq!(map.lookup_str("gv4").unwrap().to_string(), "gv0");
+        assert_eq!(map.lookup_str("heap3").unwrap().to_string(), "heap0");
         assert_eq!(map.lookup_str("ebb0").unwrap().to_string(), "ebb0");
         assert_eq!(map.lookup_str("v4").unwrap().to_string(), "v0");
         assert_eq!(map.lookup_str("v7").unwrap().to_string(), "v1");
@@ -6,6 +6,7 @@
 
 use cretonne::ir::Function;
 use cretonne::ir::entities::AnyEntity;
+use cretonne::module::DataObject;
 use testcommand::TestCommand;
 use isaspec::IsaSpec;
 use sourcemap::SourceMap;
@@ -25,6 +26,8 @@ pub struct TestFile<'a> {
     pub preamble_comments: Vec<Comment<'a>>,
     /// Parsed functions and additional details about each function.
     pub functions: Vec<(Function, Details<'a>)>,
+    /// Data objects declared between the functions.
+    pub data: Vec<DataObject>,
 }
 
 /// Additional details about a function parsed from a text string.
@@ -14,17 +14,18 @@ endif
 syn spell notoplevel
 
 syn keyword ctonHeader test isa set
-syn keyword ctonDecl function stack_slot jump_table
+syn keyword ctonDecl function stack_slot jump_table static dynamic
 syn keyword ctonFilecheck check sameln nextln unordered not regex contained
 
 syn match ctonType  /\<[bif]\d\+\(x\d\+\)\?\>/
-syn match ctonEntity /\<\(v\|ss\|jt\|fn\|sig\)\d\+\>/
+syn match ctonEntity /\<\(v\|ss\|gv\|jt\|fn\|sig\|heap\)\d\+\>/
 syn match ctonLabel /\<ebb\d+\>/
 syn match ctonName /%\w\+\>/
 
 syn match ctonNumber /-\?\<\d\+\>/
 syn match ctonNumber /-\?\<0x\x\+\(\.\x*\)\(p[+-]\?\d\+\)\?\>/
 syn match ctonHexSeq /#\x\+\>/
+syn match ctonSourceLoc /@\x\+\>/
 
 syn region ctonCommentLine start=";" end="$" contains=ctonFilecheck
 
@@ -36,6 +37,7 @@ hi def link ctonLabel         Label
 hi def link ctonName          String
 hi def link ctonNumber        Number
 hi def link ctonHexSeq        Number
+hi def link ctonSourceLoc     SpecialComment
 hi def link ctonCommentLine   Comment
 hi def link ctonFilecheck     SpecialComment
 
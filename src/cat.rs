@@ -1,11 +1,13 @@
 //! The `cat` sub-command.
 //!
 //! Read a sequence of Cretonne IL files and print them again to stdout. This has the effect of
-//! normalizing formatting and removing comments.
+//! normalizing formatting and removing comments. The data objects in a file are printed before its
+//! functions.
 
 use std::borrow::Cow;
+use std::fmt::Write;
 use cretonne::ir::Function;
-use cton_reader::{parse_functions, TestCommand};
+use cton_reader::{parse_test, TestCommand};
 use CommandResult;
 use utils::read_to_string;
 use filetest::subtest::{self, SubTest, Context, Result as STResult};
@@ -23,11 +25,14 @@ pub fn run(files: Vec<String>) -> CommandResult {
 fn cat_one(filename: String) -> CommandResult {
     let buffer = read_to_string(&filename)
         .map_err(|e| format!("{}: {}", filename, e))?;
-    let items = parse_functions(&buffer)
+    let test = parse_test(&buffer)
         .map_err(|e| format!("{}: {}", filename, e))?;
 
-    for (idx, func) in items.into_iter().enumerate() {
-        if idx != 0 {
+    for data in &test.data {
+        println!("{}", data);
+    }
+    for (idx, (func, _)) in test.functions.into_iter().enumerate() {
+        if idx != 0 || !test.data.is_empty() {
             println!("");
         }
         print!("{}", func);
@@ -39,7 +44,7 @@ fn cat_one(filename: String) -> CommandResult {
 /// Object implementing the `test cat` sub-test.
 ///
 /// This command is used for testing the parser and function printer. It simply parses a function
-/// and prints it out again.
+/// and prints it out again, preceded by the data objects declared in the test file.
 ///
 /// The result is verified by filecheck.
 struct TestCat;
@@ -63,6 +68,11 @@ impl SubTest for TestCat {
     }
 
     fn run(&self, func: Cow<Function>, context: &Context) -> STResult<()> {
-        subtest::run_filecheck(&func.display(context.isa).to_string(), context)
+        let mut text = String::new();
+        for data in context.data {
+            writeln!(&mut text, "{}", data).map_err(|e| e.to_string())?;
+        }
+        write!(&mut text, "{}", func.display(context.isa)).map_err(|e| e.to_string())?;
+        subtest::run_filecheck(&text, context)
     }
 }
@@ -0,0 +1,47 @@
+//! The `compile` sub-command.
+//!
+//! Read a Cretonne IL file, compile all the functions for the ISA given in the file, and write
+//! them to a relocatable object file along with the data objects declared in the file.
+//!
+//! The functions are collected in a module which checks that all the calls between them agree
+//! with the callee signatures.
+
+use cretonne::Module;
+use cton_obj::ObjectBuilder;
+use cton_reader::parse_test;
+use std::fs::File;
+use std::io::Write;
+use CommandResult;
+use utils::read_to_string;
+
+pub fn run(filename: &str, output: &str) -> CommandResult {
+    let buffer = read_to_string(filename)
+        .map_err(|e| format!("{}: {}", filename, e))?;
+    let test = parse_test(&buffer)
+        .map_err(|e| format!("{}: {}", filename, e))?;
+    let isa = test.isa_spec
+        .unique_isa()
+        .ok_or_else(|| format!("{}: compiling requires a single `isa` command", filename))?;
+
+    let mut module = Module::new();
+    for (func, _) in test.functions {
+        module
+            .define_function(func)
+            .map_err(|e| format!("{}: {}", filename, e))?;
+    }
+    for data in test.data {
+        module
+            .define_data(data)
+            .map_err(|e| format!("{}: {}", filename, e))?;
+    }
+
+    let mut obj = ObjectBuilder::new(isa)
+        .map_err(|e| format!("{}: {}", filename, e))?;
+    module
+        .compile(isa, &mut obj)
+        .map_err(|e| format!("{}: {}", filename, e))?;
+
+    File::create(output)
+        .and_then(|mut file| file.write_all(&obj.finish()))
+        .map_err(|e| format!("{}: {}", output, e))
+}
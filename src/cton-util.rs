@@ -1,6 +1,8 @@
 #[macro_use(dbg)]
 extern crate cretonne;
 extern crate cton_reader;
+extern crate cton_obj;
+extern crate cton_interpreter;
 extern crate docopt;
 extern crate rustc_serialize;
 extern crate filecheck;
@@ -15,6 +17,9 @@ mod utils;
 mod filetest;
 mod cat;
 mod print_cfg;
+mod compile;
+mod disasm;
+mod stats;
 mod rsfilecheck;
 
 const USAGE: &str = "
@@ -25,10 +30,16 @@ Usage:
     cton-util cat <file>...
     cton-util filecheck [-v] <file>
     cton-util print-cfg <file>...
+    cton-util compile -o <output> <file>
+    cton-util disasm <file>...
+    cton-util stats [--csv] <file>...
     cton-util --help | --version
 
 Options:
     -v, --verbose  be more verbose
+    -o, --output <output>
+                   write an object file to <output>
+    --csv          write statistics as CSV instead of JSON
     -h, --help     print this help message
     --version      print the Cretonne version
 
@@ -40,8 +51,13 @@ struct Args {
     cmd_cat: bool,
     cmd_filecheck: bool,
     cmd_print_cfg: bool,
+    cmd_compile: bool,
+    cmd_disasm: bool,
+    cmd_stats: bool,
     arg_file: Vec<String>,
+    flag_output: String,
     flag_verbose: bool,
+    flag_csv: bool,
 }
 
 /// A command either succeeds or fails with an error message.
@@ -67,6 +83,12 @@ fn cton_util() -> CommandResult {
         rsfilecheck::run(args.arg_file, args.flag_verbose)
     } else if args.cmd_print_cfg {
         print_cfg::run(args.arg_file)
+    } else if args.cmd_compile {
+        compile::run(&args.arg_file[0], &args.flag_output)
+    } else if args.cmd_disasm {
+        disasm::run(args.arg_file)
+    } else if args.cmd_stats {
+        stats::run(args.arg_file, args.flag_csv)
     } else {
         // Debugging / shouldn't happen with proper command line handling above.
         Err(format!("Unhandled args: {:?}", args))
@@ -0,0 +1,84 @@
+//! The `disasm` sub-command.
+//!
+//! Read a series of Cretonne IL files, compile the functions for the ISA given in each file, and
+//! print a disassembly of the generated machine code.
+//!
+//! Relocations are not applied, so calls and symbol addresses show up with zero displacements.
+
+use cretonne::Context;
+use cretonne::binemit::{self, CodeOffset, NullTrapSink, Reloc, RelocSink};
+use cretonne::ir::{Constant, FuncRef, GlobalVar, JumpTable};
+use cton_reader::parse_test;
+use CommandResult;
+use utils::read_to_string;
+
+pub fn run(files: Vec<String>) -> CommandResult {
+    for (i, f) in files.into_iter().enumerate() {
+        if i != 0 {
+            println!("");
+        }
+        disasm_file(&f)?
+    }
+    Ok(())
+}
+
+/// Relocation sink that ignores all relocations.
+struct NullRelocSink {}
+
+impl RelocSink for NullRelocSink {
+    fn reloc_ebb(&mut self, _: CodeOffset, _: Reloc, _: CodeOffset) {}
+    fn reloc_func(&mut self, _: CodeOffset, _: Reloc, _: FuncRef) {}
+    fn reloc_globalsym(&mut self, _: CodeOffset, _: Reloc, _: GlobalVar) {}
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, _: JumpTable) {}
+    fn reloc_constant(&mut self, _: CodeOffset, _: Reloc, _: Constant) {}
+}
+
+fn disasm_file(filename: &str) -> CommandResult {
+    let buffer = read_to_string(filename)
+        .map_err(|e| format!("{}: {}", filename, e))?;
+    let test = parse_test(&buffer)
+        .map_err(|e| format!("{}: {}", filename, e))?;
+    let isa = test.isa_spec
+        .unique_isa()
+        .ok_or_else(|| format!("{}: disassembling requires a single `isa` command", filename))?;
+    let dis = isa.disassembler()
+        .ok_or_else(|| format!("{}: no disassembler for {}", filename, isa.name()))?;
+
+    for (i, (func, _)) in test.functions.into_iter().enumerate() {
+        if i != 0 {
+            println!("");
+        }
+        let mut context = Context::new();
+        context.func = func;
+        let size = context
+            .compile(isa)
+            .map_err(|e| format!("{}: {}: {}", filename, context.func.name, e))?;
+        let mut code = vec![0; size as usize];
+        context.emit_to_memory(&mut code, &mut NullRelocSink {}, &mut NullTrapSink {}, isa);
+
+        // Jump tables and constants follow the instructions.
+        let func = &context.func;
+        let end = func.jump_tables
+            .keys()
+            .map(|jt| func.jt_offsets[jt])
+            .chain(func.constants.keys().map(|c| func.const_offsets[c]))
+            .min()
+            .unwrap_or(size);
+        println!("function {}:", func.name);
+        print!("{}", binemit::disassemble(&*dis, &code[0..end as usize], 0));
+        if end < size {
+            println!("; data");
+            print!("{}", binemit::disassemble(&NoInsts, &code[end as usize..], end));
+        }
+    }
+    Ok(())
+}
+
+/// Disassembler that doesn't decode anything, for printing data as bytes.
+struct NoInsts;
+
+impl binemit::Disassembler for NoInsts {
+    fn decode(&self, _: &[u8], _: CodeOffset) -> Option<binemit::DecodedInst> {
+        None
+    }
+}
@@ -6,7 +6,7 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::Write;
-use cretonne::binemit;
+use cretonne::binemit::{self, CodeSink};
 use cretonne::ir;
 use cretonne::ir::entities::AnyEntity;
 use cretonne::isa::TargetIsa;
@@ -30,6 +30,8 @@ struct TextSink {
     rnames: &'static [&'static str],
     offset: binemit::CodeOffset,
     text: String,
+    // The raw bytes emitted, for the disassembler.
+    bytes: Vec<u8>,
 }
 
 impl TextSink {
@@ -39,11 +41,43 @@ impl TextSink {
             rnames: isa.reloc_names(),
             offset: 0,
             text: String::new(),
+            bytes: Vec::new(),
         }
     }
-}
 
+    /// Clear the text and bytes for the next instruction.
+    fn clear(&mut self) {
+        self.text.clear();
+        self.bytes.clear();
+    }
+
+    /// Record `size` little-endian bytes of `x`.
+    fn put_bytes(&mut self, x: u64, size: usize) {
+        for n in 0..size {
+            self.bytes.push((x >> (8 * n)) as u8);
+        }
+    }
+}
 
+/// Parse the bytes in the hexadecimal tokens of a `bin:` directive.
+///
+/// Each token is a little-endian number of 1, 2, 4, or 8 bytes, like the `TextSink` output.
+/// Relocations and trap codes are skipped.
+fn parse_bin(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for tok in text.split_whitespace() {
+        let size = tok.len() / 2;
+        if tok.len() % 2 != 0 || !(size as u32).is_power_of_two() || size > 8 {
+            continue;
+        }
+        if let Ok(x) = u64::from_str_radix(tok, 16) {
+            for n in 0..size {
+                bytes.push((x >> (8 * n)) as u8);
+            }
+        }
+    }
+    bytes
+}
 
 impl binemit::CodeSink for TextSink {
     fn offset(&self) -> binemit::CodeOffset {
@@ -52,21 +86,25 @@ impl binemit::CodeSink for TextSink {
 
     fn put1(&mut self, x: u8) {
         write!(self.text, "{:02x} ", x).unwrap();
+        self.put_bytes(x as u64, 1);
         self.offset += 1;
     }
 
     fn put2(&mut self, x: u16) {
         write!(self.text, "{:04x} ", x).unwrap();
+        self.put_bytes(x as u64, 2);
         self.offset += 2;
     }
 
     fn put4(&mut self, x: u32) {
         write!(self.text, "{:08x} ", x).unwrap();
+        self.put_bytes(x as u64, 4);
         self.offset += 4;
     }
 
     fn put8(&mut self, x: u64) {
         write!(self.text, "{:016x} ", x).unwrap();
+        self.put_bytes(x as u64, 8);
         self.offset += 8;
     }
 
@@ -78,9 +116,23 @@ impl binemit::CodeSink for TextSink {
         write!(self.text, "{}({}) ", self.rnames[reloc.0 as usize], fref).unwrap();
     }
 
+    fn reloc_globalsym(&mut self, reloc: binemit::Reloc, global: ir::GlobalVar) {
+        write!(self.text, "{}({}) ", self.rnames[reloc.0 as usize], global).unwrap();
+    }
+
     fn reloc_jt(&mut self, reloc: binemit::Reloc, jt: ir::JumpTable) {
         write!(self.text, "{}({}) ", self.rnames[reloc.0 as usize], jt).unwrap();
     }
+
+    fn reloc_constant(&mut self, reloc: binemit::Reloc, constant: ir::Constant) {
+        write!(self.text, "{}({}) ", self.rnames[reloc.0 as usize], constant).unwrap();
+    }
+
+    fn trap(&mut self, code: ir::TrapCode, _srcloc: ir::SourceLoc) {
+        write!(self.text, "{} ", code).unwrap();
+    }
+
+    fn srcloc(&mut self, _srcloc: ir::SourceLoc) {}
 }
 
 impl SubTest for TestBinEmit {
@@ -99,6 +151,7 @@ impl SubTest for TestBinEmit {
     fn run(&self, func: Cow<ir::Function>, context: &Context) -> Result<()> {
         let isa = context.isa.expect("binemit needs an ISA");
         let encinfo = isa.encoding_info();
+        let disasm = isa.disassembler();
         // TODO: Run a verifier pass over the code first to detect any bad encodings or missing/bad
         // value locations. The current error reporting is just crashing...
         let mut func = func.into_owned();
@@ -125,13 +178,22 @@ impl SubTest for TestBinEmit {
             if let Some(want) = match_directive(comment.text, "bin:") {
                 match comment.entity {
                     AnyEntity::Inst(inst) => {
-                        if let Some(prev) = bins.insert(inst, want) {
+                        if let Some(prev) = bins.insert(comment.entity, want) {
                             return Err(format!("multiple 'bin:' directives on {}: '{}' and '{}'",
                                                func.dfg.display_inst(inst),
                                                prev,
                                                want));
                         }
                     }
+                    AnyEntity::JumpTable(_) |
+                    AnyEntity::Constant(_) => {
+                        if let Some(prev) = bins.insert(comment.entity, want) {
+                            return Err(format!("multiple 'bin:' directives on {}: '{}' and '{}'",
+                                               comment.entity,
+                                               prev,
+                                               want));
+                        }
+                    }
                     _ => {
                         return Err(format!("'bin:' directive on non-inst {}: {}",
                                            comment.entity,
@@ -153,12 +215,13 @@ impl SubTest for TestBinEmit {
                        "Inconsistent {} header offset",
                        ebb);
             for inst in func.layout.ebb_insts(ebb) {
-                sink.text.clear();
+                sink.clear();
                 let enc = func.encodings.get_or_default(inst);
 
                 // Send legal encodings into the emitter.
                 if enc.is_legal() {
                     let before = sink.offset;
+                    sink.srcloc(func.srclocs.get_or_default(inst));
                     isa.emit_inst(&func, inst, &mut sink);
                     let emitted = sink.offset - before;
                     // Verify the encoding recipe sizes against the ISAs emit_inst implementation.
@@ -167,10 +230,23 @@ impl SubTest for TestBinEmit {
                                "Inconsistent size for [{}] {}",
                                encinfo.display(enc),
                                func.dfg.display_inst(inst));
+
+                    // The emitted code must be understood by the disassembler.
+                    if let Some(ref dis) = disasm {
+                        if binemit::decode_all(&**dis, &sink.bytes, before).is_none() {
+                            return Err(format!("Can't disassemble [{}] {}: {}\n{}",
+                                               encinfo.display(enc),
+                                               func.dfg.display_inst(inst),
+                                               sink.text.trim(),
+                                               binemit::disassemble(&**dis,
+                                                                    &sink.bytes,
+                                                                    before)));
+                        }
+                    }
                 }
 
                 // Check against bin: directives.
-                if let Some(want) = bins.remove(&inst) {
+                if let Some(want) = bins.remove(&AnyEntity::Inst(inst)) {
                     if !enc.is_legal() {
                         return Err(format!("{} can't be encoded: {}",
                                            inst,
@@ -178,16 +254,64 @@ impl SubTest for TestBinEmit {
                     }
                     let have = sink.text.trim();
                     if have != want {
-                        return Err(format!("Bad machine code for {}: {}\nWant: {}\nGot:  {}",
-                                           inst,
-                                           func.dfg.display_inst(inst),
-                                           want,
-                                           have));
+                        let mut msg = format!("Bad machine code for {}: {}\nWant: {}\nGot:  {}",
+                                              inst,
+                                              func.dfg.display_inst(inst),
+                                              want,
+                                              have);
+                        // Show the mnemonics too when the ISA has a disassembler.
+                        if let Some(ref dis) = disasm {
+                            let at = sink.offset - sink.bytes.len() as binemit::CodeOffset;
+                            write!(msg,
+                                   "\nWant:\n{}Got:\n{}",
+                                   binemit::disassemble(&**dis, &parse_bin(want), at),
+                                   binemit::disassemble(&**dis, &sink.bytes, at))
+                                    .unwrap();
+                        }
+                        return Err(msg);
                     }
                 }
             }
         }
 
+        // Jump tables are emitted after the function body.
+        for jt in func.jump_tables.keys() {
+            while sink.offset < func.jt_offsets[jt] {
+                sink.put1(0);
+            }
+            sink.clear();
+            binemit::emit_jump_table(&func, jt, &mut sink);
+
+            if let Some(want) = bins.remove(&AnyEntity::JumpTable(jt)) {
+                let have = sink.text.trim();
+                if have != want {
+                    return Err(format!("Bad jump table contents for {}\nWant: {}\nGot:  {}",
+                                       jt,
+                                       want,
+                                       have));
+                }
+            }
+        }
+
+        // The constant pool follows the jump tables.
+        for constant in func.constants.keys() {
+            while sink.offset < func.const_offsets[constant] {
+                sink.put1(0);
+            }
+            sink.clear();
+            binemit::emit_constant(&func, constant, &mut sink);
+
+            if let Some(want) = bins.remove(&AnyEntity::Constant(constant)) {
+                let have = sink.text.trim();
+                if have != want {
+                    return Err(format!("Bad constant pool entry for {}\nWant: {}\nGot:  {}",
+                                       constant,
+                                       want,
+                                       have));
+                }
+            }
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,146 @@
+//! Test command for running functions in the interpreter.
+//!
+//! The `test interpret` test command looks for `run:` directives like this:
+//!
+//!     ; run: %add(1, 2) == 3
+//!
+//! The function is called with the given arguments in the Cretonne IL interpreter, and the
+//! returned values are compared to the expected values. Both are parsed according to the
+//! signature of the called function. A call can also be expected to trap:
+//!
+//!     ; run: %div(1, 0) == trap int_divz
+//!
+//! A directive without `==` only checks that the call returns without trapping. The called
+//! function can call the other functions in the test file.
+
+use cretonne::ir::{Function, FunctionName, TrapCode, Type};
+use cton_interpreter::{Interpreter, Error, DataValue};
+use cton_reader::TestCommand;
+use filetest::subtest::{SubTest, Context, Result};
+use std::borrow::{Borrow, Cow};
+use utils::match_directive;
+
+/// Maximum number of instructions executed by a `run:` directive.
+const STEP_LIMIT: u64 = 1_000_000;
+
+struct TestInterpret;
+
+pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
+    assert_eq!(parsed.command, "interpret");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestInterpret))
+    }
+}
+
+impl SubTest for TestInterpret {
+    fn name(&self) -> Cow<str> {
+        Cow::from("interpret")
+    }
+
+    fn needs_all_functions(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
+        let func: &Function = func.borrow();
+        let mut functions = vec![func];
+        functions.extend(context.functions.iter().filter(|f| f.name != func.name));
+
+        for comment in &context.details.comments {
+            if let Some(tail) = match_directive(comment.text, "run:") {
+                run_directive(&functions, tail).map_err(|e| format!("run: {}: {}", tail, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run the call in a `run:` directive and check the result.
+fn run_directive(functions: &[&Function], text: &str) -> Result<()> {
+    let (call, expected) = match text.find("==") {
+        Some(pos) => (text[0..pos].trim(), Some(text[pos + 2..].trim())),
+        None => (text.trim(), None),
+    };
+    let open = match call.find('(') {
+        Some(pos) if call.starts_with('%') && call.ends_with(')') => pos,
+        _ => return Err("expected a call like %fn(1, 2)".to_string()),
+    };
+    let name = FunctionName::new(&call[1..open]);
+    let callee = functions
+        .iter()
+        .find(|f| f.name == name)
+        .ok_or_else(|| format!("no function named {}", name))?;
+    let types: Vec<Type> = callee
+        .signature
+        .argument_types
+        .iter()
+        .map(|at| at.value_type)
+        .collect();
+    let args = parse_values(&call[open + 1..call.len() - 1], &types)?;
+
+    // Every call gets a fresh interpreter so memory doesn't carry over between directives.
+    let mut interp = Interpreter::new();
+    interp.set_step_limit(Some(STEP_LIMIT));
+    for func in functions {
+        interp.add_function(func)?;
+    }
+    let result = interp.call(&name, &args);
+
+    let expected = match expected {
+        None => return result.map(|_| ()).map_err(|e| e.to_string()),
+        Some(e) => e,
+    };
+    if expected.starts_with("trap ") {
+        let code: TrapCode = expected[5..]
+            .trim()
+            .parse()
+            .map_err(|_| format!("bad trap code in '{}'", expected))?;
+        return match result {
+                   Err(Error::Trap(c)) if c == code => Ok(()),
+                   Err(e) => Err(e.to_string()),
+                   Ok(values) => {
+                       Err(format!("expected trap {}, got {}", code, display_values(&values)))
+                   }
+               };
+    }
+    let types: Vec<Type> = callee
+        .signature
+        .return_types
+        .iter()
+        .map(|at| at.value_type)
+        .collect();
+    let want = parse_values(expected, &types)?;
+    let got = result.map_err(|e| e.to_string())?;
+    if got != want {
+        return Err(format!("expected {}, got {}",
+                           display_values(&want),
+                           display_values(&got)));
+    }
+    Ok(())
+}
+
+/// Parse a comma-separated list of values with `types`.
+fn parse_values(text: &str, types: &[Type]) -> Result<Vec<DataValue>> {
+    let text = text.trim();
+    let parts: Vec<&str> = if text.is_empty() {
+        Vec::new()
+    } else {
+        text.split(',').collect()
+    };
+    if parts.len() != types.len() {
+        return Err(format!("expected {} values, got '{}'", types.len(), text));
+    }
+    parts
+        .iter()
+        .zip(types)
+        .map(|(part, &ty)| DataValue::parse(part, ty))
+        .collect()
+}
+
+/// Format a list of values like they are written in a `run:` directive.
+fn display_values(values: &[DataValue]) -> String {
+    let strings: Vec<String> = values.iter().map(DataValue::to_string).collect();
+    strings.join(", ")
+}
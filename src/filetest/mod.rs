@@ -16,12 +16,19 @@ pub mod subtest;
 mod binemit;
 mod concurrent;
 mod domtree;
+mod if_conversion;
+mod interpret;
 mod legalizer;
 mod licm;
+mod redundant_ebb_args;
 mod regalloc;
 mod runner;
 mod runone;
+mod sccp;
 mod simple_gvn;
+mod simplify_cfg;
+mod strength_reduction;
+mod unroll;
 mod verifier;
 
 /// The result of running the test in a file.
@@ -66,6 +73,13 @@ fn new_subtest(parsed: &TestCommand) -> subtest::Result<Box<subtest::SubTest>> {
         "regalloc" => regalloc::subtest(parsed),
         "binemit" => binemit::subtest(parsed),
         "simple-gvn" => simple_gvn::subtest(parsed),
+        "sccp" => sccp::subtest(parsed),
+        "redundant-ebb-args" => redundant_ebb_args::subtest(parsed),
+        "simplify-cfg" => simplify_cfg::subtest(parsed),
+        "strength-reduction" => strength_reduction::subtest(parsed),
+        "unroll" => unroll::subtest(parsed),
+        "if-conversion" => if_conversion::subtest(parsed),
+        "interpret" => interpret::subtest(parsed),
         _ => Err(format!("unknown test command '{}'", parsed.command)),
     }
 }
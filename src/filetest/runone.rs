@@ -53,10 +53,23 @@ pub fn run(path: &Path) -> TestResult {
         Some(t) => t,
     };
 
+    // Copy all the functions for the tests that need them, for example to resolve calls.
+    let functions: Vec<Function> = if tests.iter().any(|st| st.needs_all_functions()) {
+        testfile
+            .functions
+            .iter()
+            .map(|&(ref func, _)| func.clone())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
     for (func, details) in testfile.functions {
         let mut context = Context {
             preamble_comments: &testfile.preamble_comments,
             details,
+            functions: &functions,
+            data: &testfile.data,
             verified: false,
             flags,
             isa: None,
@@ -4,6 +4,7 @@ use std::result;
 use std::borrow::Cow;
 use cretonne::ir::Function;
 use cretonne::isa::TargetIsa;
+use cretonne::module::DataObject;
 use cretonne::settings::Flags;
 use cton_reader::{Details, Comment};
 use filecheck::{self, CheckerBuilder, Checker, Value as FCValue};
@@ -18,6 +19,13 @@ pub struct Context<'a> {
     /// Additional details about the function from the parser.
     pub details: Details<'a>,
 
+    /// All the functions in the test file. Only present for sub-tests whose `needs_all_functions`
+    /// method returned `true`. For other sub-tests, this is empty.
+    pub functions: &'a [Function],
+
+    /// All the data objects declared in the test file.
+    pub data: &'a [DataObject],
+
     /// Was the function verified before running this test?
     pub verified: bool,
 
@@ -53,6 +61,11 @@ pub trait SubTest {
         false
     }
 
+    /// Does this test need to see the other functions in the test file?
+    fn needs_all_functions(&self) -> bool {
+        false
+    }
+
     /// Run this test on `func`.
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()>;
 }
@@ -0,0 +1,44 @@
+//! The `stats` sub-command.
+//!
+//! Read a series of Cretonne IL files, compile every function for each ISA given in its file, and
+//! print statistics about the generated code as JSON or CSV.
+
+use cretonne::Context;
+use cretonne::stats::Stats;
+use cton_reader::{parse_test, IsaSpec};
+use CommandResult;
+use utils::read_to_string;
+
+pub fn run(files: Vec<String>, csv: bool) -> CommandResult {
+    let mut stats = Stats::new();
+    let mut context = Context::new();
+    for filename in files {
+        let buffer = read_to_string(&filename)
+            .map_err(|e| format!("{}: {}", filename, e))?;
+        let test = parse_test(&buffer)
+            .map_err(|e| format!("{}: {}", filename, e))?;
+        let isas = match test.isa_spec {
+            IsaSpec::Some(ref isas) => isas,
+            IsaSpec::None(_) => return Err(format!("{}: no `isa` commands", filename)),
+        };
+        for isa in isas {
+            for &(ref func, _) in &test.functions {
+                context.func = func.clone();
+                let fstats = context
+                    .compile_with_stats(&**isa)
+                    .map_err(|e| format!("{}: {}: {}", filename, func.name, e))?;
+                stats.add(fstats);
+            }
+        }
+    }
+
+    // Writing to a `String` can't fail.
+    let mut out = String::new();
+    if csv {
+        stats.write_csv(&mut out).unwrap();
+    } else {
+        stats.write_json(&mut out).unwrap();
+    }
+    print!("{}", out);
+    Ok(())
+}